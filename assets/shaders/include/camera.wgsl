@@ -0,0 +1,5 @@
+// Camera UBO (CameraUniform на стороне Rust).
+struct Camera {
+    // Здесь теперь PV (OpenGL->WGPU преобразование применяется на CPU).
+    mvp : mat4x4<f32>,
+};
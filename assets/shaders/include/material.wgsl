@@ -0,0 +1,15 @@
+// MaterialUniform / LightingUniform на стороне Rust (раскладка проверяется тестами через naga).
+struct Material {
+    base_color: vec4<f32>,
+    metallic_roughness: vec2<f32>,
+    // ALPHA_MASK: fragments with alpha below the cutoff are discarded
+    alpha_cutoff: f32,
+    emissive: vec4<f32>,
+};
+
+struct Lighting {
+    light_direction: vec3<f32>,
+    light_intensity: f32,
+    light_color: vec3<f32>,
+    ambient_intensity: f32,
+};
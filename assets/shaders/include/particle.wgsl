@@ -0,0 +1,30 @@
+// K3: EmitterUniform / GpuParticle on the Rust side (checked by the particle tests).
+
+struct Emitter {
+    model: mat4x4<f32>,
+    color_start: vec4<f32>,
+    color_end: vec4<f32>,
+    gravity: vec3<f32>,
+    drag: f32,
+    camera_pos: vec3<f32>,
+    dt: f32,
+    // x = radius, y = cone angle, z = speed min, w = speed max
+    shape_params: vec4<f32>,
+    lifetime: vec2<f32>,
+    size: vec2<f32>,
+    // x = strength, y = scale
+    curl: vec2<f32>,
+    time: f32,
+    seed: u32,
+    shape: u32,
+    spawn_start: u32,
+    spawn_count: u32,
+    capacity: u32,
+};
+
+struct Particle {
+    // xyz = world position, w = age (s)
+    pos_age: vec4<f32>,
+    // xyz = velocity, w = lifetime (s); dead when age >= lifetime
+    vel_life: vec4<f32>,
+};
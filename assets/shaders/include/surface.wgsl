@@ -0,0 +1,17 @@
+// J2: surface a material produces for lighting (default path or a compiled material graph).
+struct SurfaceInput {
+    world_pos: vec3<f32>,
+    // normalized world-space normal
+    normal: vec3<f32>,
+    uv: vec2<f32>,
+    // normalized direction towards the camera
+    view_dir: vec3<f32>,
+};
+
+struct Surface {
+    base_color: vec3<f32>,
+    alpha: f32,
+    metallic: f32,
+    roughness: f32,
+    emissive: vec3<f32>,
+};
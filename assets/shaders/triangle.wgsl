@@ -1,22 +1,10 @@
-struct Camera {
-    // Здесь теперь PV (OpenGL->WGPU преобразование применяется на CPU).
-    mvp : mat4x4<f32>,
-};
+#include "include/camera.wgsl"
+#include "include/material.wgsl"
+#include "include/surface.wgsl"
+
 @group(0) @binding(0)
 var<uniform> u_camera : Camera;
 
-struct Material {
-    base_color: vec4<f32>,
-    metallic_roughness: vec2<f32>,
-};
-
-struct Lighting {
-    light_direction: vec3<f32>,
-    light_intensity: f32,
-    light_color: vec3<f32>,
-    ambient_intensity: f32,
-};
-
 @group(1) @binding(0)
 var<uniform> u_material : Material;
 @group(1) @binding(1)
@@ -37,6 +25,12 @@ struct VsIn {
     @location(4) i_col1 : vec4<f32>,
     @location(5) i_col2 : vec4<f32>,
     @location(6) i_col3 : vec4<f32>,
+    // L1: x = LOD cross-fade value
+    @location(7) i_lod  : vec4<f32>,
+#ifdef VERTEX_COLOR
+    // M1: optional stream (meshes whose layout has vertex colors)
+    @location(10) color : vec4<f32>,
+#endif
 };
 
 struct VsOut {
@@ -44,6 +38,10 @@ struct VsOut {
     @location(0) world_pos : vec3<f32>,
     @location(1) normal : vec3<f32>,
     @location(2) uv : vec2<f32>,
+    @location(3) @interpolate(flat) lod_fade : f32,
+#ifdef VERTEX_COLOR
+    @location(4) color : vec4<f32>,
+#endif
 };
 
 @vertex
@@ -62,27 +60,83 @@ fn vs_main(in: VsIn) -> VsOut {
     out.world_pos = world_pos4.xyz;
     out.normal = normalize(normal_matrix * in.normal);
     out.uv = in.uv;
+    out.lod_fade = in.i_lod.x;
+#ifdef VERTEX_COLOR
+    out.color = in.color;
+#endif
     return out;
 }
 
+// 4x4 ordered dither threshold in [0, 1).
+fn bayer4(p: vec2<u32>) -> f32 {
+    var m = array<u32, 16>(0u, 8u, 2u, 10u, 12u, 4u, 14u, 6u, 3u, 11u, 1u, 9u, 15u, 7u, 13u, 5u);
+    return (f32(m[(p.y & 3u) * 4u + (p.x & 3u)]) + 0.5) / 16.0;
+}
+
+// L1: [0,1) = incoming LOD with coverage f, [1,2] = outgoing LOD with coverage 2-f.
+fn lod_dither_discard(frag_pos: vec2<f32>, f: f32) -> bool {
+    if (f == 1.0) {
+        return false;
+    }
+    let d = bayer4(vec2<u32>(frag_pos));
+    if (f < 1.0) {
+        return d >= f;
+    }
+    return d < f - 1.0;
+}
+
+// Surface from the material parameters and the diffuse texture.
+fn default_surface(in: SurfaceInput) -> Surface {
+    let texture_color = textureSample(t_diffuse, s_diffuse, in.uv);
+    return Surface(
+        u_material.base_color.rgb * texture_color.rgb,
+        u_material.base_color.a * texture_color.a,
+        u_material.metallic_roughness.x,
+        u_material.metallic_roughness.y,
+        u_material.emissive.rgb,
+    );
+}
+
 @fragment
 fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
+#ifdef LOD_CROSSFADE
+    if (lod_dither_discard(in.pos.xy, in.lod_fade)) {
+        discard;
+    }
+#endif
+
     // Normalize interpolated normal
     let normal = normalize(in.normal);
 
     // Light direction (directional light)
     let light_dir = normalize(-u_lighting.light_direction);
+    let view_dir = normalize(-in.world_pos); // Assuming camera at origin for simplicity
 
-    // Sample the diffuse texture
-    let texture_color = textureSample(t_diffuse, s_diffuse, in.uv);
-    let base_color = u_material.base_color.rgb * texture_color.rgb;
+    let surface_in = SurfaceInput(in.world_pos, normal, in.uv, view_dir);
+#ifdef MATERIAL_GRAPH
+    // J2: function appended by the material graph compiler
+    let surface = material_graph(surface_in);
+#else
+    let surface = default_surface(surface_in);
+#endif
+#ifdef VERTEX_COLOR
+    let base_color = surface.base_color * in.color.rgb;
+    let alpha = surface.alpha * in.color.a;
+#else
+    let base_color = surface.base_color;
+    let alpha = surface.alpha;
+#endif
+#ifdef ALPHA_MASK
+    if (alpha < u_material.alpha_cutoff) {
+        discard;
+    }
+#endif
 
     // Lambert diffuse lighting
     let n_dot_l = max(dot(normal, light_dir), 0.0);
     let diffuse = u_lighting.light_color * u_lighting.light_intensity * n_dot_l;
 
     // Blinn-Phong specular (simple view direction from camera)
-    let view_dir = normalize(-in.world_pos); // Assuming camera at origin for simplicity
     let half_dir = normalize(light_dir + view_dir);
     let n_dot_h = max(dot(normal, half_dir), 0.0);
     let shininess = 32.0;
@@ -92,8 +146,8 @@ fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
     // Ambient lighting
     let ambient = u_lighting.light_color * u_lighting.ambient_intensity;
 
-    // Final color: ambient + diffuse + specular
-    let final_color = base_color * (ambient + diffuse) + specular;
+    // Final color: ambient + diffuse + specular + emission
+    let final_color = base_color * (ambient + diffuse) + specular + surface.emissive;
 
-    return vec4<f32>(final_color, u_material.base_color.a * texture_color.a);
+    return vec4<f32>(final_color, alpha);
 }
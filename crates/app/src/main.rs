@@ -2,7 +2,6 @@
 //! A2: logging + CLI backend flag.
 
 use anyhow::Result;
-use wgpu;
 
 fn parse_backend_arg() -> wgpu::Backends {
     // Accept: --gpu-backend=auto|vulkan|dx12|metal|gl
@@ -41,26 +40,98 @@ fn parse_show_fps_arg() -> bool {
     false
 }
 
+fn parse_profile_arg() -> bool {
+    // --profile[=on|off]: H4 per-pass GPU timings, по умолчанию off
+    for arg in std::env::args() {
+        if arg == "--profile" {
+            return true;
+        }
+        if let Some(val) = arg.strip_prefix("--profile=") {
+            return matches!(
+                val.to_ascii_lowercase().as_str(),
+                "1" | "true" | "on" | "yes"
+            );
+        }
+    }
+    false
+}
+
+fn parse_gpu_culling_arg() -> bool {
+    // --gpu-culling: L2 compute culling + indirect draws (если поддерживается)
+    std::env::args().any(|arg| arg == "--gpu-culling")
+}
+
+fn parse_occlusion_culling_arg() -> bool {
+    // --occlusion-culling: L1 Hi-Z occlusion по depth предыдущего кадра
+    std::env::args().any(|arg| arg == "--occlusion-culling")
+}
+
+fn parse_hiz_debug_arg() -> Option<u32> {
+    // --hiz-debug=<mip>: показать mip Hi-Z пирамиды в углу экрана
+    std::env::args()
+        .filter_map(|arg| {
+            arg.strip_prefix("--hiz-debug=")
+                .and_then(|v| v.parse().ok())
+        })
+        .next_back()
+}
+
+fn parse_retained_arg() -> bool {
+    // --retained: L2 постоянные instance-буферы, аплоад только изменённых сущностей
+    std::env::args().any(|arg| arg == "--retained")
+}
+
+fn parse_draw_threads_arg() -> usize {
+    // --draw-threads=<n>: G1 многопоточная подготовка draw list (0 = все ядра)
+    let n = std::env::args()
+        .filter_map(|arg| {
+            arg.strip_prefix("--draw-threads=")
+                .and_then(|v| v.parse().ok())
+        })
+        .next_back()
+        .unwrap_or(1);
+    if n == 0 {
+        std::thread::available_parallelism().map_or(1, |n| n.get())
+    } else {
+        n
+    }
+}
+
+fn parse_particles_arg() -> bool {
+    // --particles: K3 демо-эмиттеры GPU частиц (нужны compute шейдеры)
+    std::env::args().any(|arg| arg == "--particles")
+}
+
+fn parse_stats_csv_arg() -> Option<std::path::PathBuf> {
+    // --stats-csv=<path>: дамп RenderStats каждого кадра в CSV
+    std::env::args()
+        .filter_map(|arg| {
+            arg.strip_prefix("--stats-csv=")
+                .map(std::path::PathBuf::from)
+        })
+        .next_back()
+}
+
 fn parse_size_args() -> (u32, u32) {
     let mut w: Option<u32> = None;
     let mut h: Option<u32> = None;
 
     for arg in std::env::args() {
         if let Some(v) = arg.strip_prefix("--size=") {
-            if let Some((sw, sh)) = v.split_once('x').or_else(|| v.split_once('X')) {
-                if let (Ok(pw), Ok(ph)) = (sw.parse::<u32>(), sh.parse::<u32>()) {
-                    w = Some(pw);
-                    h = Some(ph);
-                }
+            if let Some((sw, sh)) = v.split_once('x').or_else(|| v.split_once('X'))
+                && let (Ok(pw), Ok(ph)) = (sw.parse::<u32>(), sh.parse::<u32>())
+            {
+                w = Some(pw);
+                h = Some(ph);
             }
         } else if let Some(v) = arg.strip_prefix("--width=") {
             if let Ok(pw) = v.parse::<u32>() {
                 w = Some(pw);
             }
-        } else if let Some(v) = arg.strip_prefix("--height=") {
-            if let Ok(ph) = v.parse::<u32>() {
-                h = Some(ph);
-            }
+        } else if let Some(v) = arg.strip_prefix("--height=")
+            && let Ok(ph) = v.parse::<u32>()
+        {
+            h = Some(ph);
         }
     }
 
@@ -74,16 +145,40 @@ fn main() -> Result<()> {
 
     let chosen = parse_backend_arg();
     let show_fps = parse_show_fps_arg();
+    let profile = parse_profile_arg();
+    let stats_csv = parse_stats_csv_arg();
+    let gpu_culling = parse_gpu_culling_arg();
+    let occlusion_culling = parse_occlusion_culling_arg();
+    let hiz_debug_mip = parse_hiz_debug_arg();
+    let retained = parse_retained_arg();
+    let draw_threads = parse_draw_threads_arg();
+    let particles = parse_particles_arg();
     let (width, height) = parse_size_args();
     log::info!(
-        "Starting Svarog3D (A2/B3). Backend: {:?}, show_fps={}, window_size={}x{}",
+        "Starting Svarog3D (A2/B3). Backend: {:?}, show_fps={}, profile={}, window_size={}x{}",
         chosen,
         show_fps,
+        profile,
         width,
         height
     );
 
-    platform::run_with_renderer(chosen, show_fps, width, height)?;
+    platform::run_with_renderer(
+        chosen,
+        platform::RunOptions {
+            show_fps,
+            profile,
+            gpu_culling,
+            occlusion_culling,
+            hiz_debug_mip,
+            stats_csv,
+            retained,
+            draw_threads,
+            particles,
+            width,
+            height,
+        },
+    )?;
 
     log::info!("Graceful shutdown. Bye!");
     Ok(())
@@ -0,0 +1,895 @@
+//! J1: skeletons, animation clips and keyframe sampling for skinned meshes.
+//!
+//! A pose is one local [`JointTransform`] per joint. Clips overwrite the joints they animate
+//! (step, linear or cubic spline keys, as in glTF); [`Skeleton::sample_blended`] mixes several
+//! clips by weight and [`Skeleton::joint_matrices`] turns the pose into skinning matrices
+//! (`global * inverse_bind`). Clips may also animate morph target weights ([`MorphChannel`]),
+//! which are applied to the bind pose before skinning. Matrices are column-major `[f32; 16]`
+//! like the rest of the crate.
+
+use anyhow::{Result, bail};
+
+use crate::gltf::{IDENTITY, mat_mul};
+use crate::mesh::{Aabb, MeshData, MeshVertex};
+
+/// Local translation, rotation (quaternion x, y, z, w) and scale of a joint.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct JointTransform {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+    pub scale: [f32; 3],
+}
+
+impl JointTransform {
+    pub const IDENTITY: Self = Self {
+        translation: [0.0; 3],
+        rotation: [0.0, 0.0, 0.0, 1.0],
+        scale: [1.0; 3],
+    };
+
+    /// Column-major `T * R * S`.
+    pub fn matrix(&self) -> [f32; 16] {
+        let [x, y, z, w] = self.rotation;
+        let (t, s) = (self.translation, self.scale);
+        let (xx, yy, zz) = (x * x, y * y, z * z);
+        let (xy, xz, yz) = (x * y, x * z, y * z);
+        let (wx, wy, wz) = (w * x, w * y, w * z);
+        [
+            (1.0 - 2.0 * (yy + zz)) * s[0],
+            2.0 * (xy + wz) * s[0],
+            2.0 * (xz - wy) * s[0],
+            0.0,
+            2.0 * (xy - wz) * s[1],
+            (1.0 - 2.0 * (xx + zz)) * s[1],
+            2.0 * (yz + wx) * s[1],
+            0.0,
+            2.0 * (xz + wy) * s[2],
+            2.0 * (yz - wx) * s[2],
+            (1.0 - 2.0 * (xx + yy)) * s[2],
+            0.0,
+            t[0],
+            t[1],
+            t[2],
+            1.0,
+        ]
+    }
+
+    /// Component-wise blend; rotations take the shortest path (normalized lerp).
+    pub fn blend(&self, other: &Self, t: f32) -> Self {
+        let dot: f32 = (0..4).map(|i| self.rotation[i] * other.rotation[i]).sum();
+        let sign = if dot < 0.0 { -1.0 } else { 1.0 };
+        let rotation = normalize4(std::array::from_fn(|i| {
+            self.rotation[i] + (other.rotation[i] * sign - self.rotation[i]) * t
+        }));
+        Self {
+            translation: std::array::from_fn(|i| {
+                lerp(self.translation[i], other.translation[i], t)
+            }),
+            rotation,
+            scale: std::array::from_fn(|i| lerp(self.scale[i], other.scale[i], t)),
+        }
+    }
+}
+
+impl Default for JointTransform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Joint {
+    pub name: Option<String>,
+    /// Parent joint; `None` for roots (placed under [`Skeleton::root`]).
+    pub parent: Option<usize>,
+    /// Local transform when no clip animates the joint.
+    pub rest: JointTransform,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Skeleton {
+    pub joints: Vec<Joint>,
+    /// Per joint: mesh space -> joint space at bind time.
+    pub inverse_bind: Vec<[f32; 16]>,
+    /// Transform above the root joints (non-joint ancestors in the source file).
+    pub root: [f32; 16],
+    // Joint indices with parents before children
+    order: Vec<usize>,
+}
+
+impl Skeleton {
+    /// Validates parent links (in range, acyclic) and the inverse bind count.
+    pub fn new(joints: Vec<Joint>, inverse_bind: Vec<[f32; 16]>, root: [f32; 16]) -> Result<Self> {
+        if joints.is_empty() {
+            bail!("skeleton has no joints");
+        }
+        if inverse_bind.len() != joints.len() {
+            bail!(
+                "{} inverse bind matrices for {} joints",
+                inverse_bind.len(),
+                joints.len()
+            );
+        }
+        let mut order = Vec::with_capacity(joints.len());
+        let mut placed = vec![false; joints.len()];
+        // Repeated passes place a joint once its parent is placed; no progress = cycle
+        while order.len() < joints.len() {
+            let before = order.len();
+            for (i, joint) in joints.iter().enumerate() {
+                if placed[i] {
+                    continue;
+                }
+                match joint.parent {
+                    Some(p) if p >= joints.len() => bail!("joint {i} has missing parent {p}"),
+                    Some(p) if !placed[p] => continue,
+                    _ => {
+                        placed[i] = true;
+                        order.push(i);
+                    }
+                }
+            }
+            if order.len() == before {
+                bail!("joint hierarchy has a cycle");
+            }
+        }
+        Ok(Self {
+            joints,
+            inverse_bind,
+            root,
+            order,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.joints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.joints.is_empty()
+    }
+
+    pub fn rest_pose(&self) -> Vec<JointTransform> {
+        self.joints.iter().map(|j| j.rest).collect()
+    }
+
+    /// Skinning matrices (`global * inverse_bind`) for `pose`.
+    pub fn joint_matrices(&self, pose: &[JointTransform], out: &mut Vec<[f32; 16]>) {
+        out.clear();
+        out.resize(self.joints.len(), IDENTITY);
+        // Globals first (parents precede children in `order`), then the bind offset
+        for &i in &self.order {
+            let parent = self.joints[i].parent.map_or(self.root, |p| out[p]);
+            out[i] = mat_mul(&parent, &pose[i].matrix());
+        }
+        for (m, inverse_bind) in out.iter_mut().zip(&self.inverse_bind) {
+            *m = mat_mul(m, inverse_bind);
+        }
+    }
+
+    /// Rest pose overwritten by the weighted blend of `layers` (`(clip, time, weight)`).
+    /// Joints a layer does not animate contribute their rest transform for that layer.
+    pub fn sample_blended(
+        &self,
+        layers: &[(&AnimationClip, f32, f32)],
+        pose: &mut Vec<JointTransform>,
+        scratch: &mut Vec<JointTransform>,
+    ) {
+        pose.clear();
+        pose.extend(self.joints.iter().map(|j| j.rest));
+        let mut total = 0.0;
+        for &(clip, time, weight) in layers {
+            if weight <= 0.0 {
+                continue;
+            }
+            scratch.clear();
+            scratch.extend(self.joints.iter().map(|j| j.rest));
+            clip.sample(time, scratch);
+            total += weight;
+            // Running weighted average: the first layer replaces the rest pose
+            let t = weight / total;
+            for (p, s) in pose.iter_mut().zip(scratch.iter()) {
+                *p = p.blend(s, t);
+            }
+        }
+    }
+}
+
+/// How values between two keys are computed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    Step,
+    #[default]
+    Linear,
+    /// Hermite spline; every key stores `(in_tangent, value, out_tangent)`.
+    CubicSpline,
+}
+
+/// Key values of one channel (three entries per key for [`Interpolation::CubicSpline`]).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Keyframes {
+    Translation(Vec<[f32; 3]>),
+    Rotation(Vec<[f32; 4]>),
+    Scale(Vec<[f32; 3]>),
+}
+
+impl Keyframes {
+    fn width(&self) -> usize {
+        match self {
+            Keyframes::Rotation(_) => 4,
+            _ => 3,
+        }
+    }
+
+    fn flat(&self) -> &[f32] {
+        match self {
+            Keyframes::Translation(v) | Keyframes::Scale(v) => v.as_flattened(),
+            Keyframes::Rotation(v) => v.as_flattened(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.flat().len() / self.width()
+    }
+}
+
+/// Animated property of one joint.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Channel {
+    pub joint: usize,
+    pub interpolation: Interpolation,
+    /// Key times in seconds, increasing.
+    pub times: Vec<f32>,
+    pub keys: Keyframes,
+}
+
+impl Channel {
+    /// Checks key counts against `times` and the interpolation mode.
+    pub fn new(
+        joint: usize,
+        interpolation: Interpolation,
+        times: Vec<f32>,
+        keys: Keyframes,
+    ) -> Result<Self> {
+        let per_key = if interpolation == Interpolation::CubicSpline {
+            3
+        } else {
+            1
+        };
+        if keys.len() != times.len() * per_key {
+            bail!(
+                "{} keys for {} key times ({interpolation:?})",
+                keys.len(),
+                times.len()
+            );
+        }
+        if times.windows(2).any(|w| w[1] < w[0]) {
+            bail!("key times must not decrease");
+        }
+        Ok(Self {
+            joint,
+            interpolation,
+            times,
+            keys,
+        })
+    }
+
+    /// Write the value at `time` (clamped to the key range) into `pose[joint]`.
+    pub fn sample(&self, time: f32, pose: &mut [JointTransform]) {
+        let Some(target) = pose.get_mut(self.joint) else {
+            return;
+        };
+        let mut value = [0.0; 4];
+        let width = self.keys.width();
+        let rotation = matches!(self.keys, Keyframes::Rotation(_));
+        let out = &mut value[..width];
+        if !sample_keys(
+            &self.times,
+            self.keys.flat(),
+            self.interpolation,
+            rotation,
+            time,
+            out,
+        ) {
+            return;
+        }
+        match self.keys {
+            Keyframes::Translation(_) => target.translation = [value[0], value[1], value[2]],
+            Keyframes::Rotation(_) => target.rotation = normalize4(value),
+            Keyframes::Scale(_) => target.scale = [value[0], value[1], value[2]],
+        }
+    }
+}
+
+/// Morph target weights over time: `targets` values per key (three groups per key for
+/// [`Interpolation::CubicSpline`]).
+#[derive(Clone, Debug, PartialEq)]
+pub struct MorphChannel {
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    pub targets: usize,
+    pub weights: Vec<f32>,
+}
+
+impl MorphChannel {
+    pub fn new(
+        interpolation: Interpolation,
+        times: Vec<f32>,
+        targets: usize,
+        weights: Vec<f32>,
+    ) -> Result<Self> {
+        let per_key = if interpolation == Interpolation::CubicSpline {
+            3
+        } else {
+            1
+        };
+        if targets == 0 || weights.len() != times.len() * per_key * targets {
+            bail!(
+                "{} weights for {} key times and {targets} targets",
+                weights.len(),
+                times.len()
+            );
+        }
+        if times.windows(2).any(|w| w[1] < w[0]) {
+            bail!("key times must not decrease");
+        }
+        Ok(Self {
+            interpolation,
+            times,
+            targets,
+            weights,
+        })
+    }
+
+    /// Write the weights at `time` into `out` (extra targets on either side are left alone).
+    pub fn sample(&self, time: f32, out: &mut [f32]) {
+        let mut value = vec![0.0; self.targets];
+        if sample_keys(
+            &self.times,
+            &self.weights,
+            self.interpolation,
+            false,
+            time,
+            &mut value,
+        ) {
+            for (o, v) in out.iter_mut().zip(value) {
+                *o = v;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AnimationClip {
+    pub name: Option<String>,
+    /// Last key time over all channels.
+    pub duration: f32,
+    pub channels: Vec<Channel>,
+    /// Morph target weights of the animated mesh.
+    pub morph: Option<MorphChannel>,
+}
+
+impl AnimationClip {
+    pub fn new(name: Option<String>, channels: Vec<Channel>) -> Self {
+        let duration = channels
+            .iter()
+            .filter_map(|c| c.times.last().copied())
+            .fold(0.0, f32::max);
+        Self {
+            name,
+            duration,
+            channels,
+            morph: None,
+        }
+    }
+
+    /// Add a morph weight channel (extends the duration if it runs longer).
+    pub fn with_morph(mut self, morph: MorphChannel) -> Self {
+        self.duration = self
+            .duration
+            .max(morph.times.last().copied().unwrap_or(0.0));
+        self.morph = Some(morph);
+        self
+    }
+
+    /// Overwrite the animated joints of `pose` with their values at `time`.
+    pub fn sample(&self, time: f32, pose: &mut [JointTransform]) {
+        for channel in &self.channels {
+            channel.sample(time, pose);
+        }
+    }
+}
+
+/// `base` morph weights overwritten by the weighted blend of `layers` (`(clip, time, weight)`);
+/// layers without a morph channel contribute `base`, like unanimated joints in poses.
+pub fn blend_morph_weights(
+    layers: &[(&AnimationClip, f32, f32)],
+    base: &[f32],
+    out: &mut Vec<f32>,
+    scratch: &mut Vec<f32>,
+) {
+    out.clear();
+    out.extend_from_slice(base);
+    let mut total = 0.0;
+    for &(clip, time, weight) in layers {
+        if weight <= 0.0 {
+            continue;
+        }
+        scratch.clear();
+        scratch.extend_from_slice(base);
+        if let Some(morph) = &clip.morph {
+            morph.sample(time, scratch);
+        }
+        total += weight;
+        let t = weight / total;
+        for (o, s) in out.iter_mut().zip(scratch.iter()) {
+            *o = lerp(*o, *s, t);
+        }
+    }
+}
+
+// Sample keys at `time` into `out` (`out.len()` components per key); false when there are
+// no keys. `rotation` keys are slerped and normalized by the caller.
+fn sample_keys(
+    times: &[f32],
+    values: &[f32],
+    mode: Interpolation,
+    rotation: bool,
+    time: f32,
+    out: &mut [f32],
+) -> bool {
+    let width = out.len();
+    let (Some(&first), Some(&last)) = (times.first(), times.last()) else {
+        return false;
+    };
+    let stride = if mode == Interpolation::CubicSpline {
+        3 * width
+    } else {
+        width
+    };
+    // Value of key `k` (skipping the in-tangent of cubic keys)
+    let key = |k: usize| {
+        let base = k * stride
+            + if mode == Interpolation::CubicSpline {
+                width
+            } else {
+                0
+            };
+        &values[base..base + width]
+    };
+    if time <= first || times.len() == 1 {
+        out.copy_from_slice(key(0));
+        return true;
+    }
+    if time >= last {
+        out.copy_from_slice(key(times.len() - 1));
+        return true;
+    }
+    let next = times.partition_point(|&t| t <= time);
+    let prev = next - 1;
+    let dt = times[next] - times[prev];
+    let s = if dt > 0.0 {
+        (time - times[prev]) / dt
+    } else {
+        0.0
+    };
+    match mode {
+        Interpolation::Step => out.copy_from_slice(key(prev)),
+        Interpolation::Linear if rotation => {
+            let q = slerp(
+                key(prev).try_into().unwrap(),
+                key(next).try_into().unwrap(),
+                s,
+            );
+            out.copy_from_slice(&q);
+        }
+        Interpolation::Linear => {
+            for (i, o) in out.iter_mut().enumerate() {
+                *o = lerp(key(prev)[i], key(next)[i], s);
+            }
+        }
+        Interpolation::CubicSpline => {
+            let out_tangent = &values[prev * stride + 2 * width..prev * stride + 3 * width];
+            let in_tangent = &values[next * stride..next * stride + width];
+            let (s2, s3) = (s * s, s * s * s);
+            let h00 = 2.0 * s3 - 3.0 * s2 + 1.0;
+            let h10 = s3 - 2.0 * s2 + s;
+            let h01 = -2.0 * s3 + 3.0 * s2;
+            let h11 = s3 - s2;
+            for (i, o) in out.iter_mut().enumerate() {
+                *o = h00 * key(prev)[i]
+                    + h10 * dt * out_tangent[i]
+                    + h01 * key(next)[i]
+                    + h11 * dt * in_tangent[i];
+            }
+        }
+    }
+    true
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+fn normalize4(q: [f32; 4]) -> [f32; 4] {
+    let len = q.iter().map(|c| c * c).sum::<f32>().sqrt();
+    if len > 0.0 {
+        q.map(|c| c / len)
+    } else {
+        [0.0, 0.0, 0.0, 1.0]
+    }
+}
+
+// Shortest-path spherical interpolation (normalized lerp for nearly equal rotations).
+fn slerp(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    let mut dot: f32 = (0..4).map(|i| a[i] * b[i]).sum();
+    let b = if dot < 0.0 {
+        dot = -dot;
+        b.map(|c| -c)
+    } else {
+        b
+    };
+    if dot > 0.9995 {
+        return normalize4(std::array::from_fn(|i| lerp(a[i], b[i], t)));
+    }
+    let theta = dot.clamp(-1.0, 1.0).acos();
+    let sin = theta.sin();
+    let (wa, wb) = (((1.0 - t) * theta).sin() / sin, (t * theta).sin() / sin);
+    std::array::from_fn(|i| a[i] * wa + b[i] * wb)
+}
+
+/// Up to four joint influences per vertex (parallel to `MeshData::vertices`).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SkinWeights {
+    pub joints: Vec<[u16; 4]>,
+    /// Weights summing to 1 per vertex.
+    pub weights: Vec<[f32; 4]>,
+}
+
+impl SkinWeights {
+    /// Normalizes weights; vertices with no weight are bound fully to their first joint.
+    pub fn new(joints: Vec<[u16; 4]>, mut weights: Vec<[f32; 4]>) -> Result<Self> {
+        if joints.len() != weights.len() {
+            bail!(
+                "{} joint sets for {} weight sets",
+                joints.len(),
+                weights.len()
+            );
+        }
+        for w in &mut weights {
+            let sum: f32 = w.iter().sum();
+            if sum > 0.0 {
+                *w = w.map(|x| x / sum);
+            } else {
+                *w = [1.0, 0.0, 0.0, 0.0];
+            }
+        }
+        Ok(Self { joints, weights })
+    }
+
+    pub fn len(&self) -> usize {
+        self.joints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.joints.is_empty()
+    }
+
+    /// Highest referenced joint index (with non-zero weight), if any.
+    pub fn max_joint(&self) -> Option<u16> {
+        self.joints
+            .iter()
+            .zip(&self.weights)
+            .flat_map(|(j, w)| (0..4).filter(|&i| w[i] > 0.0).map(|i| j[i]))
+            .max()
+    }
+
+    /// Bind-pose box of the vertices each joint influences (`None` = no influence), grown
+    /// by the morph targets of `mesh` for weights in [0, 1].
+    pub fn joint_bounds(&self, mesh: &MeshData, joint_count: usize) -> Vec<Option<Aabb>> {
+        let mut boxes: Vec<Option<Aabb>> = vec![None; joint_count];
+        for (i, (joints, weights)) in self
+            .joints
+            .iter()
+            .zip(&self.weights)
+            .enumerate()
+            .take(mesh.vertices.len())
+        {
+            let v = mesh.morph_extent(i);
+            for j in (0..4).filter(|&j| weights[j] > 0.0) {
+                let Some(slot) = boxes.get_mut(joints[j] as usize) else {
+                    continue;
+                };
+                let b = slot.get_or_insert(v);
+                for k in 0..3 {
+                    b.min[k] = b.min[k].min(v.min[k]);
+                    b.max[k] = b.max[k].max(v.max[k]);
+                }
+            }
+        }
+        boxes
+    }
+}
+
+/// CPU morphing (`morph_weights`, one per target) then linear blend skinning of `mesh`
+/// (same math as the renderer's `cs_skin`).
+pub fn skin_vertices(
+    mesh: &MeshData,
+    skin: &SkinWeights,
+    matrices: &[[f32; 16]],
+    morph_weights: &[f32],
+    out: &mut Vec<MeshVertex>,
+) {
+    mesh.morphed_vertices(morph_weights, out);
+    for ((v, joints), weights) in out.iter_mut().zip(&skin.joints).zip(&skin.weights) {
+        let mut m = [0.0; 16];
+        for i in 0..4 {
+            let joint = (joints[i] as usize).min(matrices.len() - 1);
+            for (acc, x) in m.iter_mut().zip(&matrices[joint]) {
+                *acc += x * weights[i];
+            }
+        }
+        let p = transform_point(&m, v.position, 1.0);
+        let n = transform_point(&m, v.normal, 0.0);
+        let len = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+        let normal = if len > 0.0 {
+            n.map(|c| c / len)
+        } else {
+            v.normal
+        };
+        *v = MeshVertex::new(p, normal, v.uv);
+    }
+}
+
+/// Box containing the skinned mesh: union of every joint's bind box moved by its matrix.
+/// Conservative, since a skinned vertex is a convex blend of its joints' transforms.
+pub fn skinned_aabb(joint_bounds: &[Option<Aabb>], matrices: &[[f32; 16]]) -> Aabb {
+    let mut corners = Vec::with_capacity(joint_bounds.len() * 8);
+    for (b, m) in joint_bounds.iter().zip(matrices) {
+        let Some(b) = b else {
+            continue;
+        };
+        for c in 0..8 {
+            let p = [
+                if c & 1 == 0 { b.min[0] } else { b.max[0] },
+                if c & 2 == 0 { b.min[1] } else { b.max[1] },
+                if c & 4 == 0 { b.min[2] } else { b.max[2] },
+            ];
+            corners.push(transform_point(m, p, 1.0));
+        }
+    }
+    Aabb::from_points(&corners)
+}
+
+fn transform_point(m: &[f32; 16], p: [f32; 3], w: f32) -> [f32; 3] {
+    std::array::from_fn(|r| m[r] * p[0] + m[4 + r] * p[1] + m[8 + r] * p[2] + m[12 + r] * w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::f32::consts::FRAC_PI_2;
+
+    fn about_z(angle: f32) -> [f32; 4] {
+        [0.0, 0.0, (angle * 0.5).sin(), (angle * 0.5).cos()]
+    }
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1e-4, "{a:?} != {b:?}");
+        }
+    }
+
+    // Two joints stacked along +Y, the child one unit above its parent.
+    fn two_bone() -> Skeleton {
+        let joints = vec![
+            Joint {
+                name: Some("root".into()),
+                parent: None,
+                rest: JointTransform::IDENTITY,
+            },
+            Joint {
+                name: Some("tip".into()),
+                parent: Some(0),
+                rest: JointTransform {
+                    translation: [0.0, 1.0, 0.0],
+                    ..JointTransform::IDENTITY
+                },
+            },
+        ];
+        let mut down = IDENTITY;
+        down[13] = -1.0;
+        Skeleton::new(joints, vec![IDENTITY, down], IDENTITY).unwrap()
+    }
+
+    #[test]
+    fn rest_pose_gives_identity_skinning() {
+        let skeleton = two_bone();
+        let mut matrices = Vec::new();
+        skeleton.joint_matrices(&skeleton.rest_pose(), &mut matrices);
+        for m in &matrices {
+            assert_close(m, &IDENTITY);
+        }
+    }
+
+    #[test]
+    fn interpolation_modes() {
+        let times = vec![0.0, 1.0];
+        let linear = Channel::new(
+            0,
+            Interpolation::Linear,
+            times.clone(),
+            Keyframes::Translation(vec![[0.0; 3], [2.0, 0.0, 0.0]]),
+        )
+        .unwrap();
+        let step = Channel::new(
+            0,
+            Interpolation::Step,
+            times.clone(),
+            Keyframes::Translation(vec![[0.0; 3], [2.0, 0.0, 0.0]]),
+        )
+        .unwrap();
+        // Zero tangents: the Hermite curve is smoothstep between the values
+        let cubic = Channel::new(
+            0,
+            Interpolation::CubicSpline,
+            times.clone(),
+            Keyframes::Scale(vec![
+                [0.0; 3], [1.0; 3], [0.0; 3], [0.0; 3], [3.0; 3], [0.0; 3],
+            ]),
+        )
+        .unwrap();
+        let rotation = Channel::new(
+            0,
+            Interpolation::Linear,
+            times,
+            Keyframes::Rotation(vec![about_z(0.0), about_z(FRAC_PI_2)]),
+        )
+        .unwrap();
+
+        let mut pose = [JointTransform::IDENTITY];
+        for c in [&linear, &cubic, &rotation] {
+            c.sample(0.25, &mut pose);
+        }
+        assert_close(&pose[0].translation, &[0.5, 0.0, 0.0]);
+        let smooth = 1.0 + 2.0 * (3.0 * 0.0625 - 2.0 * 0.015625);
+        assert_close(&pose[0].scale, &[smooth; 3]);
+        assert_close(&pose[0].rotation, &about_z(FRAC_PI_2 * 0.25));
+
+        step.sample(0.9, &mut pose);
+        assert_close(&pose[0].translation, &[0.0; 3]);
+        // Clamped past the last key
+        linear.sample(5.0, &mut pose);
+        assert_close(&pose[0].translation, &[2.0, 0.0, 0.0]);
+
+        assert!(
+            Channel::new(
+                0,
+                Interpolation::CubicSpline,
+                vec![0.0],
+                Keyframes::Scale(vec![[1.0; 3]])
+            )
+            .is_err()
+        );
+        assert!(
+            Channel::new(
+                0,
+                Interpolation::Step,
+                vec![1.0, 0.0],
+                Keyframes::Scale(vec![[1.0; 3]; 2])
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn blending_and_skinning_bend_the_tip() {
+        let skeleton = two_bone();
+        let bend = AnimationClip::new(
+            Some("bend".into()),
+            vec![
+                Channel::new(
+                    1,
+                    Interpolation::Linear,
+                    vec![0.0, 1.0],
+                    Keyframes::Rotation(vec![about_z(0.0), about_z(FRAC_PI_2)]),
+                )
+                .unwrap(),
+            ],
+        );
+        assert_eq!(bend.duration, 1.0);
+
+        let (mut pose, mut scratch) = (Vec::new(), Vec::new());
+        // Half weight against the rest pose = half the bend
+        skeleton.sample_blended(
+            &[(&bend, 1.0, 1.0), (&bend, 0.0, 1.0)],
+            &mut pose,
+            &mut scratch,
+        );
+        assert_close(&pose[1].rotation, &about_z(FRAC_PI_2 * 0.5));
+
+        skeleton.sample_blended(&[(&bend, 1.0, 1.0)], &mut pose, &mut scratch);
+        let mut matrices = Vec::new();
+        skeleton.joint_matrices(&pose, &mut matrices);
+
+        // A vertex at the top, bound to the tip, swings from (0, 2) to (-1, 1)
+        let mesh = MeshData::new(
+            vec![
+                MeshVertex::new([0.0, 2.0, 0.0], [0.0, 1.0, 0.0], [0.0; 2]),
+                MeshVertex::new([0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0; 2]),
+            ],
+            vec![0, 1, 0],
+        );
+        let skin = SkinWeights::new(
+            vec![[1, 0, 0, 0], [0, 0, 0, 0]],
+            vec![[2.0, 0.0, 0.0, 0.0], [0.0; 4]],
+        )
+        .unwrap();
+        assert_eq!(skin.weights[0], [1.0, 0.0, 0.0, 0.0]);
+        let mut skinned = Vec::new();
+        skin_vertices(&mesh, &skin, &matrices, &[], &mut skinned);
+        assert_close(&skinned[0].position, &[-1.0, 1.0, 0.0]);
+        assert_close(&skinned[0].normal, &[-1.0, 0.0, 0.0]);
+        assert_close(&skinned[1].position, &[0.0; 3]);
+
+        let aabb = skinned_aabb(&skin.joint_bounds(&mesh, skeleton.len()), &matrices);
+        assert_close(&aabb.min, &[-1.0, 0.0, 0.0]);
+        assert_close(&aabb.max, &[0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn morph_weights_sample_and_blend() {
+        // Two targets, keys at 0 and 1: (0, 1) -> (1, 0)
+        let morph = MorphChannel::new(
+            Interpolation::Linear,
+            vec![0.0, 1.0],
+            2,
+            vec![0.0, 1.0, 1.0, 0.0],
+        )
+        .unwrap();
+        let clip = AnimationClip::new(None, Vec::new()).with_morph(morph);
+        assert_eq!(clip.duration, 1.0);
+        let mut weights = [0.0; 3];
+        clip.morph.as_ref().unwrap().sample(0.25, &mut weights);
+        assert_close(&weights, &[0.25, 0.75, 0.0]);
+
+        let still = AnimationClip::default();
+        let (mut out, mut scratch) = (Vec::new(), Vec::new());
+        blend_morph_weights(
+            &[(&clip, 1.0, 1.0), (&still, 0.0, 1.0)],
+            &[0.0, 0.5],
+            &mut out,
+            &mut scratch,
+        );
+        assert_close(&out, &[0.5, 0.25]);
+        assert!(MorphChannel::new(Interpolation::Step, vec![0.0], 2, vec![1.0]).is_err());
+    }
+
+    #[test]
+    fn invalid_skeletons_are_rejected() {
+        let joint = |parent| Joint {
+            name: None,
+            parent,
+            rest: JointTransform::IDENTITY,
+        };
+        assert!(
+            Skeleton::new(
+                vec![joint(Some(1)), joint(Some(0))],
+                vec![IDENTITY; 2],
+                IDENTITY
+            )
+            .is_err()
+        );
+        assert!(Skeleton::new(vec![joint(Some(5))], vec![IDENTITY], IDENTITY).is_err());
+        assert!(Skeleton::new(vec![joint(None)], vec![], IDENTITY).is_err());
+        // Children listed before their parents are fine
+        assert!(
+            Skeleton::new(
+                vec![joint(Some(1)), joint(None)],
+                vec![IDENTITY; 2],
+                IDENTITY
+            )
+            .is_ok()
+        );
+    }
+}
@@ -0,0 +1,1711 @@
+//! J1: glTF 2.0 importer (`.gltf` + `.bin`/images, data URIs, or binary `.glb`).
+//!
+//! Produces one `MeshData` per primitive (tangent/`TEXCOORD_1`/`COLOR_0`/skin data kept as
+//! extra vertex streams), PBR metallic-roughness materials, decoded textures (PNG; embedded
+//! or external), the node hierarchy with local transforms, cameras, `KHR_lights_punctual`
+//! lights, skins (`JOINTS_0`/`WEIGHTS_0`, inverse bind matrices) and animations;
+//! [`GltfScene::skeleton`] / [`GltfScene::animation_clip`] convert the latter to
+//! [`crate::animation`] types. Everything is resolved offline: external files are read
+//! relative to the `.gltf` file, no network access.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::animation::{
+    AnimationClip, Channel, Interpolation, Joint, JointTransform, Keyframes, MorphChannel,
+    Skeleton, SkinWeights,
+};
+use crate::material::AlphaMode;
+use crate::mesh::{MeshData, MeshVertex, MorphTarget};
+use crate::normals::{NormalMode, NormalOptions, generate_normals};
+use crate::texture::{AddressMode, FilterMode, SamplerDesc, TextureData};
+use crate::value::{self, Value};
+use crate::vertex::VertexStreams;
+
+/// Column-major 4x4 identity.
+pub const IDENTITY: [f32; 16] = [
+    1.0, 0.0, 0.0, 0.0, //
+    0.0, 1.0, 0.0, 0.0, //
+    0.0, 0.0, 1.0, 0.0, //
+    0.0, 0.0, 0.0, 1.0,
+];
+
+#[derive(Clone, Debug, Default)]
+pub struct GltfScene {
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<GltfMaterial>,
+    pub textures: Vec<GltfTexture>,
+    /// Decoded images (RGBA8), referenced by `GltfTexture::image`.
+    pub images: Vec<TextureData>,
+    pub nodes: Vec<GltfNode>,
+    /// Root nodes of the default scene.
+    pub roots: Vec<usize>,
+    pub cameras: Vec<GltfCamera>,
+    pub lights: Vec<GltfLight>,
+    pub skins: Vec<GltfSkin>,
+    pub animations: Vec<GltfAnimation>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct GltfMesh {
+    pub name: Option<String>,
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+/// One draw of a mesh: geometry with a single material.
+#[derive(Clone, Debug)]
+pub struct GltfPrimitive {
+    pub mesh: MeshData,
+    /// Index into `GltfScene::materials` (`None` = glTF default material).
+    pub material: Option<usize>,
+    /// Joint influences (indices into the node's `GltfSkin::joints`), if skinned.
+    pub skin: Option<SkinWeights>,
+}
+
+/// PBR metallic-roughness material. Texture fields index `GltfScene::textures`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfMaterial {
+    pub name: Option<String>,
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: [f32; 3],
+    pub base_color_texture: Option<usize>,
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub occlusion_texture: Option<usize>,
+    pub emissive_texture: Option<usize>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+
+impl Default for GltfMaterial {
+    // Values the glTF spec uses for missing properties
+    fn default() -> Self {
+        Self {
+            name: None,
+            base_color: [1.0; 4],
+            metallic: 1.0,
+            roughness: 1.0,
+            emissive: [0.0; 3],
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            occlusion_texture: None,
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false,
+        }
+    }
+}
+
+/// Image plus sampler state (raw GL enums as in the file, `None` = unspecified).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GltfTexture {
+    pub image: usize,
+    pub mag_filter: Option<u32>,
+    pub min_filter: Option<u32>,
+    pub wrap_s: u32,
+    pub wrap_t: u32,
+}
+
+/// GL `REPEAT`, the default wrap mode.
+pub const WRAP_REPEAT: u32 = 10497;
+
+/// Largest element count accepted for an accessor without a bufferView (zero-filled).
+const MAX_UNBACKED_ELEMENTS: usize = 1 << 24;
+
+impl GltfTexture {
+    /// E2: sampler descriptor for the GL enums (unspecified filters are linear).
+    pub fn sampler(&self) -> SamplerDesc {
+        let address = |wrap| match wrap {
+            33071 => AddressMode::ClampToEdge,
+            33648 => AddressMode::MirrorRepeat,
+            _ => AddressMode::Repeat,
+        };
+        // NEAREST, NEAREST_MIPMAP_NEAREST, NEAREST_MIPMAP_LINEAR
+        let nearest = |filter: Option<u32>| matches!(filter, Some(9728 | 9984 | 9986));
+        let filter = |nearest| {
+            if nearest {
+                FilterMode::Nearest
+            } else {
+                FilterMode::Linear
+            }
+        };
+        SamplerDesc {
+            address_u: address(self.wrap_s),
+            address_v: address(self.wrap_t),
+            mag_filter: filter(nearest(self.mag_filter)),
+            min_filter: filter(nearest(self.min_filter)),
+            // Only *_MIPMAP_LINEAR (or no filter given) blends between levels
+            mipmap_filter: filter(matches!(self.min_filter, Some(9728 | 9729 | 9984 | 9985))),
+            // NEAREST / LINEAR don't mipmap at all
+            mipmaps: !matches!(self.min_filter, Some(9728 | 9729)),
+            ..SamplerDesc::default()
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NodeTransform {
+    /// Column-major matrix.
+    Matrix([f32; 16]),
+    /// Translation, rotation quaternion (x, y, z, w), scale.
+    Trs {
+        translation: [f32; 3],
+        rotation: [f32; 4],
+        scale: [f32; 3],
+    },
+}
+
+impl NodeTransform {
+    /// Column-major local matrix (`T * R * S` for TRS).
+    pub fn matrix(&self) -> [f32; 16] {
+        match *self {
+            NodeTransform::Matrix(m) => m,
+            NodeTransform::Trs {
+                translation,
+                rotation,
+                scale,
+            } => JointTransform {
+                translation,
+                rotation,
+                scale,
+            }
+            .matrix(),
+        }
+    }
+
+    /// TRS form; matrices are decomposed (assumes no shear).
+    pub fn to_joint_transform(&self) -> JointTransform {
+        let m = match *self {
+            NodeTransform::Trs {
+                translation,
+                rotation,
+                scale,
+            } => {
+                return JointTransform {
+                    translation,
+                    rotation,
+                    scale,
+                };
+            }
+            NodeTransform::Matrix(m) => m,
+        };
+        let column = |c: usize| [m[c * 4], m[c * 4 + 1], m[c * 4 + 2]];
+        let length = |v: [f32; 3]| (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]).sqrt();
+        let scale = [length(column(0)), length(column(1)), length(column(2))];
+        // Rotation matrix r[row][col] with the scale divided out
+        let r = |row: usize, col: usize| {
+            if scale[col] > 0.0 {
+                m[col * 4 + row] / scale[col]
+            } else {
+                0.0
+            }
+        };
+        let trace = r(0, 0) + r(1, 1) + r(2, 2);
+        let rotation = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            [
+                (r(2, 1) - r(1, 2)) / s,
+                (r(0, 2) - r(2, 0)) / s,
+                (r(1, 0) - r(0, 1)) / s,
+                0.25 * s,
+            ]
+        } else if r(0, 0) > r(1, 1) && r(0, 0) > r(2, 2) {
+            let s = (1.0 + r(0, 0) - r(1, 1) - r(2, 2)).sqrt() * 2.0;
+            [
+                0.25 * s,
+                (r(0, 1) + r(1, 0)) / s,
+                (r(0, 2) + r(2, 0)) / s,
+                (r(2, 1) - r(1, 2)) / s,
+            ]
+        } else if r(1, 1) > r(2, 2) {
+            let s = (1.0 + r(1, 1) - r(0, 0) - r(2, 2)).sqrt() * 2.0;
+            [
+                (r(0, 1) + r(1, 0)) / s,
+                0.25 * s,
+                (r(1, 2) + r(2, 1)) / s,
+                (r(0, 2) - r(2, 0)) / s,
+            ]
+        } else {
+            let s = (1.0 + r(2, 2) - r(0, 0) - r(1, 1)).sqrt() * 2.0;
+            [
+                (r(0, 2) + r(2, 0)) / s,
+                (r(1, 2) + r(2, 1)) / s,
+                0.25 * s,
+                (r(1, 0) - r(0, 1)) / s,
+            ]
+        };
+        JointTransform {
+            translation: [m[12], m[13], m[14]],
+            rotation,
+            scale,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfNode {
+    pub name: Option<String>,
+    pub transform: NodeTransform,
+    pub children: Vec<usize>,
+    pub mesh: Option<usize>,
+    pub camera: Option<usize>,
+    pub light: Option<usize>,
+    /// Skin used by this node's mesh.
+    pub skin: Option<usize>,
+}
+
+/// Joint nodes with their inverse bind matrices (identity when the file has none).
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfSkin {
+    pub name: Option<String>,
+    pub joints: Vec<usize>,
+    pub inverse_bind: Vec<[f32; 16]>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimationPath {
+    Translation,
+    Rotation,
+    Scale,
+    /// Morph target weights.
+    Weights,
+}
+
+/// One animated node property with its resolved sampler.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfChannel {
+    pub node: usize,
+    pub path: AnimationPath,
+    pub interpolation: Interpolation,
+    pub times: Vec<f32>,
+    /// Flat output values (`times.len()` elements, three per key for cubic splines).
+    pub values: Vec<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfAnimation {
+    pub name: Option<String>,
+    pub channels: Vec<GltfChannel>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Projection {
+    Perspective {
+        /// Vertical field of view in radians.
+        yfov: f32,
+        aspect_ratio: Option<f32>,
+        znear: f32,
+        /// `None` = infinite far plane.
+        zfar: Option<f32>,
+    },
+    Orthographic {
+        xmag: f32,
+        ymag: f32,
+        znear: f32,
+        zfar: f32,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfCamera {
+    pub name: Option<String>,
+    pub projection: Projection,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LightKind {
+    Directional,
+    Point,
+    /// Cone angles in radians.
+    Spot {
+        inner_cone: f32,
+        outer_cone: f32,
+    },
+}
+
+/// `KHR_lights_punctual` light (points down its node's -Z axis).
+#[derive(Clone, Debug, PartialEq)]
+pub struct GltfLight {
+    pub name: Option<String>,
+    pub kind: LightKind,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// `None` = infinite range.
+    pub range: Option<f32>,
+}
+
+impl GltfScene {
+    /// World matrix of every node (column-major); nodes outside the scene get identity.
+    pub fn world_matrices(&self) -> Vec<[f32; 16]> {
+        let mut world = vec![IDENTITY; self.nodes.len()];
+        let mut stack: Vec<(usize, [f32; 16])> =
+            self.roots.iter().map(|&r| (r, IDENTITY)).collect();
+        // Bounded by node count, so a malformed cyclic hierarchy cannot loop forever
+        let mut budget = self.nodes.len();
+        while let Some((node, parent)) = stack.pop() {
+            if budget == 0 {
+                break;
+            }
+            budget -= 1;
+            let m = mat_mul(&parent, &self.nodes[node].transform.matrix());
+            world[node] = m;
+            stack.extend(self.nodes[node].children.iter().map(|&c| (c, m)));
+        }
+        world
+    }
+
+    /// Total primitive count over all meshes.
+    pub fn primitive_count(&self) -> usize {
+        self.meshes.iter().map(|m| m.primitives.len()).sum()
+    }
+
+    /// Parent of every node (`None` for roots).
+    pub fn parents(&self) -> Vec<Option<usize>> {
+        let mut parents = vec![None; self.nodes.len()];
+        for (i, node) in self.nodes.iter().enumerate() {
+            for &c in &node.children {
+                parents[c] = Some(i);
+            }
+        }
+        parents
+    }
+
+    /// Skeleton of `skin`: joint parents follow the node hierarchy, rest transforms are the
+    /// joints' node transforms and the world matrix of the first root joint's parent node
+    /// becomes [`Skeleton::root`]. The skinned mesh node's own transform is left to the caller.
+    pub fn skeleton(&self, skin: usize) -> Result<Skeleton> {
+        let skin = self
+            .skins
+            .get(skin)
+            .ok_or_else(|| anyhow!("skin {skin} does not exist"))?;
+        let parents = self.parents();
+        let joint_of = |node: usize| skin.joints.iter().position(|&j| j == node);
+        let mut root = None;
+        let joints = skin
+            .joints
+            .iter()
+            .map(|&node| {
+                let parent = parents[node].and_then(joint_of);
+                if parent.is_none() && root.is_none() {
+                    root = Some(parents[node]);
+                }
+                Joint {
+                    name: self.nodes[node].name.clone(),
+                    parent,
+                    rest: self.nodes[node].transform.to_joint_transform(),
+                }
+            })
+            .collect();
+        let root = match root.flatten() {
+            Some(node) => self.world_matrices()[node],
+            None => IDENTITY,
+        };
+        Skeleton::new(joints, skin.inverse_bind.clone(), root)
+    }
+
+    /// Clip of `animation` for the mesh on `node`: channels targeting joints of the node's
+    /// skin plus the node's morph weight channel. Channels of other nodes are skipped.
+    pub fn animation_clip(&self, animation: usize, node: usize) -> Result<AnimationClip> {
+        let a = self
+            .animations
+            .get(animation)
+            .ok_or_else(|| anyhow!("animation {animation} does not exist"))?;
+        let n = self
+            .nodes
+            .get(node)
+            .ok_or_else(|| anyhow!("node {node} does not exist"))?;
+        let joints = n
+            .skin
+            .and_then(|s| self.skins.get(s))
+            .map_or(&[][..], |s| &s.joints);
+        let mut channels = Vec::new();
+        let mut morph = None;
+        for c in &a.channels {
+            if c.path == AnimationPath::Weights {
+                if c.node == node && !c.times.is_empty() {
+                    let per_key = if c.interpolation == Interpolation::CubicSpline {
+                        3
+                    } else {
+                        1
+                    };
+                    let targets = c.values.len() / (c.times.len() * per_key);
+                    morph = Some(MorphChannel::new(
+                        c.interpolation,
+                        c.times.clone(),
+                        targets,
+                        c.values.clone(),
+                    )?);
+                }
+                continue;
+            }
+            let Some(joint) = joints.iter().position(|&j| j == c.node) else {
+                continue;
+            };
+            let keys = match c.path {
+                AnimationPath::Translation => Keyframes::Translation(chunks(&c.values)?),
+                AnimationPath::Rotation => Keyframes::Rotation(chunks(&c.values)?),
+                AnimationPath::Scale => Keyframes::Scale(chunks(&c.values)?),
+                AnimationPath::Weights => unreachable!("handled above"),
+            };
+            channels.push(Channel::new(joint, c.interpolation, c.times.clone(), keys)?);
+        }
+        let clip = AnimationClip::new(a.name.clone(), channels);
+        Ok(match morph {
+            Some(morph) => clip.with_morph(morph),
+            None => clip,
+        })
+    }
+}
+
+fn chunks<const N: usize>(values: &[f32]) -> Result<Vec<[f32; N]>> {
+    if !values.len().is_multiple_of(N) {
+        bail!("{} values do not split into groups of {N}", values.len());
+    }
+    Ok(values
+        .chunks_exact(N)
+        .map(|c| c.try_into().expect("chunk of N"))
+        .collect())
+}
+
+/// Column-major `a * b`.
+pub fn mat_mul(a: &[f32; 16], b: &[f32; 16]) -> [f32; 16] {
+    let mut out = [0.0; 16];
+    for col in 0..4 {
+        for row in 0..4 {
+            out[col * 4 + row] = (0..4).map(|k| a[k * 4 + row] * b[col * 4 + k]).sum();
+        }
+    }
+    out
+}
+
+/// Load a `.gltf` or `.glb` file (detected from the content).
+pub fn load_gltf(path: impl AsRef<Path>) -> Result<GltfScene> {
+    let path = path.as_ref();
+    let bytes =
+        fs::read(path).with_context(|| format!("Failed to read glTF file {}", path.display()))?;
+    load_gltf_from_slice(&bytes, path.parent())
+        .with_context(|| format!("Failed to load glTF file {}", path.display()))
+}
+
+/// Load glTF JSON or GLB bytes; external URIs are resolved against `base_dir`.
+pub fn load_gltf_from_slice(bytes: &[u8], base_dir: Option<&Path>) -> Result<GltfScene> {
+    let (json, bin) = if bytes.starts_with(b"glTF") {
+        parse_glb(bytes)?
+    } else {
+        (bytes, None)
+    };
+    let json = std::str::from_utf8(json).context("glTF JSON is not valid UTF-8")?;
+    let root = value::parse(json).context("Invalid glTF JSON")?;
+    let version = root
+        .get("asset")
+        .and_then(|a| a.get("version"))
+        .and_then(Value::as_str)
+        .ok_or_else(|| anyhow!("missing asset.version"))?;
+    if !version.starts_with("2.") {
+        bail!("unsupported glTF version {version}");
+    }
+    let mut doc = Document {
+        root: &root,
+        base_dir: base_dir.map(Path::to_path_buf),
+        buffers: Vec::new(),
+    };
+    doc.load_buffers(bin)?;
+    doc.scene()
+}
+
+// GLB container: 12-byte header, JSON chunk, optional BIN chunk.
+fn parse_glb(bytes: &[u8]) -> Result<(&[u8], Option<&[u8]>)> {
+    let word = |at: usize| -> Result<u32> {
+        bytes
+            .get(at..at + 4)
+            .map(|b| u32::from_le_bytes(b.try_into().expect("4 bytes")))
+            .ok_or_else(|| anyhow!("truncated GLB at byte {at}"))
+    };
+    let version = word(4)?;
+    if version != 2 {
+        bail!("unsupported GLB version {version}");
+    }
+    let total = (word(8)? as usize).min(bytes.len());
+    let mut at = 12;
+    let (mut json, mut bin) = (None, None);
+    while at + 8 <= total {
+        let len = word(at)? as usize;
+        let kind = word(at + 4)?;
+        let chunk = bytes
+            .get(at + 8..at + 8 + len)
+            .ok_or_else(|| anyhow!("GLB chunk at byte {at} runs past the end"))?;
+        match kind {
+            0x4E4F_534A => json = json.or(Some(chunk)), // "JSON"
+            0x004E_4942 => bin = bin.or(Some(chunk)),   // "BIN\0"
+            _ => {}                                     // unknown chunks are skipped
+        }
+        at += 8 + len;
+    }
+    Ok((json.ok_or_else(|| anyhow!("GLB has no JSON chunk"))?, bin))
+}
+
+struct Document<'a> {
+    root: &'a Value,
+    base_dir: Option<PathBuf>,
+    buffers: Vec<Vec<u8>>,
+}
+
+/// Accessor resolved against its bufferView: `count` elements of `components` values,
+/// `size` bytes each. `bytes` is `None` for an all-zero accessor without a bufferView.
+struct Accessor<'a> {
+    bytes: Option<&'a [u8]>,
+    offset: usize,
+    stride: usize,
+    count: usize,
+    components: usize,
+    component_type: usize,
+    size: usize,
+    normalized: bool,
+}
+
+impl Accessor<'_> {
+    // Bytes of every component in order (bounds were checked when resolving).
+    fn for_each(&self, mut f: impl FnMut(&[u8])) {
+        const ZERO: [u8; 4] = [0; 4];
+        for i in 0..self.count {
+            let base = self.offset + i * self.stride;
+            for c in 0..self.components {
+                f(match self.bytes {
+                    Some(bytes) => &bytes[base + c * self.size..base + (c + 1) * self.size],
+                    None => &ZERO[..self.size],
+                });
+            }
+        }
+    }
+}
+
+// Elements of a top-level array (`meshes`, `nodes`, ...); missing = empty.
+fn array<'a>(obj: &'a Value, key: &str) -> &'a [Value] {
+    obj.get(key).and_then(Value::as_seq).unwrap_or(&[])
+}
+
+fn name(obj: &Value) -> Option<String> {
+    obj.get("name").and_then(Value::as_str).map(str::to_string)
+}
+
+fn index(obj: &Value, key: &str) -> Result<Option<usize>> {
+    obj.get(key)
+        .map(|v| {
+            v.as_usize()
+                .ok_or_else(|| anyhow!("'{key}' must be an index"))
+        })
+        .transpose()
+}
+
+fn number(obj: &Value, key: &str, default: f32) -> Result<f32> {
+    obj.get(key).map_or(Ok(default), |v| {
+        v.as_f32()
+            .ok_or_else(|| anyhow!("'{key}' must be a number"))
+    })
+}
+
+fn numbers<const N: usize>(obj: &Value, key: &str, default: [f32; N]) -> Result<[f32; N]> {
+    let Some(v) = obj.get(key) else {
+        return Ok(default);
+    };
+    value::floats(v)?
+        .try_into()
+        .map_err(|_| anyhow!("'{key}' must have {N} numbers"))
+}
+
+// Index of `textureInfo.index`.
+fn texture_ref(obj: &Value, key: &str) -> Result<Option<usize>> {
+    obj.get(key).map_or(Ok(None), |info| index(info, "index"))
+}
+
+impl Document<'_> {
+    fn load_buffers(&mut self, glb_bin: Option<&[u8]>) -> Result<()> {
+        for (i, buffer) in array(self.root, "buffers").iter().enumerate() {
+            let data = match buffer.get("uri").and_then(Value::as_str) {
+                Some(uri) => self.read_uri(uri).with_context(|| format!("buffer {i}"))?,
+                // The GLB binary chunk is the first buffer without a URI
+                None if i == 0 => glb_bin
+                    .ok_or_else(|| anyhow!("buffer 0 has no uri and there is no GLB binary chunk"))?
+                    .to_vec(),
+                None => bail!("buffer {i} has no uri"),
+            };
+            let byte_length = index(buffer, "byteLength")?.unwrap_or(0);
+            if data.len() < byte_length {
+                bail!(
+                    "buffer {i} has {} bytes, byteLength says {byte_length}",
+                    data.len()
+                );
+            }
+            self.buffers.push(data);
+        }
+        Ok(())
+    }
+
+    // `data:` URI (base64) or a file next to the glTF.
+    fn read_uri(&self, uri: &str) -> Result<Vec<u8>> {
+        if let Some(rest) = uri.strip_prefix("data:") {
+            let (_, payload) = rest
+                .split_once(";base64,")
+                .ok_or_else(|| anyhow!("only base64 data URIs are supported"))?;
+            return decode_base64(payload);
+        }
+        if uri.contains("://") {
+            bail!("remote URI {uri} is not supported");
+        }
+        let path = percent_decode(uri);
+        let path = match &self.base_dir {
+            Some(dir) => dir.join(path),
+            None => PathBuf::from(path),
+        };
+        fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))
+    }
+
+    fn buffer_view(&self, view: usize) -> Result<(&[u8], Option<usize>)> {
+        let v = array(self.root, "bufferViews")
+            .get(view)
+            .ok_or_else(|| anyhow!("bufferView {view} does not exist"))?;
+        let buffer =
+            index(v, "buffer")?.ok_or_else(|| anyhow!("bufferView {view} has no buffer"))?;
+        let data = self
+            .buffers
+            .get(buffer)
+            .ok_or_else(|| anyhow!("buffer {buffer} does not exist"))?;
+        let offset = index(v, "byteOffset")?.unwrap_or(0);
+        let length = index(v, "byteLength")?
+            .ok_or_else(|| anyhow!("bufferView {view} has no byteLength"))?;
+        let bytes = offset
+            .checked_add(length)
+            .and_then(|end| data.get(offset..end))
+            .ok_or_else(|| anyhow!("bufferView {view} is out of buffer {buffer} bounds"))?;
+        Ok((bytes, index(v, "byteStride")?))
+    }
+
+    /// Accessor elements as floats (normalized integers mapped to [0,1] / [-1,1]).
+    fn read_floats(&self, accessor: usize) -> Result<(Vec<f32>, usize)> {
+        let a = self.accessor(accessor)?;
+        let mut values = Vec::with_capacity(a.count * a.components);
+        a.for_each(|b| {
+            values.push(match a.component_type {
+                5120 => {
+                    let v = b[0] as i8 as f32;
+                    if a.normalized {
+                        (v / 127.0).max(-1.0)
+                    } else {
+                        v
+                    }
+                }
+                5121 => {
+                    let v = b[0] as f32;
+                    if a.normalized { v / 255.0 } else { v }
+                }
+                5122 => {
+                    let v = i16::from_le_bytes([b[0], b[1]]) as f32;
+                    if a.normalized {
+                        (v / 32767.0).max(-1.0)
+                    } else {
+                        v
+                    }
+                }
+                5123 => {
+                    let v = u16::from_le_bytes([b[0], b[1]]) as f32;
+                    if a.normalized { v / 65535.0 } else { v }
+                }
+                5125 => u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32,
+                _ => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            })
+        });
+        Ok((values, a.components))
+    }
+
+    /// Accessor elements of an unsigned integer type, read exactly (indices, joints).
+    fn read_uints(&self, accessor: usize) -> Result<(Vec<u32>, usize)> {
+        let a = self.accessor(accessor)?;
+        if !matches!(a.component_type, 5121 | 5123 | 5125) {
+            bail!("accessor {accessor} must have an unsigned integer componentType");
+        }
+        let mut values = Vec::with_capacity(a.count * a.components);
+        a.for_each(|b| {
+            values.push(match a.component_type {
+                5121 => b[0] as u32,
+                5123 => u16::from_le_bytes([b[0], b[1]]) as u32,
+                _ => u32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+            })
+        });
+        Ok((values, a.components))
+    }
+
+    fn read_indices(&self, accessor: usize) -> Result<Vec<u32>> {
+        let (values, components) = self.read_uints(accessor)?;
+        if components != 1 {
+            bail!("index accessor {accessor} must be SCALAR");
+        }
+        Ok(values)
+    }
+
+    // Accessor layout, checked against its bufferView.
+    fn accessor(&self, accessor: usize) -> Result<Accessor<'_>> {
+        let a = array(self.root, "accessors")
+            .get(accessor)
+            .ok_or_else(|| anyhow!("accessor {accessor} does not exist"))?;
+        if a.get("sparse").is_some() {
+            bail!("accessor {accessor}: sparse accessors are not supported");
+        }
+        let count =
+            index(a, "count")?.ok_or_else(|| anyhow!("accessor {accessor} has no count"))?;
+        let components = match a.get("type").and_then(Value::as_str) {
+            Some("SCALAR") => 1,
+            Some("VEC2") => 2,
+            Some("VEC3") => 3,
+            Some("VEC4") | Some("MAT2") => 4,
+            Some("MAT3") => 9,
+            Some("MAT4") => 16,
+            other => bail!("accessor {accessor}: unknown type {other:?}"),
+        };
+        let component_type = index(a, "componentType")?.unwrap_or(0);
+        let size = match component_type {
+            5120 | 5121 => 1,
+            5122 | 5123 => 2,
+            5125 | 5126 => 4,
+            other => bail!("accessor {accessor}: unknown componentType {other}"),
+        };
+        let normalized = a
+            .get("normalized")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let element = size * components;
+
+        // No bufferView: all zeros. Nothing bounds the count here, so cap it.
+        let Some(view) = index(a, "bufferView")? else {
+            if count > MAX_UNBACKED_ELEMENTS {
+                bail!("accessor {accessor}: {count} elements without a bufferView");
+            }
+            return Ok(Accessor {
+                bytes: None,
+                offset: 0,
+                stride: element,
+                count,
+                components,
+                component_type,
+                size,
+                normalized,
+            });
+        };
+        let (bytes, stride) = self.buffer_view(view)?;
+        let offset = index(a, "byteOffset")?.unwrap_or(0);
+        let stride = stride.unwrap_or(element);
+        if stride < element {
+            bail!(
+                "accessor {accessor}: byteStride {stride} is smaller than an element ({element} bytes)"
+            );
+        }
+        let end = match count.checked_sub(1) {
+            Some(last) => stride
+                .checked_mul(last)
+                .and_then(|x| x.checked_add(offset))
+                .and_then(|x| x.checked_add(element)),
+            None => Some(0),
+        };
+        if end.is_none_or(|end| end > bytes.len()) {
+            bail!("accessor {accessor} reads past the end of bufferView {view}");
+        }
+        Ok(Accessor {
+            bytes: Some(bytes),
+            offset,
+            stride,
+            count,
+            components,
+            component_type,
+            size,
+            normalized,
+        })
+    }
+
+    fn scene(&self) -> Result<GltfScene> {
+        let mut scene = GltfScene::default();
+        for (i, image) in array(self.root, "images").iter().enumerate() {
+            scene
+                .images
+                .push(self.image(image).with_context(|| format!("image {i}"))?);
+        }
+        for (i, texture) in array(self.root, "textures").iter().enumerate() {
+            scene.textures.push(
+                self.texture(texture, scene.images.len())
+                    .with_context(|| format!("texture {i}"))?,
+            );
+        }
+        for (i, material) in array(self.root, "materials").iter().enumerate() {
+            scene
+                .materials
+                .push(material_from(material).with_context(|| format!("material {i}"))?);
+        }
+        for (i, mesh) in array(self.root, "meshes").iter().enumerate() {
+            scene
+                .meshes
+                .push(self.mesh(mesh).with_context(|| format!("mesh {i}"))?);
+        }
+        for (i, camera) in array(self.root, "cameras").iter().enumerate() {
+            scene
+                .cameras
+                .push(camera_from(camera).with_context(|| format!("camera {i}"))?);
+        }
+        let lights = self
+            .root
+            .get("extensions")
+            .and_then(|e| e.get("KHR_lights_punctual"))
+            .map_or(&[][..], |ext| array(ext, "lights"));
+        for (i, light) in lights.iter().enumerate() {
+            scene
+                .lights
+                .push(light_from(light).with_context(|| format!("light {i}"))?);
+        }
+        for (i, node) in array(self.root, "nodes").iter().enumerate() {
+            scene
+                .nodes
+                .push(node_from(node).with_context(|| format!("node {i}"))?);
+        }
+        for (i, skin) in array(self.root, "skins").iter().enumerate() {
+            scene
+                .skins
+                .push(self.skin(skin).with_context(|| format!("skin {i}"))?);
+        }
+        for (i, animation) in array(self.root, "animations").iter().enumerate() {
+            scene.animations.push(
+                self.animation(animation)
+                    .with_context(|| format!("animation {i}"))?,
+            );
+        }
+
+        // References between top-level objects
+        let check = |what: &str, i: usize, link: Option<usize>, len: usize| match link {
+            Some(l) if l >= len => Err(anyhow!("{what} {i} references missing index {l}")),
+            _ => Ok(()),
+        };
+        for (i, node) in scene.nodes.iter().enumerate() {
+            check("node", i, node.mesh, scene.meshes.len())?;
+            check("node", i, node.camera, scene.cameras.len())?;
+            check("node", i, node.light, scene.lights.len())?;
+            check("node", i, node.skin, scene.skins.len())?;
+            for &child in &node.children {
+                check("node", i, Some(child), scene.nodes.len())?;
+            }
+        }
+        for (i, material) in scene.materials.iter().enumerate() {
+            for texture in [
+                material.base_color_texture,
+                material.metallic_roughness_texture,
+                material.normal_texture,
+                material.occlusion_texture,
+                material.emissive_texture,
+            ] {
+                check("material", i, texture, scene.textures.len())?;
+            }
+        }
+        for (i, mesh) in scene.meshes.iter().enumerate() {
+            for primitive in &mesh.primitives {
+                check("mesh", i, primitive.material, scene.materials.len())?;
+            }
+        }
+        for (i, skin) in scene.skins.iter().enumerate() {
+            for &joint in &skin.joints {
+                check("skin", i, Some(joint), scene.nodes.len())?;
+            }
+        }
+        for (i, animation) in scene.animations.iter().enumerate() {
+            for channel in &animation.channels {
+                check("animation", i, Some(channel.node), scene.nodes.len())?;
+            }
+        }
+
+        scene.roots = self.roots(&scene.nodes)?;
+        Ok(scene)
+    }
+
+    // Root nodes of the default scene; without scenes, every node that is nobody's child.
+    fn roots(&self, nodes: &[GltfNode]) -> Result<Vec<usize>> {
+        let scenes = array(self.root, "scenes");
+        if scenes.is_empty() {
+            let mut is_child = vec![false; nodes.len()];
+            for &c in nodes.iter().flat_map(|n| &n.children) {
+                is_child[c] = true;
+            }
+            return Ok((0..nodes.len()).filter(|&i| !is_child[i]).collect());
+        }
+        let default = index(self.root, "scene")?.unwrap_or(0);
+        let scene = scenes
+            .get(default)
+            .ok_or_else(|| anyhow!("default scene {default} does not exist"))?;
+        let roots: Vec<usize> = array(scene, "nodes")
+            .iter()
+            .map(|n| {
+                n.as_usize()
+                    .filter(|&n| n < nodes.len())
+                    .ok_or_else(|| anyhow!("invalid scene node"))
+            })
+            .collect::<Result<_>>()?;
+        Ok(roots)
+    }
+
+    fn image(&self, image: &Value) -> Result<TextureData> {
+        let bytes = match (
+            image.get("uri").and_then(Value::as_str),
+            index(image, "bufferView")?,
+        ) {
+            (Some(uri), _) => self.read_uri(uri)?,
+            (None, Some(view)) => self.buffer_view(view)?.0.to_vec(),
+            (None, None) => bail!("image has neither uri nor bufferView"),
+        };
+        let png = bytes.starts_with(b"\x89PNG");
+        let mime = image.get("mimeType").and_then(Value::as_str);
+        if !png {
+            bail!("unsupported image format (mimeType {mime:?}); only PNG is supported");
+        }
+        TextureData::from_png_bytes(&bytes)
+    }
+
+    fn texture(&self, texture: &Value, image_count: usize) -> Result<GltfTexture> {
+        let image =
+            index(texture, "source")?.ok_or_else(|| anyhow!("texture has no source image"))?;
+        if image >= image_count {
+            bail!("texture source {image} does not exist");
+        }
+        let sampler = match index(texture, "sampler")? {
+            Some(s) => Some(
+                array(self.root, "samplers")
+                    .get(s)
+                    .ok_or_else(|| anyhow!("sampler {s} does not exist"))?,
+            ),
+            None => None,
+        };
+        let field = |key: &str| -> Result<Option<u32>> {
+            Ok(match sampler {
+                Some(s) => index(s, key)?.map(|v| v as u32),
+                None => None,
+            })
+        };
+        Ok(GltfTexture {
+            image,
+            mag_filter: field("magFilter")?,
+            min_filter: field("minFilter")?,
+            wrap_s: field("wrapS")?.unwrap_or(WRAP_REPEAT),
+            wrap_t: field("wrapT")?.unwrap_or(WRAP_REPEAT),
+        })
+    }
+
+    fn skin(&self, skin: &Value) -> Result<GltfSkin> {
+        let joints: Vec<usize> = array(skin, "joints")
+            .iter()
+            .map(|j| {
+                j.as_usize()
+                    .ok_or_else(|| anyhow!("joint must be a node index"))
+            })
+            .collect::<Result<_>>()?;
+        if joints.is_empty() {
+            bail!("skin has no joints");
+        }
+        let inverse_bind = match index(skin, "inverseBindMatrices")? {
+            Some(accessor) => {
+                let (values, components) = self.read_floats(accessor)?;
+                if components != 16 || values.len() != joints.len() * 16 {
+                    bail!("inverseBindMatrices must be {} MAT4 elements", joints.len());
+                }
+                chunks(&values)?
+            }
+            None => vec![IDENTITY; joints.len()],
+        };
+        Ok(GltfSkin {
+            name: name(skin),
+            joints,
+            inverse_bind,
+        })
+    }
+
+    fn animation(&self, animation: &Value) -> Result<GltfAnimation> {
+        let samplers = array(animation, "samplers");
+        let mut channels = Vec::new();
+        for (i, channel) in array(animation, "channels").iter().enumerate() {
+            let target = channel
+                .get("target")
+                .ok_or_else(|| anyhow!("channel {i} has no target"))?;
+            // Targets without a node belong to extensions (e.g. KHR_animation_pointer)
+            let Some(node) = index(target, "node")? else {
+                continue;
+            };
+            let path = match target.get("path").and_then(Value::as_str) {
+                Some("translation") => AnimationPath::Translation,
+                Some("rotation") => AnimationPath::Rotation,
+                Some("scale") => AnimationPath::Scale,
+                Some("weights") => AnimationPath::Weights,
+                other => bail!("channel {i}: unknown target path {other:?}"),
+            };
+            let sampler = index(channel, "sampler")?
+                .and_then(|s| samplers.get(s))
+                .ok_or_else(|| anyhow!("channel {i} has no valid sampler"))?;
+            let interpolation = match sampler.get("interpolation").and_then(Value::as_str) {
+                None | Some("LINEAR") => Interpolation::Linear,
+                Some("STEP") => Interpolation::Step,
+                Some("CUBICSPLINE") => Interpolation::CubicSpline,
+                Some(other) => bail!("channel {i}: unknown interpolation {other}"),
+            };
+            let input = index(sampler, "input")?
+                .ok_or_else(|| anyhow!("channel {i}: sampler has no input"))?;
+            let output = index(sampler, "output")?
+                .ok_or_else(|| anyhow!("channel {i}: sampler has no output"))?;
+            let (times, _) = self.read_floats(input)?;
+            let (values, _) = self.read_floats(output)?;
+            channels.push(GltfChannel {
+                node,
+                path,
+                interpolation,
+                times,
+                values,
+            });
+        }
+        Ok(GltfAnimation {
+            name: name(animation),
+            channels,
+        })
+    }
+
+    fn mesh(&self, mesh: &Value) -> Result<GltfMesh> {
+        // Morph target defaults and names are per mesh, shared by all primitives
+        let weights = match mesh.get("weights") {
+            Some(w) => value::floats(w)?,
+            None => Vec::new(),
+        };
+        let names: Vec<Option<String>> = mesh
+            .get("extras")
+            .map_or(&[][..], |e| array(e, "targetNames"))
+            .iter()
+            .map(|n| n.as_str().map(str::to_string))
+            .collect();
+        let mut primitives = Vec::new();
+        for (i, primitive) in array(mesh, "primitives").iter().enumerate() {
+            match self
+                .primitive(primitive)
+                .with_context(|| format!("primitive {i}"))?
+            {
+                Some(mut p) => {
+                    for (t, target) in p.mesh.morph_targets.iter_mut().enumerate() {
+                        target.default_weight = weights.get(t).copied().unwrap_or(0.0);
+                        target.name = names.get(t).cloned().flatten();
+                    }
+                    primitives.push(p);
+                }
+                None => log::warn!("Skipping non-triangle glTF primitive {i}"),
+            }
+        }
+        Ok(GltfMesh {
+            name: name(mesh),
+            primitives,
+        })
+    }
+
+    // `None` for points/lines (nothing to draw with the triangle pipeline).
+    fn primitive(&self, primitive: &Value) -> Result<Option<GltfPrimitive>> {
+        let mode = index(primitive, "mode")?.unwrap_or(4);
+        if !(4..=6).contains(&mode) {
+            return Ok(None);
+        }
+        let attributes = primitive
+            .get("attributes")
+            .ok_or_else(|| anyhow!("primitive has no attributes"))?;
+        let position =
+            index(attributes, "POSITION")?.ok_or_else(|| anyhow!("primitive has no POSITION"))?;
+        let (positions, components) = self.read_floats(position)?;
+        if components != 3 {
+            bail!("POSITION must be VEC3");
+        }
+        let count = positions.len() / 3;
+        let attribute = |key: &str, width: usize| -> Result<Option<Vec<f32>>> {
+            let Some(accessor) = index(attributes, key)? else {
+                return Ok(None);
+            };
+            let (values, components) = self.read_floats(accessor)?;
+            if components != width || values.len() != count * width {
+                bail!("{key} must be {count} VEC{width} elements");
+            }
+            Ok(Some(values))
+        };
+        let normals = attribute("NORMAL", 3)?;
+        let uvs = attribute("TEXCOORD_0", 2)?;
+        let tangents = attribute("TANGENT", 4)?;
+        let uv1 = attribute("TEXCOORD_1", 2)?;
+        // COLOR_0 may be RGB or RGBA
+        let colors = match index(attributes, "COLOR_0")? {
+            Some(accessor) => {
+                let (values, components) = self.read_floats(accessor)?;
+                if !(3..=4).contains(&components) || values.len() != count * components {
+                    bail!("COLOR_0 must be {count} VEC3 or VEC4 elements");
+                }
+                let rgba = values
+                    .chunks_exact(components)
+                    .map(|c| [c[0], c[1], c[2], c.get(3).copied().unwrap_or(1.0)]);
+                Some(rgba.collect())
+            }
+            None => None,
+        };
+        let skin = match (index(attributes, "JOINTS_0")?, attribute("WEIGHTS_0", 4)?) {
+            (Some(accessor), Some(weights)) => {
+                let (joints, components) = self.read_uints(accessor)?;
+                if components != 4 || joints.len() != count * 4 {
+                    bail!("JOINTS_0 must be {count} VEC4 elements");
+                }
+                if let Some(&bad) = joints.iter().find(|&&j| j > u16::MAX as u32) {
+                    bail!("JOINTS_0 index {bad} does not fit 16 bits");
+                }
+                let joints = joints
+                    .chunks_exact(4)
+                    .map(|j| [j[0], j[1], j[2], j[3]].map(|x| x as u16))
+                    .collect();
+                Some(SkinWeights::new(joints, chunks(&weights)?)?)
+            }
+            (None, None) => None,
+            _ => bail!("JOINTS_0 and WEIGHTS_0 must be given together"),
+        };
+
+        let vertices: Vec<MeshVertex> = (0..count)
+            .map(|i| {
+                let p = &positions[i * 3..i * 3 + 3];
+                // Missing normals are generated below
+                let n = normals
+                    .as_ref()
+                    .map_or([0.0; 3], |n| [n[i * 3], n[i * 3 + 1], n[i * 3 + 2]]);
+                let uv = uvs
+                    .as_ref()
+                    .map_or([0.0, 0.0], |t| [t[i * 2], t[i * 2 + 1]]);
+                MeshVertex::new([p[0], p[1], p[2]], n, uv)
+            })
+            .collect();
+
+        let raw = match index(primitive, "indices")? {
+            Some(accessor) => self.read_indices(accessor)?,
+            None => (0..count as u32).collect(),
+        };
+        if let Some(&bad) = raw.iter().find(|&&i| i as usize >= count) {
+            bail!("index {bad} is out of range ({count} vertices)");
+        }
+        let indices = match mode {
+            4 => raw,
+            // Triangle strip: flip every other triangle to keep the winding
+            5 => (2..raw.len())
+                .flat_map(|i| {
+                    if i % 2 == 0 {
+                        [raw[i - 2], raw[i - 1], raw[i]]
+                    } else {
+                        [raw[i - 1], raw[i - 2], raw[i]]
+                    }
+                })
+                .collect(),
+            // Triangle fan
+            _ => (2..raw.len())
+                .flat_map(|i| [raw[0], raw[i - 1], raw[i]])
+                .collect(),
+        };
+        if indices.len() % 3 != 0 {
+            bail!("index count {} is not a multiple of 3", indices.len());
+        }
+        // M1: extra streams keep every imported attribute (joints/weights also for static use)
+        let streams = VertexStreams {
+            tangents: tangents.as_deref().map(chunks).transpose()?,
+            uv1: uv1.as_deref().map(chunks).transpose()?,
+            colors,
+            joints: skin.as_ref().map(|s| s.joints.clone()),
+            weights: skin.as_ref().map(|s| s.weights.clone()),
+        };
+        let mut targets = Vec::new();
+        for (t, target) in array(primitive, "targets").iter().enumerate() {
+            let delta = |key: &str| -> Result<Option<Vec<[f32; 3]>>> {
+                let Some(accessor) = index(target, key)? else {
+                    return Ok(None);
+                };
+                let (values, components) = self.read_floats(accessor)?;
+                if components != 3 || values.len() != count * 3 {
+                    bail!("morph target {t}: {key} must be {count} VEC3 elements");
+                }
+                Ok(Some(chunks(&values)?))
+            };
+            targets.push(MorphTarget {
+                name: None,
+                positions: delta("POSITION")?.unwrap_or_else(|| vec![[0.0; 3]; count]),
+                normals: delta("NORMAL")?,
+                tangents: delta("TANGENT")?,
+                default_weight: 0.0,
+            });
+        }
+        let mut mesh = MeshData::new(vertices, indices)
+            .with_morph_targets(targets)?
+            .with_streams(streams)?;
+        let mut skin = skin;
+        // Without NORMAL the spec asks for flat normals; splitting vertices per face also
+        // splits the streams, so the skin is rebuilt from them
+        if normals.is_none() {
+            let flat = NormalOptions {
+                mode: NormalMode::Flat,
+                ..Default::default()
+            };
+            generate_normals(&mut mesh, &flat, None);
+            if let Some(skin) = skin.as_mut() {
+                skin.joints = mesh.streams.joints.clone().unwrap_or_default();
+                skin.weights = mesh.streams.weights.clone().unwrap_or_default();
+            }
+        }
+        Ok(Some(GltfPrimitive {
+            mesh,
+            material: index(primitive, "material")?,
+            skin,
+        }))
+    }
+}
+
+fn material_from(m: &Value) -> Result<GltfMaterial> {
+    let mut material = GltfMaterial {
+        name: name(m),
+        emissive: numbers(m, "emissiveFactor", [0.0; 3])?,
+        normal_texture: texture_ref(m, "normalTexture")?,
+        occlusion_texture: texture_ref(m, "occlusionTexture")?,
+        emissive_texture: texture_ref(m, "emissiveTexture")?,
+        double_sided: m
+            .get("doubleSided")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+        ..Default::default()
+    };
+    if let Some(pbr) = m.get("pbrMetallicRoughness") {
+        material.base_color = numbers(pbr, "baseColorFactor", [1.0; 4])?;
+        material.metallic = number(pbr, "metallicFactor", 1.0)?;
+        material.roughness = number(pbr, "roughnessFactor", 1.0)?;
+        material.base_color_texture = texture_ref(pbr, "baseColorTexture")?;
+        material.metallic_roughness_texture = texture_ref(pbr, "metallicRoughnessTexture")?;
+    }
+    material.alpha_mode = match m.get("alphaMode").and_then(Value::as_str) {
+        None | Some("OPAQUE") => AlphaMode::Opaque,
+        Some("MASK") => AlphaMode::Mask(number(m, "alphaCutoff", 0.5)?),
+        Some("BLEND") => AlphaMode::Blend,
+        Some(other) => bail!("unknown alphaMode {other}"),
+    };
+    Ok(material)
+}
+
+fn node_from(n: &Value) -> Result<GltfNode> {
+    let transform = match n.get("matrix") {
+        Some(_) => NodeTransform::Matrix(numbers(n, "matrix", IDENTITY)?),
+        None => NodeTransform::Trs {
+            translation: numbers(n, "translation", [0.0; 3])?,
+            rotation: numbers(n, "rotation", [0.0, 0.0, 0.0, 1.0])?,
+            scale: numbers(n, "scale", [1.0; 3])?,
+        },
+    };
+    let children = array(n, "children")
+        .iter()
+        .map(|c| {
+            c.as_usize()
+                .ok_or_else(|| anyhow!("child must be a node index"))
+        })
+        .collect::<Result<_>>()?;
+    let light = match n
+        .get("extensions")
+        .and_then(|e| e.get("KHR_lights_punctual"))
+    {
+        Some(ext) => index(ext, "light")?,
+        None => None,
+    };
+    Ok(GltfNode {
+        name: name(n),
+        transform,
+        children,
+        mesh: index(n, "mesh")?,
+        camera: index(n, "camera")?,
+        light,
+        skin: index(n, "skin")?,
+    })
+}
+
+fn camera_from(c: &Value) -> Result<GltfCamera> {
+    let projection = match c.get("type").and_then(Value::as_str) {
+        Some("perspective") => {
+            let p = c
+                .get("perspective")
+                .ok_or_else(|| anyhow!("missing 'perspective'"))?;
+            Projection::Perspective {
+                yfov: number(p, "yfov", 0.0)?,
+                aspect_ratio: p.get("aspectRatio").and_then(Value::as_f32),
+                znear: number(p, "znear", 0.0)?,
+                zfar: p.get("zfar").and_then(Value::as_f32),
+            }
+        }
+        Some("orthographic") => {
+            let o = c
+                .get("orthographic")
+                .ok_or_else(|| anyhow!("missing 'orthographic'"))?;
+            Projection::Orthographic {
+                xmag: number(o, "xmag", 1.0)?,
+                ymag: number(o, "ymag", 1.0)?,
+                znear: number(o, "znear", 0.0)?,
+                zfar: number(o, "zfar", 1.0)?,
+            }
+        }
+        other => bail!("unknown camera type {other:?}"),
+    };
+    Ok(GltfCamera {
+        name: name(c),
+        projection,
+    })
+}
+
+fn light_from(l: &Value) -> Result<GltfLight> {
+    let kind = match l.get("type").and_then(Value::as_str) {
+        Some("directional") => LightKind::Directional,
+        Some("point") => LightKind::Point,
+        Some("spot") => {
+            let spot = l.get("spot");
+            let cone =
+                |key: &str, default: f32| spot.map_or(Ok(default), |s| number(s, key, default));
+            LightKind::Spot {
+                inner_cone: cone("innerConeAngle", 0.0)?,
+                outer_cone: cone("outerConeAngle", std::f32::consts::FRAC_PI_4)?,
+            }
+        }
+        other => bail!("unknown light type {other:?}"),
+    };
+    Ok(GltfLight {
+        name: name(l),
+        kind,
+        color: numbers(l, "color", [1.0; 3])?,
+        intensity: number(l, "intensity", 1.0)?,
+        range: l.get("range").and_then(Value::as_f32),
+    })
+}
+
+/// Standard base64 (padding optional, whitespace ignored).
+fn decode_base64(text: &str) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut acc, mut bits) = (0u32, 0);
+    for c in text.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            c if c.is_ascii_whitespace() => continue,
+            c => bail!("invalid base64 character {:?}", c as char),
+        };
+        acc = (acc << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+    Ok(out)
+}
+
+// `%20` etc. in relative URIs.
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 3;
+            }
+            (b, _) => {
+                out.push(b);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../assets/models/gltf")
+            .join(name)
+    }
+
+    fn assert_close(a: &[f32], b: &[f32]) {
+        assert_eq!(a.len(), b.len());
+        for (x, y) in a.iter().zip(b) {
+            assert!((x - y).abs() < 1e-5, "{a:?} != {b:?}");
+        }
+    }
+
+    #[test]
+    fn data_uri_triangle_with_hierarchy_camera_and_light() {
+        let scene = load_gltf(sample("triangle.gltf")).unwrap_or_else(|e| panic!("{e:?}"));
+        assert_eq!(scene.meshes.len(), 1);
+        let mesh = &scene.meshes[0].primitives[0].mesh;
+        assert_eq!(mesh.vertices.len(), 3);
+        assert_eq!(mesh.indices, [0, 1, 2]);
+        assert_eq!(mesh.vertices[1].position, [1.0, 0.0, 0.0]);
+
+        assert_eq!(scene.roots, [0]);
+        assert_eq!(scene.nodes[0].children, [1, 2, 3]);
+        let world = scene.world_matrices();
+        // Child translation (0, 1, 0) under the root's (2, 0, 0)
+        assert_close(&world[1][12..15], &[2.0, 1.0, 0.0]);
+
+        let camera = scene.nodes.iter().find_map(|n| n.camera).unwrap();
+        assert!(matches!(
+            scene.cameras[camera].projection,
+            Projection::Perspective {
+                zfar: Some(100.0),
+                ..
+            }
+        ));
+        let light = scene.nodes.iter().find_map(|n| n.light).unwrap();
+        assert_eq!(scene.lights[light].kind, LightKind::Point);
+        assert_eq!(scene.lights[light].intensity, 20.0);
+    }
+
+    #[test]
+    fn external_buffer_and_texture() {
+        let scene = load_gltf(sample("quad.gltf")).unwrap_or_else(|e| panic!("{e:?}"));
+        let primitive = &scene.meshes[0].primitives[0];
+        assert_eq!(primitive.mesh.vertices.len(), 4);
+        assert_eq!(primitive.mesh.indices.len(), 6);
+        assert_eq!(primitive.mesh.vertices[2].uv, [1.0, 1.0]);
+        // No NORMAL in the file: flat normals from the triangles
+        let vertex = |i: u32| primitive.mesh.vertices[i as usize];
+        let dot = |a: [f32; 3], b: [f32; 3]| a[0] * b[0] + a[1] * b[1] + a[2] * b[2];
+        let sub = |a: [f32; 3], b: [f32; 3]| [a[0] - b[0], a[1] - b[1], a[2] - b[2]];
+        for tri in primitive.mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [tri[0], tri[1], tri[2]].map(vertex);
+            assert!(a.normal == b.normal && b.normal == c.normal);
+            assert!((dot(a.normal, a.normal) - 1.0).abs() < 1e-5);
+            assert!(dot(a.normal, sub(b.position, a.position)).abs() < 1e-5);
+            assert!(dot(a.normal, sub(c.position, a.position)).abs() < 1e-5);
+        }
+
+        let material = &scene.materials[primitive.material.unwrap()];
+        assert_eq!(material.alpha_mode, AlphaMode::Mask(0.25));
+        assert!(material.double_sided);
+        let texture = scene.textures[material.base_color_texture.unwrap()];
+        assert_eq!(texture.mag_filter, Some(9728));
+        assert_eq!(texture.wrap_s, 33071);
+        let sampler = texture.sampler();
+        assert_eq!(sampler.address_v, AddressMode::ClampToEdge);
+        // NEAREST min filter: base level only
+        assert_eq!(
+            (sampler.min_filter, sampler.mipmap_filter),
+            (FilterMode::Nearest, FilterMode::Nearest)
+        );
+        assert!(!sampler.mipmaps);
+        let image = &scene.images[texture.image];
+        assert_eq!((image.width, image.height), (4, 4));
+        assert!(image.is_valid());
+    }
+
+    #[test]
+    fn glb_with_embedded_image_and_two_primitives() {
+        let scene = load_gltf(sample("cubes.glb")).unwrap_or_else(|e| panic!("{e:?}"));
+        assert_eq!(scene.meshes.len(), 1);
+        assert_eq!(scene.primitive_count(), 2);
+        let [a, b] = [
+            &scene.meshes[0].primitives[0],
+            &scene.meshes[0].primitives[1],
+        ];
+        assert_ne!(a.material, b.material);
+        assert_eq!(a.mesh.indices.len(), 36);
+        assert!(a.mesh.is_valid() && b.mesh.is_valid());
+
+        let red = &scene.materials[a.material.unwrap()];
+        assert_eq!(red.base_color, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!((red.metallic, red.roughness), (0.0, 0.5));
+        let textured = &scene.materials[b.material.unwrap()];
+        let image = &scene.images[scene.textures[textured.base_color_texture.unwrap()].image];
+        assert_eq!((image.width, image.height), (2, 2));
+
+        // Rotated node: 90° about Y maps +X to -Z
+        let world = scene.world_matrices();
+        assert_close(&world[0][0..3], &[0.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn skinned_glb_with_clips() {
+        let scene = load_gltf(sample("arm.glb")).unwrap_or_else(|e| panic!("{e:?}"));
+        let primitive = &scene.meshes[0].primitives[0];
+        let skin = primitive.skin.as_ref().unwrap();
+        assert_eq!(skin.len(), primitive.mesh.vertices.len());
+        // Normalized u8 weights 128/127 sum to one after loading
+        assert_close(&skin.weights[8], &[128.0 / 255.0, 127.0 / 255.0, 0.0, 0.0]);
+        assert_eq!(scene.nodes[3].skin, Some(0));
+        assert_eq!(primitive.mesh.streams.joints.as_ref(), Some(&skin.joints));
+
+        let skeleton = scene.skeleton(0).unwrap();
+        assert_eq!(skeleton.joints[1].parent, Some(0));
+        let mut matrices = Vec::new();
+        skeleton.joint_matrices(&skeleton.rest_pose(), &mut matrices);
+        for m in &matrices {
+            assert_close(m, &IDENTITY);
+        }
+
+        let bend = scene.animation_clip(0, 3).unwrap();
+        assert_eq!((bend.name.as_deref(), bend.duration), (Some("bend"), 2.0));
+        let sway = scene.animation_clip(1, 3).unwrap();
+        assert_eq!(sway.channels[0].interpolation, Interpolation::Step);
+        assert_eq!(sway.channels[1].interpolation, Interpolation::CubicSpline);
+
+        // Elbow bent 90° about Z: the top ring swings over to -X
+        let mut pose = skeleton.rest_pose();
+        bend.sample(1.0, &mut pose);
+        skeleton.joint_matrices(&pose, &mut matrices);
+        let mut skinned = Vec::new();
+        crate::animation::skin_vertices(&primitive.mesh, skin, &matrices, &[], &mut skinned);
+        assert_close(&skinned[16].position, &[-1.0, 0.8, -0.2]);
+    }
+
+    #[test]
+    fn morph_targets_with_weight_animation() {
+        let scene = load_gltf(sample("morph.gltf")).unwrap_or_else(|e| panic!("{e:?}"));
+        let mesh = &scene.meshes[0].primitives[0].mesh;
+        assert_eq!(mesh.morph_targets.len(), 2);
+        assert_eq!(mesh.morph_targets[0].name.as_deref(), Some("lift"));
+        assert_eq!(mesh.default_morph_weights(), vec![0.25, 0.0]);
+        assert!(mesh.morph_targets[0].normals.is_some());
+        assert!(mesh.morph_targets[1].normals.is_none());
+
+        let clip = scene.animation_clip(0, 0).unwrap();
+        assert!(clip.channels.is_empty());
+        assert_eq!(clip.duration, 2.0);
+        let mut weights = vec![0.0; 2];
+        clip.morph.as_ref().unwrap().sample(1.5, &mut weights);
+        assert_close(&weights, &[0.5, 0.75]);
+
+        let mut morphed = Vec::new();
+        mesh.morphed_vertices(&[1.0, 1.0], &mut morphed);
+        assert_close(&morphed[2].position, &[1.5, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn accessor_and_container_errors() {
+        let gltf = |json: &str| load_gltf_from_slice(json.as_bytes(), None);
+        assert!(gltf(r#"{"asset": {"version": "1.0"}}"#).is_err());
+        assert!(gltf(r#"{"asset": {"version": "2.0"}, "buffers": [{"byteLength": 4}]}"#).is_err());
+        // Accessor past the end of its buffer view
+        let err = gltf(
+            r#"{"asset": {"version": "2.0"},
+                "buffers": [{"uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAA", "byteLength": 12}],
+                "bufferViews": [{"buffer": 0, "byteLength": 12}],
+                "accessors": [{"bufferView": 0, "componentType": 5126, "count": 2, "type": "VEC3"}],
+                "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}]}"#,
+        )
+        .unwrap_err();
+        assert!(format!("{err:#}").contains("past the end"), "{err:#}");
+        assert!(load_gltf_from_slice(b"glTF\x01\0\0\0\x0c\0\0\0", None).is_err());
+    }
+
+    #[test]
+    fn extra_attributes_become_vertex_streams() {
+        let scene = load_gltf_from_slice(
+            br#"{"asset": {"version": "2.0"},
+                "buffers": [{"uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA/wAA/wD/AP8AAP+AAAAAAAAAAAAAAIA/AAAAAAAAAD8AAIA/", "byteLength": 72}],
+                "bufferViews": [{"buffer": 0, "byteLength": 36}, {"buffer": 0, "byteOffset": 36, "byteLength": 12},
+                                {"buffer": 0, "byteOffset": 48, "byteLength": 24}],
+                "accessors": [{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3"},
+                              {"bufferView": 1, "componentType": 5121, "normalized": true, "count": 3, "type": "VEC4"},
+                              {"bufferView": 2, "componentType": 5126, "count": 3, "type": "VEC2"}],
+                "meshes": [{"primitives": [{"attributes": {"POSITION": 0, "COLOR_0": 1, "TEXCOORD_1": 2}}]}]}"#,
+            None,
+        )
+        .unwrap_or_else(|e| panic!("{e:?}"));
+        let streams = &scene.meshes[0].primitives[0].mesh.streams;
+        assert_eq!(
+            streams.colors.as_ref().unwrap()[2],
+            [0.0, 0.0, 1.0, 128.0 / 255.0]
+        );
+        assert_eq!(streams.uv1.as_ref().unwrap()[2], [0.5, 1.0]);
+        assert!(streams.tangents.is_none() && streams.joints.is_none());
+    }
+
+    #[test]
+    fn malformed_accessors_are_errors() {
+        // POSITION without a bufferView (3 zero vertices) + the accessor under test as indices
+        let load = |view: &str, indices: &str| {
+            let doc = r#"{"asset": {"version": "2.0"},
+                "buffers": [{"uri": "data:application/octet-stream;base64,AQAAAQ==", "byteLength": 4}],
+                "bufferViews": [VIEW],
+                "accessors": [{"componentType": 5126, "count": 3, "type": "VEC3"}, INDICES],
+                "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "indices": 1}]}]}"#;
+            let doc = doc.replace("VIEW", view).replace("INDICES", indices);
+            load_gltf_from_slice(doc.as_bytes(), None).map_err(|e| format!("{e:#}"))
+        };
+        let view = r#"{"buffer": 0, "byteLength": 4}"#;
+        // u32 indices are read exactly (2^24 + 1 would round through f32)
+        let err = load(
+            view,
+            r#"{"bufferView": 0, "componentType": 5125, "count": 1, "type": "SCALAR"}"#,
+        )
+        .unwrap_err();
+        assert!(err.contains("index 16777217 is out of range"), "{err}");
+        assert!(
+            load(
+                view,
+                r#"{"bufferView": 0, "componentType": 5126, "count": 1, "type": "SCALAR"}"#
+            )
+            .is_err()
+        );
+
+        // Offsets and counts that overflow are errors, not panics
+        let huge = r#"{"buffer": 0, "byteOffset": 18446744073709551615, "byteLength": 4}"#;
+        assert!(
+            load(
+                huge,
+                r#"{"bufferView": 0, "componentType": 5125, "count": 1, "type": "SCALAR"}"#
+            )
+            .is_err()
+        );
+        let count = r#"{"bufferView": 0, "byteStride": 4, "componentType": 5125, "count": 4611686018427387904, "type": "SCALAR"}"#;
+        assert!(load(view, count).is_err());
+        let stride = r#"{"bufferView": 0, "byteStride": 0, "componentType": 5125, "count": 1000000, "type": "SCALAR"}"#;
+        assert!(load(view, stride).is_err());
+        let unbacked = r#"{"componentType": 5125, "count": 1000000000000, "type": "SCALAR"}"#;
+        let err = load(view, unbacked).unwrap_err();
+        assert!(err.contains("without a bufferView"), "{err}");
+    }
+
+    #[test]
+    fn base64_and_percent_decoding() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGVs\nbG8").unwrap(), b"hello");
+        assert!(decode_base64("a*b").is_err());
+        assert_eq!(percent_decode("my%20file.bin"), "my file.bin");
+    }
+
+    #[test]
+    fn trs_matrix_matches_composition() {
+        let half = std::f32::consts::FRAC_1_SQRT_2;
+        let trs = NodeTransform::Trs {
+            translation: [1.0, 2.0, 3.0],
+            rotation: [0.0, 0.0, half, half], // 90° about Z
+            scale: [2.0, 2.0, 2.0],
+        };
+        let m = trs.matrix();
+        // X axis -> 2 * +Y, translation in the last column
+        assert_close(&m[0..3], &[0.0, 2.0, 0.0]);
+        assert_close(&m[12..15], &[1.0, 2.0, 3.0]);
+        assert_eq!(mat_mul(&IDENTITY, &m), m);
+        // Decomposing the matrix gives the TRS back
+        let back = NodeTransform::Matrix(m).to_joint_transform();
+        assert_close(&back.translation, &[1.0, 2.0, 3.0]);
+        assert_close(&back.rotation, &[0.0, 0.0, half, half]);
+        assert_close(&back.scale, &[2.0, 2.0, 2.0]);
+    }
+}
@@ -1,7 +1,27 @@
 //! Asset loading/parsers (meshes, textures, shaders).
 //! E1: minimal OBJ mesh loader producing CPU-friendly mesh data.
-//! E2: texture loading (RGBA8) with basic filtering.
+//! J2: OBJ models split per `usemtl` with MTL material libraries.
+//! E1: flat/smooth normal generation with smoothing groups and crease angles.
+//! E2: texture loading (RGBA8), CPU mip chains and sampler descriptors.
+//! L1: LOD chains (authored or generated by quadric simplification).
+//! M1: binary mesh files (`*.meshbin`) loaded by memory mapping.
+//! M1: mesh processing (welding, cleanup, cache/overdraw/fetch optimization, merge, transform).
+//! M1: vertex layout descriptors with optional streams and quantized formats.
+//! J1: glTF 2.0 import (meshes, PBR materials, textures, nodes, cameras, lights).
+//! J1: skeletons, animation clips (step/linear/cubic keys) and CPU skinning helpers.
+//! J2: material descriptors in RON/JSON and node-based material graphs.
 
+pub mod animation;
+pub mod gltf;
+pub mod lod;
+pub mod material;
+pub mod material_graph;
 pub mod mesh;
+pub mod mesh_bin;
+pub mod normals;
 pub mod obj;
+pub mod process;
+pub mod simplify;
 pub mod texture;
+pub mod value;
+pub mod vertex;
@@ -0,0 +1,125 @@
+//! L1: mesh LOD chains.
+//! Authored LODs are sibling OBJ files named `<stem>_LOD<n>.obj`; missing levels are
+//! generated with [`crate::simplify::simplify`].
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+
+use crate::mesh::MeshData;
+use crate::obj::load_obj_from_path;
+use crate::simplify::simplify;
+
+/// Upper bound on LOD levels (including LOD0).
+pub const MAX_LOD_LEVELS: usize = 8;
+
+/// A level must drop at least this fraction of triangles to be kept.
+const MIN_REDUCTION: f32 = 0.1;
+
+/// Meshes from finest (`levels[0]`) to coarsest.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshLods {
+    pub levels: Vec<MeshData>,
+}
+
+impl MeshLods {
+    /// Single-level chain.
+    pub fn from_mesh(mesh: MeshData) -> Self {
+        Self { levels: vec![mesh] }
+    }
+
+    /// Generate up to `count` levels (LOD0 included), each keeping `ratio` of the
+    /// previous level's triangles. Stops early when the simplifier makes no progress.
+    pub fn generate(base: MeshData, count: usize, ratio: f32) -> Self {
+        let mut lods = Self::from_mesh(base);
+        lods.extend_generated(count, ratio);
+        lods
+    }
+
+    /// Append generated levels until there are `count` of them.
+    pub fn extend_generated(&mut self, count: usize, ratio: f32) {
+        let count = count.min(MAX_LOD_LEVELS);
+        let ratio = ratio.clamp(0.05, 0.95);
+        while self.levels.len() < count {
+            let prev = self.levels.last().expect("chain has LOD0");
+            let target = ((prev.indices.len() as f32 * ratio) as usize / 3) * 3;
+            let next = simplify(prev, target, f32::MAX);
+            if !next.is_valid()
+                || next.indices.len() as f32 > prev.indices.len() as f32 * (1.0 - MIN_REDUCTION)
+            {
+                log::debug!(
+                    "L1: stopping LOD generation at {} levels",
+                    self.levels.len()
+                );
+                break;
+            }
+            self.levels.push(next);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.levels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels.is_empty()
+    }
+}
+
+/// Path of authored LOD `level` next to `base` (`suzanne.obj` -> `suzanne_LOD1.obj`).
+pub fn lod_path(base: &Path, level: usize) -> PathBuf {
+    let stem = base.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match base.extension() {
+        Some(ext) => format!("{stem}_LOD{level}.{}", ext.to_string_lossy()),
+        None => format!("{stem}_LOD{level}"),
+    };
+    base.with_file_name(name)
+}
+
+/// Load `path` plus authored `_LOD<n>` siblings, then generate the remaining levels
+/// up to `count` (pass 1 to disable generation).
+pub fn load_obj_lods(path: impl AsRef<Path>, count: usize, ratio: f32) -> Result<MeshLods> {
+    let path = path.as_ref();
+    let mut lods = MeshLods::from_mesh(load_obj_from_path(path)?);
+    for level in 1..MAX_LOD_LEVELS {
+        let lod = lod_path(path, level);
+        if !lod.exists() {
+            break;
+        }
+        let mesh = load_obj_from_path(&lod)
+            .with_context(|| format!("Failed to load authored LOD {level}"))?;
+        lods.levels.push(mesh);
+    }
+    let authored = lods.len();
+    lods.extend_generated(count, ratio);
+    log::info!(
+        "L1: {} LOD levels for {} ({} authored)",
+        lods.len(),
+        path.display(),
+        authored
+    );
+    Ok(lods)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lod_paths_use_suffix() {
+        assert_eq!(
+            lod_path(Path::new("assets/models/suzanne.obj"), 2),
+            Path::new("assets/models/suzanne_LOD2.obj")
+        );
+    }
+
+    #[test]
+    fn generated_chain_gets_coarser() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../assets/models/suzanne.obj");
+        let lods = load_obj_lods(&path, 3, 0.5).expect("suzanne loads");
+        assert!(lods.len() >= 2);
+        for pair in lods.levels.windows(2) {
+            assert!(pair[1].indices.len() < pair[0].indices.len());
+        }
+    }
+}
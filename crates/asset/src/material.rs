@@ -0,0 +1,274 @@
+//! J2: material descriptors (`*.material.ron` / `*.material.json`).
+//!
+//! ```ron
+//! Material(
+//!     shader: Some("triangle.wgsl"),      // file in assets/shaders, None = main shader
+//!     base_color: (1.0, 0.8, 0.3, 1.0),
+//!     metallic: 1.0,
+//!     roughness: 0.3,
+//!     emissive: (0.0, 0.0, 0.0),
+//!     base_color_texture: Some("textures/gold.png"), // relative to the assets root
+//!     sampler: (address: Repeat, filter: Linear, anisotropy: 8), // see below
+//!     alpha_mode: Opaque,                  // Opaque | Mask(cutoff) | Blend
+//!     cull_mode: None,                     // None | Back | Front
+//!     graph: None,                         // Some("materials/x.matgraph.ron"): node graph surface
+//! )
+//! ```
+//! Every field is optional; unknown fields are errors (typos should not pass silently).
+//! `sampler` takes `address` (`Repeat` | `MirrorRepeat` | `ClampToEdge`) or per-axis
+//! `address_u`/`address_v`, `filter` (`Linear` | `Nearest`) or `mag_filter`/`min_filter`/
+//! `mipmap_filter`, `mipmaps` (`false` = base level only) and `anisotropy`; the default is
+//! repeating trilinear.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::texture::{AddressMode, FilterMode, SamplerDesc};
+use crate::value::{self, Value, float, floats, kind, optional_string};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum AlphaMode {
+    #[default]
+    Opaque,
+    /// Alpha test: fragments below the cutoff are discarded.
+    Mask(f32),
+    /// Alpha blending (drawn after opaque geometry, no depth writes).
+    Blend,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CullMode {
+    #[default]
+    None,
+    Back,
+    Front,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MaterialDesc {
+    /// Shader file name in the shader directory; `None` = the renderer's main shader.
+    pub shader: Option<String>,
+    pub base_color: [f32; 4],
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: [f32; 3],
+    /// Asset path (relative to the assets root) of the base color texture.
+    pub base_color_texture: Option<String>,
+    /// E2: how the base color texture is sampled.
+    pub sampler: SamplerDesc,
+    pub alpha_mode: AlphaMode,
+    pub cull_mode: CullMode,
+    /// Asset path of a node graph (`material_graph`) that computes the surface instead;
+    /// the parameters above become the graph's defaults for unconnected outputs.
+    pub graph: Option<String>,
+}
+
+impl Default for MaterialDesc {
+    fn default() -> Self {
+        Self {
+            shader: None,
+            base_color: [1.0, 1.0, 1.0, 1.0],
+            metallic: 0.0,
+            roughness: 0.5,
+            emissive: [0.0, 0.0, 0.0],
+            base_color_texture: None,
+            sampler: SamplerDesc::default(),
+            alpha_mode: AlphaMode::Opaque,
+            cull_mode: CullMode::None,
+            graph: None,
+        }
+    }
+}
+
+/// Load a material file (RON or JSON, detected from the content).
+pub fn load_material(path: impl AsRef<Path>) -> Result<MaterialDesc> {
+    let path = path.as_ref();
+    let src = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read material {}", path.display()))?;
+    parse_material(&src).with_context(|| format!("Invalid material {}", path.display()))
+}
+
+/// Parse a RON (`Material(...)`) or JSON (`{...}`) material descriptor.
+pub fn parse_material(src: &str) -> Result<MaterialDesc> {
+    let fields = match value::parse(src)? {
+        Value::Struct(name, fields) if name.as_deref().is_none_or(|n| n == "Material") => fields,
+        other => bail!(
+            "expected Material(...) or a JSON object, found {}",
+            kind(&other)
+        ),
+    };
+    let mut desc = MaterialDesc::default();
+    for (key, v) in &fields {
+        let field = || format!("field '{key}'");
+        match key.as_str() {
+            "shader" => desc.shader = optional_string(v).with_context(field)?,
+            "base_color" => {
+                let c = floats(v).with_context(field)?;
+                desc.base_color = match c[..] {
+                    [r, g, b] => [r, g, b, 1.0],
+                    [r, g, b, a] => [r, g, b, a],
+                    _ => bail!("{}: expected 3 or 4 components", field()),
+                };
+            }
+            "metallic" => desc.metallic = float(v).with_context(field)?,
+            "roughness" => desc.roughness = float(v).with_context(field)?,
+            "emissive" => {
+                desc.emissive = floats(v)
+                    .with_context(field)?
+                    .try_into()
+                    .map_err(|_| anyhow!("{}: expected 3 components", field()))?;
+            }
+            "base_color_texture" => {
+                desc.base_color_texture = optional_string(v).with_context(field)?
+            }
+            "sampler" => desc.sampler = parse_sampler(v).with_context(field)?,
+            "alpha_mode" => {
+                desc.alpha_mode = match v.as_variant() {
+                    Some(("Opaque", None)) => AlphaMode::Opaque,
+                    Some(("Blend", None)) => AlphaMode::Blend,
+                    Some(("Mask", cutoff)) => AlphaMode::Mask(
+                        cutoff
+                            .map(float)
+                            .transpose()
+                            .with_context(field)?
+                            .unwrap_or(0.5),
+                    ),
+                    _ => bail!("{}: expected Opaque, Mask(cutoff) or Blend", field()),
+                };
+            }
+            "cull_mode" => {
+                desc.cull_mode = match v.as_variant() {
+                    Some(("None", None)) => CullMode::None,
+                    Some(("Back", None)) => CullMode::Back,
+                    Some(("Front", None)) => CullMode::Front,
+                    _ => bail!("{}: expected None, Back or Front", field()),
+                };
+            }
+            "graph" => desc.graph = optional_string(v).with_context(field)?,
+            _ => bail!("unknown field '{key}'"),
+        }
+    }
+    Ok(desc)
+}
+
+fn parse_sampler(v: &Value) -> Result<SamplerDesc> {
+    let Value::Struct(_, fields) = v else {
+        bail!(
+            "expected (address: ..., filter: ..., anisotropy: ...), found {}",
+            kind(v)
+        );
+    };
+    let address = |v: &Value| -> Result<AddressMode> {
+        Ok(match v.as_variant() {
+            Some(("Repeat", None)) => AddressMode::Repeat,
+            Some(("MirrorRepeat", None)) => AddressMode::MirrorRepeat,
+            Some(("ClampToEdge", None)) => AddressMode::ClampToEdge,
+            _ => bail!("expected Repeat, MirrorRepeat or ClampToEdge"),
+        })
+    };
+    let filter = |v: &Value| -> Result<FilterMode> {
+        Ok(match v.as_variant() {
+            Some(("Linear", None)) => FilterMode::Linear,
+            Some(("Nearest", None)) => FilterMode::Nearest,
+            _ => bail!("expected Linear or Nearest"),
+        })
+    };
+    let mut sampler = SamplerDesc::default();
+    for (key, v) in fields {
+        let field = || format!("field '{key}'");
+        match key.as_str() {
+            "address" => {
+                sampler.address_u = address(v).with_context(field)?;
+                sampler.address_v = sampler.address_u;
+            }
+            "address_u" => sampler.address_u = address(v).with_context(field)?,
+            "address_v" => sampler.address_v = address(v).with_context(field)?,
+            "filter" => {
+                sampler.mag_filter = filter(v).with_context(field)?;
+                sampler.min_filter = sampler.mag_filter;
+                sampler.mipmap_filter = sampler.mag_filter;
+            }
+            "mag_filter" => sampler.mag_filter = filter(v).with_context(field)?,
+            "min_filter" => sampler.min_filter = filter(v).with_context(field)?,
+            "mipmap_filter" => sampler.mipmap_filter = filter(v).with_context(field)?,
+            "mipmaps" => {
+                sampler.mipmaps = v
+                    .as_bool()
+                    .ok_or_else(|| anyhow!("{}: expected a bool", field()))?
+            }
+            "anisotropy" => {
+                sampler.anisotropy = v
+                    .as_usize()
+                    .and_then(|a| u16::try_from(a).ok())
+                    .filter(|a| (1..=16).contains(a))
+                    .ok_or_else(|| anyhow!("{}: expected an integer in 1..=16", field()))?;
+            }
+            _ => bail!("unknown field '{key}'"),
+        }
+    }
+    Ok(sampler)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ron_and_json_descriptors_agree() {
+        let ron = parse_material(
+            r#"Material(
+                base_color: (1.0, 0.5, 0.25),
+                metallic: 1.0,
+                emissive: (0.1, 0.0, 0.0),
+                base_color_texture: Some("textures/a.png"),
+                sampler: (address: ClampToEdge, address_v: MirrorRepeat, min_filter: Nearest, mipmaps: false, anisotropy: 4),
+                alpha_mode: Mask(0.3),
+                cull_mode: Back,
+            )"#,
+        )
+        .unwrap();
+        let json = parse_material(
+            r#"{
+                "base_color": [1.0, 0.5, 0.25, 1.0],
+                "metallic": 1.0,
+                "emissive": [0.1, 0.0, 0.0],
+                "base_color_texture": "textures/a.png",
+                "sampler": {"address_u": "ClampToEdge", "address_v": "MirrorRepeat", "min_filter": "Nearest", "mipmaps": false, "anisotropy": 4},
+                "alpha_mode": {"Mask": 0.3},
+                "cull_mode": "Back"
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(ron, json);
+        assert_eq!(ron.base_color, [1.0, 0.5, 0.25, 1.0]);
+        assert_eq!(ron.alpha_mode, AlphaMode::Mask(0.3));
+        assert_eq!(ron.roughness, MaterialDesc::default().roughness);
+        assert_eq!(ron.sampler.address_u, AddressMode::ClampToEdge);
+        assert!(!ron.sampler.mipmaps);
+        // Mixed filters disable anisotropic filtering
+        assert_eq!(
+            (ron.sampler.anisotropy, ron.sampler.anisotropy_clamp()),
+            (4, 1)
+        );
+    }
+
+    #[test]
+    fn bad_fields_are_errors() {
+        assert!(parse_material("Material(metalic: 1.0)").is_err());
+        assert!(parse_material("Material(base_color: (1.0, 2.0))").is_err());
+        assert!(parse_material("Material(alpha_mode: Glass)").is_err());
+        assert!(parse_material("Mesh(metallic: 1.0)").is_err());
+        assert!(parse_material("Material(sampler: (anisotropy: 32))").is_err());
+        assert!(parse_material("Material(sampler: (address: Wrap))").is_err());
+    }
+
+    #[test]
+    fn sample_materials_load() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../assets/materials");
+        for name in ["gold.material.ron", "glass.material.json"] {
+            load_material(dir.join(name)).unwrap_or_else(|e| panic!("{name}: {e:?}"));
+        }
+    }
+}
@@ -0,0 +1,594 @@
+//! J2: node-based materials (`*.matgraph.ron`).
+//!
+//! A graph is a list of nodes; every node has one output and a fixed set of inputs that
+//! reference other nodes by index (`None` = unconnected, the node's default is used).
+//! The graph outputs feed the PBR surface inputs. The renderer compiles graphs to WGSL.
+//!
+//! ```ron
+//! MaterialGraph(
+//!     nodes: [
+//!         (op: Uv, pos: (20.0, 40.0)),
+//!         (op: UvTransform(scale: (4.0, 4.0), offset: (0.0, 0.0), rotation: 0.0), inputs: [Some(0)]),
+//!         (op: TextureSample, inputs: [Some(1)]),
+//!         (op: Fresnel(power: 3.0), inputs: [None]),
+//!     ],
+//!     outputs: (base_color: Some(2), emissive: Some(3)),
+//! )
+//! ```
+//! Cycles are not rejected here (an editor passes through them); the compiler reports them.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+
+use crate::value::{self, Value, float, floats, kind};
+
+/// Index of a node in `MaterialGraph::nodes`.
+pub type NodeId = usize;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeOp {
+    Float(f32),
+    /// RGBA constant.
+    Color([f32; 4]),
+    /// Mesh texture coordinates.
+    Uv,
+    /// World-space normal.
+    Normal,
+    /// Direction from the surface to the camera.
+    ViewDir,
+    /// `base_color` of the material parameters.
+    MaterialColor,
+    /// Sample of the material's base color texture at `uv`.
+    TextureSample,
+    /// Scale and rotate around the UV center, then offset.
+    UvTransform {
+        scale: [f32; 2],
+        offset: [f32; 2],
+        rotation: f32,
+    },
+    /// `(1 - N·V)^power` for `normal`.
+    Fresnel {
+        power: f32,
+    },
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Power,
+    /// Linear blend `a -> b` by `t`.
+    Mix,
+    OneMinus,
+    Saturate,
+    /// Component selection, e.g. `"rgb"` or `"x"`.
+    Swizzle(String),
+}
+
+impl NodeOp {
+    /// One node of every kind with default parameters (e.g. for an "add node" menu).
+    pub fn palette() -> Vec<NodeOp> {
+        vec![
+            NodeOp::Float(1.0),
+            NodeOp::Color([1.0, 1.0, 1.0, 1.0]),
+            NodeOp::Uv,
+            NodeOp::Normal,
+            NodeOp::ViewDir,
+            NodeOp::MaterialColor,
+            NodeOp::TextureSample,
+            NodeOp::UvTransform {
+                scale: [1.0, 1.0],
+                offset: [0.0, 0.0],
+                rotation: 0.0,
+            },
+            NodeOp::Fresnel { power: 5.0 },
+            NodeOp::Add,
+            NodeOp::Subtract,
+            NodeOp::Multiply,
+            NodeOp::Divide,
+            NodeOp::Power,
+            NodeOp::Mix,
+            NodeOp::OneMinus,
+            NodeOp::Saturate,
+            NodeOp::Swizzle("rgb".to_string()),
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            NodeOp::Float(_) => "Float",
+            NodeOp::Color(_) => "Color",
+            NodeOp::Uv => "Uv",
+            NodeOp::Normal => "Normal",
+            NodeOp::ViewDir => "ViewDir",
+            NodeOp::MaterialColor => "MaterialColor",
+            NodeOp::TextureSample => "TextureSample",
+            NodeOp::UvTransform { .. } => "UvTransform",
+            NodeOp::Fresnel { .. } => "Fresnel",
+            NodeOp::Add => "Add",
+            NodeOp::Subtract => "Subtract",
+            NodeOp::Multiply => "Multiply",
+            NodeOp::Divide => "Divide",
+            NodeOp::Power => "Power",
+            NodeOp::Mix => "Mix",
+            NodeOp::OneMinus => "OneMinus",
+            NodeOp::Saturate => "Saturate",
+            NodeOp::Swizzle(_) => "Swizzle",
+        }
+    }
+
+    /// Input port names, in `Node::inputs` order.
+    pub fn inputs(&self) -> &'static [&'static str] {
+        match self {
+            NodeOp::Float(_)
+            | NodeOp::Color(_)
+            | NodeOp::Uv
+            | NodeOp::Normal
+            | NodeOp::ViewDir
+            | NodeOp::MaterialColor => &[],
+            NodeOp::TextureSample | NodeOp::UvTransform { .. } => &["uv"],
+            NodeOp::Fresnel { .. } => &["normal"],
+            NodeOp::Add | NodeOp::Subtract | NodeOp::Multiply | NodeOp::Divide | NodeOp::Power => {
+                &["a", "b"]
+            }
+            NodeOp::Mix => &["a", "b", "t"],
+            NodeOp::OneMinus | NodeOp::Saturate | NodeOp::Swizzle(_) => &["in"],
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Node {
+    pub op: NodeOp,
+    /// One entry per `op.inputs()` port.
+    pub inputs: Vec<Option<NodeId>>,
+    /// Editor position.
+    pub pos: [f32; 2],
+}
+
+impl Node {
+    pub fn new(op: NodeOp, pos: [f32; 2]) -> Self {
+        let inputs = vec![None; op.inputs().len()];
+        Self { op, inputs, pos }
+    }
+}
+
+/// PBR surface inputs a graph can drive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GraphOutput {
+    BaseColor,
+    Alpha,
+    Metallic,
+    Roughness,
+    Emissive,
+}
+
+impl GraphOutput {
+    pub const ALL: [GraphOutput; 5] = [
+        GraphOutput::BaseColor,
+        GraphOutput::Alpha,
+        GraphOutput::Metallic,
+        GraphOutput::Roughness,
+        GraphOutput::Emissive,
+    ];
+
+    /// Field name in files (and in the WGSL `Surface` struct).
+    pub fn name(self) -> &'static str {
+        match self {
+            GraphOutput::BaseColor => "base_color",
+            GraphOutput::Alpha => "alpha",
+            GraphOutput::Metallic => "metallic",
+            GraphOutput::Roughness => "roughness",
+            GraphOutput::Emissive => "emissive",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MaterialGraph {
+    pub nodes: Vec<Node>,
+    /// Indexed by `GraphOutput as usize`; `None` = the material parameter is used.
+    pub outputs: [Option<NodeId>; 5],
+    /// Editor position of the output node.
+    pub output_pos: [f32; 2],
+}
+
+impl MaterialGraph {
+    pub fn output(&self, output: GraphOutput) -> Option<NodeId> {
+        self.outputs[output as usize]
+    }
+
+    pub fn set_output(&mut self, output: GraphOutput, node: Option<NodeId>) {
+        self.outputs[output as usize] = node;
+    }
+
+    pub fn add_node(&mut self, op: NodeOp, pos: [f32; 2]) -> NodeId {
+        self.nodes.push(Node::new(op, pos));
+        self.nodes.len() - 1
+    }
+
+    /// Remove a node; links to it are cut and later node ids shift down by one.
+    pub fn remove_node(&mut self, id: NodeId) {
+        if id >= self.nodes.len() {
+            return;
+        }
+        self.nodes.remove(id);
+        let remap = |link: &mut Option<NodeId>| {
+            *link = match *link {
+                Some(n) if n == id => None,
+                Some(n) if n > id => Some(n - 1),
+                other => other,
+            };
+        };
+        for node in &mut self.nodes {
+            node.inputs.iter_mut().for_each(remap);
+        }
+        self.outputs.iter_mut().for_each(remap);
+    }
+
+    /// Does `node` (transitively) read `target`? Used to refuse links that close a cycle.
+    pub fn depends_on(&self, node: NodeId, target: NodeId) -> bool {
+        let mut stack = vec![node];
+        let mut seen = vec![false; self.nodes.len()];
+        while let Some(n) = stack.pop() {
+            if n == target {
+                return true;
+            }
+            if n >= self.nodes.len() || std::mem::replace(&mut seen[n], true) {
+                continue;
+            }
+            stack.extend(self.nodes[n].inputs.iter().flatten());
+        }
+        false
+    }
+
+    /// Serialize to the RON form `parse_graph` reads.
+    pub fn to_ron(&self) -> String {
+        let mut out = String::from("MaterialGraph(\n    nodes: [\n");
+        for node in &self.nodes {
+            let inputs: Vec<String> = node.inputs.iter().map(|i| link_ron(*i)).collect();
+            let _ = writeln!(
+                out,
+                "        (op: {}, inputs: [{}], pos: ({:?}, {:?})),",
+                op_ron(&node.op),
+                inputs.join(", "),
+                node.pos[0],
+                node.pos[1]
+            );
+        }
+        out.push_str("    ],\n    outputs: (\n");
+        for output in GraphOutput::ALL {
+            let _ = writeln!(
+                out,
+                "        {}: {},",
+                output.name(),
+                link_ron(self.output(output))
+            );
+        }
+        let _ = writeln!(
+            out,
+            "    ),\n    output_pos: ({:?}, {:?}),\n)",
+            self.output_pos[0], self.output_pos[1]
+        );
+        out
+    }
+}
+
+fn link_ron(link: Option<NodeId>) -> String {
+    link.map_or_else(|| "None".to_string(), |n| format!("Some({n})"))
+}
+
+fn op_ron(op: &NodeOp) -> String {
+    match op {
+        NodeOp::Float(v) => format!("Float({v:?})"),
+        NodeOp::Color([r, g, b, a]) => format!("Color(({r:?}, {g:?}, {b:?}, {a:?}))"),
+        NodeOp::UvTransform {
+            scale,
+            offset,
+            rotation,
+        } => format!(
+            "UvTransform(scale: ({:?}, {:?}), offset: ({:?}, {:?}), rotation: {rotation:?})",
+            scale[0], scale[1], offset[0], offset[1]
+        ),
+        NodeOp::Fresnel { power } => format!("Fresnel(power: {power:?})"),
+        NodeOp::Swizzle(mask) => format!("Swizzle({mask:?})"),
+        other => other.name().to_string(),
+    }
+}
+
+/// Load a graph file (RON or JSON).
+pub fn load_graph(path: impl AsRef<Path>) -> Result<MaterialGraph> {
+    let path = path.as_ref();
+    let src = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read material graph {}", path.display()))?;
+    parse_graph(&src).with_context(|| format!("Invalid material graph {}", path.display()))
+}
+
+/// Parse a `MaterialGraph(...)` (or JSON object) descriptor.
+pub fn parse_graph(src: &str) -> Result<MaterialGraph> {
+    let fields = match value::parse(src)? {
+        Value::Struct(name, fields) if name.as_deref().is_none_or(|n| n == "MaterialGraph") => {
+            fields
+        }
+        other => bail!(
+            "expected MaterialGraph(...) or a JSON object, found {}",
+            kind(&other)
+        ),
+    };
+    let mut graph = MaterialGraph::default();
+    for (key, v) in &fields {
+        let field = || format!("field '{key}'");
+        match key.as_str() {
+            "nodes" => {
+                let items = v
+                    .as_seq()
+                    .ok_or_else(|| anyhow!("{}: expected a list", field()))?;
+                for (i, item) in items.iter().enumerate() {
+                    graph
+                        .nodes
+                        .push(parse_node(item).with_context(|| format!("node {i}"))?);
+                }
+            }
+            "outputs" => {
+                let Value::Struct(_, outputs) = v else {
+                    bail!("{}: expected (base_color: ..., ...)", field());
+                };
+                for (name, link) in outputs {
+                    let output = GraphOutput::ALL
+                        .into_iter()
+                        .find(|o| o.name() == name)
+                        .ok_or_else(|| anyhow!("unknown output '{name}'"))?;
+                    graph.set_output(
+                        output,
+                        parse_link(link).with_context(|| format!("output '{name}'"))?,
+                    );
+                }
+            }
+            "output_pos" => graph.output_pos = pos(v).with_context(field)?,
+            _ => bail!("unknown field '{key}'"),
+        }
+    }
+
+    // Links must point at existing nodes
+    let count = graph.nodes.len();
+    let check = |link: &Option<NodeId>, what: &dyn Fn() -> String| match link {
+        Some(n) if *n >= count => Err(anyhow!(
+            "{}: node {n} does not exist ({count} nodes)",
+            what()
+        )),
+        _ => Ok(()),
+    };
+    for (i, node) in graph.nodes.iter().enumerate() {
+        for (port, link) in node.op.inputs().iter().zip(&node.inputs) {
+            check(link, &|| format!("node {i} input '{port}'"))?;
+        }
+    }
+    for output in GraphOutput::ALL {
+        check(&graph.output(output), &|| {
+            format!("output '{}'", output.name())
+        })?;
+    }
+    Ok(graph)
+}
+
+fn parse_node(v: &Value) -> Result<Node> {
+    let Value::Struct(_, fields) = v else {
+        bail!(
+            "expected (op: ..., inputs: [...], pos: (x, y)), found {}",
+            kind(v)
+        );
+    };
+    let mut op = None;
+    let mut inputs = Vec::new();
+    let mut node_pos = [0.0, 0.0];
+    for (key, v) in fields {
+        let field = || format!("field '{key}'");
+        match key.as_str() {
+            "op" => op = Some(parse_op(v).with_context(field)?),
+            "inputs" => {
+                inputs = v
+                    .as_seq()
+                    .ok_or_else(|| anyhow!("{}: expected a list", field()))?
+                    .iter()
+                    .map(parse_link)
+                    .collect::<Result<_>>()
+                    .with_context(field)?;
+            }
+            "pos" => node_pos = pos(v).with_context(field)?,
+            _ => bail!("unknown field '{key}'"),
+        }
+    }
+    let op = op.ok_or_else(|| anyhow!("missing field 'op'"))?;
+    let ports = op.inputs().len();
+    if inputs.len() > ports {
+        bail!("{} takes {ports} inputs, found {}", op.name(), inputs.len());
+    }
+    inputs.resize(ports, None);
+    Ok(Node {
+        op,
+        inputs,
+        pos: node_pos,
+    })
+}
+
+fn parse_op(v: &Value) -> Result<NodeOp> {
+    // `UvTransform(scale: ...)` carries its fields itself; other payloads come as a variant
+    let (name, payload) = match v {
+        Value::Struct(Some(name), _) => (name.as_str(), Some(v)),
+        _ => v
+            .as_variant()
+            .ok_or_else(|| anyhow!("expected a node kind, found {}", kind(v)))?,
+    };
+    let payload = || payload.ok_or_else(|| anyhow!("{name} needs a parameter"));
+    let param = |key: &str| -> Result<&Value> {
+        let Value::Struct(_, fields) = payload()? else {
+            bail!("{name}: expected named parameters");
+        };
+        fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+            .ok_or_else(|| anyhow!("{name}: missing parameter '{key}'"))
+    };
+    let pair = |key: &str| -> Result<[f32; 2]> {
+        floats(param(key)?)?
+            .try_into()
+            .map_err(|_| anyhow!("{name}: '{key}' expects 2 components"))
+    };
+    Ok(match name {
+        "Float" => NodeOp::Float(float(payload()?)?),
+        "Color" => NodeOp::Color(match floats(payload()?)?[..] {
+            [r, g, b] => [r, g, b, 1.0],
+            [r, g, b, a] => [r, g, b, a],
+            _ => bail!("Color: expected 3 or 4 components"),
+        }),
+        "Uv" => NodeOp::Uv,
+        "Normal" => NodeOp::Normal,
+        "ViewDir" => NodeOp::ViewDir,
+        "MaterialColor" => NodeOp::MaterialColor,
+        "TextureSample" => NodeOp::TextureSample,
+        "UvTransform" => NodeOp::UvTransform {
+            scale: pair("scale")?,
+            offset: pair("offset")?,
+            rotation: float(param("rotation")?)?,
+        },
+        "Fresnel" => NodeOp::Fresnel {
+            power: float(param("power")?)?,
+        },
+        "Add" => NodeOp::Add,
+        "Subtract" => NodeOp::Subtract,
+        "Multiply" => NodeOp::Multiply,
+        "Divide" => NodeOp::Divide,
+        "Power" => NodeOp::Power,
+        "Mix" => NodeOp::Mix,
+        "OneMinus" => NodeOp::OneMinus,
+        "Saturate" => NodeOp::Saturate,
+        "Swizzle" => NodeOp::Swizzle(
+            payload()?
+                .as_str()
+                .ok_or_else(|| anyhow!("Swizzle: expected a string mask"))?
+                .to_string(),
+        ),
+        other => bail!("unknown node kind '{other}'"),
+    })
+}
+
+/// `Some(3)`, bare `3` or `None`/`null`.
+fn parse_link(v: &Value) -> Result<Option<NodeId>> {
+    v.as_option()
+        .map(|n| {
+            n.as_usize()
+                .ok_or_else(|| anyhow!("expected a node index, found {}", kind(n)))
+        })
+        .transpose()
+}
+
+fn pos(v: &Value) -> Result<[f32; 2]> {
+    floats(v)?
+        .try_into()
+        .map_err(|_| anyhow!("expected (x, y)"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> MaterialGraph {
+        let mut graph = MaterialGraph::default();
+        let uv = graph.add_node(NodeOp::Uv, [0.0, 0.0]);
+        let transform = graph.add_node(
+            NodeOp::UvTransform {
+                scale: [2.0, 2.0],
+                offset: [0.5, 0.0],
+                rotation: 0.25,
+            },
+            [150.0, 0.0],
+        );
+        graph.nodes[transform].inputs[0] = Some(uv);
+        let tex = graph.add_node(NodeOp::TextureSample, [300.0, 0.0]);
+        graph.nodes[tex].inputs[0] = Some(transform);
+        let rgb = graph.add_node(NodeOp::Swizzle("rgb".to_string()), [450.0, 0.0]);
+        graph.nodes[rgb].inputs[0] = Some(tex);
+        let fresnel = graph.add_node(NodeOp::Fresnel { power: 3.0 }, [300.0, 120.0]);
+        graph.set_output(GraphOutput::BaseColor, Some(rgb));
+        graph.set_output(GraphOutput::Emissive, Some(fresnel));
+        graph.output_pos = [600.0, 40.0];
+        graph
+    }
+
+    #[test]
+    fn ron_round_trip() {
+        let graph = sample();
+        let parsed =
+            parse_graph(&graph.to_ron()).unwrap_or_else(|e| panic!("{e:?}\n{}", graph.to_ron()));
+        assert_eq!(parsed, graph);
+
+        // Every node kind survives serialization
+        let mut all = MaterialGraph::default();
+        for op in NodeOp::palette() {
+            all.add_node(op, [1.5, -2.0]);
+        }
+        assert_eq!(parse_graph(&all.to_ron()).unwrap(), all);
+    }
+
+    #[test]
+    fn json_and_short_forms_parse() {
+        let graph = parse_graph(
+            r#"{
+                "nodes": [
+                    {"op": {"Float": 0.5}},
+                    {"op": {"Fresnel": {"power": 2.0}}, "inputs": [null]},
+                    {"op": "Mix", "inputs": [0, 1]}
+                ],
+                "outputs": {"roughness": 2}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(graph.nodes[1].op, NodeOp::Fresnel { power: 2.0 });
+        // Missing inputs are unconnected
+        assert_eq!(graph.nodes[2].inputs, [Some(0), Some(1), None]);
+        assert_eq!(graph.output(GraphOutput::Roughness), Some(2));
+        assert_eq!(graph.output(GraphOutput::BaseColor), None);
+    }
+
+    #[test]
+    fn invalid_graphs_are_errors() {
+        assert!(parse_graph("MaterialGraph(nodes: [(op: Glow)])").is_err());
+        assert!(parse_graph("MaterialGraph(nodes: [(op: Add, inputs: [Some(5)])])").is_err());
+        assert!(
+            parse_graph("MaterialGraph(nodes: [(op: OneMinus, inputs: [None, None])])").is_err()
+        );
+        assert!(parse_graph("MaterialGraph(outputs: (albedo: None))").is_err());
+        assert!(parse_graph("MaterialGraph(nodes: [(op: Fresnel)])").is_err());
+    }
+
+    #[test]
+    fn remove_node_remaps_links() {
+        let mut graph = sample();
+        // Removing the transform cuts the sample's uv link and shifts later ids
+        graph.remove_node(1);
+        assert_eq!(graph.nodes.len(), 4);
+        assert_eq!(graph.nodes[1].op, NodeOp::TextureSample);
+        assert_eq!(graph.nodes[1].inputs, [None]);
+        assert_eq!(graph.nodes[2].inputs, [Some(1)]);
+        assert_eq!(graph.output(GraphOutput::BaseColor), Some(2));
+        assert_eq!(graph.output(GraphOutput::Emissive), Some(3));
+    }
+
+    #[test]
+    fn depends_on_follows_links() {
+        let graph = sample();
+        assert!(graph.depends_on(3, 0));
+        assert!(!graph.depends_on(0, 3));
+        assert!(!graph.depends_on(4, 0));
+    }
+
+    #[test]
+    fn sample_graph_loads() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../assets/materials/graphs/rim.matgraph.ron");
+        let graph = load_graph(&path).unwrap_or_else(|e| panic!("{e:?}"));
+        assert!(graph.output(GraphOutput::BaseColor).is_some());
+    }
+}
@@ -1,7 +1,13 @@
 //! CPU-side mesh representation used by loaders.
 
+use anyhow::{Result, bail};
+
+use crate::vertex::VertexStreams;
+
 /// Vertex with position/normal/uv. Values are in object space.
-#[derive(Clone, Copy, Debug, Default, PartialEq)]
+/// `repr(C)` so binary mesh files (`mesh_bin`) can be viewed in place.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct MeshVertex {
     pub position: [f32; 3],
     pub normal: [f32; 3],
@@ -18,22 +24,221 @@ impl MeshVertex {
     }
 }
 
+/// Axis-aligned bounding box in object space.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Aabb {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl Aabb {
+    /// Smallest box containing all points (zero box for an empty slice).
+    pub fn from_points<'a>(points: impl IntoIterator<Item = &'a [f32; 3]>) -> Self {
+        let mut iter = points.into_iter();
+        let Some(first) = iter.next() else {
+            return Self::default();
+        };
+        let (mut min, mut max) = (*first, *first);
+        for p in iter {
+            for i in 0..3 {
+                min[i] = min[i].min(p[i]);
+                max[i] = max[i].max(p[i]);
+            }
+        }
+        Self { min, max }
+    }
+
+    pub fn center(&self) -> [f32; 3] {
+        [
+            (self.min[0] + self.max[0]) * 0.5,
+            (self.min[1] + self.max[1]) * 0.5,
+            (self.min[2] + self.max[2]) * 0.5,
+        ]
+    }
+
+    /// Half of the box size along each axis.
+    pub fn extents(&self) -> [f32; 3] {
+        [
+            (self.max[0] - self.min[0]) * 0.5,
+            (self.max[1] - self.min[1]) * 0.5,
+            (self.max[2] - self.min[2]) * 0.5,
+        ]
+    }
+}
+
+/// Bounding sphere in object space.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct BoundingSphere {
+    pub center: [f32; 3],
+    pub radius: f32,
+}
+
+/// Local bounding volumes computed at load time (used for culling).
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MeshBounds {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+}
+
+impl MeshBounds {
+    /// Box plus its circumscribed sphere (for bounds of deformed meshes).
+    pub fn from_aabb(aabb: Aabb) -> Self {
+        let e = aabb.extents();
+        Self {
+            aabb,
+            sphere: BoundingSphere {
+                center: aabb.center(),
+                radius: (e[0] * e[0] + e[1] * e[1] + e[2] * e[2]).sqrt(),
+            },
+        }
+    }
+
+    /// AABB plus a sphere centered on the box that encloses every vertex.
+    pub fn from_vertices(vertices: &[MeshVertex]) -> Self {
+        let aabb = Aabb::from_points(vertices.iter().map(|v| &v.position));
+        let center = aabb.center();
+        let radius_sq = vertices
+            .iter()
+            .map(|v| {
+                let d = [
+                    v.position[0] - center[0],
+                    v.position[1] - center[1],
+                    v.position[2] - center[2],
+                ];
+                d[0] * d[0] + d[1] * d[1] + d[2] * d[2]
+            })
+            .fold(0.0f32, f32::max);
+        Self {
+            aabb,
+            sphere: BoundingSphere {
+                center,
+                radius: radius_sq.sqrt(),
+            },
+        }
+    }
+}
+
+/// J1: blend shape; per-vertex deltas (parallel to `MeshData::vertices`) scaled by a weight.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MorphTarget {
+    pub name: Option<String>,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    /// Tangent (xyz) deltas; kept for tangent-space shading, not used by the current vertex format.
+    pub tangents: Option<Vec<[f32; 3]>>,
+    /// Weight when nothing animates the target.
+    pub default_weight: f32,
+}
+
 /// Indexed triangle mesh with tightly-packed vertices.
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct MeshData {
     pub vertices: Vec<MeshVertex>,
     pub indices: Vec<u32>,
+    /// Object-space bounds; call [`MeshData::recompute_bounds`] after editing vertices.
+    pub bounds: MeshBounds,
+    /// J1: optional morph targets (empty for static meshes).
+    pub morph_targets: Vec<MorphTarget>,
+    /// M1: optional extra vertex streams (tangents, second UVs, colors, joints/weights).
+    pub streams: VertexStreams,
 }
 
 impl MeshData {
     pub fn new(vertices: Vec<MeshVertex>, indices: Vec<u32>) -> Self {
-        Self { vertices, indices }
+        let bounds = MeshBounds::from_vertices(&vertices);
+        Self {
+            vertices,
+            indices,
+            bounds,
+            morph_targets: Vec::new(),
+            streams: VertexStreams::default(),
+        }
+    }
+
+    /// M1: attach extra vertex streams; fails when a stream does not match the vertex count.
+    pub fn with_streams(mut self, streams: VertexStreams) -> Result<Self> {
+        let n = self.vertices.len();
+        if streams.lengths().any(|len| len != n) {
+            bail!("vertex streams do not have {n} elements");
+        }
+        self.streams = streams;
+        Ok(self)
+    }
+
+    /// J1: attach morph targets; fails when a delta array does not match the vertex count.
+    pub fn with_morph_targets(mut self, targets: Vec<MorphTarget>) -> Result<Self> {
+        let n = self.vertices.len();
+        for (i, t) in targets.iter().enumerate() {
+            let lengths = [
+                Some(t.positions.len()),
+                t.normals.as_ref().map(Vec::len),
+                t.tangents.as_ref().map(Vec::len),
+            ];
+            if lengths.into_iter().flatten().any(|len| len != n) {
+                bail!("morph target {i} does not have {n} deltas");
+            }
+        }
+        self.morph_targets = targets;
+        Ok(self)
+    }
+
+    /// J1: default weight of every morph target.
+    pub fn default_morph_weights(&self) -> Vec<f32> {
+        self.morph_targets
+            .iter()
+            .map(|t| t.default_weight)
+            .collect()
+    }
+
+    /// J1: vertices with `weights` (one per target, missing = 0) of the morph deltas added.
+    pub fn morphed_vertices(&self, weights: &[f32], out: &mut Vec<MeshVertex>) {
+        out.clear();
+        out.extend_from_slice(&self.vertices);
+        for (t, &w) in self.morph_targets.iter().zip(weights) {
+            if w == 0.0 {
+                continue;
+            }
+            for (v, d) in out.iter_mut().zip(&t.positions) {
+                for (x, d) in v.position.iter_mut().zip(d) {
+                    *x += d * w;
+                }
+            }
+            for (v, d) in out.iter_mut().zip(t.normals.iter().flatten()) {
+                for (x, d) in v.normal.iter_mut().zip(d) {
+                    *x += d * w;
+                }
+            }
+        }
+    }
+
+    /// E1: regenerate vertex normals (see [`crate::normals`]), splitting vertices at creases.
+    pub fn generate_normals(&mut self, options: &crate::normals::NormalOptions) {
+        crate::normals::generate_normals(self, options, None);
+    }
+
+    /// J1: box of vertex `i` over all morph weights in [0, 1] (the vertex itself without targets).
+    pub fn morph_extent(&self, i: usize) -> Aabb {
+        let p = self.vertices[i].position;
+        let (mut min, mut max) = (p, p);
+        for t in &self.morph_targets {
+            for k in 0..3 {
+                let d = t.positions[i][k];
+                min[k] += d.min(0.0);
+                max[k] += d.max(0.0);
+            }
+        }
+        Aabb { min, max }
     }
 
     /// Returns `true` if both vertex and index buffers are non-empty.
     pub fn is_valid(&self) -> bool {
         !self.vertices.is_empty() && !self.indices.is_empty()
     }
+
+    /// Refresh [`MeshData::bounds`] from the current vertices.
+    pub fn recompute_bounds(&mut self) {
+        self.bounds = MeshBounds::from_vertices(&self.vertices);
+    }
 }
 
 #[cfg(test)]
@@ -45,4 +250,49 @@ mod tests {
         let data = MeshData::new(vec![MeshVertex::default()], vec![0]);
         assert!(data.is_valid());
     }
+
+    #[test]
+    fn bounds_enclose_vertices() {
+        let data = MeshData::new(
+            vec![
+                MeshVertex::new([-1.0, 0.0, 2.0], [0.0; 3], [0.0; 2]),
+                MeshVertex::new([3.0, 4.0, -2.0], [0.0; 3], [0.0; 2]),
+            ],
+            vec![0, 1, 0],
+        );
+        assert_eq!(data.bounds.aabb.min, [-1.0, 0.0, -2.0]);
+        assert_eq!(data.bounds.aabb.max, [3.0, 4.0, 2.0]);
+        assert_eq!(data.bounds.sphere.center, [1.0, 2.0, 0.0]);
+        assert!((data.bounds.sphere.radius - 12.0f32.sqrt()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn morph_targets_add_weighted_deltas() {
+        let vertex = MeshVertex::new([0.0; 3], [0.0, 0.0, 1.0], [0.0; 2]);
+        let target = |d: [f32; 3]| MorphTarget {
+            positions: vec![d],
+            ..Default::default()
+        };
+        let data = MeshData::new(vec![vertex], vec![0, 0, 0])
+            .with_morph_targets(vec![target([1.0, 0.0, 0.0]), target([0.0, -2.0, 0.0])])
+            .unwrap();
+        let mut out = Vec::new();
+        data.morphed_vertices(&[0.5, 0.25], &mut out);
+        assert_eq!(out[0].position, [0.5, -0.5, 0.0]);
+        let extent = data.morph_extent(0);
+        assert_eq!(
+            (extent.min, extent.max),
+            ([0.0, -2.0, 0.0], [1.0, 0.0, 0.0])
+        );
+
+        let bad = MorphTarget {
+            positions: vec![[0.0; 3]; 2],
+            ..Default::default()
+        };
+        assert!(
+            MeshData::new(vec![vertex], vec![0, 0, 0])
+                .with_morph_targets(vec![bad])
+                .is_err()
+        );
+    }
 }
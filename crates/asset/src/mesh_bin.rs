@@ -0,0 +1,502 @@
+//! M1: binary mesh container (`*.meshbin`), the optimized runtime format of the asset pipeline.
+//!
+//! Little-endian layout, every section 16-byte aligned so blobs can be viewed in place:
+//!
+//! ```text
+//! header      magic "MESHBIN\0", version, vertex stride, attribute count, LOD count,
+//!             build key, CRC32 of the header (without this field), attributes and LOD table
+//! attributes  per attribute: semantic, format, byte offset (u32 each)
+//! LOD table   per LOD: vertex/index blob offsets (u64), vertex/index counts, blob CRC32s,
+//!             bounds (AABB min/max, sphere center/radius)
+//! blobs       vertices (`MeshVertex` layout) and u32 indices of every LOD
+//! ```
+//!
+//! [`MeshFile::open`] memory-maps the file and validates the structure, checksums and index
+//! ranges once; [`MeshFile::lod`] then hands out slices of the mapping with no parsing.
+//! The build key ([`build_key`]) identifies the settings a cached file was built with, so
+//! a cache made with other settings can be rebuilt instead of trusted by timestamp.
+//! Morph targets and extra vertex streams are not stored.
+
+use std::fs::{self, File};
+use std::path::Path;
+
+use anyhow::{Context, Result, bail, ensure};
+
+use crate::lod::MeshLods;
+use crate::mesh::{Aabb, BoundingSphere, MeshBounds, MeshData, MeshVertex};
+// Version 1 files only hold the `MeshVertex` layout
+pub use crate::vertex::{AttributeFormat, MESH_VERTEX_LAYOUT, Semantic, VertexAttribute};
+
+pub const MAGIC: [u8; 8] = *b"MESHBIN\0";
+pub const VERSION: u32 = 1;
+
+const HEADER_SIZE: usize = 32;
+const ATTRIBUTE_SIZE: usize = 12;
+const LOD_ENTRY_SIZE: usize = 72;
+const ALIGN: usize = 16;
+
+/// Zero-copy view of one LOD level.
+#[derive(Clone, Copy, Debug)]
+pub struct MeshView<'a> {
+    pub vertices: &'a [MeshVertex],
+    pub indices: &'a [u32],
+    pub bounds: MeshBounds,
+}
+
+impl MeshView<'_> {
+    /// Raw vertex bytes, laid out like the renderer's vertex buffer.
+    pub fn vertex_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(self.vertices)
+    }
+
+    /// Owned copy (e.g. for CPU processing).
+    pub fn to_mesh_data(&self) -> MeshData {
+        let mut mesh = MeshData::new(self.vertices.to_vec(), self.indices.to_vec());
+        mesh.bounds = self.bounds;
+        mesh
+    }
+}
+
+enum Storage {
+    Mapped(memmap2::Mmap),
+    // u32 words keep the blobs 4-byte aligned
+    Owned(Vec<u32>, usize),
+}
+
+impl Storage {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            Self::Mapped(map) => map,
+            Self::Owned(words, len) => &bytemuck::cast_slice(words)[..*len],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct LodEntry {
+    vertices: std::ops::Range<usize>,
+    indices: std::ops::Range<usize>,
+    bounds: MeshBounds,
+}
+
+/// Validated binary mesh file.
+pub struct MeshFile {
+    storage: Storage,
+    lods: Vec<LodEntry>,
+    build_key: u32,
+}
+
+impl MeshFile {
+    /// Memory-map and validate `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open mesh file: {}", path.display()))?;
+        // SAFETY: the mapping is read-only; like every mmap user we assume the file is not
+        // truncated or rewritten by another process while it is mapped.
+        let map = unsafe { memmap2::Mmap::map(&file) }
+            .with_context(|| format!("Failed to map mesh file: {}", path.display()))?;
+        Self::new(Storage::Mapped(map))
+            .with_context(|| format!("Invalid mesh file: {}", path.display()))
+    }
+
+    /// Validate an in-memory file (copied once into aligned storage).
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut words = vec![0u32; bytes.len().div_ceil(4)];
+        bytemuck::cast_slice_mut::<u32, u8>(&mut words)[..bytes.len()].copy_from_slice(bytes);
+        Self::new(Storage::Owned(words, bytes.len()))
+    }
+
+    fn new(storage: Storage) -> Result<Self> {
+        let lods = validate(storage.bytes())?;
+        let build_key = u32::from_le_bytes(storage.bytes()[24..28].try_into().expect("4 bytes"));
+        Ok(Self {
+            storage,
+            lods,
+            build_key,
+        })
+    }
+
+    pub fn lod_count(&self) -> usize {
+        self.lods.len()
+    }
+
+    /// Level `level` (0 = finest).
+    pub fn lod(&self, level: usize) -> Option<MeshView<'_>> {
+        let entry = self.lods.get(level)?;
+        let bytes = self.storage.bytes();
+        // Ranges and alignment were checked by `validate`
+        Some(MeshView {
+            vertices: bytemuck::cast_slice(&bytes[entry.vertices.clone()]),
+            indices: bytemuck::cast_slice(&bytes[entry.indices.clone()]),
+            bounds: entry.bounds,
+        })
+    }
+
+    /// Key of the settings the file was built with (see [`build_key`]).
+    pub fn build_key(&self) -> u32 {
+        self.build_key
+    }
+
+    /// Owned LOD chain.
+    pub fn to_lods(&self) -> MeshLods {
+        MeshLods {
+            levels: (0..self.lod_count())
+                .filter_map(|l| self.lod(l))
+                .map(|v| v.to_mesh_data())
+                .collect(),
+        }
+    }
+}
+
+/// Key for a description of the build settings, e.g. `"lods=4 ratio=0.5"`; salted with
+/// the crate version so files from an older pipeline do not match.
+pub fn build_key(settings: &str) -> u32 {
+    crc32_parts(&[
+        env!("CARGO_PKG_VERSION").as_bytes(),
+        b"\0",
+        settings.as_bytes(),
+    ])
+}
+
+/// Encode `lods` into the binary container, tagged with `build_key`.
+pub fn encode(lods: &MeshLods, build_key: u32) -> Result<Vec<u8>> {
+    ensure!(!lods.levels.is_empty(), "mesh has no LOD levels");
+    for (l, mesh) in lods.levels.iter().enumerate() {
+        ensure!(mesh.is_valid(), "LOD {l} has no vertices or indices");
+        ensure!(
+            mesh.morph_targets.is_empty(),
+            "LOD {l}: morph targets are not supported in mesh files"
+        );
+        ensure!(
+            mesh.streams.is_empty(),
+            "LOD {l}: extra vertex streams are not supported in mesh files"
+        );
+        if let Some(&i) = mesh
+            .indices
+            .iter()
+            .find(|&&i| i as usize >= mesh.vertices.len())
+        {
+            bail!(
+                "LOD {l}: index {i} out of range ({} vertices)",
+                mesh.vertices.len()
+            );
+        }
+    }
+    let table_end = HEADER_SIZE
+        + MESH_VERTEX_LAYOUT.len() * ATTRIBUTE_SIZE
+        + lods.levels.len() * LOD_ENTRY_SIZE;
+    let mut out = vec![0u8; align(table_end)];
+
+    // Blobs first so the table can record their offsets
+    let mut table = Vec::with_capacity(lods.levels.len() * LOD_ENTRY_SIZE);
+    for mesh in &lods.levels {
+        let vertex_bytes: &[u8] = bytemuck::cast_slice(&mesh.vertices);
+        let index_bytes: &[u8] = bytemuck::cast_slice(&mesh.indices);
+        let vertex_offset = out.len();
+        out.extend_from_slice(vertex_bytes);
+        out.resize(align(out.len()), 0);
+        let index_offset = out.len();
+        out.extend_from_slice(index_bytes);
+        out.resize(align(out.len()), 0);
+
+        put_u64(&mut table, vertex_offset as u64);
+        put_u64(&mut table, index_offset as u64);
+        put_u32(&mut table, count_u32(mesh.vertices.len())?);
+        put_u32(&mut table, count_u32(mesh.indices.len())?);
+        put_u32(&mut table, crc32(vertex_bytes));
+        put_u32(&mut table, crc32(index_bytes));
+        let b = &mesh.bounds;
+        for f in b
+            .aabb
+            .min
+            .iter()
+            .chain(&b.aabb.max)
+            .chain(&b.sphere.center)
+            .chain([&b.sphere.radius])
+        {
+            put_u32(&mut table, f.to_bits());
+        }
+    }
+
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    header.extend_from_slice(&MAGIC);
+    put_u32(&mut header, VERSION);
+    put_u32(&mut header, std::mem::size_of::<MeshVertex>() as u32);
+    put_u32(&mut header, MESH_VERTEX_LAYOUT.len() as u32);
+    put_u32(&mut header, lods.levels.len() as u32);
+    put_u32(&mut header, build_key);
+    let mut attributes = Vec::with_capacity(MESH_VERTEX_LAYOUT.len() * ATTRIBUTE_SIZE);
+    for a in MESH_VERTEX_LAYOUT {
+        put_u32(&mut attributes, a.semantic as u32);
+        put_u32(&mut attributes, a.format as u32);
+        put_u32(&mut attributes, a.offset);
+    }
+    let crc = crc32_parts(&[&header, &attributes, &table]);
+    put_u32(&mut header, crc);
+
+    let mut at = 0;
+    for part in [&header, &attributes, &table] {
+        out[at..at + part.len()].copy_from_slice(part);
+        at += part.len();
+    }
+    Ok(out)
+}
+
+/// Write `lods` to `path` (through a temporary file, so readers never see a partial file).
+pub fn write_mesh_file(path: impl AsRef<Path>, lods: &MeshLods, build_key: u32) -> Result<()> {
+    let path = path.as_ref();
+    let bytes = encode(lods, build_key)?;
+    let tmp = path.with_extension("meshbin.tmp");
+    fs::write(&tmp, bytes).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path)
+        .with_context(|| format!("Failed to move mesh file to {}", path.display()))
+}
+
+fn validate(bytes: &[u8]) -> Result<Vec<LodEntry>> {
+    ensure!(
+        bytes.len() >= HEADER_SIZE,
+        "file too short for a header ({} bytes)",
+        bytes.len()
+    );
+    ensure!(bytes[..8] == MAGIC, "not a mesh file (bad magic)");
+    let u32_at = |at: usize| u32::from_le_bytes(bytes[at..at + 4].try_into().expect("4 bytes"));
+    let version = u32_at(8);
+    ensure!(
+        version == VERSION,
+        "unsupported mesh file version {version} (expected {VERSION})"
+    );
+    let stride = u32_at(12) as usize;
+    let attribute_count = u32_at(16) as usize;
+    let lod_count = u32_at(20) as usize;
+    ensure!(
+        (1..=crate::lod::MAX_LOD_LEVELS).contains(&lod_count),
+        "invalid LOD count {lod_count}"
+    );
+    ensure!(
+        attribute_count <= 16,
+        "invalid attribute count {attribute_count}"
+    );
+    let attributes_end = HEADER_SIZE + attribute_count * ATTRIBUTE_SIZE;
+    let table_end = attributes_end + lod_count * LOD_ENTRY_SIZE;
+    ensure!(
+        bytes.len() >= table_end,
+        "file truncated inside the LOD table"
+    );
+    let crc = crc32_parts(&[&bytes[..HEADER_SIZE - 4], &bytes[HEADER_SIZE..table_end]]);
+    ensure!(crc == u32_at(HEADER_SIZE - 4), "header checksum mismatch");
+
+    // Version 1 readers only view `MeshVertex` data in place
+    let layout: Vec<(u32, u32, u32)> = (0..attribute_count)
+        .map(|a| {
+            let at = HEADER_SIZE + a * ATTRIBUTE_SIZE;
+            (u32_at(at), u32_at(at + 4), u32_at(at + 8))
+        })
+        .collect();
+    let expected: Vec<(u32, u32, u32)> = MESH_VERTEX_LAYOUT
+        .iter()
+        .map(|a| (a.semantic as u32, a.format as u32, a.offset))
+        .collect();
+    ensure!(
+        stride == std::mem::size_of::<MeshVertex>() && layout == expected,
+        "unsupported vertex layout (stride {stride}, {attribute_count} attributes)"
+    );
+
+    let mut lods = Vec::with_capacity(lod_count);
+    for l in 0..lod_count {
+        let at = attributes_end + l * LOD_ENTRY_SIZE;
+        let u64_at = |at: usize| u64::from_le_bytes(bytes[at..at + 8].try_into().expect("8 bytes"));
+        let f32_at = |at: usize| f32::from_bits(u32_at(at));
+        let (vertex_count, index_count) = (u32_at(at + 16) as usize, u32_at(at + 20) as usize);
+        ensure!(vertex_count > 0 && index_count > 0, "LOD {l} is empty");
+        let blob = |offset: u64, len: usize| -> Result<std::ops::Range<usize>> {
+            let start = usize::try_from(offset)
+                .ok()
+                .filter(|&o| o % 4 == 0 && o >= table_end);
+            let range = start.and_then(|s| Some(s..s.checked_add(len)?));
+            match range {
+                Some(r) if r.end <= bytes.len() => Ok(r),
+                _ => bail!("LOD {l}: blob at {offset} (+{len} bytes) is outside the file"),
+            }
+        };
+        let size = |count: usize, width: usize| {
+            count
+                .checked_mul(width)
+                .ok_or_else(|| anyhow::anyhow!("LOD {l}: blob size overflows"))
+        };
+        let vertices = blob(u64_at(at), size(vertex_count, stride)?)?;
+        let indices = blob(u64_at(at + 8), size(index_count, 4)?)?;
+        ensure!(
+            crc32(&bytes[vertices.clone()]) == u32_at(at + 24),
+            "LOD {l}: vertex data checksum mismatch"
+        );
+        ensure!(
+            crc32(&bytes[indices.clone()]) == u32_at(at + 28),
+            "LOD {l}: index data checksum mismatch"
+        );
+        let index_words: &[u32] = bytemuck::try_cast_slice(&bytes[indices.clone()])
+            .map_err(|e| anyhow::anyhow!("LOD {l}: misaligned index data: {e}"))?;
+        if let Some(&i) = index_words.iter().find(|&&i| i as usize >= vertex_count) {
+            bail!("LOD {l}: index {i} out of range ({vertex_count} vertices)");
+        }
+        bytemuck::try_cast_slice::<u8, MeshVertex>(&bytes[vertices.clone()])
+            .map_err(|e| anyhow::anyhow!("LOD {l}: misaligned vertex data: {e}"))?;
+
+        let f = |i: usize| f32_at(at + 32 + i * 4);
+        lods.push(LodEntry {
+            vertices,
+            indices,
+            bounds: MeshBounds {
+                aabb: Aabb {
+                    min: [f(0), f(1), f(2)],
+                    max: [f(3), f(4), f(5)],
+                },
+                sphere: BoundingSphere {
+                    center: [f(6), f(7), f(8)],
+                    radius: f(9),
+                },
+            },
+        });
+    }
+    Ok(lods)
+}
+
+fn align(len: usize) -> usize {
+    len.next_multiple_of(ALIGN)
+}
+
+fn count_u32(count: usize) -> Result<u32> {
+    u32::try_from(count).map_err(|_| anyhow::anyhow!("too many elements for a mesh file ({count})"))
+}
+
+fn put_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn put_u64(out: &mut Vec<u8>, v: u64) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+// CRC-32 (IEEE, reflected), table generated at compile time
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(bytes: &[u8]) -> u32 {
+    crc32_parts(&[bytes])
+}
+
+fn crc32_parts(parts: &[&[u8]]) -> u32 {
+    let mut c = !0u32;
+    for &b in parts.iter().flat_map(|p| p.iter()) {
+        c = CRC_TABLE[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8);
+    }
+    !c
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lods() -> MeshLods {
+        let v = |p: [f32; 3]| MeshVertex::new(p, [0.0, 0.0, 1.0], [p[0], p[1]]);
+        let quad = MeshData::new(
+            vec![
+                v([0.0, 0.0, 0.0]),
+                v([1.0, 0.0, 0.0]),
+                v([1.0, 1.0, 0.0]),
+                v([0.0, 1.0, 0.0]),
+            ],
+            vec![0, 1, 2, 0, 2, 3],
+        );
+        let tri = MeshData::new(quad.vertices[..3].to_vec(), vec![0, 1, 2]);
+        MeshLods {
+            levels: vec![quad, tri],
+        }
+    }
+
+    #[test]
+    fn crc_matches_reference() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn round_trip_through_file() {
+        let lods = lods();
+        let path = std::env::temp_dir().join(format!(
+            "mesh_bin_round_trip_{}.meshbin",
+            std::process::id()
+        ));
+        let key = build_key("lods=2");
+        write_mesh_file(&path, &lods, key).unwrap();
+        let file = MeshFile::open(&path).unwrap_or_else(|e| panic!("{e:?}"));
+        assert_eq!(file.lod_count(), 2);
+        assert_eq!(file.build_key(), key);
+        assert_ne!(key, build_key("lods=3"));
+        let lod0 = file.lod(0).unwrap();
+        assert_eq!(lod0.vertices, &lods.levels[0].vertices[..]);
+        assert_eq!(lod0.indices, &lods.levels[0].indices[..]);
+        assert_eq!(lod0.bounds, lods.levels[0].bounds);
+        assert_eq!(lod0.vertex_bytes().len(), 4 * 32);
+        assert_eq!(file.to_lods(), lods);
+        assert!(file.lod(2).is_none());
+        drop(file);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn corrupt_and_truncated_files_are_errors() {
+        let bytes = encode(&lods(), 7).unwrap();
+        assert!(MeshFile::from_bytes(&bytes).is_ok());
+        // Every truncation fails cleanly
+        for len in 0..bytes.len() {
+            if let Ok(file) = MeshFile::from_bytes(&bytes[..len]) {
+                // Only padding after the last blob may be cut
+                assert!(
+                    len >= bytes.len() - ALIGN && file.lod_count() == 2,
+                    "truncated to {len} bytes"
+                );
+            }
+        }
+        // Flipping any byte in the header, table or data is caught by a check
+        let table_end = HEADER_SIZE + 3 * ATTRIBUTE_SIZE + 2 * LOD_ENTRY_SIZE;
+        let data = align(table_end);
+        for at in (0..table_end).chain(data..data + 4 * 32 + 24) {
+            let mut corrupt = bytes.clone();
+            corrupt[at] ^= 0x40;
+            assert!(
+                MeshFile::from_bytes(&corrupt).is_err(),
+                "flipped byte {at} went unnoticed"
+            );
+        }
+        let mut future = bytes.clone();
+        future[8] = 2;
+        let err = MeshFile::from_bytes(&future).err().unwrap();
+        assert!(err.to_string().contains("version 2"), "{err}");
+        assert!(MeshFile::open("/nonexistent/x.meshbin").is_err());
+    }
+
+    #[test]
+    fn encode_rejects_bad_meshes() {
+        assert!(encode(&MeshLods::default(), 0).is_err());
+        let mut bad = lods();
+        bad.levels[1].indices[2] = 7;
+        assert!(encode(&bad, 0).is_err());
+    }
+}
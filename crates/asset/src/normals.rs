@@ -0,0 +1,263 @@
+//! E1: vertex normal generation for meshes without (usable) normals.
+//!
+//! Flat normals give every triangle its own face normal. Smooth normals average the face
+//! normals around each position (welded by exact position, so UV seams don't crease) weighted
+//! by triangle area or corner angle. Faces only smooth together when they share a smoothing
+//! group (group 0 = flat, as in OBJ `s off`) and meet at no more than the crease angle.
+//! Vertices are split where one vertex ends up needing several normals.
+
+use std::collections::HashMap;
+
+use crate::mesh::{MeshData, MeshVertex};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NormalWeighting {
+    /// Larger triangles pull harder (cheap, good for even tessellation).
+    Area,
+    /// Weight by the triangle's angle at the vertex (independent of tessellation).
+    #[default]
+    Angle,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalMode {
+    Flat,
+    Smooth(NormalWeighting),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct NormalOptions {
+    pub mode: NormalMode,
+    /// Faces meeting at a sharper angle (radians) keep separate normals; π smooths everything.
+    pub crease_angle: f32,
+}
+
+impl Default for NormalOptions {
+    fn default() -> Self {
+        Self {
+            mode: NormalMode::Smooth(NormalWeighting::Angle),
+            crease_angle: std::f32::consts::PI,
+        }
+    }
+}
+
+/// Replace the normals of `mesh`. `smoothing_groups` holds one group per triangle
+/// (`None` = every face in group 1).
+pub fn generate_normals(
+    mesh: &mut MeshData,
+    options: &NormalOptions,
+    smoothing_groups: Option<&[u32]>,
+) {
+    generate(mesh, options, smoothing_groups, None);
+}
+
+// `authored[v]` vertices keep their normal (OBJ faces that had `vn`)
+pub(crate) fn generate(
+    mesh: &mut MeshData,
+    options: &NormalOptions,
+    smoothing_groups: Option<&[u32]>,
+    authored: Option<&[bool]>,
+) {
+    let triangles = mesh.indices.len() / 3;
+    let group = |f: usize| {
+        smoothing_groups
+            .and_then(|g| g.get(f).copied())
+            .unwrap_or(1)
+    };
+    let position = |i: u32| mesh.vertices[i as usize].position;
+    let key = |p: [f32; 3]| p.map(|x| (x + 0.0).to_bits());
+
+    // Raw cross product (length = twice the area), unit normal and corner angles per face
+    let mut cross = Vec::with_capacity(triangles);
+    let mut unit = Vec::with_capacity(triangles);
+    let mut angles = Vec::with_capacity(triangles);
+    for tri in mesh.indices.chunks_exact(3) {
+        let [a, b, c] = [tri[0], tri[1], tri[2]].map(position);
+        let n = cross3(sub(b, a), sub(c, a));
+        cross.push(n);
+        unit.push(normalize(n).unwrap_or([0.0; 3]));
+        angles.push([angle(a, b, c), angle(b, c, a), angle(c, a, b)]);
+    }
+
+    let mut around: HashMap<[u32; 3], Vec<(usize, usize)>> = HashMap::new();
+    if matches!(options.mode, NormalMode::Smooth(_)) {
+        for (corner, &i) in mesh.indices.iter().enumerate().take(triangles * 3) {
+            around
+                .entry(key(position(i)))
+                .or_default()
+                .push((corner / 3, corner % 3));
+        }
+    }
+    let min_cos = options.crease_angle.cos();
+
+    let mut vertices = Vec::with_capacity(mesh.vertices.len());
+    let mut source: Vec<usize> = Vec::with_capacity(mesh.vertices.len());
+    let mut unique: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+    let mut indices = Vec::with_capacity(mesh.indices.len());
+    for (corner, &i) in mesh.indices.iter().enumerate().take(triangles * 3) {
+        let f = corner / 3;
+        let vertex = mesh.vertices[i as usize];
+        let normal = if authored.is_some_and(|a| a.get(i as usize).copied().unwrap_or(false)) {
+            vertex.normal
+        } else {
+            match options.mode {
+                NormalMode::Smooth(weighting) if group(f) != 0 => {
+                    let mut sum = [0.0; 3];
+                    for &(g, k) in &around[&key(vertex.position)] {
+                        if group(g) != group(f) || dot(unit[g], unit[f]) < min_cos {
+                            continue;
+                        }
+                        let w = match weighting {
+                            NormalWeighting::Area => cross[g],
+                            NormalWeighting::Angle => unit[g].map(|x| x * angles[g][k]),
+                        };
+                        sum = add(sum, w);
+                    }
+                    normalize(sum).unwrap_or(unit[f])
+                }
+                _ => unit[f],
+            }
+        };
+        // Degenerate faces keep whatever normal the vertex had
+        let normal = if normal == [0.0; 3] {
+            vertex.normal
+        } else {
+            normal
+        };
+        let index = *unique
+            .entry((i, normal.map(f32::to_bits)))
+            .or_insert_with(|| {
+                vertices.push(MeshVertex { normal, ..vertex });
+                source.push(i as usize);
+                (vertices.len() - 1) as u32
+            });
+        indices.push(index);
+    }
+
+    let remap = |deltas: &Vec<[f32; 3]>| source.iter().map(|&s| deltas[s]).collect::<Vec<_>>();
+    for target in &mut mesh.morph_targets {
+        target.positions = remap(&target.positions);
+        target.normals = target.normals.as_ref().map(remap);
+        target.tangents = target.tangents.as_ref().map(remap);
+    }
+    mesh.streams = mesh.streams.pick(&source);
+    mesh.vertices = vertices;
+    mesh.indices = indices;
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross3(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn normalize(v: [f32; 3]) -> Option<[f32; 3]> {
+    let len = dot(v, v).sqrt();
+    (len > 1e-12).then(|| v.map(|x| x / len))
+}
+
+// Angle at `p` between the edges towards `a` and `b`
+fn angle(p: [f32; 3], a: [f32; 3], b: [f32; 3]) -> f32 {
+    match (normalize(sub(a, p)), normalize(sub(b, p))) {
+        (Some(u), Some(v)) => dot(u, v).clamp(-1.0, 1.0).acos(),
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two triangles folded 90° along the X axis: one in the XY plane, one in the XZ plane
+    fn fold() -> MeshData {
+        let v = |p| MeshVertex::new(p, [0.0; 3], [0.0; 2]);
+        let vertices = vec![
+            v([0.0, 0.0, 0.0]),
+            v([1.0, 0.0, 0.0]),
+            v([0.0, 1.0, 0.0]),
+            v([0.0, 0.0, 1.0]),
+        ];
+        MeshData::new(vertices, vec![0, 1, 2, 0, 3, 1])
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        assert!(
+            a.iter().zip(b).all(|(x, y)| (x - y).abs() < 1e-5),
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn flat_and_smooth_normals() {
+        let mut flat = fold();
+        generate_normals(
+            &mut flat,
+            &NormalOptions {
+                mode: NormalMode::Flat,
+                ..Default::default()
+            },
+            None,
+        );
+        // The shared edge vertices split into one copy per face
+        assert_eq!(flat.vertices.len(), 6);
+        assert_close(
+            flat.vertices[flat.indices[0] as usize].normal,
+            [0.0, 0.0, 1.0],
+        );
+        assert_close(
+            flat.vertices[flat.indices[3] as usize].normal,
+            [0.0, 1.0, 0.0],
+        );
+
+        let mut smooth = fold();
+        smooth.generate_normals(&NormalOptions::default());
+        assert_eq!(smooth.vertices.len(), 4);
+        let h = std::f32::consts::FRAC_1_SQRT_2;
+        assert_close(smooth.vertices[0].normal, [0.0, h, h]);
+        assert_close(smooth.vertices[2].normal, [0.0, 0.0, 1.0]);
+
+        // Below the 90° fold the crease keeps the faces apart
+        let mut creased = fold();
+        creased.generate_normals(&NormalOptions {
+            crease_angle: 60f32.to_radians(),
+            ..Default::default()
+        });
+        assert_eq!(creased.vertices.len(), 6);
+    }
+
+    #[test]
+    fn smoothing_groups_and_weighting() {
+        let mut grouped = fold();
+        generate_normals(&mut grouped, &NormalOptions::default(), Some(&[1, 2]));
+        assert_eq!(grouped.vertices.len(), 6);
+        let mut off = fold();
+        generate_normals(&mut off, &NormalOptions::default(), Some(&[0, 0]));
+        assert_eq!(off.vertices.len(), 6);
+
+        // A larger second face pulls area-weighted normals towards it, not angle-weighted ones
+        let mut big = fold();
+        big.vertices[3].position = [0.0, 0.0, 4.0];
+        big.vertices[1].position = [4.0, 0.0, 0.0];
+        let mut angle = big.clone();
+        angle.generate_normals(&NormalOptions::default());
+        big.generate_normals(&NormalOptions {
+            mode: NormalMode::Smooth(NormalWeighting::Area),
+            ..Default::default()
+        });
+        assert!(big.vertices[0].normal[1] > angle.vertices[0].normal[1]);
+    }
+}
@@ -1,15 +1,121 @@
 //! Minimal OBJ parser supporting positions, normals and texture coordinates.
+//! E1: `load_obj_*` flatten the file into one mesh.
+//! E1: faces without `vn` get generated normals honoring `s` smoothing groups (faces before
+//! any `s` statement are smoothed together, `s off`/`s 0` faces are flat).
+//! J2: `load_obj_model*` keep objects/groups (`o`/`g`) and split submeshes per `usemtl`,
+//! with materials from the `mtllib` files (see [`parse_mtl`]).
 
 use std::{
     collections::HashMap,
-    fs::File,
+    fs::{self, File},
     io::{self, BufRead, BufReader},
     path::Path,
 };
 
 use anyhow::{Context, Result, anyhow};
 
+use crate::material::{AlphaMode, MaterialDesc};
 use crate::mesh::{MeshData, MeshVertex};
+use crate::normals::{self, NormalOptions};
+
+/// Material from an MTL library. Texture paths are as written, relative to the `.mtl` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjMaterial {
+    pub name: String,
+    /// `Kd`
+    pub diffuse: [f32; 3],
+    /// `Ks`
+    pub specular: [f32; 3],
+    /// `Ns` (specular exponent)
+    pub shininess: f32,
+    /// `Ke`
+    pub emissive: [f32; 3],
+    /// `d`, or `1 - Tr`
+    pub dissolve: f32,
+    /// `map_Kd`
+    pub diffuse_texture: Option<String>,
+    /// `map_Bump` / `bump` / `norm`
+    pub bump_texture: Option<String>,
+    /// PBR extension `Pr`
+    pub roughness: Option<f32>,
+    /// PBR extension `Pm`
+    pub metallic: Option<f32>,
+    /// `map_Pr`
+    pub roughness_texture: Option<String>,
+    /// `map_Pm`
+    pub metallic_texture: Option<String>,
+}
+
+impl ObjMaterial {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            diffuse: [1.0; 3],
+            specular: [0.0; 3],
+            shininess: 0.0,
+            emissive: [0.0; 3],
+            dissolve: 1.0,
+            diffuse_texture: None,
+            bump_texture: None,
+            roughness: None,
+            metallic: None,
+            roughness_texture: None,
+            metallic_texture: None,
+        }
+    }
+
+    /// Renderer material: `Pr`/`Pm` when present, else roughness derived from `Ns`
+    /// (Blinn-Phong exponent to GGX alpha) and no metalness. `texture_dir` (relative to the
+    /// assets root) is prepended to the diffuse texture path.
+    pub fn to_material_desc(&self, texture_dir: &str) -> MaterialDesc {
+        let roughness = self
+            .roughness
+            .unwrap_or_else(|| (2.0 / (self.shininess.max(0.0) + 2.0)).sqrt());
+        let [r, g, b] = self.diffuse;
+        MaterialDesc {
+            base_color: [r, g, b, self.dissolve],
+            metallic: self.metallic.unwrap_or(0.0),
+            roughness: roughness.clamp(0.0, 1.0),
+            emissive: self.emissive,
+            base_color_texture: self.diffuse_texture.as_ref().map(|t| match texture_dir {
+                "" => t.clone(),
+                dir => format!("{}/{t}", dir.trim_end_matches('/')),
+            }),
+            alpha_mode: if self.dissolve < 1.0 {
+                AlphaMode::Blend
+            } else {
+                AlphaMode::Opaque
+            },
+            ..MaterialDesc::default()
+        }
+    }
+}
+
+/// Faces sharing an object, group and material, as a compact mesh.
+#[derive(Clone, Debug)]
+pub struct ObjSubmesh {
+    /// Last `o` name before the faces.
+    pub object: Option<String>,
+    /// Last `g` name(s) before the faces.
+    pub group: Option<String>,
+    /// Index into [`ObjModel::materials`]; `None` without `usemtl` or for unknown names.
+    pub material: Option<usize>,
+    pub mesh: MeshData,
+}
+
+/// OBJ file split into submeshes, plus the materials of its MTL libraries.
+#[derive(Clone, Debug, Default)]
+pub struct ObjModel {
+    pub submeshes: Vec<ObjSubmesh>,
+    pub materials: Vec<ObjMaterial>,
+}
+
+impl ObjModel {
+    /// Material of `submesh`, if any.
+    pub fn material(&self, submesh: &ObjSubmesh) -> Option<&ObjMaterial> {
+        submesh.material.and_then(|m| self.materials.get(m))
+    }
+}
 
 /// Load an OBJ mesh from a file path.
 pub fn load_obj_from_path(path: impl AsRef<Path>) -> Result<MeshData> {
@@ -28,7 +134,180 @@ pub fn load_obj_from_str(contents: &str) -> Result<MeshData> {
     parse_obj(io::Cursor::new(contents))
 }
 
+/// Load an OBJ model; `mtllib` files are resolved next to the OBJ file. Missing libraries are
+/// logged and their materials left unassigned, as DCC exports often ship without them.
+pub fn load_obj_model(path: impl AsRef<Path>) -> Result<ObjModel> {
+    let path = path.as_ref();
+    let file =
+        File::open(path).with_context(|| format!("Failed to open OBJ file: {}", path.display()))?;
+    let parsed = parse_faces(BufReader::new(file))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut materials = Vec::new();
+    for lib in &parsed.libraries {
+        match load_mtl(dir.join(lib)) {
+            Ok(mut m) => materials.append(&mut m),
+            Err(e) => log::warn!("{}: {e:#}", path.display()),
+        }
+    }
+    build_model(parsed, materials)
+}
+
+/// Parse an OBJ model string with the materials of `mtl` (its `mtllib` lines are ignored).
+pub fn load_obj_model_from_str(contents: &str, mtl: &str) -> Result<ObjModel> {
+    let materials = parse_mtl(mtl)?;
+    build_model(parse_faces(io::Cursor::new(contents))?, materials)
+}
+
+/// Load an MTL material library.
+pub fn load_mtl(path: impl AsRef<Path>) -> Result<Vec<ObjMaterial>> {
+    let path = path.as_ref();
+    let src = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read MTL file: {}", path.display()))?;
+    parse_mtl(&src).with_context(|| format!("Invalid MTL file: {}", path.display()))
+}
+
+/// Parse an MTL library. Unsupported statements are ignored; texture statements skip their
+/// options (such as `-bm 1.0`) and take the rest of the line as the path, spaces included.
+pub fn parse_mtl(src: &str) -> Result<Vec<ObjMaterial>> {
+    let mut materials: Vec<ObjMaterial> = Vec::new();
+    for (line_no, line) in src.lines().enumerate() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let mut parts = trimmed.split_whitespace();
+        let tag = parts.next().unwrap_or_default();
+        if tag == "newmtl" {
+            let name = trimmed[tag.len()..].trim();
+            if name.is_empty() {
+                anyhow::bail!("Missing material name on line {}", line_no + 1);
+            }
+            materials.push(ObjMaterial::new(name));
+            continue;
+        }
+        let Some(material) = materials.last_mut() else {
+            anyhow::bail!("'{}' before any newmtl on line {}", tag, line_no + 1);
+        };
+        let texture = || texture_path(&trimmed[tag.len()..], line_no);
+        match tag {
+            "Kd" => material.diffuse = parse_rgb(parts, line_no)?,
+            "Ks" => material.specular = parse_rgb(parts, line_no)?,
+            "Ke" => material.emissive = parse_rgb(parts, line_no)?,
+            "Ns" => material.shininess = parse_f32(parts.next(), line_no, "Ns")?,
+            "d" => material.dissolve = parse_f32(parts.next(), line_no, "d")?,
+            "Tr" => material.dissolve = 1.0 - parse_f32(parts.next(), line_no, "Tr")?,
+            "Pr" => material.roughness = Some(parse_f32(parts.next(), line_no, "Pr")?),
+            "Pm" => material.metallic = Some(parse_f32(parts.next(), line_no, "Pm")?),
+            "map_Kd" => material.diffuse_texture = Some(texture()?),
+            "map_Bump" | "map_bump" | "bump" | "norm" => material.bump_texture = Some(texture()?),
+            "map_Pr" => material.roughness_texture = Some(texture()?),
+            "map_Pm" => material.metallic_texture = Some(texture()?),
+            _ => {
+                // Ignore other statements (Ka/Ni/illum/map_Ks/...)
+            }
+        }
+    }
+    Ok(materials)
+}
+
+// Texture statement arguments: `-option value...` pairs, then the path
+fn texture_path(args: &str, line_no: usize) -> Result<String> {
+    fn token(s: &str) -> (&str, &str) {
+        let (token, rest) = s.split_at(s.find(char::is_whitespace).unwrap_or(s.len()));
+        (token, rest.trim_start())
+    }
+    let mut rest = args.trim_start();
+    while rest.starts_with('-') {
+        let (option, tail) = token(rest);
+        rest = tail;
+        // -o/-s/-t take 1 to 3 numbers, -mm takes 2, everything else 1
+        let values = match option {
+            "-o" | "-s" | "-t" => 3,
+            "-mm" => 2,
+            _ => 1,
+        };
+        for i in 0..values {
+            let (value, tail) = token(rest);
+            if i > 0 && value.parse::<f32>().is_err() {
+                break;
+            }
+            rest = tail;
+        }
+    }
+    Some(rest.trim())
+        .filter(|path| !path.is_empty())
+        .map(str::to_string)
+        .ok_or_else(|| anyhow!("Missing texture path on line {}", line_no + 1))
+}
+
 fn parse_obj<R: BufRead>(reader: R) -> Result<MeshData> {
+    let parsed = parse_faces(reader)?;
+    // One mesh in file order: take each triangle from its part in turn
+    let mut cursors = vec![0; parsed.parts.len()];
+    let mut indices = Vec::with_capacity(parsed.order.len() * 3);
+    for &part in &parsed.order {
+        let start = cursors[part];
+        indices.extend_from_slice(&parsed.parts[part].indices[start..start + 3]);
+        cursors[part] += 3;
+    }
+    Ok(MeshData::new(parsed.vertices, indices))
+}
+
+// Faces with the same object/group/material, indexing the shared vertex list
+struct Part {
+    object: Option<String>,
+    group: Option<String>,
+    material: Option<String>,
+    indices: Vec<u32>,
+    // Smoothing group per triangle
+    smoothing: Vec<u32>,
+}
+
+struct ParsedObj {
+    vertices: Vec<MeshVertex>,
+    parts: Vec<Part>,
+    // Part of every triangle, in file order
+    order: Vec<usize>,
+    libraries: Vec<String>,
+}
+
+fn build_model(parsed: ParsedObj, materials: Vec<ObjMaterial>) -> Result<ObjModel> {
+    let mut submeshes = Vec::with_capacity(parsed.parts.len());
+    for part in parsed.parts {
+        let material = part.material.as_ref().and_then(|name| {
+            let found = materials.iter().position(|m| &m.name == name);
+            if found.is_none() {
+                log::warn!("OBJ material '{name}' not found in its MTL libraries");
+            }
+            found
+        });
+        // Compact the shared vertex list to the vertices this part uses
+        let mut remap: HashMap<u32, u32> = HashMap::new();
+        let mut vertices = Vec::new();
+        let indices = part
+            .indices
+            .iter()
+            .map(|&i| {
+                *remap.entry(i).or_insert_with(|| {
+                    vertices.push(parsed.vertices[i as usize]);
+                    (vertices.len() - 1) as u32
+                })
+            })
+            .collect();
+        submeshes.push(ObjSubmesh {
+            object: part.object,
+            group: part.group,
+            material,
+            mesh: MeshData::new(vertices, indices),
+        });
+    }
+    Ok(ObjModel {
+        submeshes,
+        materials,
+    })
+}
+
+fn parse_faces<R: BufRead>(reader: R) -> Result<ParsedObj> {
     let mut positions: Vec<[f32; 3]> = Vec::new();
     let mut normals: Vec<[f32; 3]> = Vec::new();
     let mut texcoords: Vec<[f32; 2]> = Vec::new();
@@ -38,7 +317,16 @@ fn parse_obj<R: BufRead>(reader: R) -> Result<MeshData> {
 
     let mut unique: HashMap<Key, u32> = HashMap::new();
     let mut vertices: Vec<MeshVertex> = Vec::new();
-    let mut indices: Vec<u32> = Vec::new();
+    let mut face_parts: Vec<Part> = Vec::new();
+    let mut order: Vec<usize> = Vec::new();
+    let mut libraries: Vec<String> = Vec::new();
+    // Current o/g/usemtl state and the part it maps to (created on the first face)
+    let (mut object, mut group, mut material): (Option<String>, Option<String>, Option<String>) =
+        (None, None, None);
+    let mut current: Option<usize> = None;
+    let mut smoothing = 1;
+    // Whether each vertex came with a `vn`
+    let mut authored: Vec<bool> = Vec::new();
 
     for (line_no, line) in reader.lines().enumerate() {
         let line = line.with_context(|| format!("Failed to read line {}", line_no + 1))?;
@@ -52,7 +340,38 @@ fn parse_obj<R: BufRead>(reader: R) -> Result<MeshData> {
             .next()
             .ok_or_else(|| anyhow!("Malformed OBJ line {}: '{}'", line_no + 1, trimmed))?;
 
+        // Rest of the line after the tag (names may contain spaces)
+        let rest = || {
+            Some(trimmed[tag.len()..].trim())
+                .filter(|r| !r.is_empty())
+                .map(str::to_string)
+        };
+
         match tag {
+            "o" => {
+                object = rest();
+                current = None;
+            }
+            "g" => {
+                group = rest();
+                current = None;
+            }
+            "usemtl" => {
+                material = rest();
+                current = None;
+            }
+            "mtllib" => libraries.extend(parts.map(str::to_string)),
+            "s" => {
+                smoothing = match parts.next() {
+                    Some("off") => 0,
+                    value => value
+                        .ok_or_else(|| anyhow!("Missing smoothing group on line {}", line_no + 1))?
+                        .parse()
+                        .with_context(|| {
+                            format!("Invalid smoothing group on line {}", line_no + 1)
+                        })?,
+                };
+            }
             "v" => {
                 let x = parse_f32(parts.next(), line_no, "x coordinate")?;
                 let y = parse_f32(parts.next(), line_no, "y coordinate")?;
@@ -97,6 +416,7 @@ fn parse_obj<R: BufRead>(reader: R) -> Result<MeshData> {
                             let idx = u32::try_from(vertices.len())
                                 .map_err(|_| anyhow!("Too many vertices in OBJ (>{})", u32::MAX))?;
                             vertices.push(MeshVertex::new(position, normal, uv));
+                            authored.push(vni.is_some());
                             unique.insert(key, idx);
                             idx
                         }
@@ -107,24 +427,90 @@ fn parse_obj<R: BufRead>(reader: R) -> Result<MeshData> {
                 if face_indices.len() < 3 {
                     continue;
                 }
+                // Faces returning to an earlier object/group/material extend its part
+                let part = *current.get_or_insert_with(|| {
+                    match face_parts.iter().position(|p| {
+                        p.object == object && p.group == group && p.material == material
+                    }) {
+                        Some(i) => i,
+                        None => {
+                            face_parts.push(Part {
+                                object: object.clone(),
+                                group: group.clone(),
+                                material: material.clone(),
+                                indices: Vec::new(),
+                                smoothing: Vec::new(),
+                            });
+                            face_parts.len() - 1
+                        }
+                    }
+                });
+                order.extend(std::iter::repeat_n(part, face_indices.len() - 2));
+                let part = &mut face_parts[part];
                 // Triangulate fan
                 for tri in 1..(face_indices.len() - 1) {
-                    indices.push(face_indices[0]);
-                    indices.push(face_indices[tri]);
-                    indices.push(face_indices[tri + 1]);
+                    part.indices.push(face_indices[0]);
+                    part.indices.push(face_indices[tri]);
+                    part.indices.push(face_indices[tri + 1]);
+                    part.smoothing.push(smoothing);
                 }
             }
             _ => {
-                // Ignore other directives (o/g/s/usemtl/etc.)
+                // Ignore other directives (l/p/etc.)
             }
         }
     }
 
-    if vertices.is_empty() || indices.is_empty() {
+    if vertices.is_empty() || face_parts.is_empty() {
         anyhow::bail!("OBJ contained no triangles");
     }
 
-    Ok(MeshData::new(vertices, indices))
+    // Generate the missing normals over all parts at once, so smoothing crosses material
+    // boundaries, then split the indices back
+    if authored.contains(&false) {
+        let indices = face_parts
+            .iter()
+            .flat_map(|p| p.indices.iter().copied())
+            .collect();
+        let groups: Vec<u32> = face_parts
+            .iter()
+            .flat_map(|p| p.smoothing.iter().copied())
+            .collect();
+        let mut mesh = MeshData::new(vertices, indices);
+        normals::generate(
+            &mut mesh,
+            &NormalOptions::default(),
+            Some(&groups),
+            Some(&authored),
+        );
+        let mut rest = mesh.indices.as_slice();
+        for part in &mut face_parts {
+            let (head, tail) = rest.split_at(part.indices.len());
+            part.indices = head.to_vec();
+            rest = tail;
+        }
+        vertices = mesh.vertices;
+    }
+
+    Ok(ParsedObj {
+        vertices,
+        parts: face_parts,
+        order,
+        libraries,
+    })
+}
+
+fn parse_rgb<'a>(mut parts: impl Iterator<Item = &'a str>, line_no: usize) -> Result<[f32; 3]> {
+    let r = parse_f32(parts.next(), line_no, "red")?;
+    // A single value sets all three channels
+    match parts.next() {
+        Some(g) => Ok([
+            r,
+            parse_f32(Some(g), line_no, "green")?,
+            parse_f32(parts.next(), line_no, "blue")?,
+        ]),
+        None => Ok([r; 3]),
+    }
 }
 
 fn parse_f32(value: Option<&str>, line_no: usize, what: &str) -> Result<f32> {
@@ -207,4 +593,117 @@ mod tests {
         assert_eq!(mesh.indices.len(), 3);
         assert!(mesh.is_valid());
     }
+
+    #[test]
+    fn usemtl_splits_submeshes_and_mtl_maps_materials() {
+        let obj = r#"
+            mtllib scene.mtl
+            o Crate
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            g lid top
+            usemtl Wood
+            f 1 2 3
+            usemtl Metal
+            f 1 3 4
+            usemtl Wood
+            f 2 3 4
+            usemtl Missing
+            f 1 2 4
+        "#;
+        let mtl = r#"
+            newmtl Wood
+            Kd 0.6 0.4 0.2
+            Ns 98
+            map_Kd textures/wood.png
+            map_Bump -bm 0.5 textures/wood_n.png
+            newmtl Metal
+            Kd 0.9
+            d 0.5
+            Pr 0.2
+            Pm 1.0
+            map_Kd -o 0.5 0.5 -clamp on My Textures/metal.png
+        "#;
+        let model = load_obj_model_from_str(obj, mtl).expect("parse model");
+        assert_eq!(model.materials.len(), 2);
+        assert_eq!(model.submeshes.len(), 3);
+
+        let wood = &model.submeshes[0];
+        assert_eq!(
+            (wood.object.as_deref(), wood.group.as_deref()),
+            (Some("Crate"), Some("lid top"))
+        );
+        assert_eq!(wood.mesh.indices.len(), 6);
+        assert_eq!(wood.mesh.vertices.len(), 4);
+        let material = model.material(wood).unwrap();
+        assert_eq!(
+            material.bump_texture.as_deref(),
+            Some("textures/wood_n.png")
+        );
+        let desc = material.to_material_desc("models");
+        assert_eq!(
+            desc.base_color_texture.as_deref(),
+            Some("models/textures/wood.png")
+        );
+        assert!((desc.roughness - 0.02f32.sqrt()).abs() < 1e-6);
+
+        let metal = model
+            .material(&model.submeshes[1])
+            .unwrap()
+            .to_material_desc("");
+        assert_eq!(metal.base_color, [0.9, 0.9, 0.9, 0.5]);
+        assert_eq!(
+            metal.base_color_texture.as_deref(),
+            Some("My Textures/metal.png")
+        );
+        assert_eq!(
+            (metal.metallic, metal.roughness, metal.alpha_mode),
+            (1.0, 0.2, AlphaMode::Blend)
+        );
+        assert_eq!(model.submeshes[2].material, None);
+
+        // The flat loader keeps every face in one mesh with shared vertices
+        // in file order
+        let mesh = load_obj_from_str(obj).unwrap();
+        assert_eq!((mesh.vertices.len(), mesh.indices.len()), (4, 12));
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3, 1, 2, 3, 0, 1, 3]);
+    }
+
+    #[test]
+    fn missing_normals_are_generated_per_smoothing_group() {
+        // Quads in the XY and YZ planes sharing the edge 1-2, plus an authored triangle
+        let obj = |second_group: &str| {
+            format!(
+                "v 0 0 0\nv 0 1 0\nv 1 0 0\nv 1 1 0\nv 0 0 1\nv 0 1 1\n\
+                 v 5 5 5\nv 6 5 5\nv 5 6 5\nvn 0 1 0\n\
+                 s 1\nf 1 3 4 2\nf 7//1 8//1 9//1\ns {second_group}\nf 1 2 6 5\n"
+            )
+        };
+        let mesh = load_obj_from_str(&obj("off")).unwrap();
+        let normal =
+            |mesh: &MeshData, corner: usize| mesh.vertices[mesh.indices[corner] as usize].normal;
+        assert_eq!(normal(&mesh, 0), [0.0, 0.0, 1.0]);
+        assert_eq!(normal(&mesh, 12), [1.0, 0.0, 0.0]);
+        assert_eq!(normal(&mesh, 6), [0.0, 1.0, 0.0]);
+        // Vertex 1 split between the flat and the smooth quad
+        assert_eq!(mesh.vertices.len(), 11);
+
+        let mesh = load_obj_from_str(&obj("1")).unwrap();
+        let h = std::f32::consts::FRAC_1_SQRT_2;
+        let n = normal(&mesh, 0);
+        assert!(
+            (n[0] - h).abs() < 1e-5 && n[1].abs() < 1e-5 && (n[2] - h).abs() < 1e-5,
+            "{n:?}"
+        );
+        assert_eq!(mesh.vertices.len(), 9);
+    }
+
+    #[test]
+    fn mtl_errors() {
+        assert!(parse_mtl("Kd 1 1 1").is_err());
+        assert!(parse_mtl("newmtl a\nKd 1 x 1").is_err());
+        assert!(parse_mtl("newmtl a\nmap_Kd").is_err());
+    }
 }
@@ -0,0 +1,685 @@
+//! M1: mesh processing for import time and offline asset tools.
+//!
+//! Every operation works on [`MeshData`] in place and keeps morph targets in step with the
+//! vertices. [`optimize`] chains the usual pre-upload passes: degenerate removal, vertex cache
+//! order (Forsyth), overdraw-aware cluster order and vertex fetch order.
+
+use std::collections::HashMap;
+
+use crate::mesh::{BoundingSphere, MeshBounds, MeshData, MeshVertex, MorphTarget};
+use crate::vertex::VertexStreams;
+
+/// Post-transform cache size the vertex cache order is tuned for.
+pub const VERTEX_CACHE_SIZE: usize = 32;
+
+/// Remove degenerate triangles, then reorder for the vertex cache, overdraw and vertex fetch.
+pub fn optimize(mesh: &mut MeshData) {
+    remove_degenerate_triangles(mesh, 0.0);
+    optimize_vertex_cache(mesh);
+    optimize_overdraw(mesh);
+    optimize_vertex_fetch(mesh);
+}
+
+/// Merge vertices whose position, normal and UV (and morph deltas and extra streams) all lie within `epsilon`
+/// of each other. Returns the number of vertices removed.
+pub fn weld_vertices(mesh: &mut MeshData, epsilon: f32) -> usize {
+    let epsilon = epsilon.max(0.0);
+    let cell = |p: [f32; 3]| -> [i64; 3] {
+        if epsilon > 0.0 {
+            p.map(|x| (x / epsilon).floor() as i64)
+        } else {
+            p.map(|x| (x + 0.0).to_bits() as i64)
+        }
+    };
+    let close = |a: &[f32], b: &[f32]| a.iter().zip(b).all(|(x, y)| (x - y).abs() <= epsilon);
+    let same = |a: usize, b: usize| {
+        let (va, vb) = (&mesh.vertices[a], &mesh.vertices[b]);
+        close(&va.position, &vb.position)
+            && close(&va.normal, &vb.normal)
+            && close(&va.uv, &vb.uv)
+            && mesh.morph_targets.iter().all(|t| {
+                close(&t.positions[a], &t.positions[b])
+                    && t.normals.as_ref().is_none_or(|n| close(&n[a], &n[b]))
+                    && t.tangents.as_ref().is_none_or(|n| close(&n[a], &n[b]))
+            })
+            && mesh.streams.close(a, b, epsilon)
+    };
+
+    let mut grid: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    let mut remap = vec![None; mesh.vertices.len()];
+    let mut count = 0u32;
+    for v in 0..mesh.vertices.len() {
+        let c = cell(mesh.vertices[v].position);
+        // Neighbouring cells can hold vertices within epsilon
+        let range = if epsilon > 0.0 { -1..=1 } else { 0..=0 };
+        let mut found = None;
+        'search: for dx in range.clone() {
+            for dy in range.clone() {
+                for dz in range.clone() {
+                    let key = [c[0] + dx, c[1] + dy, c[2] + dz];
+                    if let Some(&other) = grid
+                        .get(&key)
+                        .and_then(|list| list.iter().find(|&&o| same(o, v)))
+                    {
+                        found = remap[other];
+                        break 'search;
+                    }
+                }
+            }
+        }
+        remap[v] = Some(found.unwrap_or_else(|| {
+            grid.entry(c).or_default().push(v);
+            count += 1;
+            count - 1
+        }));
+    }
+    let removed = mesh.vertices.len() - count as usize;
+    apply_remap(mesh, &remap, count as usize);
+    removed
+}
+
+/// Drop triangles that repeat a vertex or whose area is at most `min_area`. Vertices are kept
+/// ([`optimize_vertex_fetch`] drops unused ones). Returns the number of triangles removed.
+pub fn remove_degenerate_triangles(mesh: &mut MeshData, min_area: f32) -> usize {
+    let before = mesh.indices.len() / 3;
+    let vertices = &mesh.vertices;
+    let indices: Vec<u32> = mesh
+        .indices
+        .chunks_exact(3)
+        .filter(|t| {
+            if t[0] == t[1] || t[1] == t[2] || t[0] == t[2] {
+                return false;
+            }
+            let [a, b, c] = [t[0], t[1], t[2]].map(|i| vertices[i as usize].position);
+            length(cross(sub(b, a), sub(c, a))) * 0.5 > min_area
+        })
+        .flatten()
+        .copied()
+        .collect();
+    mesh.indices = indices;
+    before - mesh.indices.len() / 3
+}
+
+/// Average cache misses per triangle for a FIFO post-transform cache of `cache_size`
+/// (0.5 is ideal for large grids, 3 means no reuse).
+pub fn acmr(indices: &[u32], cache_size: usize) -> f32 {
+    let triangles = indices.len() / 3;
+    if triangles == 0 {
+        return 0.0;
+    }
+    let mut cache: std::collections::VecDeque<u32> =
+        std::collections::VecDeque::with_capacity(cache_size);
+    let mut misses = 0;
+    for &i in &indices[..triangles * 3] {
+        if !cache.contains(&i) {
+            misses += 1;
+            if cache.len() == cache_size {
+                cache.pop_front();
+            }
+            cache.push_back(i);
+        }
+    }
+    misses as f32 / triangles as f32
+}
+
+/// Reorder triangles for the post-transform vertex cache (Tom Forsyth's linear-speed
+/// algorithm: greedily emit the triangle whose vertices score highest by cache position and
+/// remaining valence).
+pub fn optimize_vertex_cache(mesh: &mut MeshData) {
+    let triangles = mesh.indices.len() / 3;
+    let vertex_count = mesh.vertices.len();
+    if triangles == 0 {
+        return;
+    }
+    // Triangles of every vertex, flattened (offsets into `adjacency`)
+    let mut offsets = vec![0usize; vertex_count + 1];
+    for &i in &mesh.indices[..triangles * 3] {
+        offsets[i as usize + 1] += 1;
+    }
+    for v in 0..vertex_count {
+        offsets[v + 1] += offsets[v];
+    }
+    let mut adjacency = vec![0usize; triangles * 3];
+    let mut fill = offsets.clone();
+    for (corner, &i) in mesh.indices[..triangles * 3].iter().enumerate() {
+        adjacency[fill[i as usize]] = corner / 3;
+        fill[i as usize] += 1;
+    }
+    // Triangles not yet emitted per vertex
+    let mut live: Vec<usize> = (0..vertex_count)
+        .map(|v| offsets[v + 1] - offsets[v])
+        .collect();
+    let mut cache_pos: Vec<Option<usize>> = vec![None; vertex_count];
+    let mut vertex_score: Vec<f32> = (0..vertex_count)
+        .map(|v| forsyth_score(None, live[v]))
+        .collect();
+    let tri = |t: usize| {
+        [
+            mesh.indices[t * 3],
+            mesh.indices[t * 3 + 1],
+            mesh.indices[t * 3 + 2],
+        ]
+    };
+    let mut tri_score: Vec<f32> = (0..triangles)
+        .map(|t| tri(t).iter().map(|&v| vertex_score[v as usize]).sum())
+        .collect();
+    let mut emitted = vec![false; triangles];
+    let mut cache: Vec<u32> = Vec::with_capacity(VERTEX_CACHE_SIZE + 3);
+    let mut order = Vec::with_capacity(triangles * 3);
+    // Scan position for the fallback search when the cache holds no live triangle
+    let mut cursor = 0;
+    let mut best = Some(best_triangle(&tri_score, &emitted, 0..triangles));
+
+    while let Some(t) = best {
+        emitted[t] = true;
+        let corners = tri(t);
+        order.extend_from_slice(&corners);
+        for &v in &corners {
+            live[v as usize] -= 1;
+        }
+        // New cache: the triangle's vertices in front, then the rest in LRU order
+        let mut next: Vec<u32> = corners.to_vec();
+        next.extend(cache.iter().copied().filter(|v| !corners.contains(v)));
+        for &v in next.iter().skip(VERTEX_CACHE_SIZE) {
+            cache_pos[v as usize] = None;
+            vertex_score[v as usize] = forsyth_score(None, live[v as usize]);
+        }
+        let evicted: Vec<u32> = next.iter().skip(VERTEX_CACHE_SIZE).copied().collect();
+        next.truncate(VERTEX_CACHE_SIZE);
+        for (pos, &v) in next.iter().enumerate() {
+            cache_pos[v as usize] = Some(pos);
+            vertex_score[v as usize] = forsyth_score(Some(pos), live[v as usize]);
+        }
+        cache = next;
+
+        // Rescore the live triangles touching changed vertices and pick the best of them
+        best = None;
+        let mut best_score = -1.0;
+        for &v in cache.iter().chain(&evicted) {
+            for &n in &adjacency[offsets[v as usize]..offsets[v as usize + 1]] {
+                if emitted[n] {
+                    continue;
+                }
+                tri_score[n] = tri(n).iter().map(|&u| vertex_score[u as usize]).sum();
+                if cache_pos[v as usize].is_some() && tri_score[n] > best_score {
+                    best_score = tri_score[n];
+                    best = Some(n);
+                }
+            }
+        }
+        if best.is_none() {
+            while cursor < triangles && emitted[cursor] {
+                cursor += 1;
+            }
+            if cursor < triangles {
+                best = Some(best_triangle(&tri_score, &emitted, cursor..triangles));
+            }
+        }
+    }
+    mesh.indices = order;
+}
+
+/// Reorder clusters of triangles so outward-facing ones draw first and occlude the rest.
+/// Clusters start at cache-cold triangles of the current order (run after
+/// [`optimize_vertex_cache`]), so the vertex cache efficiency is nearly unchanged.
+pub fn optimize_overdraw(mesh: &mut MeshData) {
+    let triangles = mesh.indices.len() / 3;
+    if triangles == 0 {
+        return;
+    }
+    // Cluster boundaries: triangles whose three vertices all miss a simulated FIFO cache
+    let mut starts = vec![0];
+    let mut cache: std::collections::VecDeque<u32> = std::collections::VecDeque::new();
+    for t in 0..triangles {
+        let mut misses = 0;
+        for &i in &mesh.indices[t * 3..t * 3 + 3] {
+            if !cache.contains(&i) {
+                misses += 1;
+                if cache.len() == VERTEX_CACHE_SIZE / 2 {
+                    cache.pop_front();
+                }
+                cache.push_back(i);
+            }
+        }
+        if misses == 3 && t > 0 {
+            starts.push(t);
+        }
+    }
+    starts.push(triangles);
+
+    let position = |i: u32| mesh.vertices[i as usize].position;
+    let center = mesh.bounds.aabb.center();
+    let mut clusters: Vec<(f32, std::ops::Range<usize>)> = starts
+        .windows(2)
+        .map(|w| {
+            // Area-weighted centroid and normal of the cluster
+            let (mut centroid, mut normal, mut area) = ([0.0; 3], [0.0; 3], 0.0);
+            for t in w[0]..w[1] {
+                let [a, b, c] = [0, 1, 2].map(|k| position(mesh.indices[t * 3 + k]));
+                let n = cross(sub(b, a), sub(c, a));
+                let weight = length(n);
+                for k in 0..3 {
+                    centroid[k] += (a[k] + b[k] + c[k]) / 3.0 * weight;
+                    normal[k] += n[k];
+                }
+                area += weight;
+            }
+            let centroid = if area > 0.0 {
+                centroid.map(|x| x / area)
+            } else {
+                position(mesh.indices[w[0] * 3])
+            };
+            let len = length(normal);
+            let key = if len > 0.0 {
+                dot(sub(centroid, center), normal) / len
+            } else {
+                0.0
+            };
+            (key, w[0]..w[1])
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.0.total_cmp(&a.0));
+    mesh.indices = clusters
+        .iter()
+        .flat_map(|(_, r)| mesh.indices[r.start * 3..r.end * 3].iter().copied())
+        .collect();
+}
+
+/// Reorder vertices by first use in the index buffer (sequential fetches) and drop unused ones.
+pub fn optimize_vertex_fetch(mesh: &mut MeshData) {
+    let mut remap = vec![None; mesh.vertices.len()];
+    let mut count = 0u32;
+    for &i in &mesh.indices {
+        remap[i as usize].get_or_insert_with(|| {
+            count += 1;
+            count - 1
+        });
+    }
+    apply_remap(mesh, &remap, count as usize);
+}
+
+/// Box plus the smaller of the box-centered and Ritter spheres, for tighter culling than
+/// [`MeshBounds::from_vertices`].
+pub fn compute_bounds(vertices: &[MeshVertex]) -> MeshBounds {
+    let loose = MeshBounds::from_vertices(vertices);
+    let ritter = ritter_sphere(vertices);
+    MeshBounds {
+        aabb: loose.aabb,
+        sphere: if ritter.radius < loose.sphere.radius {
+            ritter
+        } else {
+            loose.sphere
+        },
+    }
+}
+
+// Ritter's approximate bounding sphere: span between two far points, grown to cover outliers
+fn ritter_sphere(vertices: &[MeshVertex]) -> BoundingSphere {
+    let Some(first) = vertices.first() else {
+        return BoundingSphere::default();
+    };
+    let farthest = |from: [f32; 3]| {
+        vertices
+            .iter()
+            .map(|v| v.position)
+            .max_by(|a, b| length(sub(*a, from)).total_cmp(&length(sub(*b, from))))
+            .unwrap_or(from)
+    };
+    let a = farthest(first.position);
+    let b = farthest(a);
+    let mut center = [0, 1, 2].map(|k| (a[k] + b[k]) * 0.5);
+    let mut radius = length(sub(b, a)) * 0.5;
+    for v in vertices {
+        let d = length(sub(v.position, center));
+        if d > radius {
+            let grow = (d - radius) * 0.5;
+            radius += grow;
+            let dir = sub(v.position, center);
+            center = [0, 1, 2].map(|k| center[k] + dir[k] / d * grow);
+        }
+    }
+    BoundingSphere { center, radius }
+}
+
+/// Concatenate meshes into one. Morph targets are matched by index; meshes without a target
+/// contribute zero deltas (and default values for streams they lack).
+pub fn merge(meshes: &[MeshData]) -> MeshData {
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    let target_count = meshes
+        .iter()
+        .map(|m| m.morph_targets.len())
+        .max()
+        .unwrap_or(0);
+    let mut targets: Vec<MorphTarget> = (0..target_count)
+        .map(|t| {
+            let from = meshes.iter().find_map(|m| m.morph_targets.get(t));
+            MorphTarget {
+                name: from.and_then(|f| f.name.clone()),
+                default_weight: from.map_or(0.0, |f| f.default_weight),
+                ..MorphTarget::default()
+            }
+        })
+        .collect();
+    let has = |t: usize, f: fn(&MorphTarget) -> bool| {
+        meshes.iter().any(|m| m.morph_targets.get(t).is_some_and(f))
+    };
+    for (t, target) in targets.iter_mut().enumerate() {
+        target.normals = has(t, |m| m.normals.is_some()).then(Vec::new);
+        target.tangents = has(t, |m| m.tangents.is_some()).then(Vec::new);
+    }
+    let mut streams = VertexStreams::default();
+    for mesh in meshes {
+        let base = vertices.len() as u32;
+        streams.append(vertices.len(), &mesh.streams, mesh.vertices.len());
+        vertices.extend_from_slice(&mesh.vertices);
+        indices.extend(mesh.indices.iter().map(|i| i + base));
+        let n = mesh.vertices.len();
+        for (t, target) in targets.iter_mut().enumerate() {
+            let source = mesh.morph_targets.get(t);
+            let zeros = || vec![[0.0; 3]; n];
+            target
+                .positions
+                .extend(source.map_or_else(zeros, |s| s.positions.clone()));
+            if let Some(normals) = &mut target.normals {
+                normals.extend(source.and_then(|s| s.normals.clone()).unwrap_or_else(zeros));
+            }
+            if let Some(tangents) = &mut target.tangents {
+                tangents.extend(
+                    source
+                        .and_then(|s| s.tangents.clone())
+                        .unwrap_or_else(zeros),
+                );
+            }
+        }
+    }
+    let mut merged = MeshData::new(vertices, indices);
+    merged.morph_targets = targets;
+    merged.streams = streams;
+    merged
+}
+
+/// Transform positions by the column-major `matrix`, normals by its inverse transpose and
+/// morph deltas and tangents by its linear part. Mirroring matrices flip the winding to keep faces
+/// front-facing.
+pub fn transform(mesh: &mut MeshData, matrix: &[f32; 16]) {
+    let m = matrix;
+    let linear = |v: [f32; 3]| [0, 1, 2].map(|r| m[r] * v[0] + m[4 + r] * v[1] + m[8 + r] * v[2]);
+    let normal_matrix = inverse_transpose(m);
+    let normal = |n: [f32; 3]| {
+        let t = [0, 1, 2].map(|r| {
+            normal_matrix[r * 3] * n[0]
+                + normal_matrix[r * 3 + 1] * n[1]
+                + normal_matrix[r * 3 + 2] * n[2]
+        });
+        let len = length(t);
+        if len > 0.0 { t.map(|x| x / len) } else { n }
+    };
+    for v in &mut mesh.vertices {
+        let p = linear(v.position);
+        v.position = [p[0] + m[12], p[1] + m[13], p[2] + m[14]];
+        v.normal = normal(v.normal);
+    }
+    for target in &mut mesh.morph_targets {
+        for d in &mut target.positions {
+            *d = linear(*d);
+        }
+        for d in target.tangents.iter_mut().flatten() {
+            *d = linear(*d);
+        }
+        // Normal deltas stay unnormalized (they are added to a normal before normalizing)
+        for d in target.normals.iter_mut().flatten() {
+            *d = [0, 1, 2].map(|r| {
+                normal_matrix[r * 3] * d[0]
+                    + normal_matrix[r * 3 + 1] * d[1]
+                    + normal_matrix[r * 3 + 2] * d[2]
+            });
+        }
+    }
+    // Mirroring also flips the handedness of the tangent frame
+    let sign = if determinant3(m) < 0.0 { -1.0 } else { 1.0 };
+    for t in mesh.streams.tangents.iter_mut().flatten() {
+        let v = linear([t[0], t[1], t[2]]);
+        let len = length(v);
+        if len > 0.0 {
+            *t = [v[0] / len, v[1] / len, v[2] / len, t[3] * sign];
+        }
+    }
+    if determinant3(m) < 0.0 {
+        for tri in mesh.indices.chunks_exact_mut(3) {
+            tri.swap(1, 2);
+        }
+    }
+    mesh.recompute_bounds();
+}
+
+// Forsyth's vertex score: recently used vertices and vertices with few remaining triangles win
+fn forsyth_score(cache_pos: Option<usize>, live: usize) -> f32 {
+    if live == 0 {
+        return -1.0;
+    }
+    let cache = match cache_pos {
+        None => 0.0,
+        // The last triangle's vertices score lower so strips don't backtrack
+        Some(pos) if pos < 3 => 0.75,
+        Some(pos) => (1.0 - (pos - 3) as f32 / (VERTEX_CACHE_SIZE - 3) as f32).powf(1.5),
+    };
+    cache + 2.0 / (live as f32).sqrt()
+}
+
+fn best_triangle(scores: &[f32], emitted: &[bool], range: std::ops::Range<usize>) -> usize {
+    range
+        .filter(|&t| !emitted[t])
+        .max_by(|&a, &b| scores[a].total_cmp(&scores[b]).then(b.cmp(&a)))
+        .expect("range holds a live triangle")
+}
+
+// Apply `remap` (old vertex → new index, `None` = dropped) to vertices, indices, morph
+// targets and streams; several old vertices may map to one new vertex (the first one's data is kept)
+fn apply_remap(mesh: &mut MeshData, remap: &[Option<u32>], count: usize) {
+    let mut source = vec![usize::MAX; count];
+    for (old, new) in remap.iter().enumerate() {
+        if let Some(new) = *new
+            && source[new as usize] == usize::MAX
+        {
+            source[new as usize] = old;
+        }
+    }
+    mesh.vertices = source.iter().map(|&s| mesh.vertices[s]).collect();
+    for i in &mut mesh.indices {
+        *i = remap[*i as usize].expect("indexed vertex is kept");
+    }
+    let pick = |deltas: &Vec<[f32; 3]>| source.iter().map(|&s| deltas[s]).collect::<Vec<_>>();
+    for target in &mut mesh.morph_targets {
+        target.positions = pick(&target.positions);
+        target.normals = target.normals.as_ref().map(pick);
+        target.tangents = target.tangents.as_ref().map(pick);
+    }
+    mesh.streams = mesh.streams.pick(&source);
+    mesh.recompute_bounds();
+}
+
+// Inverse transpose of the upper 3x3 of a column-major 4x4, row-major (identity if singular)
+fn inverse_transpose(m: &[f32; 16]) -> [f32; 9] {
+    let a = |c: usize, r: usize| m[c * 4 + r];
+    let det = determinant3(m);
+    if det.abs() < 1e-12 {
+        return [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+    }
+    // Cofactor matrix divided by the determinant = inverse transpose
+    let cof =
+        |r0: usize, r1: usize, c0: usize, c1: usize| a(c0, r0) * a(c1, r1) - a(c1, r0) * a(c0, r1);
+    [
+        cof(1, 2, 1, 2),
+        -cof(1, 2, 0, 2),
+        cof(1, 2, 0, 1),
+        -cof(0, 2, 1, 2),
+        cof(0, 2, 0, 2),
+        -cof(0, 2, 0, 1),
+        cof(0, 1, 1, 2),
+        -cof(0, 1, 0, 2),
+        cof(0, 1, 0, 1),
+    ]
+    .map(|x| x / det)
+}
+
+fn determinant3(m: &[f32; 16]) -> f32 {
+    let a = |c: usize, r: usize| m[c * 4 + r];
+    a(0, 0) * (a(1, 1) * a(2, 2) - a(2, 1) * a(1, 2))
+        - a(1, 0) * (a(0, 1) * a(2, 2) - a(2, 1) * a(0, 2))
+        + a(2, 0) * (a(0, 1) * a(1, 2) - a(1, 1) * a(0, 2))
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // n x n quad grid in the XY plane, indexed row by row (poor cache order)
+    fn grid(n: u32) -> MeshData {
+        let mut vertices = Vec::new();
+        for y in 0..=n {
+            for x in 0..=n {
+                vertices.push(MeshVertex::new(
+                    [x as f32, y as f32, 0.0],
+                    [0.0, 0.0, 1.0],
+                    [0.0; 2],
+                ));
+            }
+        }
+        let mut indices = Vec::new();
+        for y in 0..n {
+            for x in 0..n {
+                let i = y * (n + 1) + x;
+                indices.extend_from_slice(&[i, i + 1, i + n + 2, i, i + n + 2, i + n + 1]);
+            }
+        }
+        MeshData::new(vertices, indices)
+    }
+
+    fn sorted_triangles(mesh: &MeshData) -> Vec<[[u32; 3]; 3]> {
+        let key = |i: u32| mesh.vertices[i as usize].position.map(f32::to_bits);
+        let mut tris: Vec<[[u32; 3]; 3]> = mesh
+            .indices
+            .chunks_exact(3)
+            .map(|t| {
+                // Rotate so the smallest corner comes first (winding preserved)
+                let k = [key(t[0]), key(t[1]), key(t[2])];
+                let start = (0..3).min_by_key(|&s| k[s]).unwrap();
+                [k[start], k[(start + 1) % 3], k[(start + 2) % 3]]
+            })
+            .collect();
+        tris.sort();
+        tris
+    }
+
+    #[test]
+    fn weld_merges_near_duplicates_only() {
+        let v = |p: [f32; 3], uv: [f32; 2]| MeshVertex::new(p, [0.0, 0.0, 1.0], uv);
+        let mut mesh = MeshData::new(
+            vec![
+                v([0.0; 3], [0.0; 2]),
+                v([1.0, 0.0, 0.0], [0.0; 2]),
+                v([0.0, 1.0, 0.0], [0.0; 2]),
+                v([1e-4, 0.0, 0.0], [0.0; 2]),
+                v([0.0, 1.0, 0.0], [0.5, 0.0]),
+            ],
+            vec![0, 1, 2, 3, 1, 4],
+        );
+        assert_eq!(weld_vertices(&mut mesh, 1e-3), 1);
+        assert_eq!(mesh.vertices.len(), 4);
+        // The UV seam survives
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 1, 3]);
+    }
+
+    #[test]
+    fn degenerate_triangles_are_removed() {
+        let mut mesh = grid(1);
+        mesh.vertices
+            .push(MeshVertex::new([0.5, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0; 2]));
+        mesh.indices.extend_from_slice(&[0, 0, 1, 0, 1, 4]);
+        assert_eq!(remove_degenerate_triangles(&mut mesh, 0.0), 2);
+        assert_eq!(mesh.indices.len(), 6);
+    }
+
+    #[test]
+    fn optimize_keeps_triangles_and_improves_cache() {
+        let mut mesh = grid(32);
+        let before = sorted_triangles(&mesh);
+        let cold = acmr(&mesh.indices, 16);
+        optimize(&mut mesh);
+        assert_eq!(sorted_triangles(&mesh), before);
+        assert!(
+            acmr(&mesh.indices, 16) < cold * 0.8,
+            "{} vs {cold}",
+            acmr(&mesh.indices, 16)
+        );
+        // Vertex fetch order follows first use
+        let mut seen = 0;
+        for &i in &mesh.indices {
+            assert!(i <= seen);
+            seen = seen.max(i + 1);
+        }
+    }
+
+    #[test]
+    fn merge_transform_and_bounds() {
+        let mut a = grid(1);
+        let b = grid(2);
+        let merged = merge(&[a.clone(), b.clone()]);
+        assert_eq!(merged.vertices.len(), a.vertices.len() + b.vertices.len());
+        assert_eq!(merged.indices[a.indices.len()], a.vertices.len() as u32);
+
+        // Mirror in X and move: winding flips, normals stay unit length
+        #[rustfmt::skip]
+        let mirror = [
+            -2.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            5.0, 0.0, 0.0, 1.0,
+        ];
+        let first = a.indices[..3].to_vec();
+        transform(&mut a, &mirror);
+        assert_eq!(a.vertices[1].position, [3.0, 0.0, 0.0]);
+        assert_eq!(a.vertices[0].normal, [0.0, 0.0, 1.0]);
+        assert_eq!(a.indices[..3], [first[0], first[2], first[1]]);
+        assert_eq!(a.bounds.aabb.min, [3.0, 0.0, 0.0]);
+
+        // A long thin triangle: the Ritter sphere spans the long edge and beats the
+        // box-centered one (radius 5 vs sqrt(26)); a square grid is never made worse
+        let sliver: Vec<MeshVertex> = [[0.0, 0.0, 0.0], [10.0, 0.0, 0.0], [5.0, 2.0, 0.0]]
+            .map(|p| MeshVertex::new(p, [0.0, 0.0, 1.0], [0.0; 2]))
+            .to_vec();
+        let square = grid(4).vertices;
+        let covered = |vertices: &[MeshVertex]| {
+            let sphere = compute_bounds(vertices).sphere;
+            assert!(sphere.radius <= MeshBounds::from_vertices(vertices).sphere.radius);
+            for v in vertices {
+                assert!(length(sub(v.position, sphere.center)) <= sphere.radius + 1e-4);
+            }
+            sphere.radius
+        };
+        assert!((covered(&sliver) - 5.0).abs() < 1e-5);
+        assert!(MeshBounds::from_vertices(&sliver).sphere.radius > 5.09);
+        covered(&square);
+    }
+}
@@ -0,0 +1,404 @@
+//! L1: quadric-error mesh simplification (Garland–Heckbert, half-edge collapse).
+//! Vertices on open borders and attribute seams are locked so UVs/normals do not tear.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+
+use crate::mesh::MeshData;
+
+/// Normals of triangles around a collapse may not turn by more than ~80°.
+const MIN_NORMAL_DOT: f32 = 0.2;
+
+/// Symmetric 4x4 error quadric stored as its 10 unique coefficients.
+#[derive(Clone, Copy, Debug, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Quadric of the plane `ax + by + cz + d = 0`, scaled by `weight`.
+    fn from_plane(a: f64, b: f64, c: f64, d: f64, weight: f64) -> Self {
+        Self(
+            [
+                a * a,
+                a * b,
+                a * c,
+                a * d,
+                b * b,
+                b * c,
+                b * d,
+                c * c,
+                c * d,
+                d * d,
+            ]
+            .map(|v| v * weight),
+        )
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for (a, b) in self.0.iter_mut().zip(other.0) {
+            *a += b;
+        }
+    }
+
+    /// Squared distance error of `p` (never negative).
+    fn error(&self, p: [f32; 3]) -> f64 {
+        let [x, y, z] = p.map(f64::from);
+        let q = &self.0;
+        let e = q[0] * x * x
+            + 2.0 * q[1] * x * y
+            + 2.0 * q[2] * x * z
+            + 2.0 * q[3] * x
+            + q[4] * y * y
+            + 2.0 * q[5] * y * z
+            + 2.0 * q[6] * y
+            + q[7] * z * z
+            + 2.0 * q[8] * z
+            + q[9];
+        e.max(0.0)
+    }
+}
+
+/// Candidate collapse `from -> to` (min-heap by cost).
+#[derive(Clone, Copy, Debug)]
+struct Collapse {
+    cost: f64,
+    from: u32,
+    to: u32,
+    /// `versions[from]`/`versions[to]` when the candidate was queued.
+    version_from: u32,
+    version_to: u32,
+}
+
+impl PartialEq for Collapse {
+    fn eq(&self, other: &Self) -> bool {
+        self.cost == other.cost
+    }
+}
+
+impl Eq for Collapse {}
+
+impl PartialOrd for Collapse {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Collapse {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max-heap: invert for cheapest-first.
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+/// Reduce `mesh` to about `target_index_count` indices.
+///
+/// Stops early once the cheapest collapse exceeds `max_error` (squared object-space
+/// distance). Returns a compacted mesh with recomputed bounds.
+pub fn simplify(mesh: &MeshData, target_index_count: usize, max_error: f32) -> MeshData {
+    let vertex_count = mesh.vertices.len();
+    let mut triangles: Vec<[u32; 3]> = mesh
+        .indices
+        .chunks_exact(3)
+        .map(|t| [t[0], t[1], t[2]])
+        .filter(|t| t[0] != t[1] && t[1] != t[2] && t[0] != t[2])
+        .collect();
+    let target_triangles = target_index_count / 3;
+    if triangles.len() <= target_triangles || vertex_count == 0 {
+        return compact(mesh, &triangles);
+    }
+
+    let positions: Vec<[f32; 3]> = mesh.vertices.iter().map(|v| v.position).collect();
+
+    // Per-vertex quadrics from incident triangle planes (area weighted).
+    let mut quadrics = vec![Quadric::default(); vertex_count];
+    for tri in &triangles {
+        let [p0, p1, p2] = tri.map(|i| positions[i as usize]);
+        let n = cross(sub(p1, p0), sub(p2, p0));
+        let len = length(n);
+        if len <= f32::EPSILON {
+            continue;
+        }
+        let [a, b, c] = n.map(|v| f64::from(v / len));
+        let d = -(a * f64::from(p0[0]) + b * f64::from(p0[1]) + c * f64::from(p0[2]));
+        let q = Quadric::from_plane(a, b, c, d, f64::from(len) * 0.5);
+        for &i in tri {
+            quadrics[i as usize].add(&q);
+        }
+    }
+
+    // Border (and seam) edges are used by exactly one triangle.
+    let mut edge_use: HashMap<(u32, u32), u32> = HashMap::new();
+    for tri in &triangles {
+        for (a, b) in edges(tri) {
+            *edge_use.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+        }
+    }
+    let mut locked = vec![false; vertex_count];
+    for (&(a, b), &count) in &edge_use {
+        if count == 1 {
+            locked[a as usize] = true;
+            locked[b as usize] = true;
+        }
+    }
+
+    let mut vertex_tris: Vec<Vec<u32>> = vec![Vec::new(); vertex_count];
+    for (t, tri) in triangles.iter().enumerate() {
+        for &i in tri {
+            vertex_tris[i as usize].push(t as u32);
+        }
+    }
+
+    let mut versions = vec![0u32; vertex_count];
+    let mut heap = BinaryHeap::new();
+    let push_edge = |heap: &mut BinaryHeap<Collapse>,
+                     quadrics: &[Quadric],
+                     versions: &[u32],
+                     locked: &[bool],
+                     a: u32,
+                     b: u32| {
+        let mut q = quadrics[a as usize];
+        q.add(&quadrics[b as usize]);
+        for (from, to) in [(a, b), (b, a)] {
+            if locked[from as usize] {
+                continue;
+            }
+            heap.push(Collapse {
+                cost: q.error(positions[to as usize]),
+                from,
+                to,
+                version_from: versions[from as usize],
+                version_to: versions[to as usize],
+            });
+        }
+    };
+    for &(a, b) in edge_use.keys() {
+        push_edge(&mut heap, &quadrics, &versions, &locked, a, b);
+    }
+
+    let mut alive_tris = vec![true; triangles.len()];
+    let mut live_count = triangles.len();
+    let max_error = f64::from(max_error);
+
+    while live_count > target_triangles {
+        let Some(c) = heap.pop() else {
+            break;
+        };
+        if c.cost > max_error {
+            break;
+        }
+        let (from, to) = (c.from as usize, c.to as usize);
+        if c.version_from != versions[from] || c.version_to != versions[to] || locked[from] {
+            continue;
+        }
+        // Edge may have disappeared since it was queued.
+        if !vertex_tris[from]
+            .iter()
+            .any(|&t| alive_tris[t as usize] && triangles[t as usize].contains(&c.to))
+        {
+            continue;
+        }
+        if flips_triangles(
+            &triangles,
+            &alive_tris,
+            &vertex_tris[from],
+            &positions,
+            c.from,
+            c.to,
+        ) {
+            continue;
+        }
+
+        // Collapse: re-point triangles, drop the degenerate ones.
+        let moved = std::mem::take(&mut vertex_tris[from]);
+        for &t in &moved {
+            let tu = t as usize;
+            if !alive_tris[tu] {
+                continue;
+            }
+            let tri = &mut triangles[tu];
+            for i in tri.iter_mut() {
+                if *i == c.from {
+                    *i = c.to;
+                }
+            }
+            if tri[0] == tri[1] || tri[1] == tri[2] || tri[0] == tri[2] {
+                alive_tris[tu] = false;
+                live_count -= 1;
+            } else {
+                vertex_tris[to].push(t);
+            }
+        }
+        let q_from = quadrics[from];
+        quadrics[to].add(&q_from);
+        versions[from] += 1;
+        versions[to] += 1;
+
+        // Re-queue edges around the surviving vertex with the merged quadric.
+        vertex_tris[to].retain(|&t| alive_tris[t as usize]);
+        let mut neighbors: Vec<u32> = vertex_tris[to]
+            .iter()
+            .flat_map(|&t| triangles[t as usize])
+            .filter(|&v| v != c.to)
+            .collect();
+        neighbors.sort_unstable();
+        neighbors.dedup();
+        for n in neighbors {
+            push_edge(&mut heap, &quadrics, &versions, &locked, c.to, n);
+        }
+    }
+
+    let kept: Vec<[u32; 3]> = triangles
+        .iter()
+        .zip(&alive_tris)
+        .filter_map(|(t, &alive)| alive.then_some(*t))
+        .collect();
+    compact(mesh, &kept)
+}
+
+/// `true` if moving `from` onto `to` would flip or collapse any remaining triangle.
+fn flips_triangles(
+    triangles: &[[u32; 3]],
+    alive: &[bool],
+    tris: &[u32],
+    positions: &[[f32; 3]],
+    from: u32,
+    to: u32,
+) -> bool {
+    tris.iter().any(|&t| {
+        let tri = triangles[t as usize];
+        if !alive[t as usize] || tri.contains(&to) {
+            return false;
+        }
+        let p = tri.map(|i| positions[i as usize]);
+        let q = tri.map(|i| positions[if i == from { to } else { i } as usize]);
+        let n_old = cross(sub(p[1], p[0]), sub(p[2], p[0]));
+        let n_new = cross(sub(q[1], q[0]), sub(q[2], q[0]));
+        let (l_old, l_new) = (length(n_old), length(n_new));
+        if l_new <= f32::EPSILON {
+            return true;
+        }
+        l_old > f32::EPSILON && dot(n_old, n_new) < MIN_NORMAL_DOT * l_old * l_new
+    })
+}
+
+/// Drop unreferenced vertices and rebuild the index buffer (extra streams follow their vertices).
+fn compact(mesh: &MeshData, triangles: &[[u32; 3]]) -> MeshData {
+    let vertices = &mesh.vertices;
+    let mut remap = vec![u32::MAX; vertices.len()];
+    let mut source = Vec::new();
+    let mut out_vertices = Vec::new();
+    let mut out_indices = Vec::with_capacity(triangles.len() * 3);
+    for tri in triangles {
+        for &i in tri {
+            let slot = &mut remap[i as usize];
+            if *slot == u32::MAX {
+                *slot = out_vertices.len() as u32;
+                out_vertices.push(vertices[i as usize]);
+                source.push(i as usize);
+            }
+            out_indices.push(*slot);
+        }
+    }
+    let mut out = MeshData::new(out_vertices, out_indices);
+    out.streams = mesh.streams.pick(&source);
+    out
+}
+
+fn edges(tri: &[u32; 3]) -> [(u32, u32); 3] {
+    [(tri[0], tri[1]), (tri[1], tri[2]), (tri[2], tri[0])]
+}
+
+fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mesh::MeshVertex;
+
+    /// Closed UV sphere without seams (poles shared, longitude wraps).
+    fn sphere(rings: u32, segments: u32) -> MeshData {
+        let mut vertices = vec![MeshVertex::new([0.0, 1.0, 0.0], [0.0, 1.0, 0.0], [0.0; 2])];
+        for r in 1..rings {
+            let phi = std::f32::consts::PI * r as f32 / rings as f32;
+            for s in 0..segments {
+                let theta = std::f32::consts::TAU * s as f32 / segments as f32;
+                let p = [phi.sin() * theta.cos(), phi.cos(), phi.sin() * theta.sin()];
+                vertices.push(MeshVertex::new(p, p, [0.0; 2]));
+            }
+        }
+        vertices.push(MeshVertex::new(
+            [0.0, -1.0, 0.0],
+            [0.0, -1.0, 0.0],
+            [0.0; 2],
+        ));
+        let bottom = vertices.len() as u32 - 1;
+        let ring = |r: u32, s: u32| 1 + (r - 1) * segments + s % segments;
+
+        let mut indices = Vec::new();
+        for s in 0..segments {
+            indices.extend([0, ring(1, s + 1), ring(1, s)]);
+            indices.extend([bottom, ring(rings - 1, s), ring(rings - 1, s + 1)]);
+        }
+        for r in 1..rings - 1 {
+            for s in 0..segments {
+                let (a, b, c, d) = (
+                    ring(r, s),
+                    ring(r, s + 1),
+                    ring(r + 1, s),
+                    ring(r + 1, s + 1),
+                );
+                indices.extend([a, b, c, b, d, c]);
+            }
+        }
+        MeshData::new(vertices, indices)
+    }
+
+    #[test]
+    fn simplify_reduces_closed_mesh() {
+        let mesh = sphere(16, 32);
+        let target = mesh.indices.len() / 4;
+        let lod = simplify(&mesh, target, f32::MAX);
+        assert!(lod.is_valid());
+        assert!(
+            lod.indices.len() <= target + 6,
+            "{} > {}",
+            lod.indices.len(),
+            target
+        );
+        assert!(lod.vertices.len() < mesh.vertices.len());
+        // Shape is preserved: every remaining vertex is an original sphere point.
+        assert!((lod.bounds.sphere.radius - 1.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn simplify_keeps_open_borders() {
+        // Single quad: every vertex is on the border, nothing may collapse.
+        let quad = MeshData::new(
+            vec![
+                MeshVertex::new([0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 0.0]),
+                MeshVertex::new([1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0]),
+                MeshVertex::new([1.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 1.0]),
+                MeshVertex::new([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0]),
+            ],
+            vec![0, 1, 2, 0, 2, 3],
+        );
+        assert_eq!(simplify(&quad, 3, f32::MAX).indices.len(), 6);
+    }
+}
@@ -1,15 +1,18 @@
 //! Texture loading and data structures.
 //! E2: Load RGBA8 textures from PNG files.
+//! E2: optional mip chains (sRGB-correct box filter) and sampler descriptors.
 
 use std::path::Path;
 
 /// Texture data in CPU-friendly format before GPU upload.
 #[derive(Clone, Debug)]
 pub struct TextureData {
+    /// All mip levels, largest first, tightly packed.
     pub data: Vec<u8>,
     pub width: u32,
     pub height: u32,
     pub format: TextureFormat,
+    pub mip_levels: u32,
 }
 
 /// Supported texture formats.
@@ -31,6 +34,7 @@ impl TextureData {
             width,
             height,
             format: TextureFormat::Rgba8,
+            mip_levels: 1,
         }
     }
 
@@ -46,11 +50,25 @@ impl TextureData {
         let (width, height) = rgba.dimensions();
         let data = rgba.into_raw();
 
-        log::info!("Loaded texture {}x{} with {} bytes", width, height, data.len());
+        log::info!(
+            "Loaded texture {}x{} with {} bytes",
+            width,
+            height,
+            data.len()
+        );
 
         Ok(Self::new_rgba8(width, height, data))
     }
 
+    /// Decode PNG bytes already in memory (e.g. embedded in a glTF file).
+    pub fn from_png_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        let img = image::load_from_memory_with_format(bytes, image::ImageFormat::Png)
+            .map_err(|e| anyhow::anyhow!("Failed to decode PNG: {}", e))?;
+        let rgba = img.to_rgba8();
+        let (width, height) = rgba.dimensions();
+        Ok(Self::new_rgba8(width, height, rgba.into_raw()))
+    }
+
     /// Create a simple test texture (checkerboard pattern).
     pub fn create_test_texture(size: u32) -> Self {
         let mut data = Vec::with_capacity((size * size * 4) as usize);
@@ -78,9 +96,181 @@ impl TextureData {
         }
     }
 
+    /// Size of mip `level` (halved per level, at least 1x1).
+    pub fn level_size(&self, level: u32) -> (u32, u32) {
+        ((self.width >> level).max(1), (self.height >> level).max(1))
+    }
+
     /// Check if the texture data is valid.
     pub fn is_valid(&self) -> bool {
-        let expected_size = (self.width * self.height * self.bytes_per_pixel()) as usize;
-        self.data.len() == expected_size && self.width > 0 && self.height > 0
+        let expected_size: u32 = (0..self.mip_levels)
+            .map(|level| {
+                let (w, h) = self.level_size(level);
+                w * h * self.bytes_per_pixel()
+            })
+            .sum();
+        self.data.len() == expected_size as usize
+            && self.width > 0
+            && self.height > 0
+            && (1..=mip_count(self.width, self.height)).contains(&self.mip_levels)
+    }
+
+    /// Append the full mip chain down to 1x1 (no-op if the data already has mips).
+    /// Each texel averages a 2x2 footprint in linear space, so sRGB colors don't darken;
+    /// the last row/column of odd-sized levels is clamped into the footprint.
+    pub fn with_mips(mut self) -> Self {
+        if self.mip_levels > 1 {
+            return self;
+        }
+        let to_linear: Vec<f32> = (0..=255u8)
+            .map(|c| srgb_to_linear(c as f32 / 255.0))
+            .collect();
+        let levels = mip_count(self.width, self.height);
+        let mut start = 0;
+        for level in 1..levels {
+            let (src_w, src_h) = self.level_size(level - 1);
+            let (w, h) = self.level_size(level);
+            let src_len = (src_w * src_h * 4) as usize;
+            let mut next = Vec::with_capacity((w * h * 4) as usize);
+            {
+                let src = &self.data[start..start + src_len];
+                for y in 0..h {
+                    for x in 0..w {
+                        let mut sum = [0.0f32; 4];
+                        for sy in [2 * y, 2 * y + 1] {
+                            for sx in [2 * x, 2 * x + 1] {
+                                let i =
+                                    ((sy.min(src_h - 1) * src_w + sx.min(src_w - 1)) * 4) as usize;
+                                for c in 0..3 {
+                                    sum[c] += to_linear[src[i + c] as usize];
+                                }
+                                sum[3] += src[i + 3] as f32 / 255.0;
+                            }
+                        }
+                        let [r, g, b, a] = sum.map(|v| v / 4.0);
+                        next.extend(
+                            [linear_to_srgb(r), linear_to_srgb(g), linear_to_srgb(b), a]
+                                .map(|v| (v * 255.0).round() as u8),
+                        );
+                    }
+                }
+            }
+            self.data.extend_from_slice(&next);
+            start += src_len;
+        }
+        self.mip_levels = levels;
+        self
+    }
+}
+
+/// Levels in a full mip chain of a `width` x `height` texture.
+pub fn mip_count(width: u32, height: u32) -> u32 {
+    u32::BITS - width.max(height).max(1).leading_zeros()
+}
+
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
     }
-}
\ No newline at end of file
+}
+
+/// What happens to UVs outside `[0, 1]`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AddressMode {
+    #[default]
+    Repeat,
+    MirrorRepeat,
+    ClampToEdge,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FilterMode {
+    Nearest,
+    #[default]
+    Linear,
+}
+
+/// How a texture is sampled. Equal descriptors share one GPU sampler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SamplerDesc {
+    pub address_u: AddressMode,
+    pub address_v: AddressMode,
+    pub mag_filter: FilterMode,
+    pub min_filter: FilterMode,
+    pub mipmap_filter: FilterMode,
+    /// `false` samples only the base level (GL `NEAREST` / `LINEAR` min filters).
+    pub mipmaps: bool,
+    /// Maximum anisotropy (1 = off, up to 16); only applies when all filters are linear.
+    pub anisotropy: u16,
+}
+
+impl Default for SamplerDesc {
+    /// Repeating trilinear, like glTF's defaults.
+    fn default() -> Self {
+        Self {
+            address_u: AddressMode::Repeat,
+            address_v: AddressMode::Repeat,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            mipmaps: true,
+            anisotropy: 1,
+        }
+    }
+}
+
+impl SamplerDesc {
+    /// Anisotropy as the GPU accepts it: clamped to 1..=16, and 1 unless every filter is linear.
+    pub fn anisotropy_clamp(&self) -> u16 {
+        let linear = [self.mag_filter, self.min_filter, self.mipmap_filter]
+            .iter()
+            .all(|&f| f == FilterMode::Linear);
+        if linear {
+            self.anisotropy.clamp(1, 16)
+        } else {
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_chain_averages_in_linear_space() {
+        // 4x2 black/white stripes
+        let texel = |white: bool| {
+            if white {
+                [255, 255, 255, 255]
+            } else {
+                [0, 0, 0, 255]
+            }
+        };
+        let data: Vec<u8> = (0..8).flat_map(|i| texel(i % 2 == 0)).collect();
+        let texture = TextureData::new_rgba8(4, 2, data).with_mips();
+        assert_eq!(texture.mip_levels, 3);
+        assert_eq!(texture.level_size(2), (1, 1));
+        assert!(texture.is_valid());
+        assert_eq!(texture.data.len(), (8 + 2 + 1) * 4);
+        // 50% coverage is ~188 in sRGB, not 128
+        assert_eq!(&texture.data[32..36], &[188, 188, 188, 255]);
+        assert_eq!(&texture.data[40..44], &[188, 188, 188, 255]);
+        // Already mipped data is left alone
+        assert_eq!(texture.clone().with_mips().data, texture.data);
+
+        assert_eq!(mip_count(1, 1), 1);
+        assert_eq!(mip_count(5, 3), 3);
+        assert_eq!(mip_count(256, 64), 9);
+    }
+}
@@ -0,0 +1,417 @@
+//! Tiny parser for RON/JSON asset descriptors (no serde format crates needed).
+//!
+//! Supports what descriptor files use: numbers, strings, booleans, lists,
+//! tuples, named/anonymous structs (`Name(key: value)`, `{"key": value}`),
+//! unit identifiers (`Blend`, `None`, `null`), `//` and `/* */` comments, trailing commas.
+
+use anyhow::{Result, anyhow, bail};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Number(f64),
+    String(String),
+    /// `[a, b]`
+    List(Vec<Value>),
+    /// `(a, b)` or `Name(a, b)` (e.g. `Some(x)`, `Mask(0.5)`)
+    Tuple(Option<String>, Vec<Value>),
+    /// `Name(key: v)`, `(key: v)` or JSON `{"key": v}`
+    Struct(Option<String>, Vec<(String, Value)>),
+    /// Bare identifier: unit enum variant, `None` (JSON `null` maps here too).
+    Ident(String),
+}
+
+impl Value {
+    pub fn as_f32(&self) -> Option<f32> {
+        match self {
+            Value::Number(n) => Some(*n as f32),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Non-negative integer (indices, counts).
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// Field of a struct / JSON object.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Struct(_, fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    /// Elements of a list or an anonymous tuple.
+    pub fn as_seq(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(items) | Value::Tuple(None, items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// `Some(x)` -> `Some(x)`, `None`/`null` -> `None`, anything else is taken as present.
+    pub fn as_option(&self) -> Option<&Value> {
+        match self {
+            Value::Ident(name) if name == "None" => None,
+            Value::Tuple(Some(name), items) if name == "Some" && items.len() == 1 => {
+                Some(&items[0])
+            }
+            other => Some(other),
+        }
+    }
+
+    /// Enum variant name and payload: `Blend`, `"Blend"`, `Mask(0.5)` or JSON `{"Mask": 0.5}`.
+    pub fn as_variant(&self) -> Option<(&str, Option<&Value>)> {
+        match self {
+            Value::Ident(name) | Value::String(name) => Some((name, None)),
+            Value::Tuple(Some(name), items) if items.len() == 1 => Some((name, Some(&items[0]))),
+            Value::Struct(None, fields) if fields.len() == 1 => {
+                Some((&fields[0].0, Some(&fields[0].1)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Parse a whole RON or JSON document.
+pub fn parse(src: &str) -> Result<Value> {
+    let mut p = Parser {
+        src,
+        pos: 0,
+        depth: 0,
+    };
+    let value = p.value()?;
+    p.skip_ws()?;
+    if p.pos < src.len() {
+        bail!("{}: unexpected trailing input", p.location());
+    }
+    Ok(value)
+}
+
+// Typed field readers shared by the descriptor parsers.
+
+pub(crate) fn kind(v: &Value) -> &'static str {
+    match v {
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::List(_) => "a list",
+        Value::Tuple(..) => "a tuple",
+        Value::Struct(..) => "another struct",
+        Value::Ident(_) => "an identifier",
+    }
+}
+
+pub(crate) fn float(v: &Value) -> Result<f32> {
+    v.as_f32()
+        .ok_or_else(|| anyhow!("expected a number, found {}", kind(v)))
+}
+
+pub(crate) fn floats(v: &Value) -> Result<Vec<f32>> {
+    v.as_seq()
+        .ok_or_else(|| anyhow!("expected a tuple or list, found {}", kind(v)))?
+        .iter()
+        .map(float)
+        .collect()
+}
+
+pub(crate) fn optional_string(v: &Value) -> Result<Option<String>> {
+    v.as_option()
+        .map(|s| {
+            s.as_str()
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!("expected a string, found {}", kind(s)))
+        })
+        .transpose()
+}
+
+// Nesting limit, so hostile input fails instead of overflowing the stack.
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+    // Values being parsed (= nesting level)
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn location(&self) -> String {
+        let before = &self.src[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let col = before.rsplit('\n').next().map_or(0, |l| l.chars().count()) + 1;
+        format!("{line}:{col}")
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn skip_ws(&mut self) -> Result<()> {
+        loop {
+            let rest = &self.src[self.pos..];
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.starts_with("//") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else if trimmed.starts_with("/*") {
+                let end = trimmed
+                    .find("*/")
+                    .ok_or_else(|| anyhow!("{}: unterminated comment", self.location()))?;
+                self.pos += end + 2;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn eat(&mut self, c: char) -> Result<bool> {
+        self.skip_ws()?;
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            Ok(true)
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        if !self.eat(c)? {
+            bail!("{}: expected '{c}'", self.location());
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        if self.depth == MAX_DEPTH {
+            bail!("{}: nested deeper than {MAX_DEPTH} levels", self.location());
+        }
+        self.depth += 1;
+        let value = self.unnested_value();
+        self.depth -= 1;
+        value
+    }
+
+    fn unnested_value(&mut self) -> Result<Value> {
+        self.skip_ws()?;
+        match self.peek() {
+            None => bail!("{}: unexpected end of input", self.location()),
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('[') => {
+                self.pos += 1;
+                Ok(Value::List(self.seq(']')?))
+            }
+            Some('{') => {
+                self.pos += 1;
+                self.fields('}', true).map(|f| Value::Struct(None, f))
+            }
+            Some('(') => {
+                self.pos += 1;
+                self.parens(None)
+            }
+            Some(c) if c == '-' || c == '+' || c == '.' || c.is_ascii_digit() => self.number(),
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let ident = self.ident();
+                match ident {
+                    "true" => return Ok(Value::Bool(true)),
+                    "false" => return Ok(Value::Bool(false)),
+                    "null" => return Ok(Value::Ident("None".to_string())),
+                    _ => {}
+                }
+                let ident = ident.to_string();
+                if self.eat('(')? {
+                    self.parens(Some(ident))
+                } else {
+                    Ok(Value::Ident(ident))
+                }
+            }
+            Some(c) => bail!("{}: unexpected '{c}'", self.location()),
+        }
+    }
+
+    // After '(': struct if the first item is `ident:`, tuple otherwise.
+    fn parens(&mut self, name: Option<String>) -> Result<Value> {
+        self.skip_ws()?;
+        let save = self.pos;
+        if self.peek().is_some_and(|c| c.is_alphabetic() || c == '_') {
+            self.ident();
+            if self.eat(':')? {
+                self.pos = save;
+                return self.fields(')', false).map(|f| Value::Struct(name, f));
+            }
+            self.pos = save;
+        }
+        Ok(Value::Tuple(name, self.seq(')')?))
+    }
+
+    fn seq(&mut self, close: char) -> Result<Vec<Value>> {
+        let mut items = Vec::new();
+        loop {
+            if self.eat(close)? {
+                return Ok(items);
+            }
+            items.push(self.value()?);
+            if !self.eat(',')? {
+                self.expect(close)?;
+                return Ok(items);
+            }
+        }
+    }
+
+    fn fields(&mut self, close: char, quoted_keys: bool) -> Result<Vec<(String, Value)>> {
+        let mut fields: Vec<(String, Value)> = Vec::new();
+        loop {
+            if self.eat(close)? {
+                return Ok(fields);
+            }
+            self.skip_ws()?;
+            let key = if quoted_keys {
+                self.string()?
+            } else {
+                self.ident().to_string()
+            };
+            if key.is_empty() {
+                bail!("{}: expected field name", self.location());
+            }
+            if fields.iter().any(|(k, _)| *k == key) {
+                bail!("{}: duplicate field '{key}'", self.location());
+            }
+            self.expect(':')?;
+            fields.push((key, self.value()?));
+            if !self.eat(',')? {
+                self.expect(close)?;
+                return Ok(fields);
+            }
+        }
+    }
+
+    fn ident(&mut self) -> &str {
+        let rest = &self.src[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let rest = &self.src[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')))
+            .unwrap_or(rest.len());
+        let text = &rest[..len];
+        let n = text
+            .parse::<f64>()
+            .map_err(|_| anyhow!("{}: invalid number '{text}'", self.location()))?;
+        self.pos += len;
+        Ok(Value::Number(n))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        if self.peek() != Some('"') {
+            bail!("{}: expected string", self.location());
+        }
+        self.pos += 1;
+        let mut out = String::new();
+        let mut chars = self.src[self.pos..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += i + 1;
+                    return Ok(out);
+                }
+                '\\' => match chars.next() {
+                    Some((_, 'n')) => out.push('\n'),
+                    Some((_, 't')) => out.push('\t'),
+                    Some((_, c @ ('"' | '\\' | '/'))) => out.push(c),
+                    _ => bail!("{}: unsupported escape in string", self.location()),
+                },
+                c => out.push(c),
+            }
+        }
+        bail!("{}: unterminated string", self.location())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ron_and_json_shapes() {
+        let ron = parse(
+            r#"// comment
+            Material(
+                color: (1.0, 0.5, -2e-1),
+                tex: Some("a/b.png"), /* inline */
+                mode: Mask(0.5),
+                cull: None,
+                list: [1, 2,],
+            )"#,
+        )
+        .unwrap();
+        let Value::Struct(Some(name), fields) = &ron else {
+            panic!("{ron:?}");
+        };
+        assert_eq!(name, "Material");
+        assert_eq!(fields[0].1.as_seq().unwrap()[2], Value::Number(-0.2));
+        assert_eq!(
+            fields[1].1.as_option().and_then(Value::as_str),
+            Some("a/b.png")
+        );
+        assert_eq!(fields[2].1.as_variant().map(|v| v.0), Some("Mask"));
+        assert!(fields[3].1.as_option().is_none());
+        assert_eq!(fields[4].1.as_seq().unwrap().len(), 2);
+
+        let json = parse(r#"{"mode": {"Mask": 0.25}, "tex": null, "on": true}"#).unwrap();
+        let Value::Struct(None, fields) = &json else {
+            panic!("{json:?}");
+        };
+        assert_eq!(
+            fields[0]
+                .1
+                .as_variant()
+                .and_then(|v| v.1)
+                .and_then(Value::as_f32),
+            Some(0.25)
+        );
+        assert!(fields[1].1.as_option().is_none());
+        assert_eq!(fields[2].1, Value::Bool(true));
+    }
+
+    #[test]
+    fn errors_report_location() {
+        let err = parse("(\n  a: 1,\n  b: ?\n)").unwrap_err().to_string();
+        assert!(err.starts_with("3:6"), "{err}");
+        assert!(parse("(a: 1, a: 2)").is_err());
+        assert!(parse("\"open").is_err());
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |depth: usize| format!("{}1{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(parse(&nested(MAX_DEPTH - 1)).is_ok());
+        let err = parse(&nested(100_000)).unwrap_err().to_string();
+        assert!(err.contains("nested deeper"), "{err}");
+        let err = parse(&"(a: ".repeat(100_000)).unwrap_err().to_string();
+        assert!(err.contains("nested deeper"), "{err}");
+    }
+}
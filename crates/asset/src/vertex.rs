@@ -0,0 +1,466 @@
+//! M1: vertex layout descriptors and optional per-vertex streams.
+//!
+//! [`MeshVertex`] holds what every mesh has (position, normal, UV). Imported assets may carry
+//! extra [`VertexStreams`] (tangents, a second UV set, colors, joints/weights).
+//! [`VertexLayout::for_mesh`] picks an interleaved layout with just the streams a mesh has,
+//! optionally quantized (snorm normals/tangents, half-float UVs, unorm colors/weights), and
+//! [`VertexLayout::encode`] packs the vertices into it. A plain mesh gets exactly the
+//! [`MeshVertex`] layout, so simple assets don't pay for attributes they don't use.
+
+use anyhow::{Result, bail};
+
+use crate::mesh::{MeshData, MeshVertex};
+
+/// Vertex attribute meaning (`u32` in mesh files).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Semantic {
+    Position = 0,
+    Normal = 1,
+    TexCoord0 = 2,
+    /// xyz = tangent, w = bitangent sign
+    Tangent = 3,
+    TexCoord1 = 4,
+    Color = 5,
+    Joints = 6,
+    Weights = 7,
+}
+
+/// Vertex attribute storage (`u32` in mesh files). Names follow `wgpu::VertexFormat`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AttributeFormat {
+    Float32x2 = 0,
+    Float32x3 = 1,
+    Float32x4 = 2,
+    Float16x2 = 3,
+    Snorm8x4 = 4,
+    Snorm16x4 = 5,
+    Unorm8x4 = 6,
+    Unorm16x4 = 7,
+    Uint16x4 = 8,
+}
+
+impl AttributeFormat {
+    pub fn size(self) -> u32 {
+        match self {
+            Self::Float16x2 | Self::Snorm8x4 | Self::Unorm8x4 => 4,
+            Self::Float32x2 | Self::Snorm16x4 | Self::Unorm16x4 | Self::Uint16x4 => 8,
+            Self::Float32x3 => 12,
+            Self::Float32x4 => 16,
+        }
+    }
+}
+
+/// One entry of the vertex layout descriptor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct VertexAttribute {
+    pub semantic: Semantic,
+    pub format: AttributeFormat,
+    pub offset: u32,
+}
+
+/// Layout of [`MeshVertex`].
+pub const MESH_VERTEX_LAYOUT: [VertexAttribute; 3] = [
+    VertexAttribute {
+        semantic: Semantic::Position,
+        format: AttributeFormat::Float32x3,
+        offset: 0,
+    },
+    VertexAttribute {
+        semantic: Semantic::Normal,
+        format: AttributeFormat::Float32x3,
+        offset: 12,
+    },
+    VertexAttribute {
+        semantic: Semantic::TexCoord0,
+        format: AttributeFormat::Float32x2,
+        offset: 24,
+    },
+];
+
+/// Optional streams parallel to `MeshData::vertices` (`None` = the mesh doesn't have it).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VertexStreams {
+    /// xyz = unit tangent, w = bitangent sign (±1).
+    pub tangents: Option<Vec<[f32; 4]>>,
+    pub uv1: Option<Vec<[f32; 2]>>,
+    /// Linear RGBA.
+    pub colors: Option<Vec<[f32; 4]>>,
+    pub joints: Option<Vec<[u16; 4]>>,
+    pub weights: Option<Vec<[f32; 4]>>,
+}
+
+impl VertexStreams {
+    pub fn is_empty(&self) -> bool {
+        self.semantics().next().is_none()
+    }
+
+    /// Semantics of the present streams, in layout order.
+    pub fn semantics(&self) -> impl Iterator<Item = Semantic> + '_ {
+        [
+            (Semantic::Tangent, self.tangents.is_some()),
+            (Semantic::TexCoord1, self.uv1.is_some()),
+            (Semantic::Color, self.colors.is_some()),
+            (Semantic::Joints, self.joints.is_some()),
+            (Semantic::Weights, self.weights.is_some()),
+        ]
+        .into_iter()
+        .filter_map(|(s, present)| present.then_some(s))
+    }
+
+    /// Lengths of the present streams.
+    pub fn lengths(&self) -> impl Iterator<Item = usize> {
+        [
+            self.tangents.as_ref().map(Vec::len),
+            self.uv1.as_ref().map(Vec::len),
+            self.colors.as_ref().map(Vec::len),
+            self.joints.as_ref().map(Vec::len),
+            self.weights.as_ref().map(Vec::len),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Streams of the vertices `source` (e.g. after reordering or splitting vertices).
+    pub fn pick(&self, source: &[usize]) -> Self {
+        fn pick<T: Copy>(stream: &Option<Vec<T>>, source: &[usize]) -> Option<Vec<T>> {
+            stream
+                .as_ref()
+                .map(|s| source.iter().map(|&i| s[i]).collect())
+        }
+        Self {
+            tangents: pick(&self.tangents, source),
+            uv1: pick(&self.uv1, source),
+            colors: pick(&self.colors, source),
+            joints: pick(&self.joints, source),
+            weights: pick(&self.weights, source),
+        }
+    }
+
+    /// Append `other` (streams of `other_len` vertices) behind `len` vertices; streams only one
+    /// side has are filled with [`VertexStreams::value`] defaults.
+    pub fn append(&mut self, len: usize, other: &Self, other_len: usize) {
+        fn append<T: Copy>(
+            stream: &mut Option<Vec<T>>,
+            len: usize,
+            other: &Option<Vec<T>>,
+            other_len: usize,
+            default: T,
+        ) {
+            if stream.is_none() && other.is_none() {
+                return;
+            }
+            let s = stream.get_or_insert_with(|| vec![default; len]);
+            match other {
+                Some(o) => s.extend_from_slice(o),
+                None => s.resize(len + other_len, default),
+            }
+        }
+        append(
+            &mut self.tangents,
+            len,
+            &other.tangents,
+            other_len,
+            [1.0, 0.0, 0.0, 1.0],
+        );
+        append(&mut self.uv1, len, &other.uv1, other_len, [0.0; 2]);
+        append(&mut self.colors, len, &other.colors, other_len, [1.0; 4]);
+        append(&mut self.joints, len, &other.joints, other_len, [0; 4]);
+        append(
+            &mut self.weights,
+            len,
+            &other.weights,
+            other_len,
+            [1.0, 0.0, 0.0, 0.0],
+        );
+    }
+
+    /// Do vertices `a` and `b` agree within `epsilon` in every stream?
+    pub fn close(&self, a: usize, b: usize, epsilon: f32) -> bool {
+        let close = |x: &[f32], y: &[f32]| x.iter().zip(y).all(|(x, y)| (x - y).abs() <= epsilon);
+        self.tangents.as_ref().is_none_or(|s| close(&s[a], &s[b]))
+            && self.uv1.as_ref().is_none_or(|s| close(&s[a], &s[b]))
+            && self.colors.as_ref().is_none_or(|s| close(&s[a], &s[b]))
+            && self.joints.as_ref().is_none_or(|s| s[a] == s[b])
+            && self.weights.as_ref().is_none_or(|s| close(&s[a], &s[b]))
+    }
+}
+
+/// Layout selection for [`VertexLayout::for_mesh`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LayoutOptions {
+    /// Snorm8 normals/tangents, half-float UVs, unorm8 colors and unorm16 weights
+    /// (positions stay full precision).
+    pub quantize: bool,
+}
+
+/// Interleaved vertex layout: attributes at byte offsets inside a `stride`-sized vertex.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VertexLayout {
+    pub stride: u32,
+    pub attributes: Vec<VertexAttribute>,
+}
+
+impl Default for VertexLayout {
+    /// The [`MeshVertex`] layout.
+    fn default() -> Self {
+        Self {
+            stride: std::mem::size_of::<MeshVertex>() as u32,
+            attributes: MESH_VERTEX_LAYOUT.to_vec(),
+        }
+    }
+}
+
+impl VertexLayout {
+    /// Pack attributes in order (every format is a multiple of 4 bytes, so offsets stay aligned).
+    pub fn packed(attributes: impl IntoIterator<Item = (Semantic, AttributeFormat)>) -> Self {
+        let mut stride = 0;
+        let attributes = attributes
+            .into_iter()
+            .map(|(semantic, format)| {
+                let offset = stride;
+                stride += format.size();
+                VertexAttribute {
+                    semantic,
+                    format,
+                    offset,
+                }
+            })
+            .collect();
+        Self { stride, attributes }
+    }
+
+    /// Base attributes plus every stream `mesh` has.
+    pub fn for_mesh(mesh: &MeshData, options: LayoutOptions) -> Self {
+        let base = [Semantic::Position, Semantic::Normal, Semantic::TexCoord0];
+        let format = |semantic| match (semantic, options.quantize) {
+            (Semantic::Position, _) | (Semantic::Normal, false) => AttributeFormat::Float32x3,
+            (Semantic::TexCoord0 | Semantic::TexCoord1, false) => AttributeFormat::Float32x2,
+            (Semantic::TexCoord0 | Semantic::TexCoord1, true) => AttributeFormat::Float16x2,
+            (Semantic::Normal | Semantic::Tangent, true) => AttributeFormat::Snorm8x4,
+            (Semantic::Tangent | Semantic::Color | Semantic::Weights, false) => {
+                AttributeFormat::Float32x4
+            }
+            (Semantic::Color, true) => AttributeFormat::Unorm8x4,
+            (Semantic::Weights, true) => AttributeFormat::Unorm16x4,
+            (Semantic::Joints, _) => AttributeFormat::Uint16x4,
+        };
+        Self::packed(
+            base.into_iter()
+                .chain(mesh.streams.semantics())
+                .map(|s| (s, format(s))),
+        )
+    }
+
+    pub fn attribute(&self, semantic: Semantic) -> Option<&VertexAttribute> {
+        self.attributes.iter().find(|a| a.semantic == semantic)
+    }
+
+    /// Is this the [`MeshVertex`] layout (vertices can be used without repacking)?
+    pub fn is_mesh_vertex(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Interleave the vertices of `mesh` (fails when a stream of the layout is missing).
+    pub fn encode(&self, mesh: &MeshData) -> Result<Vec<u8>> {
+        for a in &self.attributes {
+            if a.offset + a.format.size() > self.stride {
+                bail!(
+                    "{:?} at offset {} does not fit a {}-byte vertex",
+                    a.semantic,
+                    a.offset,
+                    self.stride
+                );
+            }
+            if value(mesh, a.semantic, 0).is_none() && !mesh.vertices.is_empty() {
+                bail!("mesh has no {:?} stream", a.semantic);
+            }
+        }
+        let mut out = vec![0u8; mesh.vertices.len() * self.stride as usize];
+        for (i, vertex) in out.chunks_exact_mut(self.stride as usize).enumerate() {
+            for a in &self.attributes {
+                let v = value(mesh, a.semantic, i).expect("checked above");
+                write(&mut vertex[a.offset as usize..], a.format, v);
+            }
+        }
+        Ok(out)
+    }
+}
+
+// Attribute `semantic` of vertex `i` widened to four floats (joints as exact integers)
+fn value(mesh: &MeshData, semantic: Semantic, i: usize) -> Option<[f32; 4]> {
+    let s = &mesh.streams;
+    let v = mesh.vertices.get(i).copied().unwrap_or_default();
+    let xyz = |p: [f32; 3]| [p[0], p[1], p[2], 0.0];
+    let xy = |p: [f32; 2]| [p[0], p[1], 0.0, 0.0];
+    Some(match semantic {
+        Semantic::Position => xyz(v.position),
+        Semantic::Normal => xyz(v.normal),
+        Semantic::TexCoord0 => xy(v.uv),
+        Semantic::Tangent => s.tangents.as_ref()?.get(i).copied().unwrap_or_default(),
+        Semantic::TexCoord1 => xy(s.uv1.as_ref()?.get(i).copied().unwrap_or_default()),
+        Semantic::Color => s.colors.as_ref()?.get(i).copied().unwrap_or_default(),
+        Semantic::Joints => s
+            .joints
+            .as_ref()?
+            .get(i)
+            .copied()
+            .unwrap_or_default()
+            .map(f32::from),
+        Semantic::Weights => s.weights.as_ref()?.get(i).copied().unwrap_or_default(),
+    })
+}
+
+fn write(out: &mut [u8], format: AttributeFormat, v: [f32; 4]) {
+    let mut put =
+        |bytes: &[u8], k: usize| out[k * bytes.len()..(k + 1) * bytes.len()].copy_from_slice(bytes);
+    match format {
+        AttributeFormat::Float32x2 | AttributeFormat::Float32x3 | AttributeFormat::Float32x4 => {
+            for (k, x) in v.iter().take(format.size() as usize / 4).enumerate() {
+                put(&x.to_le_bytes(), k);
+            }
+        }
+        AttributeFormat::Float16x2 => {
+            for (k, &x) in v.iter().take(2).enumerate() {
+                put(&f16_bits(x).to_le_bytes(), k);
+            }
+        }
+        AttributeFormat::Snorm8x4 => {
+            for (k, &x) in v.iter().enumerate() {
+                put(&[((x.clamp(-1.0, 1.0) * 127.0).round() as i8) as u8], k);
+            }
+        }
+        AttributeFormat::Snorm16x4 => {
+            for (k, &x) in v.iter().enumerate() {
+                put(
+                    &((x.clamp(-1.0, 1.0) * 32767.0).round() as i16).to_le_bytes(),
+                    k,
+                );
+            }
+        }
+        AttributeFormat::Unorm8x4 => {
+            for (k, &x) in v.iter().enumerate() {
+                put(&[(x.clamp(0.0, 1.0) * 255.0).round() as u8], k);
+            }
+        }
+        AttributeFormat::Unorm16x4 => {
+            for (k, &x) in v.iter().enumerate() {
+                put(
+                    &((x.clamp(0.0, 1.0) * 65535.0).round() as u16).to_le_bytes(),
+                    k,
+                );
+            }
+        }
+        AttributeFormat::Uint16x4 => {
+            for (k, &x) in v.iter().enumerate() {
+                put(&(x as u16).to_le_bytes(), k);
+            }
+        }
+    }
+}
+
+/// IEEE half-precision bits of `x` (round to nearest even; overflow becomes infinity).
+pub fn f16_bits(x: f32) -> u16 {
+    let bits = x.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exp = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+    if exp == 0xff {
+        return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 };
+    }
+    // Keep the top bits of `m`, dropping `shift` bits with round-half-to-even
+    let round = |m: u32, shift: u32| {
+        let kept = m >> shift;
+        let rest = m & ((1 << shift) - 1);
+        let half = 1 << (shift - 1);
+        kept + u32::from(rest > half || (rest == half && kept & 1 == 1))
+    };
+    let e = exp - 127 + 15;
+    if e >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if e <= 0 {
+        // Subnormal (a carry into the exponent yields the smallest normal, as it should)
+        if e < -10 {
+            return sign;
+        }
+        return sign | round(mantissa | 0x80_0000, (14 - e) as u32) as u16;
+    }
+    // A mantissa carry bumps the exponent, up to infinity
+    sign | round(((e as u32) << 23) | mantissa, 13) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangle() -> MeshData {
+        let v = |p| MeshVertex::new(p, [0.0, 0.0, 1.0], [p[0], p[1]]);
+        MeshData::new(
+            vec![v([0.0, 0.0, 0.0]), v([1.0, 0.0, 0.0]), v([0.0, 1.0, 0.0])],
+            vec![0, 1, 2],
+        )
+    }
+
+    #[test]
+    fn half_floats_round_correctly() {
+        assert_eq!(f16_bits(1.0), 0x3c00);
+        assert_eq!(f16_bits(-2.0), 0xc000);
+        assert_eq!(f16_bits(0.1), 0x2e66);
+        assert_eq!(f16_bits(65504.0), 0x7bff);
+        assert_eq!(f16_bits(1e6), 0x7c00);
+        assert_eq!(f16_bits(2f32.powi(-24)), 0x0001);
+        assert_eq!(f16_bits(2f32.powi(-26)), 0);
+        assert_eq!(f16_bits(f32::NAN) & 0x7e00, 0x7e00);
+    }
+
+    #[test]
+    fn simple_meshes_keep_the_mesh_vertex_layout() {
+        let mesh = triangle();
+        let layout = VertexLayout::for_mesh(&mesh, LayoutOptions::default());
+        assert!(layout.is_mesh_vertex());
+        assert_eq!(
+            layout.encode(&mesh).unwrap(),
+            bytemuck::cast_slice::<MeshVertex, u8>(&mesh.vertices)
+        );
+
+        // Quantized: 12 (position) + 4 (snorm8 normal) + 4 (half UV)
+        let quantized = VertexLayout::for_mesh(&mesh, LayoutOptions { quantize: true });
+        assert_eq!(quantized.stride, 20);
+        let bytes = quantized.encode(&mesh).unwrap();
+        assert_eq!(bytes.len(), 60);
+        // Vertex 1: normal (0, 0, 127, 0), uv (1.0, 0.0) as halves
+        assert_eq!(&bytes[32..40], &[0, 0, 127, 0, 0x00, 0x3c, 0, 0]);
+    }
+
+    #[test]
+    fn streams_extend_the_layout() {
+        let mut mesh = triangle();
+        mesh.streams.colors = Some(vec![[1.0, 0.5, 0.0, 1.0]; 3]);
+        mesh.streams.joints = Some(vec![[3, 0, 0, 0]; 3]);
+        let layout = VertexLayout::for_mesh(&mesh, LayoutOptions { quantize: true });
+        let semantics: Vec<_> = layout.attributes.iter().map(|a| a.semantic).collect();
+        assert_eq!(
+            semantics,
+            [
+                Semantic::Position,
+                Semantic::Normal,
+                Semantic::TexCoord0,
+                Semantic::Color,
+                Semantic::Joints
+            ]
+        );
+        assert_eq!(layout.stride, 20 + 4 + 8);
+        let bytes = layout.encode(&mesh).unwrap();
+        assert_eq!(&bytes[20..32], &[255, 128, 0, 255, 3, 0, 0, 0, 0, 0, 0, 0]);
+
+        // Layouts asking for streams the mesh lacks are rejected
+        assert!(layout.encode(&triangle()).is_err());
+
+        // Appending a mesh without colors fills in white
+        let mut streams = mesh.streams.clone();
+        streams.append(3, &VertexStreams::default(), 2);
+        assert_eq!(streams.colors.as_ref().map(Vec::len), Some(5));
+        assert_eq!(streams.colors.unwrap()[4], [1.0; 4]);
+        assert_eq!(mesh.streams.pick(&[2, 0]).joints.unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,168 @@
+//! J1: animator component: plays and blends the clips of a skinned or morphed mesh.
+//!
+//! The component only keeps playback state (clip index, time, weight); clips and skeletons
+//! live with the skinned mesh in the renderer, which samples the pose every frame.
+
+use crate::ecs::SkinId;
+
+/// One clip being played; clips with equal weight contribute equally to the pose.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClipPlayback {
+    /// Clip index within the skinned mesh.
+    pub clip: usize,
+    /// Playback position in seconds.
+    pub time: f32,
+    /// Time scale (1 = authored speed, negative plays backwards).
+    pub speed: f32,
+    pub weight: f32,
+    /// Weight the layer fades towards; layers that reach 0 are removed.
+    pub target_weight: f32,
+    /// Weight change per second while fading.
+    pub fade_rate: f32,
+    /// Wrap at the end of the clip instead of holding the last pose.
+    pub looping: bool,
+}
+
+impl ClipPlayback {
+    pub fn new(clip: usize) -> Self {
+        Self {
+            clip,
+            time: 0.0,
+            speed: 1.0,
+            weight: 1.0,
+            target_weight: 1.0,
+            fade_rate: 0.0,
+            looping: true,
+        }
+    }
+}
+
+/// ECS component: clip layers driving the skeleton and morph targets of `skin`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Animator {
+    pub skin: SkinId,
+    pub layers: Vec<ClipPlayback>,
+    pub paused: bool,
+    /// Per-instance morph target weights used where no clip animates them; empty = the
+    /// mesh's default weights.
+    pub morph_weights: Vec<f32>,
+}
+
+impl Animator {
+    pub fn new(skin: SkinId) -> Self {
+        Self {
+            skin,
+            layers: Vec::new(),
+            paused: false,
+            morph_weights: Vec::new(),
+        }
+    }
+
+    /// Play `clip` alone from the start (looping).
+    pub fn play(&mut self, clip: usize) {
+        self.layers.clear();
+        self.layers.push(ClipPlayback::new(clip));
+    }
+
+    /// Fade `clip` in and every other layer out over `duration` seconds. A layer already
+    /// playing `clip` keeps its time.
+    pub fn crossfade(&mut self, clip: usize, duration: f32) {
+        let rate = if duration > 0.0 {
+            1.0 / duration
+        } else {
+            f32::INFINITY
+        };
+        if !self.layers.iter().any(|l| l.clip == clip) {
+            self.layers.push(ClipPlayback {
+                weight: 0.0,
+                ..ClipPlayback::new(clip)
+            });
+        }
+        for layer in &mut self.layers {
+            layer.target_weight = if layer.clip == clip { 1.0 } else { 0.0 };
+            layer.fade_rate = rate;
+        }
+    }
+
+    /// Set the blend weight of `clip` right away, adding a layer if needed.
+    pub fn set_weight(&mut self, clip: usize, weight: f32) {
+        let weight = weight.max(0.0);
+        match self.layers.iter_mut().find(|l| l.clip == clip) {
+            Some(layer) => {
+                layer.weight = weight;
+                layer.target_weight = weight;
+            }
+            None => self.layers.push(ClipPlayback {
+                weight,
+                target_weight: weight,
+                ..ClipPlayback::new(clip)
+            }),
+        }
+    }
+
+    /// Advance times and fades by `dt`; `duration` gives each clip's length in seconds.
+    pub fn advance(&mut self, dt: f32, duration: impl Fn(usize) -> f32) {
+        if self.paused {
+            return;
+        }
+        for layer in &mut self.layers {
+            let length = duration(layer.clip);
+            layer.time += dt * layer.speed;
+            if length <= 0.0 {
+                layer.time = 0.0;
+            } else if layer.looping {
+                layer.time = layer.time.rem_euclid(length);
+            } else {
+                layer.time = layer.time.clamp(0.0, length);
+            }
+            let step = layer.fade_rate * dt;
+            layer.weight = if layer.weight < layer.target_weight {
+                (layer.weight + step).min(layer.target_weight)
+            } else {
+                (layer.weight - step).max(layer.target_weight)
+            };
+        }
+        self.layers
+            .retain(|l| l.weight > 0.0 || l.target_weight > 0.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crossfade_moves_weight_and_drops_finished_layers() {
+        let mut animator = Animator::new(SkinId::new(0));
+        animator.play(0);
+        animator.crossfade(1, 0.5);
+        animator.advance(0.25, |_| 2.0);
+        let weights: Vec<(usize, f32)> =
+            animator.layers.iter().map(|l| (l.clip, l.weight)).collect();
+        assert_eq!(weights, vec![(0, 0.5), (1, 0.5)]);
+
+        animator.advance(0.25, |_| 2.0);
+        assert_eq!(animator.layers.len(), 1);
+        assert_eq!(
+            (animator.layers[0].clip, animator.layers[0].weight),
+            (1, 1.0)
+        );
+        assert_eq!(animator.layers[0].time, 0.5);
+    }
+
+    #[test]
+    fn looping_wraps_and_one_shot_holds() {
+        let mut animator = Animator::new(SkinId::new(0));
+        animator.play(0);
+        animator.set_weight(1, 0.5);
+        animator.layers[1].looping = false;
+        animator.advance(2.5, |_| 1.0);
+        assert_eq!(animator.layers[0].time, 0.5);
+        assert_eq!(animator.layers[1].time, 1.0);
+        assert_eq!(animator.layers[1].weight, 0.5);
+
+        animator.paused = true;
+        animator.advance(0.25, |_| 1.0);
+        assert_eq!(animator.layers[0].time, 0.5);
+    }
+}
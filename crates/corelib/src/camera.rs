@@ -1,7 +1,7 @@
-use crate::{Mat4, Vec3};
+use crate::{Mat4, Vec3, frustum::Frustum};
 
 /// Simple perspective camera (right-handed).
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Camera {
     pub eye: Vec3,
     pub target: Vec3,
@@ -34,12 +34,26 @@ impl Camera {
         }
     }
 
+    /// Test fixture: 60° square camera at `(0, 0, 5)` looking at the origin (far plane 100).
+    #[cfg(any(test, feature = "test-util"))]
+    pub fn test_default() -> Self {
+        Self::new_perspective(
+            Vec3::new(0.0, 0.0, 5.0),
+            Vec3::ZERO,
+            Vec3::Y,
+            60f32.to_radians(),
+            0.1,
+            100.0,
+            1.0,
+        )
+    }
+
     #[inline]
     pub fn view(&self) -> Mat4 {
         Mat4::look_at_rh(self.eye, self.target, self.up)
     }
 
-    ///*  NOTE: This returns OpenGL-style projection (z ∈ [-1,1]).
+    /// NOTE: This returns OpenGL-style projection (z ∈ [-1,1]).
     /// Renderer multiplies by OPENGL_TO_WGPU to match z ∈ [0,1].
     #[inline]
     pub fn proj(&self) -> Mat4 {
@@ -56,6 +70,12 @@ impl Camera {
         self.proj() * self.view()
     }
 
+    /// L1: world-space frustum planes for culling.
+    #[inline]
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.proj_view())
+    }
+
     #[inline]
     pub fn with_aspect(mut self, aspect: f32) -> Self {
         self.aspect = aspect;
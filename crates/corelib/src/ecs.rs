@@ -1,5 +1,7 @@
-//! Tiny ECS: World, Entity, components: Transform + Renderable.
+//! Tiny ECS: World, Entity, components: Transform + Renderable (+ LodState, ParticleEmitter, Animator).
 
+use crate::animation::Animator;
+use crate::particles::ParticleEmitter;
 use crate::transform::Transform;
 
 /// Entity id (dense, index into component arrays).
@@ -44,16 +46,81 @@ impl TextureId {
     }
 }
 
-/// Marker component: renderable mesh + material handles.
+/// Handle for a group of mesh LODs (finest to coarsest).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LodGroupId(pub u32);
+
+impl LodGroupId {
+    pub const INVALID: LodGroupId = LodGroupId(u32::MAX);
+
+    #[inline]
+    pub const fn new(raw: u32) -> Self {
+        Self(raw)
+    }
+}
+
+/// Handle for a skinned mesh (skeleton, clips and its own skinned vertex buffer).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SkinId(pub u32);
+
+impl SkinId {
+    pub const INVALID: SkinId = SkinId(u32::MAX);
+
+    #[inline]
+    pub const fn new(raw: u32) -> Self {
+        Self(raw)
+    }
+}
+
+/// Marker component: renderable LOD group + material (+ optional texture) handles.
 #[derive(Clone, Copy, Debug)]
 pub struct Renderable {
-    pub mesh: MeshId,
+    pub lod_group: LodGroupId,
     pub material: MaterialId,
+    /// E2: `None` draws with the scene's default texture.
+    pub texture: Option<TextureId>,
 }
 
 impl Renderable {
-    pub const fn new(mesh: MeshId, material: MaterialId) -> Self {
-        Self { mesh, material }
+    pub const fn new(lod_group: LodGroupId, material: MaterialId) -> Self {
+        Self {
+            lod_group,
+            material,
+            texture: None,
+        }
+    }
+
+    pub const fn with_texture(mut self, texture: TextureId) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+}
+
+/// Per-entity LOD selection state (kept across frames for hysteresis and cross-fade).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LodState {
+    /// Currently selected level.
+    pub level: u8,
+    /// Level being faded out (equal to `level` when not fading).
+    pub previous: u8,
+    /// Cross-fade progress in [0, 1]; 1 = transition finished.
+    pub fade: f32,
+}
+
+impl LodState {
+    #[inline]
+    pub fn is_fading(&self) -> bool {
+        self.previous != self.level && self.fade < 1.0
+    }
+}
+
+impl Default for LodState {
+    fn default() -> Self {
+        Self {
+            level: 0,
+            previous: 0,
+            fade: 1.0,
+        }
     }
 }
 
@@ -63,8 +130,16 @@ impl Renderable {
 pub struct World {
     transforms: Vec<Transform>,
     renderables: Vec<Option<Renderable>>,
+    lod_states: Vec<LodState>,
+    // K3: optional particle emitters
+    emitters: Vec<Option<ParticleEmitter>>,
+    // J1: optional skeletal animation players
+    animators: Vec<Option<Animator>>,
     alive: Vec<bool>,
     len: u32,
+    // L2: entities changed since the last `take_dirty` (flag + list for O(changed) drain)
+    dirty: Vec<bool>,
+    dirty_list: Vec<Entity>,
 }
 
 impl World {
@@ -83,26 +158,85 @@ impl World {
             let new_len = (idx + 1).next_power_of_two().max(8);
             self.transforms.resize(new_len, Transform::identity());
             self.renderables.resize(new_len, None);
+            self.lod_states.resize(new_len, LodState::default());
+            self.emitters.resize(new_len, None);
+            self.animators.resize(new_len, None);
             self.alive.resize(new_len, false);
+            self.dirty.resize(new_len, false);
         }
 
         self.transforms[idx] = t;
         self.renderables[idx] = r;
+        self.lod_states[idx] = LodState::default();
+        self.emitters[idx] = None;
+        self.animators[idx] = None;
         self.alive[idx] = true;
+        self.mark_dirty(id);
         id
     }
 
+    /// Kill an entity; it is reported dirty once more so retained renderers can drop it.
+    pub fn despawn(&mut self, e: Entity) {
+        if self.is_alive(e) {
+            self.alive[e as usize] = false;
+            self.renderables[e as usize] = None;
+            self.emitters[e as usize] = None;
+            self.animators[e as usize] = None;
+            self.mark_dirty(e);
+        }
+    }
+
+    /// L2: flag an entity as changed (no-op for out-of-range ids).
+    #[inline]
+    pub fn mark_dirty(&mut self, e: Entity) {
+        if let Some(flag) = self.dirty.get_mut(e as usize)
+            && !*flag
+        {
+            *flag = true;
+            self.dirty_list.push(e);
+        }
+    }
+
+    /// L2: flag every spawned entity (e.g. after a camera change affecting LOD).
+    pub fn mark_all_dirty(&mut self) {
+        for e in 0..self.len {
+            self.mark_dirty(e);
+        }
+    }
+
+    /// L2: move changed entities (alive or despawned) into `out` and clear their flags.
+    pub fn take_dirty(&mut self, out: &mut Vec<Entity>) {
+        for &e in &self.dirty_list {
+            self.dirty[e as usize] = false;
+        }
+        out.append(&mut self.dirty_list);
+    }
+
+    /// L2: components of one alive renderable entity with mutable LOD state.
+    pub fn renderable_lod_mut(
+        &mut self,
+        e: Entity,
+    ) -> Option<(&Transform, &Renderable, &mut LodState)> {
+        let i = e as usize;
+        if !self.is_alive(e) {
+            return None;
+        }
+        let r = self.renderables[i].as_ref()?;
+        Some((&self.transforms[i], r, &mut self.lod_states[i]))
+    }
+
     #[inline]
     pub fn is_alive(&self, e: Entity) -> bool {
         let i = e as usize;
         i < self.alive.len() && self.alive[i]
     }
 
-    /// Mutable access to a transform (for animation).
+    /// Mutable access to a transform (for animation). Marks the entity dirty.
     #[inline]
     pub fn transform_mut(&mut self, e: Entity) -> Option<&mut Transform> {
         let i = e as usize;
         if self.is_alive(e) {
+            self.mark_dirty(e);
             Some(&mut self.transforms[i])
         } else {
             None
@@ -113,15 +247,124 @@ impl World {
     pub fn iter_renderables(&self) -> impl Iterator<Item = (&Transform, &Renderable)> {
         // No alloc: zip and filter by alive + has Some(Renderable)
         (0..self.len as usize).filter_map(move |i| {
-            if self.alive.get(i).copied().unwrap_or(false) {
-                if let Some(r) = self.renderables[i].as_ref() {
-                    return Some((&self.transforms[i], r));
+            if self.alive.get(i).copied().unwrap_or(false)
+                && let Some(r) = self.renderables[i].as_ref()
+            {
+                return Some((&self.transforms[i], r));
+            }
+            None
+        })
+    }
+
+    /// Iterate renderables with mutable LOD state (for per-frame LOD selection).
+    pub fn iter_renderables_lod_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (&Transform, &Renderable, &mut LodState)> {
+        let len = self.len as usize;
+        self.transforms[..len]
+            .iter()
+            .zip(&self.renderables[..len])
+            .zip(&mut self.lod_states[..len])
+            .zip(&self.alive[..len])
+            .filter_map(|(((t, r), lod), &alive)| {
+                if alive && let Some(r) = r.as_ref() {
+                    return Some((t, r, lod));
                 }
+                None
+            })
+    }
+
+    /// K3: attach or remove a particle emitter.
+    pub fn set_emitter(&mut self, e: Entity, emitter: Option<ParticleEmitter>) {
+        if self.is_alive(e) {
+            self.emitters[e as usize] = emitter;
+        }
+    }
+
+    /// K3: mutable emitter access (e.g. from the inspector).
+    pub fn emitter_mut(&mut self, e: Entity) -> Option<&mut ParticleEmitter> {
+        if self.is_alive(e) {
+            self.emitters[e as usize].as_mut()
+        } else {
+            None
+        }
+    }
+
+    /// K3: iterate alive entities with an emitter.
+    pub fn iter_emitters(&self) -> impl Iterator<Item = (Entity, &Transform, &ParticleEmitter)> {
+        (0..self.len as usize).filter_map(move |i| {
+            if self.alive[i]
+                && let Some(em) = self.emitters[i].as_ref()
+            {
+                return Some((i as Entity, &self.transforms[i], em));
             }
             None
         })
     }
 
+    /// J1: attach or remove an animator. A skin's pose and skinned vertices are shared, so
+    /// an animator whose skin another entity already animates is rejected (returns false).
+    pub fn set_animator(&mut self, e: Entity, animator: Option<Animator>) -> bool {
+        if !self.is_alive(e) {
+            return false;
+        }
+        if let Some(skin) = animator.as_ref().map(|a| a.skin)
+            && self
+                .iter_animators_mut()
+                .any(|(other, a)| other != e && a.skin == skin)
+        {
+            return false;
+        }
+        self.animators[e as usize] = animator;
+        true
+    }
+
+    /// J1: mutable animator access (e.g. to start a crossfade).
+    pub fn animator_mut(&mut self, e: Entity) -> Option<&mut Animator> {
+        if self.is_alive(e) {
+            self.animators[e as usize].as_mut()
+        } else {
+            None
+        }
+    }
+
+    /// J1: iterate alive entities with an animator (advanced and sampled by the renderer).
+    pub fn iter_animators_mut(&mut self) -> impl Iterator<Item = (Entity, &mut Animator)> {
+        let len = self.len as usize;
+        self.animators[..len]
+            .iter_mut()
+            .zip(&self.alive[..len])
+            .enumerate()
+            .filter_map(|(i, (a, &alive))| {
+                if alive {
+                    a.as_mut().map(|a| (i as Entity, a))
+                } else {
+                    None
+                }
+            })
+    }
+
+    /// G1: split spawned entities into disjoint chunks of `chunk_size` for parallel jobs.
+    pub fn chunks_mut(&mut self, chunk_size: usize) -> impl Iterator<Item = WorldChunk<'_>> {
+        let len = self.len as usize;
+        let chunk_size = chunk_size.max(1);
+        self.transforms[..len]
+            .chunks(chunk_size)
+            .zip(self.renderables[..len].chunks(chunk_size))
+            .zip(self.lod_states[..len].chunks_mut(chunk_size))
+            .zip(self.alive[..len].chunks(chunk_size))
+            .enumerate()
+            .map(
+                move |(i, (((transforms, renderables), lod_states), alive))| WorldChunk {
+                    first: (i * chunk_size) as Entity,
+                    transforms,
+                    renderables,
+                    lod_states,
+                    alive,
+                },
+            )
+    }
+
     /// System example: rotate all transforms by given Euler speed * dt.
     pub fn system_rotate_all(&mut self, dt: f32, speed_xyz: [f32; 3]) {
         let [sx, sy, sz] = speed_xyz;
@@ -131,12 +374,107 @@ impl World {
                 t.rotation_euler.x += sx * dt;
                 t.rotation_euler.y += sy * dt;
                 t.rotation_euler.z += sz * dt;
+                if !self.dirty[i] {
+                    self.dirty[i] = true;
+                    self.dirty_list.push(i as Entity);
+                }
             }
         }
     }
 
+    /// G1: entity slots handed out so far (alive or despawned); bounds [`World::chunks_mut`].
+    pub fn slot_count(&self) -> usize {
+        self.len as usize
+    }
+
     /// Get the count of alive entities.
     pub fn entity_count(&self) -> usize {
         self.alive.iter().filter(|&&alive| alive).count()
     }
 }
+
+/// G1: contiguous entity range of a [`World`] (see [`World::chunks_mut`]). Chunks borrow
+/// disjoint slices, so each one can be processed on its own thread.
+pub struct WorldChunk<'a> {
+    /// Entity id of the first slot in the chunk.
+    pub first: Entity,
+    transforms: &'a [Transform],
+    renderables: &'a [Option<Renderable>],
+    lod_states: &'a mut [LodState],
+    alive: &'a [bool],
+}
+
+impl WorldChunk<'_> {
+    /// Like [`World::iter_renderables_lod_mut`], restricted to this chunk.
+    pub fn iter_renderables_lod_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (&Transform, &Renderable, &mut LodState)> {
+        self.transforms
+            .iter()
+            .zip(self.renderables)
+            .zip(self.lod_states.iter_mut())
+            .zip(self.alive)
+            .filter_map(|(((t, r), lod), &alive)| {
+                if alive && let Some(r) = r.as_ref() {
+                    return Some((t, r, lod));
+                }
+                None
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dirty_tracking_reports_each_change_once() {
+        let mut world = World::new();
+        let a = world.spawn(Transform::identity(), None);
+        let b = world.spawn(Transform::identity(), None);
+        let mut dirty = Vec::new();
+        world.take_dirty(&mut dirty);
+        assert_eq!(dirty, vec![a, b]);
+
+        dirty.clear();
+        world.take_dirty(&mut dirty);
+        assert!(dirty.is_empty());
+
+        world.transform_mut(b);
+        world.transform_mut(b);
+        world.despawn(a);
+        world.take_dirty(&mut dirty);
+        assert_eq!(dirty, vec![b, a]);
+        assert!(!world.is_alive(a));
+    }
+
+    #[test]
+    fn chunks_cover_all_renderables() {
+        let mut world = World::new();
+        for i in 0..10 {
+            let r = (i % 2 == 0).then(|| Renderable::new(LodGroupId::new(0), MaterialId::new(0)));
+            world.spawn(Transform::identity(), r);
+        }
+        let counts: Vec<(Entity, usize)> = world
+            .chunks_mut(4)
+            .map(|mut c| (c.first, c.iter_renderables_lod_mut().count()))
+            .collect();
+        assert_eq!(counts, vec![(0, 2), (4, 2), (8, 1)]);
+    }
+
+    #[test]
+    fn one_animator_per_skin() {
+        let mut world = World::new();
+        let a = world.spawn(Transform::identity(), None);
+        let b = world.spawn(Transform::identity(), None);
+        assert!(world.set_animator(a, Some(Animator::new(SkinId(0)))));
+        assert!(!world.set_animator(b, Some(Animator::new(SkinId(0)))));
+        assert!(world.animator_mut(b).is_none());
+        assert!(world.set_animator(b, Some(Animator::new(SkinId(1)))));
+
+        // Replacing an entity's own animator, or freeing the skin, is fine
+        assert!(world.set_animator(a, Some(Animator::new(SkinId(0)))));
+        world.despawn(a);
+        assert!(world.set_animator(b, Some(Animator::new(SkinId(0)))));
+    }
+}
@@ -0,0 +1,71 @@
+//! L1: view frustum planes for CPU culling.
+
+use crate::{Mat4, Vec3, Vec4};
+
+/// Plane `dot(normal, p) + d = 0`; the positive side is "inside".
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plane {
+    pub normal: Vec3,
+    pub d: f32,
+}
+
+impl Plane {
+    /// Build from raw `(a, b, c, d)` coefficients and normalize.
+    #[inline]
+    pub fn from_vec4(v: Vec4) -> Self {
+        let normal = v.truncate();
+        let len = normal.length().max(1e-12);
+        Self {
+            normal: normal / len,
+            d: v.w / len,
+        }
+    }
+
+    #[inline]
+    pub fn signed_distance(&self, p: Vec3) -> f32 {
+        self.normal.dot(p) + self.d
+    }
+}
+
+/// Six frustum planes: left, right, bottom, top, near, far.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum {
+    pub planes: [Plane; 6],
+}
+
+impl Frustum {
+    /// Extract planes from an OpenGL-style clip matrix (z ∈ [-1,1]), e.g. `Camera::proj_view`.
+    pub fn from_matrix(m: Mat4) -> Self {
+        let (r0, r1, r2, r3) = (m.row(0), m.row(1), m.row(2), m.row(3));
+        Self {
+            planes: [
+                Plane::from_vec4(r3 + r0),
+                Plane::from_vec4(r3 - r0),
+                Plane::from_vec4(r3 + r1),
+                Plane::from_vec4(r3 - r1),
+                Plane::from_vec4(r3 + r2),
+                Plane::from_vec4(r3 - r2),
+            ],
+        }
+    }
+
+    /// `false` if the sphere is completely outside any plane.
+    pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|p| p.signed_distance(center) >= -radius)
+    }
+
+    /// `false` if the box is completely outside any plane (conservative).
+    pub fn intersects_aabb(&self, min: Vec3, max: Vec3) -> bool {
+        self.planes.iter().all(|p| {
+            // "positive vertex": угол бокса дальше всех по нормали плоскости
+            let v = Vec3::new(
+                if p.normal.x >= 0.0 { max.x } else { min.x },
+                if p.normal.y >= 0.0 { max.y } else { min.y },
+                if p.normal.z >= 0.0 { max.z } else { min.z },
+            );
+            p.signed_distance(v) >= 0.0
+        })
+    }
+}
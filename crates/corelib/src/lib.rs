@@ -1,9 +1,12 @@
-//! Core types: math re-exports, Transform, Camera.
+//! Core types: math re-exports, Transform, Camera, Frustum.
 
-pub use glam::{EulerRot, Mat4, Quat, Vec3, vec3};
+pub use glam::{EulerRot, Mat4, Quat, Vec2, Vec3, Vec4, vec3};
 
+pub mod animation;
 pub mod camera;
 pub mod ecs;
+pub mod frustum;
+pub mod particles;
 pub mod transform;
 
 #[cfg(test)]
@@ -36,17 +39,23 @@ mod tests {
 
     #[test]
     fn camera_pv_is_finite() {
-        let cam = camera::Camera::new_perspective(
-            vec3(0.0, 0.0, 4.0),
-            vec3(0.0, 0.0, 0.0),
-            Vec3::Y,
-            60f32.to_radians(),
-            0.1,
-            100.0,
-            16.0 / 9.0,
-        );
+        let cam = camera::Camera {
+            aspect: 16.0 / 9.0,
+            ..camera::Camera::test_default()
+        };
         let pv = cam.proj_view();
         let a = pv.to_cols_array();
         assert!(a.iter().all(|f| f.is_finite()));
     }
+
+    #[test]
+    fn frustum_culls_points_behind_camera() {
+        let cam = camera::Camera::test_default();
+        let f = cam.frustum();
+        assert!(f.intersects_sphere(vec3(0.0, 0.0, 0.0), 0.5));
+        assert!(!f.intersects_sphere(vec3(0.0, 0.0, 10.0), 0.5));
+        assert!(!f.intersects_sphere(vec3(0.0, 0.0, -200.0), 1.0));
+        assert!(!f.intersects_aabb(vec3(50.0, -1.0, -1.0), vec3(52.0, 1.0, 1.0)));
+        assert!(f.intersects_aabb(vec3(-1.0, -1.0, -1.0), vec3(1.0, 1.0, 1.0)));
+    }
 }
@@ -0,0 +1,98 @@
+//! K3: particle emitter component (simulated on the GPU by the renderer).
+
+use crate::{Vec3, Vec4};
+
+/// Where new particles start and which way they fly (emitter local space, +Y = up).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EmitterShape {
+    /// All particles start at the origin, random directions.
+    Point,
+    /// Start inside a sphere, fly outwards.
+    Sphere { radius: f32 },
+    /// Start in a disc of `radius`, fly within `angle` (radians) around +Y.
+    Cone { angle: f32, radius: f32 },
+}
+
+/// How particles are composited.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParticleBlend {
+    /// Order independent, no sorting needed (fire, sparks).
+    #[default]
+    Additive,
+    /// Alpha blended, sorted back-to-front on the GPU every frame (smoke, dust).
+    AlphaSorted,
+}
+
+/// ECS component: continuous particle emitter attached to an entity transform.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParticleEmitter {
+    pub enabled: bool,
+    pub shape: EmitterShape,
+    /// Particles per second.
+    pub rate: f32,
+    /// Lifetime range in seconds.
+    pub lifetime: (f32, f32),
+    /// Initial speed range along the spawn direction.
+    pub speed: (f32, f32),
+    /// World-space acceleration.
+    pub gravity: Vec3,
+    /// Linear drag coefficient (1/s).
+    pub drag: f32,
+    /// Curl noise turbulence strength and spatial frequency.
+    pub curl_strength: f32,
+    pub curl_scale: f32,
+    /// Color at birth and at death (linear RGBA), interpolated over the lifetime.
+    pub color_start: Vec4,
+    pub color_end: Vec4,
+    /// Billboard size in world units at birth and at death.
+    pub size_start: f32,
+    pub size_end: f32,
+    pub blend: ParticleBlend,
+}
+
+impl Default for ParticleEmitter {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            shape: EmitterShape::Cone {
+                angle: 0.35,
+                radius: 0.05,
+            },
+            rate: 200.0,
+            lifetime: (1.5, 2.5),
+            speed: (2.0, 3.0),
+            gravity: Vec3::new(0.0, -2.0, 0.0),
+            drag: 0.3,
+            curl_strength: 0.5,
+            curl_scale: 0.8,
+            color_start: Vec4::new(1.0, 0.7, 0.2, 1.0),
+            color_end: Vec4::new(0.8, 0.1, 0.05, 0.0),
+            size_start: 0.08,
+            size_end: 0.02,
+            blend: ParticleBlend::Additive,
+        }
+    }
+}
+
+impl ParticleEmitter {
+    /// Upper bound of simultaneously alive particles (`rate * max lifetime`).
+    pub fn max_alive(&self) -> u32 {
+        let (min, max) = self.lifetime;
+        (self.rate.max(0.0) * min.max(max).max(0.0)).ceil() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_alive_covers_longest_lifetime() {
+        let e = ParticleEmitter {
+            rate: 100.0,
+            lifetime: (1.0, 2.5),
+            ..Default::default()
+        };
+        assert_eq!(e.max_alive(), 250);
+    }
+}
@@ -0,0 +1,673 @@
+//! J2: egui node editor for material graphs.
+//!
+//! Works on a copy of a graph material's graph. Every edit is pushed to the renderer,
+//! which recompiles it (a broken graph keeps the last good one rendering); Save writes
+//! the last good graph back to its `.matgraph.ron` file. A graph reloaded from disk
+//! replaces the copy.
+
+use asset::material_graph::{GraphOutput, MaterialGraph, Node, NodeId, NodeOp};
+use corelib::ecs::MaterialId;
+use egui::{
+    Align2, Color32, FontId, Painter, Pos2, Rect, Sense, Stroke, Ui, UiBuilder, Vec2, vec2,
+};
+use renderer::GpuState;
+
+const NODE_WIDTH: f32 = 160.0;
+const ROW_HEIGHT: f32 = 20.0;
+const PORT_RADIUS: f32 = 5.0;
+
+/// Input end of a link.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Port {
+    Input(NodeId, usize),
+    Output(GraphOutput),
+}
+
+enum Action {
+    /// Move a node (`None` = the output node) by a screen delta.
+    Move(Option<NodeId>, Vec2),
+    Connect(NodeId, Port),
+    Disconnect(Port),
+    Remove(NodeId),
+}
+
+#[derive(Default)]
+pub struct GraphEditor {
+    pub open: bool,
+    materials: Vec<(MaterialId, String)>,
+    selected: Option<MaterialId>,
+    graph: MaterialGraph,
+    // Renderer's graph when we last looked; anything else means it was reloaded from disk
+    synced: Option<MaterialGraph>,
+    pan: Vec2,
+    // Output port a new link is being dragged from
+    dragging_from: Option<NodeId>,
+    changed: bool,
+    save: bool,
+    unsaved: bool,
+    error: Option<String>,
+}
+
+/// The graph materials the editor works on; implemented by `GpuState`.
+pub trait GraphMaterials {
+    /// Graph materials with their file paths.
+    fn graph_materials(&self) -> Vec<(MaterialId, String)>;
+    fn material_graph(&self, id: MaterialId) -> Option<&MaterialGraph>;
+    /// Rebuild a material from an edited graph; on error the previous graph is kept.
+    fn set_material_graph(&mut self, id: MaterialId, graph: MaterialGraph) -> Result<(), String>;
+    fn save_material_graph(&mut self, id: MaterialId) -> Result<(), String>;
+}
+
+impl GraphMaterials for GpuState {
+    fn graph_materials(&self) -> Vec<(MaterialId, String)> {
+        GpuState::graph_materials(self)
+    }
+
+    fn material_graph(&self, id: MaterialId) -> Option<&MaterialGraph> {
+        GpuState::material_graph(self, id)
+    }
+
+    fn set_material_graph(&mut self, id: MaterialId, graph: MaterialGraph) -> Result<(), String> {
+        GpuState::set_material_graph(self, id, graph)
+    }
+
+    fn save_material_graph(&mut self, id: MaterialId) -> Result<(), String> {
+        GpuState::save_material_graph(self, id)
+    }
+}
+
+impl GraphEditor {
+    /// Pick up the graph materials and the selected graph (before drawing the UI).
+    pub fn sync(&mut self, gpu: &impl GraphMaterials) {
+        if !self.open {
+            return;
+        }
+        self.materials = gpu.graph_materials();
+        if self
+            .selected
+            .is_none_or(|id| !self.materials.iter().any(|(m, _)| *m == id))
+        {
+            self.selected = self.materials.first().map(|(id, _)| *id);
+            self.synced = None;
+        }
+        let current = self.selected.and_then(|id| gpu.material_graph(id));
+        if current != self.synced.as_ref() {
+            self.graph = current.cloned().unwrap_or_default();
+            self.synced = current.cloned();
+            self.dragging_from = None;
+            self.unsaved = false;
+            self.error = None;
+        }
+    }
+
+    /// Push edits made in the UI to the renderer (after drawing the UI).
+    pub fn apply(&mut self, gpu: &mut impl GraphMaterials) {
+        let Some(id) = self.selected else {
+            return;
+        };
+        if std::mem::take(&mut self.changed) {
+            self.error = gpu.set_material_graph(id, self.graph.clone()).err();
+            self.synced = gpu.material_graph(id).cloned();
+            self.unsaved = true;
+        }
+        if std::mem::take(&mut self.save) {
+            match gpu.save_material_graph(id) {
+                Ok(()) => self.unsaved = false,
+                Err(e) => self.error = Some(e),
+            }
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::Context) {
+        let mut open = self.open;
+        egui::Window::new("Material Graph")
+            .open(&mut open)
+            .default_size([820.0, 440.0])
+            .resizable(true)
+            .show(ctx, |ui| {
+                self.toolbar(ui);
+                ui.separator();
+                if self.selected.is_some() {
+                    self.canvas(ui);
+                } else {
+                    ui.label("No graph materials loaded");
+                }
+            });
+        self.open = open;
+    }
+
+    fn toolbar(&mut self, ui: &mut Ui) {
+        ui.horizontal(|ui| {
+            let current = self
+                .materials
+                .iter()
+                .find(|(id, _)| Some(*id) == self.selected)
+                .map_or("-", |(_, path)| path.as_str());
+            let mut picked = None;
+            egui::ComboBox::from_id_salt("graph_material")
+                .selected_text(current)
+                .show_ui(ui, |ui| {
+                    for (id, path) in &self.materials {
+                        if ui
+                            .selectable_label(Some(*id) == self.selected, path)
+                            .clicked()
+                        {
+                            picked = Some(*id);
+                        }
+                    }
+                });
+            if let Some(id) = picked.filter(|id| Some(*id) != self.selected) {
+                self.selected = Some(id);
+                self.synced = None;
+            }
+
+            ui.menu_button("Add node", |ui| {
+                for op in NodeOp::palette() {
+                    if ui.button(op.name()).clicked() {
+                        // Near the top-left corner of the visible canvas
+                        let pos = vec2(40.0, 40.0) - self.pan;
+                        self.graph.add_node(op, [pos.x, pos.y]);
+                        self.changed = true;
+                        ui.close_menu();
+                    }
+                }
+            });
+            let save = ui
+                .add_enabled(self.unsaved, egui::Button::new("Save"))
+                .on_hover_text("Write the last graph that compiled to its file");
+            if save.clicked() {
+                self.save = true;
+            }
+            ui.label(
+                "Drag outputs onto inputs; click an input to unlink; right-click a title to delete",
+            );
+        });
+        if let Some(err) = &self.error {
+            ui.colored_label(Color32::LIGHT_RED, err);
+        }
+    }
+
+    fn canvas(&mut self, ui: &mut Ui) {
+        let (response, painter) = ui.allocate_painter(ui.available_size(), Sense::click_and_drag());
+        let canvas = response.rect;
+        painter.rect_filled(canvas, 0.0, Color32::from_gray(24));
+        if response.dragged() {
+            self.pan += response.drag_delta();
+        }
+        let origin = canvas.min + self.pan;
+        let rects: Vec<Rect> = self
+            .graph
+            .nodes
+            .iter()
+            .map(|n| node_rect(n, origin))
+            .collect();
+        let output_rect = Rect::from_min_size(
+            origin + Vec2::from(self.graph.output_pos),
+            vec2(
+                NODE_WIDTH * 0.75,
+                ROW_HEIGHT * (1 + GraphOutput::ALL.len()) as f32,
+            ),
+        );
+        let output_port = |rect: &Rect| rect.right_top() + vec2(0.0, ROW_HEIGHT * 0.5);
+
+        // Links under the nodes
+        let link_color = Color32::from_rgb(150, 180, 230);
+        for (i, node) in self.graph.nodes.iter().enumerate() {
+            for (port, link) in node.inputs.iter().enumerate() {
+                if let Some(src) = link.filter(|&src| src < rects.len()) {
+                    let to = input_port(rects[i], param_rows(&node.op), port);
+                    draw_link(&painter, output_port(&rects[src]), to, link_color);
+                }
+            }
+        }
+        for (row, output) in GraphOutput::ALL.into_iter().enumerate() {
+            if let Some(src) = self.graph.output(output).filter(|&src| src < rects.len()) {
+                let to = input_port(output_rect, 0, row);
+                draw_link(&painter, output_port(&rects[src]), to, link_color);
+            }
+        }
+
+        let mut actions = Vec::new();
+        let mut targets: Vec<(Pos2, Port)> = Vec::new();
+        let id = ui.id().with("material_graph");
+
+        for (i, node) in self.graph.nodes.iter_mut().enumerate() {
+            let rect = rects[i];
+            let rows = param_rows(&node.op);
+            let title = format!("{i}: {}", node.op.name());
+            let header = draw_frame(&painter, rect, &title);
+            let header_response = ui.interact(
+                header.intersect(canvas),
+                id.with(("node", i)),
+                Sense::click_and_drag(),
+            );
+            if header_response.dragged() {
+                actions.push(Action::Move(Some(i), header_response.drag_delta()));
+            }
+            header_response.context_menu(|ui| {
+                if ui.button("Delete").clicked() {
+                    actions.push(Action::Remove(i));
+                    ui.close_menu();
+                }
+            });
+
+            if rows > 0 {
+                let params = Rect::from_min_size(
+                    rect.min + vec2(6.0, ROW_HEIGHT),
+                    vec2(rect.width() - 12.0, ROW_HEIGHT * rows as f32),
+                );
+                let mut child = ui.new_child(UiBuilder::new().max_rect(params));
+                child.set_clip_rect(params.intersect(canvas));
+                self.changed |= param_widgets(&mut child, &mut node.op);
+            }
+
+            for (port, name) in node.op.inputs().iter().enumerate() {
+                let pos = input_port(rect, rows, port);
+                let linked = node.inputs[port].is_some();
+                if port_widget(
+                    ui,
+                    &painter,
+                    canvas,
+                    id.with(("in", i, port)),
+                    pos,
+                    name,
+                    linked,
+                ) {
+                    actions.push(Action::Disconnect(Port::Input(i, port)));
+                }
+                targets.push((pos, Port::Input(i, port)));
+            }
+
+            let out = output_port(&rect);
+            painter.circle_filled(out, PORT_RADIUS, link_color);
+            let out_rect =
+                Rect::from_center_size(out, Vec2::splat(PORT_RADIUS * 3.0)).intersect(canvas);
+            if ui
+                .interact(out_rect, id.with(("out", i)), Sense::drag())
+                .drag_started()
+            {
+                self.dragging_from = Some(i);
+            }
+        }
+
+        // Output node: one input per surface field
+        let header = draw_frame(&painter, output_rect, "Output");
+        let header_response =
+            ui.interact(header.intersect(canvas), id.with("output"), Sense::drag());
+        if header_response.dragged() {
+            actions.push(Action::Move(None, header_response.drag_delta()));
+        }
+        for (row, output) in GraphOutput::ALL.into_iter().enumerate() {
+            let pos = input_port(output_rect, 0, row);
+            let linked = self.graph.output(output).is_some();
+            if port_widget(
+                ui,
+                &painter,
+                canvas,
+                id.with(("output", row)),
+                pos,
+                output.name(),
+                linked,
+            ) {
+                actions.push(Action::Disconnect(Port::Output(output)));
+            }
+            targets.push((pos, Port::Output(output)));
+        }
+
+        // Link being dragged: connect to the input port it is dropped on
+        if let Some(src) = self.dragging_from.filter(|&src| src < rects.len()) {
+            let pointer = ui.input(|i| i.pointer.interact_pos());
+            if let Some(pointer) = pointer {
+                draw_link(&painter, output_port(&rects[src]), pointer, Color32::YELLOW);
+            }
+            if ui.input(|i| i.pointer.any_released()) {
+                self.dragging_from = None;
+                let target = pointer.and_then(|p| {
+                    targets
+                        .iter()
+                        .find(|(pos, _)| pos.distance(p) <= PORT_RADIUS * 2.0)
+                        .map(|(_, port)| *port)
+                });
+                if let Some(port) = target {
+                    actions.push(Action::Connect(src, port));
+                }
+            }
+        }
+
+        for action in actions {
+            self.apply_action(action);
+        }
+    }
+
+    fn apply_action(&mut self, action: Action) {
+        let graph = &mut self.graph;
+        match action {
+            Action::Move(Some(node), delta) => {
+                let pos = &mut graph.nodes[node].pos;
+                *pos = [pos[0] + delta.x, pos[1] + delta.y];
+            }
+            Action::Move(None, delta) => {
+                let pos = &mut graph.output_pos;
+                *pos = [pos[0] + delta.x, pos[1] + delta.y];
+            }
+            Action::Connect(src, Port::Input(node, port)) => {
+                if src == node || graph.depends_on(src, node) {
+                    self.error = Some(format!(
+                        "linking node {src} into node {node} would create a cycle"
+                    ));
+                    return;
+                }
+                graph.nodes[node].inputs[port] = Some(src);
+            }
+            Action::Connect(src, Port::Output(output)) => graph.set_output(output, Some(src)),
+            Action::Disconnect(Port::Input(node, port)) => graph.nodes[node].inputs[port] = None,
+            Action::Disconnect(Port::Output(output)) => graph.set_output(output, None),
+            Action::Remove(node) => {
+                graph.remove_node(node);
+                self.dragging_from = None;
+            }
+        }
+        self.changed = true;
+    }
+}
+
+// Rows of parameter widgets between the title and the input ports.
+fn param_rows(op: &NodeOp) -> usize {
+    match op {
+        NodeOp::Float(_) | NodeOp::Color(_) | NodeOp::Fresnel { .. } | NodeOp::Swizzle(_) => 1,
+        NodeOp::UvTransform { .. } => 3,
+        _ => 0,
+    }
+}
+
+fn node_rect(node: &Node, origin: Pos2) -> Rect {
+    let rows = 1 + param_rows(&node.op) + node.inputs.len();
+    Rect::from_min_size(
+        origin + Vec2::from(node.pos),
+        vec2(NODE_WIDTH, ROW_HEIGHT * rows as f32),
+    )
+}
+
+fn input_port(rect: Rect, param_rows: usize, port: usize) -> Pos2 {
+    rect.left_top() + vec2(0.0, ROW_HEIGHT * ((1 + param_rows + port) as f32 + 0.5))
+}
+
+// Node background and title bar; returns the title bar rect.
+fn draw_frame(painter: &Painter, rect: Rect, title: &str) -> Rect {
+    painter.rect(
+        rect,
+        4.0,
+        Color32::from_gray(48),
+        Stroke::new(1.0, Color32::from_gray(90)),
+    );
+    let header = Rect::from_min_size(rect.min, vec2(rect.width(), ROW_HEIGHT));
+    painter.rect_filled(header, 4.0, Color32::from_rgb(58, 68, 100));
+    painter.text(
+        header.left_center() + vec2(6.0, 0.0),
+        Align2::LEFT_CENTER,
+        title,
+        FontId::proportional(13.0),
+        Color32::WHITE,
+    );
+    header
+}
+
+// Input port with its label; true when clicked while linked (= unlink).
+fn port_widget(
+    ui: &Ui,
+    painter: &Painter,
+    canvas: Rect,
+    id: egui::Id,
+    pos: Pos2,
+    name: &str,
+    linked: bool,
+) -> bool {
+    let color = if linked {
+        Color32::from_rgb(150, 180, 230)
+    } else {
+        Color32::from_gray(110)
+    };
+    painter.circle_filled(pos, PORT_RADIUS, color);
+    painter.text(
+        pos + vec2(PORT_RADIUS + 4.0, 0.0),
+        Align2::LEFT_CENTER,
+        name,
+        FontId::proportional(12.0),
+        Color32::LIGHT_GRAY,
+    );
+    let rect = Rect::from_center_size(pos, Vec2::splat(PORT_RADIUS * 3.0)).intersect(canvas);
+    linked && ui.interact(rect, id, Sense::click()).clicked()
+}
+
+fn draw_link(painter: &Painter, from: Pos2, to: Pos2, color: Color32) {
+    let dx = ((to.x - from.x).abs() * 0.5).max(30.0);
+    painter.add(egui::epaint::CubicBezierShape::from_points_stroke(
+        [from, from + vec2(dx, 0.0), to - vec2(dx, 0.0), to],
+        false,
+        Color32::TRANSPARENT,
+        Stroke::new(2.0, color),
+    ));
+}
+
+// Parameter editors; true if a value changed.
+fn param_widgets(ui: &mut Ui, op: &mut NodeOp) -> bool {
+    let drag =
+        |ui: &mut Ui, value: &mut f32| ui.add(egui::DragValue::new(value).speed(0.01)).changed();
+    let mut changed = false;
+    match op {
+        NodeOp::Float(v) => changed |= drag(ui, v),
+        NodeOp::Color(c) => changed |= ui.color_edit_button_rgba_unmultiplied(c).changed(),
+        NodeOp::UvTransform {
+            scale,
+            offset,
+            rotation,
+        } => {
+            ui.horizontal(|ui| {
+                ui.label("scale");
+                changed |= drag(ui, &mut scale[0]) | drag(ui, &mut scale[1]);
+            });
+            ui.horizontal(|ui| {
+                ui.label("offset");
+                changed |= drag(ui, &mut offset[0]) | drag(ui, &mut offset[1]);
+            });
+            ui.horizontal(|ui| {
+                ui.label("rotation");
+                changed |= ui.drag_angle(rotation).changed();
+            });
+        }
+        NodeOp::Fresnel { power } => {
+            ui.horizontal(|ui| {
+                ui.label("power");
+                changed |= drag(ui, power);
+            });
+        }
+        NodeOp::Swizzle(mask) => {
+            changed |= ui
+                .add(egui::TextEdit::singleline(mask).desired_width(60.0))
+                .changed();
+        }
+        _ => {}
+    }
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use renderer::material_graph::compile_graph;
+
+    const ID: MaterialId = MaterialId(3);
+
+    // Stands in for `GpuState`: compiles every edit and keeps the last good graph.
+    struct Materials {
+        graph: MaterialGraph,
+        saved: Option<MaterialGraph>,
+        compiled: Vec<String>,
+    }
+
+    impl Materials {
+        fn new(graph: MaterialGraph) -> Self {
+            compile_graph(&graph).expect("fixture graph compiles");
+            Self {
+                graph,
+                saved: None,
+                compiled: Vec::new(),
+            }
+        }
+    }
+
+    impl GraphMaterials for Materials {
+        fn graph_materials(&self) -> Vec<(MaterialId, String)> {
+            vec![(ID, "materials/test.matgraph.ron".to_string())]
+        }
+
+        fn material_graph(&self, id: MaterialId) -> Option<&MaterialGraph> {
+            (id == ID).then_some(&self.graph)
+        }
+
+        fn set_material_graph(
+            &mut self,
+            id: MaterialId,
+            graph: MaterialGraph,
+        ) -> Result<(), String> {
+            assert_eq!(id, ID);
+            self.compiled
+                .push(compile_graph(&graph).map_err(|e| e.to_string())?);
+            self.graph = graph;
+            Ok(())
+        }
+
+        fn save_material_graph(&mut self, id: MaterialId) -> Result<(), String> {
+            assert_eq!(id, ID);
+            self.saved = Some(self.graph.clone());
+            Ok(())
+        }
+    }
+
+    // Color -> base color
+    fn fixture() -> MaterialGraph {
+        let mut graph = MaterialGraph::default();
+        let color = graph.add_node(NodeOp::Color([1.0, 0.5, 0.25, 1.0]), [0.0, 0.0]);
+        graph.set_output(GraphOutput::BaseColor, Some(color));
+        graph
+    }
+
+    fn open_editor(gpu: &Materials) -> GraphEditor {
+        let mut editor = GraphEditor {
+            open: true,
+            ..Default::default()
+        };
+        editor.sync(gpu);
+        editor
+    }
+
+    #[test]
+    fn edits_are_compiled_applied_and_saved() {
+        let mut gpu = Materials::new(fixture());
+        let mut editor = open_editor(&gpu);
+        assert_eq!(editor.selected, Some(ID));
+        assert_eq!(editor.graph, gpu.graph);
+
+        // Color * Float -> roughness
+        let float = editor.graph.add_node(NodeOp::Float(0.5), [0.0, 100.0]);
+        let mul = editor.graph.add_node(NodeOp::Multiply, [200.0, 0.0]);
+        editor.apply_action(Action::Connect(0, Port::Input(mul, 0)));
+        editor.apply_action(Action::Connect(float, Port::Input(mul, 1)));
+        editor.apply_action(Action::Connect(mul, Port::Output(GraphOutput::Roughness)));
+        editor.apply(&mut gpu);
+
+        assert_eq!(editor.error, None);
+        assert!(editor.unsaved);
+        assert_eq!(gpu.graph, editor.graph);
+        assert_eq!(gpu.graph.output(GraphOutput::Roughness), Some(mul));
+        assert_eq!(
+            gpu.compiled.last(),
+            Some(&compile_graph(&editor.graph).unwrap())
+        );
+        assert_eq!(gpu.saved, None);
+
+        // The next frame sees its own edit, not a reload
+        let edited = editor.graph.clone();
+        editor.sync(&gpu);
+        assert_eq!(editor.graph, edited);
+        assert!(editor.unsaved);
+
+        editor.save = true;
+        editor.apply(&mut gpu);
+        assert!(!editor.unsaved);
+        assert_eq!(gpu.saved, Some(edited));
+    }
+
+    #[test]
+    fn broken_edits_keep_the_last_good_graph() {
+        let mut gpu = Materials::new(fixture());
+        let mut editor = open_editor(&gpu);
+        let good = gpu.graph.clone();
+
+        let uv = editor.graph.add_node(NodeOp::Uv, [0.0, 0.0]);
+        let swizzle = editor
+            .graph
+            .add_node(NodeOp::Swizzle("xyz".to_string()), [0.0, 0.0]);
+        editor.apply_action(Action::Connect(uv, Port::Input(swizzle, 0)));
+        editor.apply_action(Action::Connect(
+            swizzle,
+            Port::Output(GraphOutput::Emissive),
+        ));
+        editor.apply(&mut gpu);
+
+        assert!(
+            editor
+                .error
+                .as_deref()
+                .is_some_and(|e| e.contains("swizzle"))
+        );
+        assert_eq!(gpu.graph, good);
+        // The broken copy stays in the editor to be fixed
+        editor.sync(&gpu);
+        assert_eq!(editor.graph.nodes.len(), 3);
+
+        editor.graph.nodes[swizzle].op = NodeOp::Swizzle("yx".to_string());
+        editor.changed = true;
+        editor.apply(&mut gpu);
+        assert_eq!(editor.error, None);
+        assert_eq!(gpu.graph, editor.graph);
+    }
+
+    #[test]
+    fn cycles_and_removals() {
+        let mut gpu = Materials::new(fixture());
+        let mut editor = open_editor(&gpu);
+        let a = editor.graph.add_node(NodeOp::OneMinus, [0.0, 0.0]);
+        let b = editor.graph.add_node(NodeOp::Saturate, [0.0, 0.0]);
+        editor.apply_action(Action::Connect(a, Port::Input(b, 0)));
+        editor.apply(&mut gpu);
+
+        editor.apply_action(Action::Connect(b, Port::Input(a, 0)));
+        assert!(editor.error.as_deref().is_some_and(|e| e.contains("cycle")));
+        assert!(!editor.changed);
+        assert_eq!(editor.graph.nodes[a].inputs[0], None);
+
+        // Removing the color node cuts the output link and shifts the others down
+        editor.apply_action(Action::Remove(0));
+        editor.apply(&mut gpu);
+        assert_eq!(gpu.graph.output(GraphOutput::BaseColor), None);
+        assert_eq!(gpu.graph.nodes[b - 1].inputs[0], Some(a - 1));
+    }
+
+    #[test]
+    fn reloads_replace_the_copy() {
+        let mut gpu = Materials::new(fixture());
+        let mut editor = open_editor(&gpu);
+        editor.apply_action(Action::Move(Some(0), vec2(10.0, 0.0)));
+        editor.apply(&mut gpu);
+        assert!(editor.unsaved);
+
+        // Changed on disk and hot-reloaded
+        let mut reloaded = fixture();
+        reloaded.set_output(GraphOutput::Alpha, Some(0));
+        gpu.graph = reloaded.clone();
+        editor.sync(&gpu);
+        assert_eq!(editor.graph, reloaded);
+        assert!(!editor.unsaved);
+    }
+}
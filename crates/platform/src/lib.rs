@@ -1,9 +1,19 @@
 //! Platform layer: window & event loop (winit 0.30.12).
 //! Step B1 integration: create WGPU surface and clear screen.
 
+mod graph_editor;
+
 use anyhow::Result;
-use std::{env, path::PathBuf, sync::Arc, time::Instant};
-use wgpu;
+use egui_wgpu::{Renderer as EguiRenderer, ScreenDescriptor};
+use egui_winit::State as EguiWinitState;
+use std::{
+    env,
+    fs::File,
+    io::{BufWriter, Write},
+    path::PathBuf,
+    sync::Arc,
+    time::Instant,
+};
 use winit::{
     application::ApplicationHandler,
     dpi::{LogicalSize, PhysicalSize},
@@ -11,39 +21,123 @@ use winit::{
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
     window::{Window, WindowId},
 };
-use egui_winit::State as EguiWinitState;
-use egui_wgpu::Renderer as EguiRenderer;
 
-use asset::{obj, texture::TextureData};
+use asset::{gltf, lod, mesh_bin, process, texture::TextureData};
 use corelib::{
+    Vec4,
+    animation::Animator,
     camera::Camera,
-    ecs::{MaterialId, MeshId, Renderable, World},
+    ecs::{Entity, LodGroupId, MaterialId, Renderable, SkinId, TextureId, World},
+    particles::{EmitterShape, ParticleBlend, ParticleEmitter},
     transform::Transform,
     vec3,
 };
-use renderer::{DrawInstance, LightingUniform, MaterialUniform};
+use renderer::{
+    DrawInstance, LightingUniform, MaterialUniform,
+    lod::LodSettings,
+    overlay::Overlay,
+    profiler::{PassTiming, ProfilerMode},
+    retained::instance_slot,
+    stats::{RenderStats, StatsHistory},
+};
+
+use crate::graph_editor::GraphEditor;
+
+/// Window and renderer settings parsed from the command line.
+#[derive(Clone, Debug)]
+pub struct RunOptions {
+    pub show_fps: bool,
+    /// H4: per-pass GPU timings.
+    pub profile: bool,
+    /// L2: compute culling + indirect draws.
+    pub gpu_culling: bool,
+    /// L1: Hi-Z occlusion culling.
+    pub occlusion_culling: bool,
+    /// L1: show this Hi-Z mip in the screen corner.
+    pub hiz_debug_mip: Option<u32>,
+    /// Per-frame [`RenderStats`] CSV dump.
+    pub stats_csv: Option<PathBuf>,
+    /// L2: persistent instance buffers, only changed entities are re-uploaded.
+    pub retained: bool,
+    /// G1: threads for draw list building and preparation (1 = single-threaded).
+    pub draw_threads: usize,
+    /// K3: spawn demo particle emitters.
+    pub particles: bool,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        Self {
+            show_fps: false,
+            profile: false,
+            gpu_culling: false,
+            occlusion_culling: false,
+            hiz_debug_mip: None,
+            stats_csv: None,
+            retained: false,
+            draw_threads: 1,
+            particles: false,
+            width: 1280,
+            height: 720,
+        }
+    }
+}
+
+/// L1: Suzanne LOD chain length (authored `_LOD<n>` files first, rest generated).
+const SUZANNE_LOD_LEVELS: usize = 4;
 
 /// Public entry: runs a window + renderer. Returns on close.
-pub fn run_with_renderer(
-    backends: wgpu::Backends,
-    show_fps: bool,
-    width: u32,
-    height: u32,
-) -> Result<()> {
+pub fn run_with_renderer(backends: wgpu::Backends, options: RunOptions) -> Result<()> {
+    let RunOptions {
+        show_fps,
+        profile,
+        gpu_culling,
+        occlusion_culling,
+        hiz_debug_mip,
+        stats_csv,
+        retained,
+        draw_threads,
+        particles,
+        width,
+        height,
+    } = options;
+
     log::info!(
         "Env: DISPLAY={:?}, WAYLAND_DISPLAY={:?}",
         env::var("DISPLAY").ok(),
         env::var("WAYLAND_DISPLAY").ok()
     );
 
+    // Frame stats CSV is streamed row by row while the app runs.
+    let stats_csv = match stats_csv {
+        Some(path) => {
+            let mut w = BufWriter::new(File::create(&path)?);
+            writeln!(w, "{}", RenderStats::CSV_HEADER)?;
+            log::info!("Writing frame stats CSV to {}", path.display());
+            Some(w)
+        }
+        None => None,
+    };
+
     let event_loop: EventLoop<()> = EventLoop::new().expect("Failed to create event loop");
     let mut app = App {
         backends,
         show_fps,
+        profile,
+        gpu_culling,
+        occlusion_culling,
+        hiz_debug_mip,
+        stats_csv,
+        retained,
+        draw_threads,
+        particles,
         width,
         height,
         egui_state: None,
         egui_renderer: None,
+        egui_frame: None,
         ..Default::default()
     };
     event_loop
@@ -60,6 +154,13 @@ struct App {
     // Config
     backends: wgpu::Backends,
     show_fps: bool,
+    profile: bool,
+    gpu_culling: bool,
+    occlusion_culling: bool,
+    hiz_debug_mip: Option<u32>,
+    retained: bool,
+    draw_threads: usize,
+    particles: bool,
     width: u32,
     height: u32,
 
@@ -67,6 +168,14 @@ struct App {
     frames: u32,
     last_fps_instant: Option<Instant>,
 
+    // H4: periodic profiler log dump
+    last_profile_dump: Option<Instant>,
+
+    // Frame statistics (graph history + optional CSV dump)
+    stats_history: StatsHistory,
+    stats_csv: Option<BufWriter<File>>,
+    stats_frame: u64,
+
     // Animation
     last_time: Option<Instant>,
 
@@ -76,17 +185,34 @@ struct App {
 
     // Reusable per-frame draw list to avoid allocs
     draw_list: Vec<DrawInstance>,
+    // L2: reusable list of entities changed since the last frame
+    dirty_entities: Vec<Entity>,
+    // L1: camera and settings the retained LODs were last resolved against
+    retained_lod_view: Option<(Camera, LodSettings)>,
+    // G1: per-thread draw lists, concatenated into `draw_list`
+    chunk_draw_lists: Vec<Vec<DrawInstance>>,
 
     // Window state
     is_minimized: bool,
 
-    // Mesh handles
-    cube_mesh: MeshId,
-    suzanne_mesh: MeshId,
+    // L1: LOD group handles
+    cube_lods: LodGroupId,
+    suzanne_lods: LodGroupId,
+
+    // E2: texture of renderables without their own (default.png or the built-in checkerboard)
+    texture: TextureId,
+
+    // K3: entities carrying particle emitters (editable in the inspector)
+    emitter_entities: Vec<Entity>,
+
+    // J2: material graph editor window
+    graph_editor: GraphEditor,
 
     // egui state
     egui_state: Option<EguiWinitState>,
     egui_renderer: Option<EguiRenderer>,
+    // I1: this frame's tessellated UI, painted by the renderer after the 3D pass
+    egui_frame: Option<EguiFrame>,
 }
 
 impl ApplicationHandler for App {
@@ -126,36 +252,66 @@ impl ApplicationHandler for App {
         );
         self.camera = Some(camera);
         gpu.set_camera(&camera);
+        gpu.set_profiling_enabled(self.profile);
+        if self.gpu_culling && !gpu.set_gpu_culling(true) {
+            log::warn!("GPU culling requested but not supported; using CPU culling");
+        }
+        if self.occlusion_culling && !gpu.set_occlusion_culling(true) {
+            log::warn!("Occlusion culling requested but Hi-Z is not supported on this device");
+        }
+        gpu.set_hiz_debug_view(self.hiz_debug_mip);
+        gpu.set_draw_prep_threads(self.draw_threads);
 
-        // Mesh handles
-        let cube_mesh = gpu.cube_mesh_id();
-        let suzanne_mesh = {
+        // J2: shaders from assets/shaders (embedded copies if missing), watched for edits
+        let asset_root = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join("..")
+            .join("assets");
+        let shader_dir = asset_root.join("shaders");
+        gpu.set_shader_dir(shader_dir.is_dir().then_some(shader_dir));
+        gpu.set_asset_root(asset_root);
+
+        // LOD group handles (the cube has a single level)
+        let cube_lods = gpu.create_lod_group(&[gpu.cube_mesh_id()], None);
+        let suzanne_lods = {
             let asset_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
                 .join("..")
                 .join("..")
                 .join("assets")
                 .join("models")
                 .join("suzanne.obj");
-            match obj::load_obj_from_path(&asset_path) {
-                Ok(mesh) => {
-                    log::info!(
-                        "Loaded Suzanne OBJ ({} vertices, {} indices)",
-                        mesh.vertices.len(),
-                        mesh.indices.len()
-                    );
-                    gpu.upload_mesh("Suzanne", &mesh)
-                }
+            match load_cached_lods(&mut gpu, "Suzanne", &asset_path, SUZANNE_LOD_LEVELS) {
+                Ok(group) => group,
                 Err(err) => {
                     log::error!(
                         "Failed to load Suzanne OBJ from {}: {err:?}",
                         asset_path.display()
                     );
-                    cube_mesh
+                    cube_lods
                 }
             }
         };
-        self.cube_mesh = cube_mesh;
-        self.suzanne_mesh = suzanne_mesh;
+        self.cube_lods = cube_lods;
+        self.suzanne_lods = suzanne_lods;
+
+        // J1: skinned glTF arm (bends and sways, clips blended by the animator)
+        let arm_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("..")
+            .join("..")
+            .join("assets")
+            .join("models")
+            .join("gltf")
+            .join("arm.glb");
+        let arm = match load_skinned(&mut gpu, &arm_path) {
+            Ok(arm) => Some(arm),
+            Err(err) => {
+                log::warn!(
+                    "Failed to load skinned mesh {}: {err:#}",
+                    arm_path.display()
+                );
+                None
+            }
+        };
 
         // Initialize egui
         let egui_context = egui::Context::default();
@@ -167,13 +323,7 @@ impl ApplicationHandler for App {
             None,
             Some(2048),
         );
-        let egui_renderer = EguiRenderer::new(
-            gpu.device(),
-            gpu.surface_format(),
-            None,
-            1,
-            false,
-        );
+        let egui_renderer = EguiRenderer::new(gpu.device(), gpu.surface_format(), None, 1, false);
         self.egui_state = Some(egui_state);
         self.egui_renderer = Some(egui_renderer);
 
@@ -181,7 +331,7 @@ impl ApplicationHandler for App {
         gpu.setup_framegraph_example();
 
         // Load texture
-        let _default_texture = {
+        self.texture = {
             let texture_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
                 .join("..")
                 .join("..")
@@ -209,6 +359,16 @@ impl ApplicationHandler for App {
         let origin_offset_x = (grid_x as f32 - 1.0) * spacing * 0.5;
         let origin_offset_y = (grid_y as f32 - 1.0) * spacing * 0.5;
         let default_material = MaterialId::new(0);
+        // J2: material files (hot-reloaded); the default material if one fails to load
+        let mut load_material = |path: &str| {
+            gpu.load_material(path).unwrap_or_else(|e| {
+                log::warn!("Failed to load material {path}: {e}; using the default material");
+                default_material
+            })
+        };
+        let gold_material = load_material("materials/gold.material.ron");
+        let glass_material = load_material("materials/glass.material.json");
+        let rim_material = load_material("materials/rim.material.ron");
 
         for gy in 0..grid_y {
             for gx in 0..grid_x {
@@ -216,7 +376,12 @@ impl ApplicationHandler for App {
                 let z = gy as f32 * spacing - origin_offset_y;
                 let t =
                     Transform::from_trs(vec3(x, 0.0, z), vec3(0.0, 0.0, 0.0), vec3(0.9, 0.9, 0.9));
-                let r = Renderable::new(cube_mesh, default_material);
+                let material = match (gx + gy * grid_x) % 7 {
+                    3 => glass_material,
+                    5 => rim_material,
+                    _ => default_material,
+                };
+                let r = Renderable::new(cube_lods, material);
                 let _ = self.world.spawn(t, Some(r));
             }
         }
@@ -226,11 +391,76 @@ impl ApplicationHandler for App {
             vec3(0.0, 0.0, 0.0),
             vec3(1.6, 1.6, 1.6),
         );
-        let suzanne_renderable = Renderable::new(suzanne_mesh, default_material);
+        let suzanne_renderable = Renderable::new(suzanne_lods, gold_material);
         let _ = self
             .world
             .spawn(suzanne_transform, Some(suzanne_renderable));
 
+        if let Some((skin, texture)) = arm
+            && let Some(skinned) = gpu.skinned_mesh(skin)
+        {
+            let lods = gpu.create_lod_group(&[skinned.mesh()], None);
+            let t = Transform::from_trs(
+                vec3(-3.0, 1.0, 0.0),
+                vec3(0.0, 0.0, 0.0),
+                vec3(1.0, 1.0, 1.0),
+            );
+            let mut renderable = Renderable::new(lods, gold_material);
+            if let Some(texture) = texture {
+                renderable = renderable.with_texture(texture);
+            }
+            let entity = self.world.spawn(t, Some(renderable));
+            let mut animator = Animator::new(skin);
+            animator.play(0);
+            animator.set_weight(1, 0.5);
+            if !self.world.set_animator(entity, Some(animator)) {
+                log::warn!("Skin {skin:?} is already animated by another entity");
+            }
+        }
+
+        // K3: demo emitters — additive sparks above Suzanne, sorted smoke next to it
+        self.emitter_entities.clear();
+        if self.particles {
+            let sparks = self.world.spawn(
+                Transform::from_trs(
+                    vec3(0.0, 3.0, 0.0),
+                    vec3(0.0, 0.0, 0.0),
+                    vec3(1.0, 1.0, 1.0),
+                ),
+                None,
+            );
+            self.world
+                .set_emitter(sparks, Some(ParticleEmitter::default()));
+            let smoke = self.world.spawn(
+                Transform::from_trs(
+                    vec3(4.0, 0.5, 0.0),
+                    vec3(0.0, 0.0, 0.0),
+                    vec3(1.0, 1.0, 1.0),
+                ),
+                None,
+            );
+            self.world.set_emitter(
+                smoke,
+                Some(ParticleEmitter {
+                    shape: EmitterShape::Sphere { radius: 0.3 },
+                    rate: 60.0,
+                    lifetime: (3.0, 4.0),
+                    speed: (0.2, 0.5),
+                    gravity: vec3(0.0, 0.4, 0.0),
+                    drag: 0.5,
+                    curl_strength: 1.0,
+                    curl_scale: 0.5,
+                    color_start: Vec4::new(0.5, 0.5, 0.55, 0.6),
+                    color_end: Vec4::new(0.3, 0.3, 0.3, 0.0),
+                    size_start: 0.3,
+                    size_end: 1.0,
+                    blend: ParticleBlend::AlphaSorted,
+                    ..Default::default()
+                }),
+            );
+            self.emitter_entities.extend([sparks, smoke]);
+        }
+
         // Control flow + first frame
         event_loop.set_control_flow(ControlFlow::Wait);
         window.request_redraw();
@@ -240,6 +470,7 @@ impl ApplicationHandler for App {
         self.frames = 0;
         self.last_fps_instant = Some(Instant::now());
         self.last_time = Some(Instant::now());
+        self.last_profile_dump = Some(Instant::now());
         self.draw_list = Vec::with_capacity((grid_x * grid_y + 1) as usize);
         self.is_minimized = false;
     }
@@ -258,6 +489,11 @@ impl ApplicationHandler for App {
         match event {
             WindowEvent::CloseRequested => {
                 log::info!("Close requested. Exiting.");
+                if let Some(mut w) = self.stats_csv.take()
+                    && let Err(e) = w.flush()
+                {
+                    log::warn!("Failed to flush stats CSV: {e}");
+                }
                 // Сначала корректно освобождаем все GPU-ресурсы (Surface/Device/Queue).
                 let _ = self.gpu.take(); // drop happens here
                 // Затем окно (опционально, но порядок уже безопасный).
@@ -265,8 +501,8 @@ impl ApplicationHandler for App {
                 event_loop.exit();
             }
             WindowEvent::Resized(new_size) => {
-                let w = new_size.width.max(0);
-                let h = new_size.height.max(0);
+                let w = new_size.width;
+                let h = new_size.height;
                 log::info!("Resized: {}x{}", w, h);
 
                 self.is_minimized = w == 0 || h == 0;
@@ -321,11 +557,7 @@ impl ApplicationHandler for App {
                     // Rotate light direction over time
                     let light_angle = time * 0.5;
                     let lighting = LightingUniform {
-                        light_direction: [
-                            light_angle.cos() * 0.5,
-                            1.0,
-                            light_angle.sin() * 0.3,
-                        ],
+                        light_direction: [light_angle.cos() * 0.5, 1.0, light_angle.sin() * 0.3],
                         light_intensity: 1.0,
                         light_color: [1.0, 0.9, 0.8], // Warm white light
                         ambient_intensity: 0.2,
@@ -337,27 +569,64 @@ impl ApplicationHandler for App {
                     let material = MaterialUniform {
                         base_color: [material_hue, material_hue, 1.0, 1.0],
                         metallic_roughness: [0.1, 0.6],
-                        _padding: [0.0, 0.0],
+                        ..Default::default()
                     };
                     gpu.update_material(&material);
                 }
 
                 // Build draw list WITHOUT allocation (reuse vector)
                 self.draw_list.clear();
-                for (t, r) in self.world.iter_renderables() {
-                    // Use default texture for now - could be extended to per-entity textures
-                    let default_texture = if let Some(gpu) = self.gpu.as_ref() {
-                        gpu.default_texture_id()
-                    } else {
-                        corelib::ecs::TextureId::INVALID
-                    };
-                    self.draw_list.push(DrawInstance::new(*t, r.mesh, r.material, default_texture));
+                if self.retained {
+                    self.update_retained(dt);
+                } else {
+                    self.build_draw_list(dt);
                 }
 
-                // Render 3D scene (I1: egui framework integrated)
+                // K3: particle emitters follow their entities
                 if let Some(gpu) = self.gpu.as_mut() {
-                    match gpu.render_models(&self.draw_list) {
-                        Ok(()) => {}
+                    // J2: pick up edited shaders and material files before drawing
+                    gpu.poll_shaders();
+                    gpu.poll_materials();
+                    gpu.update_particles(dt, self.world.iter_emitters());
+                    gpu.update_animations(dt, self.world.iter_animators_mut().map(|(_, a)| a));
+                }
+
+                // I1: build the UI first so it is painted over this frame
+                self.process_egui_frame();
+
+                // Render 3D scene with the egui overlay
+                if let Some(gpu) = self.gpu.as_mut() {
+                    let mut overlay = self
+                        .egui_renderer
+                        .as_mut()
+                        .zip(self.egui_frame.as_ref())
+                        .map(|(renderer, frame)| EguiOverlay { renderer, frame });
+                    let overlay = overlay.as_mut().map(|o| o as &mut dyn Overlay);
+                    let result = if self.retained {
+                        gpu.render_retained(overlay)
+                    } else {
+                        gpu.render_models(&self.draw_list, overlay)
+                    };
+                    // Textures egui no longer needs go once the frame is submitted
+                    if let (Some(renderer), Some(frame)) =
+                        (self.egui_renderer.as_mut(), self.egui_frame.take())
+                    {
+                        for id in &frame.textures.free {
+                            renderer.free_texture(id);
+                        }
+                    }
+                    match result {
+                        Ok(stats) => {
+                            self.stats_history.push(stats);
+                            if let Some(w) = self.stats_csv.as_mut()
+                                && let Err(e) =
+                                    writeln!(w, "{}", stats.to_csv_row(self.stats_frame))
+                            {
+                                log::warn!("Failed to write stats CSV: {e}; disabling");
+                                self.stats_csv = None;
+                            }
+                            self.stats_frame += 1;
+                        }
                         Err(e) => {
                             log::warn!("Render error: {e:?}");
                             if renderer::GpuState::is_surface_lost(&e) {
@@ -374,8 +643,14 @@ impl ApplicationHandler for App {
                     }
                 }
 
-                // I1: Process egui events and prepare UI (rendering overlay TODO in I2)
-                self.process_egui_frame();
+                // H4: dump per-pass timings to the log once per second
+                if self.profile
+                    && let (Some(gpu), Some(t0)) = (self.gpu.as_ref(), self.last_profile_dump)
+                    && t0.elapsed().as_secs_f32() >= 1.0
+                {
+                    gpu.profiler().log_timings();
+                    self.last_profile_dump = Some(Instant::now());
+                }
 
                 // FPS: счёт и обновление заголовка раз в ~1 сек
                 if self.show_fps {
@@ -414,12 +689,112 @@ impl ApplicationHandler for App {
 }
 
 impl App {
-    /// Process egui events and prepare UI frame (I1 - ready for I2 overlay rendering).
+    /// Resolve LODs and fill `draw_list` (G1: on worker threads for big scenes).
+    fn build_draw_list(&mut self, dt: f32) {
+        let Some(gpu) = self.gpu.as_ref() else {
+            return;
+        };
+        let default_texture = self.texture;
+        let lods = gpu.lod_context();
+        let slots = self.world.slot_count();
+        let threads = renderer::parallel::effective_threads(self.draw_threads, slots);
+        if self.chunk_draw_lists.len() < threads {
+            self.chunk_draw_lists.resize_with(threads, Vec::new);
+        }
+
+        let chunk_size = slots.div_ceil(threads).max(1);
+        std::thread::scope(|s| {
+            for (mut chunk, out) in self
+                .world
+                .chunks_mut(chunk_size)
+                .zip(&mut self.chunk_draw_lists)
+            {
+                let mut job = move || {
+                    out.clear();
+                    for (t, r, lod_state) in chunk.iter_renderables_lod_mut() {
+                        // L1: LOD per instance; a cross-fade draws both levels dithered
+                        let Some(lod) = lods.resolve(r.lod_group, t, lod_state, dt) else {
+                            continue;
+                        };
+                        let texture = r.texture.unwrap_or(default_texture);
+                        out.push(
+                            DrawInstance::new(*t, lod.mesh, r.material, texture)
+                                .with_lod_fade(lod.fade),
+                        );
+                        if let Some((mesh, fade)) = lod.fading_out {
+                            out.push(
+                                DrawInstance::new(*t, mesh, r.material, texture)
+                                    .with_lod_fade(fade),
+                            );
+                        }
+                    }
+                };
+                if threads == 1 {
+                    job();
+                } else {
+                    s.spawn(job);
+                }
+            }
+        });
+        for list in &self.chunk_draw_lists[..threads] {
+            self.draw_list.extend_from_slice(list);
+        }
+    }
+
+    /// L2: push changed entities into the renderer's retained instances.
+    fn update_retained(&mut self, dt: f32) {
+        let Some(gpu) = self.gpu.as_mut() else {
+            return;
+        };
+        let default_texture = self.texture;
+        // L1: LOD selection depends on the view, so a camera/aspect/settings change
+        // re-resolves everything (unchanged instances are not re-uploaded)
+        let lod_view = (*gpu.camera(), gpu.lod_settings());
+        if self.retained_lod_view != Some(lod_view) {
+            self.retained_lod_view = Some(lod_view);
+            self.world.mark_all_dirty();
+        }
+        self.dirty_entities.clear();
+        self.world.take_dirty(&mut self.dirty_entities);
+        for &e in &self.dirty_entities {
+            let (current, outgoing) = (instance_slot(e, false), instance_slot(e, true));
+            let Some((t, r, lod_state)) = self.world.renderable_lod_mut(e) else {
+                // Despawned or no longer renderable
+                gpu.remove_instance(current);
+                gpu.remove_instance(outgoing);
+                continue;
+            };
+            let Some(lod) = gpu.resolve_lod(r.lod_group, t, lod_state, dt) else {
+                gpu.remove_instance(current);
+                gpu.remove_instance(outgoing);
+                continue;
+            };
+            let texture = r.texture.unwrap_or(default_texture);
+            gpu.set_instance(
+                current,
+                &DrawInstance::new(*t, lod.mesh, r.material, texture).with_lod_fade(lod.fade),
+            );
+            match lod.fading_out {
+                Some((mesh, fade)) => gpu.set_instance(
+                    outgoing,
+                    &DrawInstance::new(*t, mesh, r.material, texture).with_lod_fade(fade),
+                ),
+                None => gpu.remove_instance(outgoing),
+            }
+        }
+        // A running cross-fade must advance next frame even if the entity stays still.
+        for &e in &self.dirty_entities {
+            if let Some((_, _, lod_state)) = self.world.renderable_lod_mut(e)
+                && lod_state.is_fading()
+            {
+                self.world.mark_dirty(e);
+            }
+        }
+    }
+
+    /// Process egui events and tessellate the UI into `egui_frame` (I1).
     fn process_egui_frame(&mut self) {
-        if let (Some(egui_state), Some(window)) = (
-            self.egui_state.as_mut(),
-            self.window.as_ref(),
-        ) {
+        if let (Some(egui_state), Some(window)) = (self.egui_state.as_mut(), self.window.as_ref()) {
             let raw_input = egui_state.take_egui_input(window);
 
             // Prepare UI data outside the closure to avoid borrow conflicts
@@ -434,27 +809,80 @@ impl App {
                 0.0
             };
             let camera_info = self.camera.as_ref().map(|c| (c.eye, c.target, c.fov_y_rad));
-            let mesh_info = (self.cube_mesh, self.suzanne_mesh);
+            let mesh_info = (self.cube_lods, self.suzanne_lods);
+            let profiler_info = self.gpu.as_ref().and_then(|gpu| {
+                let profiler = gpu.profiler();
+                profiler
+                    .is_enabled()
+                    .then(|| (profiler.mode(), profiler.timings().to_vec()))
+            });
+            let reload_errors: Vec<(String, String)> = self
+                .gpu
+                .as_ref()
+                .map(|gpu| {
+                    let shaders = gpu.shader_errors().into_iter().map(|e| (e.name, e.message));
+                    let materials = gpu
+                        .material_errors()
+                        .into_iter()
+                        .map(|e| (e.path, e.message));
+                    shaders.chain(materials).collect()
+                })
+                .unwrap_or_default();
+
+            if let Some(gpu) = self.gpu.as_ref() {
+                self.graph_editor.sync(gpu);
+            }
 
+            let world = &mut self.world;
+            let emitter_entities = &self.emitter_entities;
+            let graph_editor = &mut self.graph_editor;
             let full_output = egui_state.egui_ctx().run(raw_input, |ctx| {
-                Self::draw_ui_content(ctx, entity_count, fps, self.show_fps, camera_info, mesh_info);
+                Self::draw_ui_content(
+                    ctx,
+                    entity_count,
+                    fps,
+                    self.show_fps,
+                    camera_info,
+                    mesh_info,
+                    profiler_info.as_ref(),
+                    &self.stats_history,
+                    (world, emitter_entities),
+                    &reload_errors,
+                    graph_editor,
+                );
             });
 
-            egui_state.handle_platform_output(window, full_output.platform_output);
+            // J2: compile and apply graph edits made this frame
+            if let Some(gpu) = self.gpu.as_mut() {
+                self.graph_editor.apply(gpu);
+            }
 
-            // I1 Complete: egui framework integrated and UI prepared
-            // Next: I2 will add camera controls and visual overlay rendering
+            egui_state.handle_platform_output(window, full_output.platform_output);
+            let pixels_per_point = full_output.pixels_per_point;
+            self.egui_frame = Some(EguiFrame {
+                primitives: egui_state
+                    .egui_ctx()
+                    .tessellate(full_output.shapes, pixels_per_point),
+                textures: full_output.textures_delta,
+                pixels_per_point,
+            });
         }
     }
 
     /// Draw the egui UI content (I1).
+    #[allow(clippy::too_many_arguments)]
     fn draw_ui_content(
         ctx: &egui::Context,
         entity_count: usize,
         fps: f32,
         show_fps: bool,
         camera_info: Option<(corelib::Vec3, corelib::Vec3, f32)>,
-        mesh_info: (corelib::ecs::MeshId, corelib::ecs::MeshId),
+        mesh_info: (LodGroupId, LodGroupId),
+        profiler_info: Option<&(ProfilerMode, Vec<PassTiming>)>,
+        stats_history: &StatsHistory,
+        emitters: (&mut World, &[Entity]),
+        reload_errors: &[(String, String)],
+        graph_editor: &mut GraphEditor,
     ) {
         // I1: Basic UI panels
         egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
@@ -466,9 +894,28 @@ impl App {
                 if show_fps {
                     ui.label(format!("FPS: {:.1}", fps));
                 }
+                ui.separator();
+                ui.toggle_value(&mut graph_editor.open, "Material graph");
             });
         });
 
+        // J2: node editor for graph materials
+        graph_editor.show(ctx);
+
+        // J2: failed shader/material reloads (the previous versions keep rendering)
+        if !reload_errors.is_empty() {
+            egui::Window::new("Reload errors")
+                .anchor(egui::Align2::RIGHT_BOTTOM, [-8.0, -8.0])
+                .collapsible(true)
+                .show(ctx, |ui| {
+                    for (name, message) in reload_errors {
+                        ui.colored_label(egui::Color32::LIGHT_RED, name);
+                        ui.label(egui::RichText::new(message).monospace().small());
+                        ui.separator();
+                    }
+                });
+        }
+
         egui::SidePanel::left("left_panel").show(ctx, |ui| {
             ui.heading("Scene Inspector");
             ui.separator();
@@ -476,7 +923,10 @@ impl App {
             if let Some((eye, target, fov)) = camera_info {
                 ui.collapsing("Camera", |ui| {
                     ui.label(format!("Eye: {:.2}, {:.2}, {:.2}", eye.x, eye.y, eye.z));
-                    ui.label(format!("Target: {:.2}, {:.2}, {:.2}", target.x, target.y, target.z));
+                    ui.label(format!(
+                        "Target: {:.2}, {:.2}, {:.2}",
+                        target.x, target.y, target.z
+                    ));
                     ui.label(format!("FOV: {:.1}°", fov.to_degrees()));
                 });
             }
@@ -490,10 +940,325 @@ impl App {
 
             ui.separator();
             ui.collapsing("Meshes", |ui| {
-                ui.label(format!("Cube mesh: {:?}", mesh_info.0));
-                ui.label(format!("Suzanne mesh: {:?}", mesh_info.1));
+                ui.label(format!("Cube LOD group: {:?}", mesh_info.0));
+                ui.label(format!("Suzanne LOD group: {:?}", mesh_info.1));
             });
+
+            if let Some(latest) = stats_history.latest() {
+                ui.separator();
+                ui.collapsing("Frame Stats", |ui| {
+                    ui.label(format!("Draw calls: {}", latest.draw_calls));
+                    ui.label(format!("Instances: {}", latest.instances));
+                    ui.label(format!(
+                        "Visible/culled: {}/{}",
+                        latest.visible_instances, latest.culled_instances
+                    ));
+                    ui.label(format!("Occluded: {}", latest.occluded_instances));
+                    ui.label(format!("Triangles: {}", latest.triangles));
+                    ui.label(format!("Batches: {}", latest.batches));
+                    ui.label(format!(
+                        "Bind group switches: {}",
+                        latest.bind_group_switches
+                    ));
+                    ui.label(format!("Pipeline switches: {}", latest.pipeline_switches));
+                    ui.label(format!("Buffer binds: {}", latest.buffer_binds));
+                    ui.label(format!("Uploaded: {} B", latest.uploaded_bytes));
+                    ui.label(format!(
+                        "Buffer reallocations: {}",
+                        latest.buffer_reallocations
+                    ));
+                    ui.label(format!("CPU prepare: {:.3} ms", latest.cpu_prepare_ms));
+
+                    Self::sparkline(
+                        ui,
+                        "Draw calls",
+                        &stats_history.series(|s| s.draw_calls as f32),
+                    );
+                    Self::sparkline(
+                        ui,
+                        "Triangles",
+                        &stats_history.series(|s| s.triangles as f32),
+                    );
+                    Self::sparkline(
+                        ui,
+                        "CPU prepare (ms)",
+                        &stats_history.series(|s| s.cpu_prepare_ms as f32),
+                    );
+                    Self::sparkline(
+                        ui,
+                        "Uploaded (B)",
+                        &stats_history.series(|s| s.uploaded_bytes as f32),
+                    );
+                });
+            }
+
+            // K3: particle emitter inspector
+            let (world, emitter_entities) = emitters;
+            if !emitter_entities.is_empty() {
+                ui.separator();
+                ui.collapsing("Particles", |ui| {
+                    for &e in emitter_entities {
+                        if let Some(emitter) = world.emitter_mut(e) {
+                            ui.collapsing(format!("Emitter #{e}"), |ui| {
+                                Self::emitter_inspector(ui, emitter)
+                            });
+                        }
+                    }
+                });
+            }
+
+            // H4: per-pass GPU timings
+            if let Some((mode, timings)) = profiler_info {
+                ui.separator();
+                ui.collapsing("Profiler", |ui| {
+                    ui.label(format!("Mode: {:?}", mode));
+                    let total: f64 = timings.iter().map(|t| t.ms).sum();
+                    for t in timings {
+                        ui.label(format!("{}: {:.3} ms", t.label, t.ms));
+                    }
+                    ui.label(format!("Total: {:.3} ms", total));
+                });
+            }
         });
     }
+
+    /// K3: editable emitter parameters.
+    fn emitter_inspector(ui: &mut egui::Ui, e: &mut ParticleEmitter) {
+        ui.checkbox(&mut e.enabled, "Enabled");
+        ui.horizontal(|ui| {
+            ui.label("Shape:");
+            let is_point = matches!(e.shape, EmitterShape::Point);
+            let is_sphere = matches!(e.shape, EmitterShape::Sphere { .. });
+            let is_cone = matches!(e.shape, EmitterShape::Cone { .. });
+            if ui.selectable_label(is_point, "Point").clicked() {
+                e.shape = EmitterShape::Point;
+            }
+            if ui.selectable_label(is_sphere, "Sphere").clicked() && !is_sphere {
+                e.shape = EmitterShape::Sphere { radius: 0.5 };
+            }
+            if ui.selectable_label(is_cone, "Cone").clicked() && !is_cone {
+                e.shape = EmitterShape::Cone {
+                    angle: 0.35,
+                    radius: 0.05,
+                };
+            }
+        });
+        match &mut e.shape {
+            EmitterShape::Point => {}
+            EmitterShape::Sphere { radius } => {
+                ui.add(egui::Slider::new(radius, 0.0..=5.0).text("Radius"));
+            }
+            EmitterShape::Cone { angle, radius } => {
+                ui.add(egui::Slider::new(angle, 0.0..=std::f32::consts::PI).text("Angle"));
+                ui.add(egui::Slider::new(radius, 0.0..=5.0).text("Radius"));
+            }
+        }
+        ui.add(egui::Slider::new(&mut e.rate, 0.0..=5000.0).text("Rate (1/s)"));
+        ui.add(egui::Slider::new(&mut e.lifetime.0, 0.05..=10.0).text("Lifetime min"));
+        ui.add(egui::Slider::new(&mut e.lifetime.1, 0.05..=10.0).text("Lifetime max"));
+        ui.add(egui::Slider::new(&mut e.speed.0, 0.0..=20.0).text("Speed min"));
+        ui.add(egui::Slider::new(&mut e.speed.1, 0.0..=20.0).text("Speed max"));
+        ui.add(egui::Slider::new(&mut e.gravity.y, -20.0..=20.0).text("Gravity Y"));
+        ui.add(egui::Slider::new(&mut e.drag, 0.0..=5.0).text("Drag"));
+        ui.add(egui::Slider::new(&mut e.curl_strength, 0.0..=10.0).text("Curl strength"));
+        ui.add(egui::Slider::new(&mut e.curl_scale, 0.01..=5.0).text("Curl scale"));
+        ui.add(egui::Slider::new(&mut e.size_start, 0.0..=2.0).text("Size start"));
+        ui.add(egui::Slider::new(&mut e.size_end, 0.0..=2.0).text("Size end"));
+        for (label, color) in [
+            ("Color start", &mut e.color_start),
+            ("Color end", &mut e.color_end),
+        ] {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                let mut rgba = color.to_array();
+                if ui.color_edit_button_rgba_unmultiplied(&mut rgba).changed() {
+                    *color = Vec4::from_array(rgba);
+                }
+            });
+        }
+        ui.horizontal(|ui| {
+            ui.label("Blend:");
+            ui.selectable_value(&mut e.blend, ParticleBlend::Additive, "Additive");
+            ui.selectable_value(&mut e.blend, ParticleBlend::AlphaSorted, "Alpha (sorted)");
+        });
+    }
+
+    /// Tiny line graph of a stats series (oldest value on the left).
+    fn sparkline(ui: &mut egui::Ui, label: &str, values: &[f32]) {
+        let max = values.iter().copied().fold(0.0f32, f32::max);
+        ui.label(format!("{label} (max {max:.1})"));
+        let (response, painter) =
+            ui.allocate_painter(egui::vec2(ui.available_width(), 40.0), egui::Sense::hover());
+        let rect = response.rect;
+        painter.rect_filled(rect, 2.0, egui::Color32::from_gray(30));
+        if values.len() < 2 || max <= 0.0 {
+            return;
+        }
+        let step = rect.width() / (values.len() - 1) as f32;
+        let points: Vec<egui::Pos2> = values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                egui::pos2(
+                    rect.left() + i as f32 * step,
+                    rect.bottom() - v / max * rect.height(),
+                )
+            })
+            .collect();
+        painter.add(egui::Shape::line(
+            points,
+            egui::Stroke::new(1.0, egui::Color32::LIGHT_GREEN),
+        ));
+    }
+}
+
+/// I1: tessellated egui output of one frame.
+struct EguiFrame {
+    primitives: Vec<egui::ClippedPrimitive>,
+    textures: egui::TexturesDelta,
+    pixels_per_point: f32,
+}
+
+/// I1: paints an [`EguiFrame`] over the renderer's finished frame.
+struct EguiOverlay<'a> {
+    renderer: &'a mut EguiRenderer,
+    frame: &'a EguiFrame,
 }
 
+impl Overlay for EguiOverlay<'_> {
+    fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        size: [u32; 2],
+    ) {
+        for (id, delta) in &self.frame.textures.set {
+            self.renderer.update_texture(device, queue, *id, delta);
+        }
+        let screen = ScreenDescriptor {
+            size_in_pixels: size,
+            pixels_per_point: self.frame.pixels_per_point,
+        };
+        // Only paint callbacks return command buffers, and the UI has none
+        self.renderer
+            .update_buffers(device, queue, encoder, &self.frame.primitives, &screen);
+        let pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("EguiPass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        self.renderer
+            .render(&mut pass.forget_lifetime(), &self.frame.primitives, &screen);
+    }
+}
+
+/// J1: upload the first skinned primitive of a glTF file with all of its skin's clips, and
+/// its base color texture (E2: sampled as the file specifies) if it has one.
+fn load_skinned(
+    gpu: &mut renderer::GpuState,
+    path: &std::path::Path,
+) -> Result<(SkinId, Option<TextureId>)> {
+    let scene = gltf::load_gltf(path)?;
+    let (node_index, node, skin_index) = scene
+        .nodes
+        .iter()
+        .enumerate()
+        .find_map(|(i, n)| Some((i, n, n.skin?)))
+        .ok_or_else(|| anyhow::anyhow!("no skinned node"))?;
+    let mesh = node
+        .mesh
+        .and_then(|m| scene.meshes.get(m))
+        .ok_or_else(|| anyhow::anyhow!("skinned node has no mesh"))?;
+    let primitive = mesh
+        .primitives
+        .iter()
+        .find(|p| p.skin.is_some())
+        .ok_or_else(|| anyhow::anyhow!("mesh has no skinned primitive"))?;
+    let skeleton = scene.skeleton(skin_index)?;
+    let clips = (0..scene.animations.len())
+        .map(|a| scene.animation_clip(a, node_index))
+        .collect::<Result<Vec<_>>>()?;
+    log::info!(
+        "Loaded skinned mesh {} ({} joints, {} clips)",
+        path.display(),
+        skeleton.len(),
+        clips.len()
+    );
+    let weights = primitive.skin.as_ref().expect("skinned primitive");
+    let skin = gpu
+        .upload_skinned_mesh("Skinned", &primitive.mesh, weights, skeleton, clips)
+        .map_err(|e| anyhow::anyhow!(e))?;
+    let texture = primitive
+        .material
+        .and_then(|m| scene.materials.get(m)?.base_color_texture)
+        .and_then(|t| scene.textures.get(t))
+        .and_then(|t| Some((scene.images.get(t.image)?, t.sampler())))
+        .map(|(image, sampler)| gpu.upload_texture_with_sampler("Skinned", image, &sampler));
+    Ok((skin, texture))
+}
+
+/// M1: LOD chain of `obj` from its `.meshbin` file in the build cache, rebuilt from the OBJ
+/// (LODs generated, optimized, file rewritten) when that is missing, older than the OBJ,
+/// built with other settings or invalid.
+fn load_cached_lods(
+    gpu: &mut renderer::GpuState,
+    label: &str,
+    obj: &std::path::Path,
+    levels: usize,
+) -> Result<LodGroupId> {
+    const RATIO: f32 = 0.5;
+    let cache_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("..")
+        .join("..")
+        .join("target")
+        .join("asset-cache");
+    let file_name = obj
+        .file_stem()
+        .map(std::ffi::OsStr::to_os_string)
+        .unwrap_or_default();
+    let cache = cache_dir.join(file_name).with_extension("meshbin");
+    let key = mesh_bin::build_key(&format!("obj lods={levels} ratio={RATIO} optimized"));
+    let modified = |p: &std::path::Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+    if modified(&cache).is_some_and(|c| modified(obj).is_none_or(|o| c >= o)) {
+        match mesh_bin::MeshFile::open(&cache) {
+            Ok(file) if file.build_key() == key => {
+                log::info!(
+                    "Loaded {label} from {} ({} LODs)",
+                    cache.display(),
+                    file.lod_count()
+                );
+                return Ok(gpu.upload_mesh_file(label, &file));
+            }
+            Ok(_) => log::info!("Rebuilding {label} mesh file: built with other settings"),
+            Err(err) => log::warn!("Rebuilding {label} mesh file: {err:#}"),
+        }
+    }
+    let mut lods = lod::load_obj_lods(obj, levels, RATIO)?;
+    for level in &mut lods.levels {
+        process::optimize(level);
+    }
+    log::info!(
+        "Loaded {label} OBJ ({} vertices, {} indices, {} LODs)",
+        lods.levels[0].vertices.len(),
+        lods.levels[0].indices.len(),
+        lods.len()
+    );
+    let written = std::fs::create_dir_all(&cache_dir)
+        .map_err(anyhow::Error::from)
+        .and_then(|()| mesh_bin::write_mesh_file(&cache, &lods, key));
+    if let Err(err) = written {
+        log::warn!("Failed to write {}: {err:#}", cache.display());
+    }
+    Ok(gpu.upload_lod_group(label, &lods))
+}
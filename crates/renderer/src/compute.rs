@@ -0,0 +1,535 @@
+//! G2: generic compute shader subsystem.
+//! Register WGSL compute shaders with a declared binding layout (group 0), build bind groups
+//! from buffers/textures checked against that layout, and dispatch either standalone
+//! (own compute pass) or inside a [`FrameGraph`] compute pass with declared resource usage.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, CommandEncoder, ComputePass, ComputePipeline, Device,
+    Sampler, ShaderStages, TextureView,
+};
+
+use crate::framegraph::{FrameGraph, FrameResources, PassDesc, PassId, ResourceId, ResourceUsage};
+use crate::profiler::GpuProfiler;
+
+/// Handle of a registered compute shader.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ComputeShaderId(pub u32);
+
+/// Kind of one declared binding.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ComputeBindingKind {
+    Uniform,
+    Storage {
+        read_only: bool,
+    },
+    /// Sampled texture (`texture_2d<f32>` etc).
+    Texture {
+        sample_type: wgpu::TextureSampleType,
+        dimension: wgpu::TextureViewDimension,
+    },
+    /// `texture_storage_*` with the given format and access.
+    StorageTexture {
+        format: wgpu::TextureFormat,
+        access: wgpu::StorageTextureAccess,
+        dimension: wgpu::TextureViewDimension,
+    },
+    Sampler(wgpu::SamplerBindingType),
+}
+
+impl ComputeBindingKind {
+    /// Read-only storage buffer.
+    pub const STORAGE_READ: Self = Self::Storage { read_only: true };
+    /// Read-write storage buffer.
+    pub const STORAGE_RW: Self = Self::Storage { read_only: false };
+
+    /// Unfiltered 2D float texture (e.g. depth pyramids, R32Float data).
+    pub fn texture_2d() -> Self {
+        Self::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+            dimension: wgpu::TextureViewDimension::D2,
+        }
+    }
+
+    /// Write-only 2D storage texture.
+    pub fn storage_texture_2d(format: wgpu::TextureFormat) -> Self {
+        Self::StorageTexture {
+            format,
+            access: wgpu::StorageTextureAccess::WriteOnly,
+            dimension: wgpu::TextureViewDimension::D2,
+        }
+    }
+
+    fn binding_type(self) -> wgpu::BindingType {
+        match self {
+            Self::Uniform => wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            Self::Storage { read_only } => wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            Self::Texture {
+                sample_type,
+                dimension,
+            } => wgpu::BindingType::Texture {
+                sample_type,
+                view_dimension: dimension,
+                multisampled: false,
+            },
+            Self::StorageTexture {
+                format,
+                access,
+                dimension,
+            } => wgpu::BindingType::StorageTexture {
+                access,
+                format,
+                view_dimension: dimension,
+            },
+            Self::Sampler(ty) => wgpu::BindingType::Sampler(ty),
+        }
+    }
+
+    /// Whether `resource` can be bound to a binding of this kind.
+    fn accepts(self, resource: &ComputeResource<'_>) -> bool {
+        matches!(
+            (self, resource),
+            (
+                Self::Uniform | Self::Storage { .. },
+                ComputeResource::Buffer(_)
+            ) | (
+                Self::Uniform | Self::Storage { .. },
+                ComputeResource::BufferRange { .. }
+            ) | (
+                Self::Texture { .. } | Self::StorageTexture { .. },
+                ComputeResource::Texture(_)
+            ) | (Self::Sampler(_), ComputeResource::Sampler(_))
+        )
+    }
+}
+
+/// One declared binding in group 0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ComputeBinding {
+    pub binding: u32,
+    pub kind: ComputeBindingKind,
+}
+
+impl ComputeBinding {
+    pub const fn new(binding: u32, kind: ComputeBindingKind) -> Self {
+        Self { binding, kind }
+    }
+}
+
+/// Everything needed to register a compute shader.
+#[derive(Clone, Debug)]
+pub struct ComputeShaderDesc<'a> {
+    pub label: &'a str,
+    /// WGSL source.
+    pub source: &'a str,
+    pub entry_point: &'a str,
+    /// Must match `@workgroup_size` in the shader; used by [`ComputeRegistry::workgroups_for`].
+    pub workgroup_size: [u32; 3],
+    pub bindings: &'a [ComputeBinding],
+}
+
+/// Resource bound to a declared binding.
+#[derive(Clone, Copy, Debug)]
+pub enum ComputeResource<'a> {
+    Buffer(&'a Buffer),
+    BufferRange {
+        buffer: &'a Buffer,
+        offset: u64,
+        size: Option<wgpu::BufferSize>,
+    },
+    Texture(&'a TextureView),
+    Sampler(&'a Sampler),
+}
+
+/// Errors from registration and bind group creation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ComputeError {
+    /// The device has no compute shader support.
+    Unsupported,
+    /// WGSL compilation or pipeline validation failed.
+    Shader {
+        label: String,
+        message: String,
+    },
+    UnknownShader(ComputeShaderId),
+    /// A declared binding has no resource.
+    MissingBinding {
+        label: String,
+        binding: u32,
+    },
+    /// A resource was given for a binding that is not declared or has another kind.
+    BindingMismatch {
+        label: String,
+        binding: u32,
+    },
+    /// A declared binding number appears twice.
+    DuplicateBinding {
+        label: String,
+        binding: u32,
+    },
+}
+
+impl fmt::Display for ComputeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unsupported => write!(f, "compute shaders are not supported on this device"),
+            Self::Shader { label, message } => write!(f, "compute shader '{label}': {message}"),
+            Self::UnknownShader(id) => write!(f, "unknown compute shader {}", id.0),
+            Self::MissingBinding { label, binding } => {
+                write!(
+                    f,
+                    "compute shader '{label}': binding {binding} has no resource"
+                )
+            }
+            Self::BindingMismatch { label, binding } => {
+                write!(
+                    f,
+                    "compute shader '{label}': resource does not match binding {binding}"
+                )
+            }
+            Self::DuplicateBinding { label, binding } => {
+                write!(
+                    f,
+                    "compute shader '{label}': binding {binding} declared twice"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ComputeError {}
+
+/// A compiled compute pipeline with its declared layout.
+pub struct ComputeShader {
+    pub label: String,
+    pub workgroup_size: [u32; 3],
+    pub bindings: Vec<ComputeBinding>,
+    pub pipeline: ComputePipeline,
+    pub bind_group_layout: BindGroupLayout,
+}
+
+/// One dispatch: shader + bound resources + workgroup counts.
+pub struct ComputeDispatch {
+    pub shader: ComputeShaderId,
+    pub bind_group: BindGroup,
+    pub workgroups: [u32; 3],
+}
+
+/// Registered compute shaders indexed by [`ComputeShaderId`].
+#[derive(Default)]
+pub struct ComputeRegistry {
+    // Arc: FrameGraph passes keep the pipeline alive across re-registration
+    shaders: Vec<Arc<ComputeShader>>,
+    /// Shaders by label, so re-registering a label replaces it in place.
+    by_label: HashMap<String, ComputeShaderId>,
+}
+
+impl ComputeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compile and register a shader. Re-registering an existing label replaces the pipeline
+    /// and keeps the id. Compile errors are returned instead of hitting the device error handler.
+    pub fn register(
+        &mut self,
+        device: &Device,
+        desc: &ComputeShaderDesc<'_>,
+    ) -> Result<ComputeShaderId, ComputeError> {
+        check_duplicate_bindings(desc.label, desc.bindings)?;
+
+        let entries: Vec<wgpu::BindGroupLayoutEntry> = desc
+            .bindings
+            .iter()
+            .map(|b| wgpu::BindGroupLayoutEntry {
+                binding: b.binding,
+                visibility: ShaderStages::COMPUTE,
+                ty: b.kind.binding_type(),
+                count: None,
+            })
+            .collect();
+
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(desc.label),
+            source: wgpu::ShaderSource::Wgsl(desc.source.into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some(&format!("{} BGL", desc.label)),
+            entries: &entries,
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some(&format!("{} PipelineLayout", desc.label)),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(desc.label),
+            layout: Some(&layout),
+            module: &module,
+            entry_point: Some(desc.entry_point),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+        if let Some(err) = pollster::block_on(device.pop_error_scope()) {
+            return Err(ComputeError::Shader {
+                label: desc.label.to_string(),
+                message: err.to_string(),
+            });
+        }
+
+        let shader = Arc::new(ComputeShader {
+            label: desc.label.to_string(),
+            workgroup_size: desc.workgroup_size,
+            bindings: desc.bindings.to_vec(),
+            pipeline,
+            bind_group_layout,
+        });
+        if let Some(&id) = self.by_label.get(desc.label) {
+            self.shaders[id.0 as usize] = shader;
+            log::info!("G2: compute shader '{}' replaced", desc.label);
+            return Ok(id);
+        }
+        let id =
+            ComputeShaderId(u32::try_from(self.shaders.len()).expect("Too many compute shaders"));
+        self.shaders.push(shader);
+        self.by_label.insert(desc.label.to_string(), id);
+        log::info!(
+            "G2: compute shader '{}' registered ({} bindings)",
+            desc.label,
+            desc.bindings.len()
+        );
+        Ok(id)
+    }
+
+    pub fn get(&self, id: ComputeShaderId) -> Option<&ComputeShader> {
+        self.shaders.get(id.0 as usize).map(|s| &**s)
+    }
+
+    pub fn find(&self, label: &str) -> Option<ComputeShaderId> {
+        self.by_label.get(label).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.shaders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.shaders.is_empty()
+    }
+
+    /// Bind group for `id` from `(binding, resource)` pairs; every declared binding must be
+    /// given exactly once with a matching resource kind.
+    pub fn bind_group(
+        &self,
+        device: &Device,
+        id: ComputeShaderId,
+        resources: &[(u32, ComputeResource<'_>)],
+    ) -> Result<BindGroup, ComputeError> {
+        let shader = self.get(id).ok_or(ComputeError::UnknownShader(id))?;
+        check_resources(&shader.label, &shader.bindings, resources)?;
+
+        let entries: Vec<wgpu::BindGroupEntry> = resources
+            .iter()
+            .map(|&(binding, resource)| wgpu::BindGroupEntry {
+                binding,
+                resource: match resource {
+                    ComputeResource::Buffer(buffer) => buffer.as_entire_binding(),
+                    ComputeResource::BufferRange {
+                        buffer,
+                        offset,
+                        size,
+                    } => wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                        buffer,
+                        offset,
+                        size,
+                    }),
+                    ComputeResource::Texture(view) => wgpu::BindingResource::TextureView(view),
+                    ComputeResource::Sampler(sampler) => wgpu::BindingResource::Sampler(sampler),
+                },
+            })
+            .collect();
+        Ok(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{} BG", shader.label)),
+            layout: &shader.bind_group_layout,
+            entries: &entries,
+        }))
+    }
+
+    /// Workgroup counts covering `threads` invocations with the shader's workgroup size.
+    pub fn workgroups_for(&self, id: ComputeShaderId, threads: [u32; 3]) -> [u32; 3] {
+        let size = self.get(id).map_or([1; 3], |s| s.workgroup_size);
+        workgroup_count(threads, size)
+    }
+
+    /// Record `dispatch` into an open compute pass.
+    pub fn record(&self, pass: &mut ComputePass<'_>, dispatch: &ComputeDispatch) {
+        let Some(shader) = self.get(dispatch.shader) else {
+            log::warn!(
+                "G2: dispatch of unknown compute shader {}",
+                dispatch.shader.0
+            );
+            return;
+        };
+        let [x, y, z] = dispatch.workgroups;
+        if x == 0 || y == 0 || z == 0 {
+            return;
+        }
+        pass.set_pipeline(&shader.pipeline);
+        pass.set_bind_group(0, &dispatch.bind_group, &[]);
+        pass.dispatch_workgroups(x, y, z);
+    }
+
+    /// Standalone: record `dispatches` in their own (optionally profiled) compute pass.
+    pub fn dispatch(
+        &self,
+        encoder: &mut CommandEncoder,
+        mut profiler: Option<&mut GpuProfiler>,
+        label: &str,
+        dispatches: &[ComputeDispatch],
+    ) {
+        let scope = profiler.as_deref_mut().and_then(|p| p.begin_pass(label));
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some(label),
+                timestamp_writes: profiler
+                    .as_deref()
+                    .and_then(|p| p.compute_timestamp_writes(scope)),
+            });
+            for dispatch in dispatches {
+                self.record(&mut cpass, dispatch);
+            }
+        }
+        if let Some(p) = profiler {
+            p.end_pass(scope);
+        }
+    }
+
+    /// FrameGraph: add a compute pass running `dispatch` with the declared resource usage.
+    /// The pipeline is captured, so the registry does not have to outlive the graph.
+    pub fn add_to_graph(
+        &self,
+        graph: &mut FrameGraph,
+        label: &str,
+        usage: &[(ResourceId, ResourceUsage)],
+        dispatch: ComputeDispatch,
+    ) -> Result<PassId, ComputeError> {
+        let shader = self
+            .shaders
+            .get(dispatch.shader.0 as usize)
+            .cloned()
+            .ok_or(ComputeError::UnknownShader(dispatch.shader))?;
+        let (inputs, outputs) = usage
+            .iter()
+            .copied()
+            .partition(|(_, u)| matches!(u, ResourceUsage::Read));
+        Ok(graph.add_compute_pass(
+            PassDesc {
+                label: label.to_string(),
+                inputs,
+                outputs,
+            },
+            Box::new(
+                move |pass: &mut ComputePass<'_>, _resources: &FrameResources<'_>| {
+                    let [x, y, z] = dispatch.workgroups;
+                    if x == 0 || y == 0 || z == 0 {
+                        return;
+                    }
+                    pass.set_pipeline(&shader.pipeline);
+                    pass.set_bind_group(0, &dispatch.bind_group, &[]);
+                    pass.dispatch_workgroups(x, y, z);
+                },
+            ),
+        ))
+    }
+}
+
+/// Workgroups needed for `threads` invocations (each axis at least 1 when non-zero).
+pub fn workgroup_count(threads: [u32; 3], workgroup_size: [u32; 3]) -> [u32; 3] {
+    std::array::from_fn(|i| threads[i].div_ceil(workgroup_size[i].max(1)))
+}
+
+fn check_duplicate_bindings(label: &str, bindings: &[ComputeBinding]) -> Result<(), ComputeError> {
+    for (i, b) in bindings.iter().enumerate() {
+        if bindings[..i].iter().any(|o| o.binding == b.binding) {
+            return Err(ComputeError::DuplicateBinding {
+                label: label.to_string(),
+                binding: b.binding,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Every declared binding gets exactly one resource of a compatible kind.
+fn check_resources(
+    label: &str,
+    bindings: &[ComputeBinding],
+    resources: &[(u32, ComputeResource<'_>)],
+) -> Result<(), ComputeError> {
+    for (i, (binding, resource)) in resources.iter().enumerate() {
+        let declared = bindings.iter().find(|b| b.binding == *binding);
+        let duplicate = resources[..i].iter().any(|(b, _)| b == binding);
+        if duplicate || !declared.is_some_and(|d| d.kind.accepts(resource)) {
+            return Err(ComputeError::BindingMismatch {
+                label: label.to_string(),
+                binding: *binding,
+            });
+        }
+    }
+    if let Some(missing) = bindings
+        .iter()
+        .find(|b| !resources.iter().any(|(r, _)| *r == b.binding))
+    {
+        return Err(ComputeError::MissingBinding {
+            label: label.to_string(),
+            binding: missing.binding,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn workgroup_counts_round_up() {
+        assert_eq!(workgroup_count([100, 1, 1], [64, 1, 1]), [2, 1, 1]);
+        assert_eq!(workgroup_count([64, 17, 0], [8, 8, 1]), [8, 3, 0]);
+    }
+
+    #[test]
+    fn declared_bindings_are_checked() {
+        let bindings = [
+            ComputeBinding::new(0, ComputeBindingKind::Uniform),
+            ComputeBinding::new(1, ComputeBindingKind::STORAGE_RW),
+        ];
+        assert_eq!(
+            check_duplicate_bindings("dup", &[bindings[0], bindings[0]]),
+            Err(ComputeError::DuplicateBinding {
+                label: "dup".into(),
+                binding: 0
+            })
+        );
+        // No device in tests: only the missing-binding path can be checked without resources.
+        assert_eq!(
+            check_resources("t", &bindings, &[]),
+            Err(ComputeError::MissingBinding {
+                label: "t".into(),
+                binding: 0
+            })
+        );
+    }
+}
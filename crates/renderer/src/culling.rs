@@ -0,0 +1,204 @@
+//! L1: CPU frustum culling of draw instances against mesh bounds.
+//! L1: Hi-Z occlusion test against a CPU snapshot of the depth pyramid.
+
+use asset::mesh::MeshBounds;
+use corelib::{Mat4, Vec2, Vec3, frustum::Frustum};
+
+/// Max texels inspected per occlusion test; bigger rects are treated as visible.
+const MAX_OCCLUSION_TEXELS: u32 = 64;
+
+/// World-space bounds of an instance.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WorldBounds {
+    pub center: Vec3,
+    pub radius: f32,
+    pub aabb_min: Vec3,
+    pub aabb_max: Vec3,
+}
+
+impl WorldBounds {
+    /// Transform local mesh bounds by a model matrix (sphere radius scaled by max axis scale).
+    pub fn from_local(bounds: &MeshBounds, model: Mat4) -> Self {
+        let center = model.transform_point3(Vec3::from(bounds.sphere.center));
+        let max_scale = model
+            .x_axis
+            .truncate()
+            .length()
+            .max(model.y_axis.truncate().length())
+            .max(model.z_axis.truncate().length());
+
+        // AABB: центр трансформируем, экстенты — через |M| (Arvo).
+        let box_center = model.transform_point3(Vec3::from(bounds.aabb.center()));
+        let e = Vec3::from(bounds.aabb.extents());
+        let world_extents = model.x_axis.truncate().abs() * e.x
+            + model.y_axis.truncate().abs() * e.y
+            + model.z_axis.truncate().abs() * e.z;
+
+        Self {
+            center,
+            radius: bounds.sphere.radius * max_scale,
+            aabb_min: box_center - world_extents,
+            aabb_max: box_center + world_extents,
+        }
+    }
+
+    /// Sphere test first (cheap), then the tighter AABB test.
+    #[inline]
+    pub fn is_visible(&self, frustum: &Frustum) -> bool {
+        frustum.intersects_sphere(self.center, self.radius)
+            && frustum.intersects_aabb(self.aabb_min, self.aabb_max)
+    }
+}
+
+/// Screen-space footprint of a box: uv rect (y down) and nearest depth.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScreenRect {
+    pub uv_min: Vec2,
+    pub uv_max: Vec2,
+    pub nearest_depth: f32,
+}
+
+/// Project a world AABB with a WGPU clip matrix (z ∈ [0,1]).
+/// Returns `None` if any corner is behind the camera (treat as visible).
+pub fn project_aabb(view_proj: Mat4, min: Vec3, max: Vec3) -> Option<ScreenRect> {
+    let mut uv_min = Vec2::splat(f32::MAX);
+    let mut uv_max = Vec2::splat(f32::MIN);
+    let mut nearest_depth = f32::MAX;
+    for i in 0..8 {
+        let corner = Vec3::new(
+            if i & 1 == 0 { min.x } else { max.x },
+            if i & 2 == 0 { min.y } else { max.y },
+            if i & 4 == 0 { min.z } else { max.z },
+        );
+        let clip = view_proj * corner.extend(1.0);
+        if clip.w <= 1e-6 {
+            return None;
+        }
+        let ndc = clip.truncate() / clip.w;
+        let uv = Vec2::new(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        uv_min = uv_min.min(uv);
+        uv_max = uv_max.max(uv);
+        nearest_depth = nearest_depth.min(ndc.z);
+    }
+    Some(ScreenRect {
+        uv_min,
+        uv_max,
+        nearest_depth,
+    })
+}
+
+/// CPU copy of one coarse Hi-Z mip plus the clip matrix its depth was rendered with.
+#[derive(Clone, Debug, Default)]
+pub struct HiZSnapshot {
+    pub width: u32,
+    pub height: u32,
+    /// Row-major max depth per texel.
+    pub depth: Vec<f32>,
+    pub view_proj: Mat4,
+}
+
+impl HiZSnapshot {
+    /// `true` if the box is entirely behind the stored depth (conservative).
+    pub fn is_occluded(&self, bounds: &WorldBounds) -> bool {
+        if self.width == 0 || self.height == 0 {
+            return false;
+        }
+        let Some(rect) = project_aabb(self.view_proj, bounds.aabb_min, bounds.aabb_max) else {
+            return false;
+        };
+        let size = Vec2::new(self.width as f32, self.height as f32);
+        let lo = (rect.uv_min * size).floor().max(Vec2::ZERO);
+        let hi = (rect.uv_max * size).ceil().min(size);
+        if lo.x >= hi.x || lo.y >= hi.y {
+            // Вне экрана — это забота frustum culling.
+            return false;
+        }
+        let (x0, y0, x1, y1) = (lo.x as u32, lo.y as u32, hi.x as u32, hi.y as u32);
+        if (x1 - x0) * (y1 - y0) > MAX_OCCLUSION_TEXELS {
+            return false;
+        }
+
+        let mut max_depth = 0.0f32;
+        for y in y0..y1 {
+            let row = (y * self.width) as usize;
+            for x in x0..x1 {
+                max_depth = max_depth.max(self.depth[row + x as usize]);
+            }
+        }
+        rect.nearest_depth > max_depth
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use asset::mesh::{Aabb, BoundingSphere};
+    use corelib::{Quat, camera::Camera, vec3};
+
+    fn unit_bounds() -> MeshBounds {
+        MeshBounds {
+            aabb: Aabb {
+                min: [-1.0; 3],
+                max: [1.0; 3],
+            },
+            sphere: BoundingSphere {
+                center: [0.0; 3],
+                radius: 3.0f32.sqrt(),
+            },
+        }
+    }
+
+    #[test]
+    fn world_bounds_follow_transform() {
+        let model = Mat4::from_scale_rotation_translation(
+            vec3(2.0, 1.0, 1.0),
+            Quat::IDENTITY,
+            vec3(10.0, 0.0, 0.0),
+        );
+        let wb = WorldBounds::from_local(&unit_bounds(), model);
+        assert_eq!(wb.center, vec3(10.0, 0.0, 0.0));
+        assert!((wb.radius - 2.0 * 3.0f32.sqrt()).abs() < 1e-5);
+        assert_eq!(wb.aabb_min, vec3(8.0, -1.0, -1.0));
+        assert_eq!(wb.aabb_max, vec3(12.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn instance_outside_frustum_is_culled() {
+        let cam = Camera::test_default();
+        let frustum = cam.frustum();
+        let visible = WorldBounds::from_local(&unit_bounds(), Mat4::IDENTITY);
+        let behind =
+            WorldBounds::from_local(&unit_bounds(), Mat4::from_translation(vec3(0.0, 0.0, 20.0)));
+        assert!(visible.is_visible(&frustum));
+        assert!(!behind.is_visible(&frustum));
+    }
+
+    #[test]
+    fn hiz_snapshot_occludes_boxes_behind_wall() {
+        let cam = Camera::test_default();
+        // WGPU clip: z remapped from [-1,1] to [0,1].
+        let to_wgpu = Mat4::from_cols_array(&[
+            1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.5, 1.0,
+        ]);
+        let view_proj = to_wgpu * cam.proj_view();
+        let wall_depth = project_aabb(view_proj, Vec3::splat(-0.1), Vec3::splat(0.1))
+            .expect("in front of camera")
+            .nearest_depth;
+        let snapshot = HiZSnapshot {
+            width: 8,
+            height: 8,
+            depth: vec![wall_depth; 64],
+            view_proj,
+        };
+
+        let behind = WorldBounds::from_local(
+            &unit_bounds(),
+            Mat4::from_translation(vec3(0.0, 0.0, -10.0)),
+        );
+        let in_front =
+            WorldBounds::from_local(&unit_bounds(), Mat4::from_translation(vec3(0.0, 0.0, 2.0)));
+        assert!(snapshot.is_occluded(&behind));
+        assert!(!snapshot.is_occluded(&in_front));
+        assert!(!HiZSnapshot::default().is_occluded(&behind));
+    }
+}
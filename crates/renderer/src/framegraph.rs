@@ -1,14 +1,17 @@
 //! Mini-FrameGraph system for G2.
 //! Explicit render passes with resource dependencies.
+//! G2: compute passes and buffer resources; passes run in dependency order.
 
 use std::collections::HashMap;
-use wgpu::{CommandEncoder, Device, RenderPass, TextureView};
+use wgpu::{Buffer, CommandEncoder, ComputePass, Device, RenderPass, TextureView};
+
+use crate::profiler::GpuProfiler;
 
 /// Handle for a framegraph resource (texture, buffer, etc).
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct ResourceId(pub u32);
 
-/// Handle for a framegraph render pass.
+/// Handle for a framegraph render or compute pass.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct PassId(pub u32);
 
@@ -30,7 +33,15 @@ pub struct ResourceDesc {
     pub usage: wgpu::TextureUsages,
 }
 
-/// Render pass description.
+/// G2: graph-owned buffer description.
+#[derive(Clone, Debug)]
+pub struct BufferDesc {
+    pub label: String,
+    pub size: u64,
+    pub usage: wgpu::BufferUsages,
+}
+
+/// Render/compute pass description.
 pub struct PassDesc {
     pub label: String,
     pub inputs: Vec<(ResourceId, ResourceUsage)>,
@@ -44,18 +55,50 @@ pub struct Resource {
     pub view: Option<TextureView>,
 }
 
+/// G2: a graph-owned buffer.
+pub struct BufferResource {
+    pub desc: BufferDesc,
+    pub buffer: Option<Buffer>,
+}
+
+/// G2: resources visible to a compute pass.
+pub struct FrameResources<'a> {
+    pub textures: HashMap<ResourceId, &'a Resource>,
+    pub buffers: HashMap<ResourceId, &'a BufferResource>,
+}
+
+impl FrameResources<'_> {
+    pub fn texture_view(&self, id: ResourceId) -> Option<&TextureView> {
+        self.textures.get(&id).and_then(|r| r.view.as_ref())
+    }
+
+    pub fn buffer(&self, id: ResourceId) -> Option<&Buffer> {
+        self.buffers.get(&id).and_then(|r| r.buffer.as_ref())
+    }
+}
+
 /// Render pass execution function.
 pub type PassExecuteFn = Box<dyn FnOnce(&mut RenderPass, &HashMap<ResourceId, &Resource>)>;
 
-/// A render pass.
+/// G2: compute pass execution function.
+pub type ComputePassExecuteFn = Box<dyn FnOnce(&mut ComputePass, &FrameResources)>;
+
+/// What a pass records into.
+pub enum PassExecute {
+    Render(PassExecuteFn),
+    Compute(ComputePassExecuteFn),
+}
+
+/// A render or compute pass.
 pub struct Pass {
     pub desc: PassDesc,
-    pub execute: PassExecuteFn,
+    pub execute: PassExecute,
 }
 
 /// Mini-FrameGraph for organizing render passes.
 pub struct FrameGraph {
     resources: HashMap<ResourceId, Resource>,
+    buffers: HashMap<ResourceId, BufferResource>,
     passes: HashMap<PassId, Pass>,
     resource_counter: u32,
     pass_counter: u32,
@@ -66,6 +109,7 @@ impl FrameGraph {
     pub fn new() -> Self {
         Self {
             resources: HashMap::new(),
+            buffers: HashMap::new(),
             passes: HashMap::new(),
             resource_counter: 0,
             pass_counter: 0,
@@ -88,8 +132,27 @@ impl FrameGraph {
         id
     }
 
+    /// G2: add a buffer resource (created in [`FrameGraph::compile`]). Shares the id space
+    /// with textures, so passes declare buffer usage the same way.
+    pub fn add_buffer(&mut self, desc: BufferDesc) -> ResourceId {
+        let id = ResourceId(self.resource_counter);
+        self.resource_counter += 1;
+        self.buffers
+            .insert(id, BufferResource { desc, buffer: None });
+        id
+    }
+
     /// Add a render pass to the framegraph.
     pub fn add_pass(&mut self, desc: PassDesc, execute: PassExecuteFn) -> PassId {
+        self.insert_pass(desc, PassExecute::Render(execute))
+    }
+
+    /// G2: add a compute pass to the framegraph.
+    pub fn add_compute_pass(&mut self, desc: PassDesc, execute: ComputePassExecuteFn) -> PassId {
+        self.insert_pass(desc, PassExecute::Compute(execute))
+    }
+
+    fn insert_pass(&mut self, desc: PassDesc, execute: PassExecute) -> PassId {
         let id = PassId(self.pass_counter);
         self.pass_counter += 1;
 
@@ -98,12 +161,76 @@ impl FrameGraph {
         id
     }
 
+    /// G2: order passes so every writer of a resource runs before its readers
+    /// (insertion order breaks ties and cycles).
+    pub fn schedule(&mut self) {
+        let mut ids: Vec<PassId> = self.passes.keys().copied().collect();
+        ids.sort_by_key(|id| id.0);
+
+        let writes = |id: &PassId, res: ResourceId| {
+            let desc = &self.passes[id].desc;
+            desc.inputs
+                .iter()
+                .chain(&desc.outputs)
+                .any(|&(r, u)| r == res && !matches!(u, ResourceUsage::Read))
+        };
+        let reads = |id: &PassId, res: ResourceId| {
+            let desc = &self.passes[id].desc;
+            desc.inputs
+                .iter()
+                .chain(&desc.outputs)
+                .any(|&(r, u)| r == res && !matches!(u, ResourceUsage::Write))
+        };
+        let resources_of = |id: &PassId| -> Vec<ResourceId> {
+            let desc = &self.passes[id].desc;
+            desc.inputs
+                .iter()
+                .chain(&desc.outputs)
+                .map(|&(r, _)| r)
+                .collect()
+        };
+        // depends_on(a, b): a reads something b writes; a reader inserted before the
+        // writer (read-modify-write across passes) keeps insertion order.
+        let depends_on = |a: &PassId, b: &PassId| {
+            a != b
+                && resources_of(a)
+                    .into_iter()
+                    .any(|r| reads(a, r) && writes(b, r) && !(reads(b, r) && b.0 > a.0))
+        };
+
+        self.execution_order.clear();
+        let mut remaining = ids;
+        while !remaining.is_empty() {
+            let ready = remaining
+                .iter()
+                .position(|a| !remaining.iter().any(|b| depends_on(a, b)))
+                .unwrap_or_else(|| {
+                    log::warn!("G2: dependency cycle in framegraph, using insertion order");
+                    0
+                });
+            self.execution_order.push(remaining.remove(ready));
+        }
+    }
+
+    /// G2: pass ids in execution order (valid after [`FrameGraph::schedule`]/`compile`).
+    pub fn execution_order(&self) -> &[PassId] {
+        &self.execution_order
+    }
+
     /// Compile the framegraph - determine execution order and create resources.
     pub fn compile(&mut self, device: &Device) {
-        // Simple execution order: just use insertion order for now
-        // A real framegraph would do topological sorting based on dependencies
-        self.execution_order.clear();
-        self.execution_order.extend(self.passes.keys().copied());
+        self.schedule();
+
+        for buffer in self.buffers.values_mut() {
+            if buffer.buffer.is_none() {
+                buffer.buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(&buffer.desc.label),
+                    size: buffer.desc.size,
+                    usage: buffer.desc.usage,
+                    mapped_at_creation: false,
+                }));
+            }
+        }
 
         // Create GPU resources
         for resource in self.resources.values_mut() {
@@ -131,27 +258,64 @@ impl FrameGraph {
     }
 
     /// Execute the framegraph.
-    pub fn execute(&mut self, encoder: &mut CommandEncoder) {
-        // In a real framegraph, we'd execute passes in dependency order
-        // For now, just demonstrate the concept with a simple approach
-
+    /// H4: when a profiler is given, each pass is wrapped in a timestamp scope.
+    pub fn execute(
+        &mut self,
+        encoder: &mut CommandEncoder,
+        mut profiler: Option<&mut GpuProfiler>,
+    ) {
         for pass_id in &self.execution_order {
             let pass = self.passes.remove(pass_id).expect("Pass should exist");
 
+            let scope = profiler
+                .as_deref_mut()
+                .and_then(|p| p.begin_pass(&pass.desc.label));
+
+            let execute = match pass.execute {
+                PassExecute::Render(execute) => execute,
+                PassExecute::Compute(execute) => {
+                    // G2: compute pass
+                    let mut compute_pass =
+                        encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                            label: Some(&pass.desc.label),
+                            timestamp_writes: profiler
+                                .as_deref()
+                                .and_then(|p| p.compute_timestamp_writes(scope)),
+                        });
+                    let resources = FrameResources {
+                        textures: self.resources.iter().map(|(id, res)| (*id, res)).collect(),
+                        buffers: self.buffers.iter().map(|(id, res)| (*id, res)).collect(),
+                    };
+                    execute(&mut compute_pass, &resources);
+                    drop(compute_pass);
+                    if let Some(p) = profiler.as_deref_mut() {
+                        p.end_pass(scope);
+                    }
+                    continue;
+                }
+            };
+
             // Create render pass
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some(&pass.desc.label),
                 color_attachments: &[],
                 depth_stencil_attachment: None,
                 occlusion_query_set: None,
-                timestamp_writes: None,
+                timestamp_writes: profiler
+                    .as_deref()
+                    .and_then(|p| p.render_timestamp_writes(scope)),
             });
 
             // Execute pass with access to resources
             let resource_refs: HashMap<ResourceId, &Resource> =
                 self.resources.iter().map(|(id, res)| (*id, res)).collect();
 
-            (pass.execute)(&mut render_pass, &resource_refs);
+            execute(&mut render_pass, &resource_refs);
+            drop(render_pass);
+
+            if let Some(p) = profiler.as_deref_mut() {
+                p.end_pass(scope);
+            }
         }
     }
 
@@ -159,10 +323,44 @@ impl FrameGraph {
     pub fn get_resource(&self, id: ResourceId) -> Option<&Resource> {
         self.resources.get(&id)
     }
+
+    /// G2: get buffer resource by id.
+    pub fn get_buffer(&self, id: ResourceId) -> Option<&BufferResource> {
+        self.buffers.get(&id)
+    }
 }
 
 impl Default for FrameGraph {
     fn default() -> Self {
         Self::new()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desc(label: &str, inputs: &[ResourceId], outputs: &[ResourceId]) -> PassDesc {
+        PassDesc {
+            label: label.to_string(),
+            inputs: inputs.iter().map(|&r| (r, ResourceUsage::Read)).collect(),
+            outputs: outputs.iter().map(|&r| (r, ResourceUsage::Write)).collect(),
+        }
+    }
+
+    #[test]
+    fn compute_writer_runs_before_reader() {
+        let mut fg = FrameGraph::new();
+        let particles = fg.add_buffer(BufferDesc {
+            label: "Particles".into(),
+            size: 1024,
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        // Reader declared first, compute writer second.
+        let draw = fg.add_pass(desc("Draw", &[particles], &[]), Box::new(|_, _| {}));
+        let simulate =
+            fg.add_compute_pass(desc("Simulate", &[], &[particles]), Box::new(|_, _| {}));
+        let unrelated = fg.add_compute_pass(desc("Other", &[], &[]), Box::new(|_, _| {}));
+        fg.schedule();
+        assert_eq!(fg.execution_order(), &[simulate, draw, unrelated]);
+    }
+}
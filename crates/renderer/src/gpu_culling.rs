@@ -0,0 +1,462 @@
+//! L2: GPU-driven culling and indirect drawing.
+//! All instance transforms are uploaded to a storage buffer, a compute pass culls them
+//! against the frustum and compacts survivors into per-batch `draw_indexed_indirect` args.
+//! L1: with a Hi-Z pyramid bound, instances behind the previous frame's depth are rejected too.
+
+use std::num::NonZeroU64;
+
+use bytemuck::{Pod, Zeroable};
+use corelib::{Mat4, frustum::Frustum};
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, BufferBindingType, BufferUsages, ComputePass,
+    ComputePipeline, Device, Queue, ShaderStages, TextureView,
+};
+
+/// Threads per workgroup in `cull.wgsl`.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Per-instance input: model matrix + batch index.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct GpuCullInstance {
+    pub model: [[f32; 4]; 4],
+    pub batch: u32,
+    /// L1: copied into [`crate::InstanceRaw::lod`].
+    pub lod_fade: f32,
+    pub _padding: [u32; 2],
+}
+
+/// Per-batch input: local bounding sphere and output region start.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct GpuCullBatch {
+    pub sphere: [f32; 4],
+    pub aabb_min: [f32; 4],
+    pub aabb_max: [f32; 4],
+    pub out_start: u32,
+    pub _padding: [u32; 3],
+}
+
+/// Layout of `wgpu` indexed indirect draw arguments.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+pub struct DrawIndexedIndirectArgs {
+    pub index_count: u32,
+    /// Reset to 0 on the CPU, incremented atomically by the cull shader.
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub first_instance: u32,
+}
+
+impl DrawIndexedIndirectArgs {
+    pub const SIZE: u64 = std::mem::size_of::<Self>() as u64;
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct CullParams {
+    planes: [[f32; 4]; 6],
+    instance_count: u32,
+    cull_enabled: u32,
+    occlusion_enabled: u32,
+    hiz_mips: u32,
+    hiz_view_proj: [[f32; 4]; 4],
+    hiz_size: [f32; 2],
+    _padding: [u32; 2],
+}
+
+/// L1: Hi-Z pyramid inputs for the occlusion test.
+pub struct OcclusionInput<'a> {
+    pub view: &'a TextureView,
+    /// Changes when the pyramid texture is recreated.
+    pub generation: u64,
+    /// WGPU clip matrix the pyramid depth was rendered with.
+    pub view_proj: Mat4,
+    pub size: (u32, u32),
+    pub mip_count: u32,
+}
+
+/// Storage buffer that grows to the next power of two.
+struct GrowableBuffer {
+    buf: Buffer,
+    label: &'static str,
+    usage: BufferUsages,
+}
+
+impl GrowableBuffer {
+    fn new(device: &Device, label: &'static str, usage: BufferUsages) -> Self {
+        Self {
+            buf: create_buffer(device, label, 256, usage),
+            label,
+            usage,
+        }
+    }
+
+    /// Returns `true` if the buffer was reallocated.
+    fn ensure(&mut self, device: &Device, size: u64) -> bool {
+        if size <= self.buf.size() {
+            return false;
+        }
+        self.buf = create_buffer(device, self.label, size.next_power_of_two(), self.usage);
+        true
+    }
+}
+
+fn create_buffer(device: &Device, label: &str, size: u64, usage: BufferUsages) -> Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size,
+        usage,
+        mapped_at_creation: false,
+    })
+}
+
+/// Compute-based culler owning the storage and indirect buffers.
+pub struct GpuCuller {
+    pipeline: ComputePipeline,
+    bgl: BindGroupLayout,
+    bind_group: Option<BindGroup>,
+
+    params_buf: Buffer,
+    instances: GrowableBuffer,
+    batches: GrowableBuffer,
+    draws: GrowableBuffer,
+    visible: GrowableBuffer,
+
+    instance_count: u32,
+
+    /// Bound in place of the pyramid when occlusion is off.
+    dummy_hiz: TextureView,
+    /// Pyramid generation the bind group was built with.
+    bound_hiz: Option<u64>,
+
+    /// `INDIRECT_FIRST_INSTANCE`: draws can start at `first_instance` in a shared buffer.
+    pub first_instance_supported: bool,
+    /// `MULTI_DRAW_INDIRECT`: consecutive draws with equal state go into one call.
+    pub multi_draw_supported: bool,
+}
+
+impl GpuCuller {
+    /// Returns `None` when the device has no compute shaders or too few storage buffers.
+    pub fn new(device: &Device) -> Option<Self> {
+        let limits = device.limits();
+        if limits.max_storage_buffers_per_shader_stage < 4
+            || limits.max_compute_workgroup_size_x < WORKGROUP_SIZE
+        {
+            log::info!("L2: GPU culling unavailable (no compute/storage support)");
+            return None;
+        }
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Cull WGSL"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/cull.wgsl").into()),
+        });
+
+        let storage = |binding: u32, read_only: bool| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Cull BGL"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(std::mem::size_of::<CullParams>() as u64),
+                    },
+                    count: None,
+                },
+                storage(1, true),
+                storage(2, true),
+                storage(3, false),
+                storage(4, false),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Cull PipelineLayout"),
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("Cull Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+            cache: None,
+        });
+
+        let dummy_hiz = device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("Cull Dummy HiZ"),
+                size: wgpu::Extent3d {
+                    width: 1,
+                    height: 1,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: crate::hiz::HIZ_FORMAT,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let features = device.features();
+        let storage_dst = BufferUsages::STORAGE | BufferUsages::COPY_DST;
+
+        Some(Self {
+            pipeline,
+            bgl,
+            bind_group: None,
+            params_buf: create_buffer(
+                device,
+                "Cull Params",
+                std::mem::size_of::<CullParams>() as u64,
+                BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            ),
+            instances: GrowableBuffer::new(device, "Cull Instances", storage_dst),
+            batches: GrowableBuffer::new(device, "Cull Batches", storage_dst),
+            draws: GrowableBuffer::new(
+                device,
+                "Cull Indirect Args",
+                storage_dst | BufferUsages::INDIRECT,
+            ),
+            visible: GrowableBuffer::new(
+                device,
+                "Cull Visible Instances",
+                BufferUsages::STORAGE | BufferUsages::VERTEX,
+            ),
+            instance_count: 0,
+            dummy_hiz,
+            bound_hiz: None,
+            first_instance_supported: features.contains(wgpu::Features::INDIRECT_FIRST_INSTANCE),
+            multi_draw_supported: features.contains(wgpu::Features::MULTI_DRAW_INDIRECT),
+        })
+    }
+
+    /// Upload inputs for this frame. Returns `(uploaded_bytes, reallocations)`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        frustum: Option<&Frustum>,
+        occlusion: Option<&OcclusionInput<'_>>,
+        instances: &[GpuCullInstance],
+        batches: &[GpuCullBatch],
+        draws: &[DrawIndexedIndirectArgs],
+    ) -> (u64, u32) {
+        let reallocations = self.reserve(device, instances.len(), batches.len());
+        let uploaded = self.write_instances(queue, 0, instances)
+            + self.write_batches(queue, batches)
+            + self.write_frame(
+                device,
+                queue,
+                frustum,
+                occlusion,
+                instances.len() as u32,
+                draws,
+            );
+        (uploaded, reallocations)
+    }
+
+    /// L2: grow buffers for the given counts; returns the number of reallocations.
+    /// Reallocated buffers lose their contents, so callers must then re-upload everything.
+    pub fn reserve(&mut self, device: &Device, instance_count: usize, batch_count: usize) -> u32 {
+        let instance_size = (instance_count * std::mem::size_of::<GpuCullInstance>()) as u64;
+        let batch_size = (batch_count * std::mem::size_of::<GpuCullBatch>()) as u64;
+        let draw_size = batch_count as u64 * DrawIndexedIndirectArgs::SIZE;
+        let visible_size =
+            (instance_count.max(1) * std::mem::size_of::<crate::InstanceRaw>()) as u64;
+
+        let mut reallocations = 0;
+        for grown in [
+            self.instances.ensure(device, instance_size),
+            self.batches.ensure(device, batch_size),
+            self.draws.ensure(device, draw_size),
+            self.visible.ensure(device, visible_size),
+        ] {
+            if grown {
+                reallocations += 1;
+            }
+        }
+        if reallocations > 0 {
+            self.bind_group = None;
+        }
+        reallocations
+    }
+
+    /// L2: write instances starting at index `first` (partial updates). Returns bytes written.
+    pub fn write_instances(&self, queue: &Queue, first: u32, instances: &[GpuCullInstance]) -> u64 {
+        if instances.is_empty() {
+            return 0;
+        }
+        let offset = first as u64 * std::mem::size_of::<GpuCullInstance>() as u64;
+        let bytes: &[u8] = bytemuck::cast_slice(instances);
+        queue.write_buffer(&self.instances.buf, offset, bytes);
+        bytes.len() as u64
+    }
+
+    /// Write per-batch bounds/output regions. Returns bytes written.
+    pub fn write_batches(&self, queue: &Queue, batches: &[GpuCullBatch]) -> u64 {
+        if batches.is_empty() {
+            return 0;
+        }
+        let bytes: &[u8] = bytemuck::cast_slice(batches);
+        queue.write_buffer(&self.batches.buf, 0, bytes);
+        bytes.len() as u64
+    }
+
+    /// Per-frame part: reset indirect args (instance counts) and write cull params.
+    /// Returns bytes written.
+    pub fn write_frame(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        frustum: Option<&Frustum>,
+        occlusion: Option<&OcclusionInput<'_>>,
+        instance_count: u32,
+        draws: &[DrawIndexedIndirectArgs],
+    ) -> u64 {
+        let hiz_generation = occlusion.map(|o| o.generation);
+        if self.bind_group.is_none() || self.bound_hiz != hiz_generation {
+            self.bind_group = Some(self.create_bind_group(device, occlusion.map(|o| o.view)));
+            self.bound_hiz = hiz_generation;
+        }
+
+        let mut params = CullParams {
+            planes: [[0.0; 4]; 6],
+            instance_count,
+            cull_enabled: frustum.is_some() as u32,
+            occlusion_enabled: occlusion.is_some() as u32,
+            hiz_mips: 1,
+            hiz_view_proj: Mat4::IDENTITY.to_cols_array_2d(),
+            hiz_size: [1.0; 2],
+            _padding: [0; 2],
+        };
+        if let Some(frustum) = frustum {
+            for (dst, p) in params.planes.iter_mut().zip(&frustum.planes) {
+                *dst = [p.normal.x, p.normal.y, p.normal.z, p.d];
+            }
+        }
+        if let Some(o) = occlusion {
+            params.hiz_mips = o.mip_count.max(1);
+            params.hiz_view_proj = o.view_proj.to_cols_array_2d();
+            params.hiz_size = [o.size.0 as f32, o.size.1 as f32];
+        }
+
+        queue.write_buffer(&self.params_buf, 0, bytemuck::bytes_of(&params));
+        let draw_bytes: &[u8] = bytemuck::cast_slice(draws);
+        if !draw_bytes.is_empty() {
+            queue.write_buffer(&self.draws.buf, 0, draw_bytes);
+        }
+        self.instance_count = instance_count;
+        (std::mem::size_of::<CullParams>() + draw_bytes.len()) as u64
+    }
+
+    /// Record the cull dispatch into a compute pass.
+    pub fn dispatch(&self, cpass: &mut ComputePass<'_>) {
+        let Some(bind_group) = self.bind_group.as_ref() else {
+            return;
+        };
+        if self.instance_count == 0 {
+            return;
+        }
+        cpass.set_pipeline(&self.pipeline);
+        cpass.set_bind_group(0, bind_group, &[]);
+        cpass.dispatch_workgroups(self.instance_count.div_ceil(WORKGROUP_SIZE), 1, 1);
+    }
+
+    /// Indirect args buffer (one [`DrawIndexedIndirectArgs`] per batch).
+    pub fn draw_args_buffer(&self) -> &Buffer {
+        &self.draws.buf
+    }
+
+    /// Compacted visible instances, laid out like `InstanceRaw`.
+    pub fn visible_buffer(&self) -> &Buffer {
+        &self.visible.buf
+    }
+
+    fn create_bind_group(&self, device: &Device, hiz: Option<&TextureView>) -> BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Cull BG"),
+            layout: &self.bgl,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: self.params_buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.instances.buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: self.batches.buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: self.draws.buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 4,
+                    resource: self.visible.buf.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 5,
+                    resource: wgpu::BindingResource::TextureView(hiz.unwrap_or(&self.dummy_hiz)),
+                },
+            ],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gpu_struct_sizes_match_wgsl() {
+        // Must match cull.wgsl (std430/uniform layout rules).
+        assert_eq!(std::mem::size_of::<GpuCullInstance>(), 80);
+        assert_eq!(std::mem::size_of::<crate::InstanceRaw>(), 80);
+        assert_eq!(std::mem::size_of::<GpuCullBatch>(), 64);
+        assert_eq!(std::mem::size_of::<CullParams>(), 192);
+        assert_eq!(DrawIndexedIndirectArgs::SIZE, 20);
+    }
+
+    #[test]
+    fn cull_shader_validates() {
+        let module = naga::front::wgsl::parse_str(include_str!("shaders/cull.wgsl"))
+            .expect("cull.wgsl parses");
+        naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .expect("cull.wgsl validates");
+    }
+}
@@ -0,0 +1,610 @@
+//! L1: Hierarchical-Z depth pyramid for occlusion culling.
+//! Built by compute passes from the previous frame's depth buffer; a coarse mip is
+//! read back asynchronously for the CPU culling path.
+
+use std::num::NonZeroU64;
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+
+use bytemuck::{Pod, Zeroable};
+use corelib::Mat4;
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, BufferUsages, CommandEncoder, ComputePipeline, Device,
+    RenderPass, RenderPipeline, ShaderStages, Texture, TextureFormat, TextureUsages, TextureView,
+};
+
+use crate::culling::HiZSnapshot;
+use crate::profiler::GpuProfiler;
+
+/// Pyramid texel format (max depth per texel).
+pub const HIZ_FORMAT: TextureFormat = TextureFormat::R32Float;
+
+/// Mips are read back once they are at most this wide.
+const READBACK_MAX_WIDTH: u32 = 64;
+
+const WORKGROUP: u32 = 8;
+
+#[repr(C)]
+#[derive(Clone, Copy, Pod, Zeroable)]
+struct DebugParams {
+    mip: u32,
+    _padding: [u32; 3],
+}
+
+struct PendingReadback {
+    mapped: Arc<AtomicBool>,
+    requested: bool,
+    width: u32,
+    height: u32,
+    view_proj: Mat4,
+}
+
+/// Size-dependent GPU objects (recreated on resize).
+struct PyramidTargets {
+    texture: Texture,
+    full_view: TextureView,
+    width: u32,
+    height: u32,
+    mip_count: u32,
+    copy_bg: BindGroup,
+    down_bgs: Vec<BindGroup>,
+    debug_bg: BindGroup,
+    readback_mip: u32,
+    readback_buf: Buffer,
+    readback_bytes_per_row: u32,
+}
+
+/// Hi-Z pyramid owner.
+pub struct HiZPyramid {
+    copy_pipeline: ComputePipeline,
+    down_pipeline: ComputePipeline,
+    copy_bgl: BindGroupLayout,
+    down_bgl: BindGroupLayout,
+
+    debug_pipeline: RenderPipeline,
+    debug_bgl: BindGroupLayout,
+    debug_buf: Buffer,
+
+    targets: PyramidTargets,
+    /// Bumped when the pyramid texture is recreated (bind groups must be rebuilt).
+    generation: u64,
+
+    /// Clip matrix of the depth the pyramid was last built from.
+    view_proj: Mat4,
+    valid: bool,
+
+    pending: Option<PendingReadback>,
+    snapshot: HiZSnapshot,
+}
+
+impl HiZPyramid {
+    /// `None` if storage textures/compute are unavailable.
+    pub fn new(
+        device: &Device,
+        depth_view: &TextureView,
+        width: u32,
+        height: u32,
+        surface_format: TextureFormat,
+    ) -> Option<Self> {
+        let limits = device.limits();
+        if limits.max_storage_textures_per_shader_stage < 1
+            || limits.max_compute_workgroup_size_x < WORKGROUP
+        {
+            log::info!("L1: Hi-Z unavailable (no compute/storage textures)");
+            return None;
+        }
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("HiZ WGSL"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/hiz.wgsl").into()),
+        });
+
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::StorageTexture {
+                access: wgpu::StorageTextureAccess::WriteOnly,
+                format: HIZ_FORMAT,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        };
+        let copy_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("HiZ Copy BGL"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Depth,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                storage_entry(1),
+            ],
+        });
+        let down_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("HiZ Downsample BGL"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                storage_entry(3),
+            ],
+        });
+
+        let compute = |label, bgl: &BindGroupLayout, entry| {
+            let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[bgl],
+                push_constant_ranges: &[],
+            });
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                module: &shader,
+                entry_point: Some(entry),
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+                cache: None,
+            })
+        };
+        let copy_pipeline = compute("HiZ Copy", &copy_bgl, "cs_copy_depth");
+        let down_pipeline = compute("HiZ Downsample", &down_bgl, "cs_downsample");
+
+        // Debug view
+        let debug_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("HiZ Debug WGSL"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/hiz_debug.wgsl").into()),
+        });
+        let debug_bgl =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("HiZ Debug BGL"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: NonZeroU64::new(
+                                std::mem::size_of::<DebugParams>() as u64
+                            ),
+                        },
+                        count: None,
+                    },
+                ],
+            });
+        let debug_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("HiZ Debug PipelineLayout"),
+            bind_group_layouts: &[&debug_bgl],
+            push_constant_ranges: &[],
+        });
+        let debug_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("HiZ Debug Pipeline"),
+            layout: Some(&debug_layout),
+            vertex: wgpu::VertexState {
+                module: &debug_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &debug_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: surface_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: wgpu::PipelineCompilationOptions::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            // Рисуется в основном проходе: depth-формат должен совпадать.
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: crate::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+            cache: None,
+        });
+        let debug_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("HiZ Debug Params"),
+            size: std::mem::size_of::<DebugParams>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let targets = create_targets(
+            device, &copy_bgl, &down_bgl, &debug_bgl, &debug_buf, depth_view, width, height,
+        );
+
+        Some(Self {
+            copy_pipeline,
+            down_pipeline,
+            copy_bgl,
+            down_bgl,
+            debug_pipeline,
+            debug_bgl,
+            debug_buf,
+            targets,
+            generation: 0,
+            view_proj: Mat4::IDENTITY,
+            valid: false,
+            pending: None,
+            snapshot: HiZSnapshot::default(),
+        })
+    }
+
+    /// Recreate the pyramid for a new depth buffer. Invalidates occlusion data.
+    pub fn resize(&mut self, device: &Device, depth_view: &TextureView, width: u32, height: u32) {
+        // Старый readback-буфер уходит вместе с targets.
+        self.pending = None;
+        self.targets = create_targets(
+            device,
+            &self.copy_bgl,
+            &self.down_bgl,
+            &self.debug_bgl,
+            &self.debug_buf,
+            depth_view,
+            width,
+            height,
+        );
+        self.generation += 1;
+        self.valid = false;
+        self.snapshot = HiZSnapshot::default();
+    }
+
+    /// Build all mips from the depth buffer that was rendered with `view_proj`.
+    /// Also schedules a readback of a coarse mip if none is in flight.
+    pub fn build(
+        &mut self,
+        encoder: &mut CommandEncoder,
+        profiler: &mut GpuProfiler,
+        view_proj: Mat4,
+    ) {
+        let t = &self.targets;
+        let scope = profiler.begin_pass("HiZBuild");
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("HiZBuild"),
+                timestamp_writes: profiler.compute_timestamp_writes(scope),
+            });
+            cpass.set_pipeline(&self.copy_pipeline);
+            cpass.set_bind_group(0, &t.copy_bg, &[]);
+            cpass.dispatch_workgroups(t.width.div_ceil(WORKGROUP), t.height.div_ceil(WORKGROUP), 1);
+
+            cpass.set_pipeline(&self.down_pipeline);
+            for (i, bg) in t.down_bgs.iter().enumerate() {
+                let mip = i as u32 + 1;
+                let (w, h) = mip_size(t.width, t.height, mip);
+                cpass.set_bind_group(0, bg, &[]);
+                cpass.dispatch_workgroups(w.div_ceil(WORKGROUP), h.div_ceil(WORKGROUP), 1);
+            }
+        }
+        profiler.end_pass(scope);
+
+        self.view_proj = view_proj;
+        self.valid = true;
+
+        if self.pending.is_none() {
+            let (w, h) = mip_size(t.width, t.height, t.readback_mip);
+            encoder.copy_texture_to_buffer(
+                wgpu::ImageCopyTexture {
+                    texture: &t.texture,
+                    mip_level: t.readback_mip,
+                    origin: wgpu::Origin3d::ZERO,
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::ImageCopyBuffer {
+                    buffer: &t.readback_buf,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(t.readback_bytes_per_row),
+                        rows_per_image: Some(h),
+                    },
+                },
+                wgpu::Extent3d {
+                    width: w,
+                    height: h,
+                    depth_or_array_layers: 1,
+                },
+            );
+            self.pending = Some(PendingReadback {
+                mapped: Arc::new(AtomicBool::new(false)),
+                requested: false,
+                width: w,
+                height: h,
+                view_proj,
+            });
+        }
+    }
+
+    /// Start mapping the readback buffer. Call after `queue.submit`.
+    pub fn after_submit(&mut self) {
+        let Some(pending) = self.pending.as_mut() else {
+            return;
+        };
+        if pending.requested {
+            return;
+        }
+        pending.requested = true;
+        let flag = pending.mapped.clone();
+        self.targets
+            .readback_buf
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |res| {
+                if let Err(e) = res {
+                    log::warn!("L1: Hi-Z readback failed: {e:?}");
+                }
+                flag.store(true, Ordering::Release);
+            });
+    }
+
+    /// Poll the readback; refresh the CPU snapshot when it completes.
+    pub fn poll_readback(&mut self, device: &Device) {
+        let Some(pending) = self.pending.as_ref() else {
+            return;
+        };
+        device.poll(wgpu::Maintain::Poll);
+        if !pending.mapped.load(Ordering::Acquire) {
+            return;
+        }
+
+        let t = &self.targets;
+        let row_floats = (t.readback_bytes_per_row / 4) as usize;
+        {
+            let data = t.readback_buf.slice(..).get_mapped_range();
+            let floats: &[f32] = bytemuck::cast_slice(&data);
+            self.snapshot.depth.clear();
+            for y in 0..pending.height as usize {
+                let row = &floats[y * row_floats..y * row_floats + pending.width as usize];
+                self.snapshot.depth.extend_from_slice(row);
+            }
+        }
+        t.readback_buf.unmap();
+        self.snapshot.width = pending.width;
+        self.snapshot.height = pending.height;
+        self.snapshot.view_proj = pending.view_proj;
+        self.pending = None;
+    }
+
+    /// Latest CPU snapshot (empty until the first readback completes).
+    pub fn snapshot(&self) -> &HiZSnapshot {
+        &self.snapshot
+    }
+
+    /// `true` once the pyramid holds real depth data.
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+
+    pub fn view(&self) -> &TextureView {
+        &self.targets.full_view
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.targets.width, self.targets.height)
+    }
+
+    pub fn mip_count(&self) -> u32 {
+        self.targets.mip_count
+    }
+
+    /// Clip matrix of the depth the pyramid was built from.
+    pub fn view_proj(&self) -> Mat4 {
+        self.view_proj
+    }
+
+    /// Update the uniform for [`HiZPyramid::draw_debug`].
+    pub fn write_debug_params(&self, queue: &wgpu::Queue, mip: u32) {
+        queue.write_buffer(
+            &self.debug_buf,
+            0,
+            bytemuck::bytes_of(&DebugParams {
+                mip,
+                _padding: [0; 3],
+            }),
+        );
+    }
+
+    /// Draw the pyramid mip into the bottom-right quarter of the current pass.
+    pub fn draw_debug(&self, rpass: &mut RenderPass<'_>, target_width: u32, target_height: u32) {
+        let (w, h) = (target_width as f32 * 0.25, target_height as f32 * 0.25);
+        rpass.set_viewport(
+            target_width as f32 - w,
+            target_height as f32 - h,
+            w,
+            h,
+            0.0,
+            1.0,
+        );
+        rpass.set_pipeline(&self.debug_pipeline);
+        rpass.set_bind_group(0, &self.targets.debug_bg, &[]);
+        rpass.draw(0..3, 0..1);
+    }
+}
+
+/// Size of a mip level (each level halves, min 1).
+pub fn mip_size(width: u32, height: u32, mip: u32) -> (u32, u32) {
+    ((width >> mip).max(1), (height >> mip).max(1))
+}
+
+/// Number of mips down to 1x1.
+pub fn mip_count_for(width: u32, height: u32) -> u32 {
+    32 - width.max(height).max(1).leading_zeros()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_targets(
+    device: &Device,
+    copy_bgl: &BindGroupLayout,
+    down_bgl: &BindGroupLayout,
+    debug_bgl: &BindGroupLayout,
+    debug_buf: &Buffer,
+    depth_view: &TextureView,
+    width: u32,
+    height: u32,
+) -> PyramidTargets {
+    let width = width.max(1);
+    let height = height.max(1);
+    let mip_count = mip_count_for(width, height);
+    let texture = device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("HiZ Pyramid"),
+        size: wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: mip_count,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: HIZ_FORMAT,
+        usage: TextureUsages::STORAGE_BINDING
+            | TextureUsages::TEXTURE_BINDING
+            | TextureUsages::COPY_SRC,
+        view_formats: &[],
+    });
+    let mip_view = |mip| {
+        texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("HiZ Mip"),
+            base_mip_level: mip,
+            mip_level_count: Some(1),
+            ..Default::default()
+        })
+    };
+    let mip_views: Vec<TextureView> = (0..mip_count).map(mip_view).collect();
+    let full_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+    let copy_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("HiZ Copy BG"),
+        layout: copy_bgl,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(depth_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::TextureView(&mip_views[0]),
+            },
+        ],
+    });
+    let down_bgs = (1..mip_count as usize)
+        .map(|mip| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("HiZ Downsample BG"),
+                layout: down_bgl,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::TextureView(&mip_views[mip - 1]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 3,
+                        resource: wgpu::BindingResource::TextureView(&mip_views[mip]),
+                    },
+                ],
+            })
+        })
+        .collect();
+    let debug_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("HiZ Debug BG"),
+        layout: debug_bgl,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&full_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: debug_buf.as_entire_binding(),
+            },
+        ],
+    });
+
+    // Первый mip шириной <= READBACK_MAX_WIDTH
+    let readback_mip = (0..mip_count)
+        .find(|&m| mip_size(width, height, m).0 <= READBACK_MAX_WIDTH)
+        .unwrap_or(mip_count - 1);
+    let (rw, rh) = mip_size(width, height, readback_mip);
+    let readback_bytes_per_row =
+        (rw * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let readback_buf = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("HiZ Readback"),
+        size: (readback_bytes_per_row * rh) as u64,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    PyramidTargets {
+        texture,
+        full_view,
+        width,
+        height,
+        mip_count,
+        copy_bg,
+        down_bgs,
+        debug_bg,
+        readback_mip,
+        readback_buf,
+        readback_bytes_per_row,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mip_chain_sizes() {
+        assert_eq!(mip_count_for(1, 1), 1);
+        assert_eq!(mip_count_for(1280, 720), 11);
+        assert_eq!(mip_size(1280, 720, 10), (1, 1));
+        assert_eq!(mip_size(1280, 720, 5), (40, 22));
+    }
+
+    #[test]
+    fn hiz_shaders_validate() {
+        for src in [
+            include_str!("shaders/hiz.wgsl"),
+            include_str!("shaders/hiz_debug.wgsl"),
+        ] {
+            let module = naga::front::wgsl::parse_str(src).expect("parse");
+            naga::valid::Validator::new(
+                naga::valid::ValidationFlags::all(),
+                naga::valid::Capabilities::empty(),
+            )
+            .validate(&module)
+            .expect("validate");
+        }
+    }
+}
@@ -1,35 +1,105 @@
 //! Renderer: wgpu init + depth + cube.
 //! D1: camera/transform from `core` with setters.
 //! G2: Mini-FrameGraph system for explicit render passes.
-
+//! H4: GPU timestamp profiler with per-pass timings.
+//! L1: CPU frustum culling against per-mesh bounds.
+//! L2: GPU-driven culling with indirect draws (compute path).
+//! L1: Hi-Z occlusion culling from the previous frame's depth pyramid.
+//! L1: LOD groups with screen-size selection and dithered cross-fade.
+//! M1: meshes packed into shared vertex/index arenas (u16 indices where possible, submeshes).
+//! M1: per-mesh vertex layouts (optional streams, quantized formats) with matching pipelines.
+//! L2: retained-mode instances with dirty-range uploads.
+//! G1: opt-in multi-threaded draw preparation with a radix sort on draw keys.
+//! G2: generic compute shaders (standalone, per-frame or as FrameGraph passes).
+//! K3: GPU particle emitters (compute simulation, sorted/additive billboards).
+//! J2: main shader loaded from `assets/shaders` with hot-reload and error recovery.
+//! J2: WGSL `#include`/`#ifdef` preprocessing with a permutation cache.
+//! J2: main bind group layouts reflected from the shader (naga), resources bound by slot.
+//! J2: data-driven materials from `.material.ron`/`.material.json` files with hot reload.
+//! J2: node-based material graphs compiled to WGSL (`material_graph`).
+//! I1: UI overlays drawn over the finished frame (`overlay`).
+//! J1: skinned and morph target meshes animated by ECS animators (compute skinning pre-pass, CPU fallback).
+
+pub mod compute;
+pub mod culling;
 pub mod framegraph;
-
-use std::num::NonZeroU64;
-use std::sync::Arc;
+pub mod gpu_culling;
+pub mod hiz;
+pub mod lod;
+pub mod material;
+pub mod material_graph;
+pub mod mesh_arena;
+pub mod overlay;
+pub mod parallel;
+pub mod particles;
+pub mod preprocess;
+pub mod profiler;
+pub mod reflect;
+pub mod retained;
+pub mod shader;
+pub mod skinning;
+pub mod stats;
+pub mod vertex_layout;
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
+use crate::compute::{
+    ComputeDispatch, ComputeError, ComputeRegistry, ComputeResource, ComputeShaderDesc,
+    ComputeShaderId,
+};
+use crate::culling::{HiZSnapshot, WorldBounds};
 use crate::framegraph::{FrameGraph, ResourceDesc};
+use crate::gpu_culling::{
+    DrawIndexedIndirectArgs, GpuCullBatch, GpuCullInstance, GpuCuller, OcclusionInput,
+};
+use crate::hiz::HiZPyramid;
+use crate::lod::{LodContext, LodDraw, LodGroup, LodLevel, LodSettings, LodStore};
+use crate::material::{MaterialError, MaterialGpu, MaterialLibrary, MaterialSource};
+use crate::material_graph::compile_graph;
+use crate::mesh_arena::BufferArena;
+use crate::overlay::Overlay;
+use crate::parallel::SortItem;
+use crate::particles::ParticleSystem;
+use crate::preprocess::{ShaderDefs, shader_defs};
+use crate::profiler::GpuProfiler;
+use crate::reflect::ShaderReflection;
+use crate::retained::{RetainedInstances, RetainedUpdate, RetainedVisibility, Visibility};
+use crate::shader::{ShaderError, ShaderId, ShaderManager, ShaderUpdate};
+use crate::skinning::{SkinnedMesh, SkinningSystem};
+use crate::stats::RenderStats;
+use crate::vertex_layout::VertexLayouts;
 
 use asset::{
-    mesh::{MeshData, MeshVertex},
-    texture::TextureData,
+    animation::{AnimationClip, Joint, JointTransform, Skeleton, SkinWeights},
+    lod::MeshLods,
+    material::{AlphaMode, CullMode, MaterialDesc},
+    material_graph::MaterialGraph,
+    mesh::{MeshBounds, MeshData, MeshVertex},
+    mesh_bin::MeshFile,
+    texture::{AddressMode, FilterMode, SamplerDesc, TextureData},
+    vertex::{LayoutOptions, VertexLayout},
 };
 use bytemuck::{Pod, Zeroable};
 use corelib::{
     Mat4, Vec3,
+    animation::Animator,
     camera::Camera,
-    ecs::{MaterialId, MeshId, TextureId},
+    ecs::{Entity, LodGroupId, LodState, MaterialId, MeshId, SkinId, TextureId},
+    frustum::Frustum,
+    particles::ParticleEmitter,
     transform::Transform,
 };
 use wgpu::{
-    BindGroup, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType,
-    BlendState, Buffer, BufferBindingType, BufferUsages, ColorTargetState, ColorWrites,
-    CommandEncoderDescriptor, DepthBiasState, DepthStencilState, Device, Extent3d, FragmentState,
-    Instance, InstanceDescriptor, LoadOp, Operations, PipelineLayoutDescriptor, PowerPreference,
-    PresentMode, Queue, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline,
-    RenderPipelineDescriptor, Sampler, SamplerDescriptor, ShaderModuleDescriptor, ShaderSource,
-    ShaderStages, StoreOp, Surface, SurfaceConfiguration, SurfaceError, TextureDescriptor,
-    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView, TextureViewDescriptor,
+    BindGroup, BindGroupLayoutDescriptor, BlendState, Buffer, BufferUsages, ColorTargetState,
+    ColorWrites, CommandEncoderDescriptor, DepthBiasState, DepthStencilState, Device, Extent3d,
+    FragmentState, Instance, InstanceDescriptor, LoadOp, Operations, PipelineLayoutDescriptor,
+    PowerPreference, PresentMode, Queue, RenderPassColorAttachment, RenderPassDescriptor,
+    RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerDescriptor, ShaderModuleDescriptor,
+    ShaderSource, StoreOp, Surface, SurfaceConfiguration, SurfaceError, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsages, TextureView, TextureViewDescriptor,
     TextureViewDimension, VertexBufferLayout, VertexState, VertexStepMode, util::DeviceExt,
 };
 use winit::{dpi::PhysicalSize, window::Window};
@@ -51,6 +121,9 @@ impl Vertex {
     };
 }
 
+// M1: mesh files upload `MeshVertex` bytes as-is (same fields, same order)
+const _: () = assert!(std::mem::size_of::<Vertex>() == std::mem::size_of::<MeshVertex>());
+
 impl From<MeshVertex> for Vertex {
     fn from(v: MeshVertex) -> Self {
         Self {
@@ -68,26 +141,44 @@ pub struct DrawInstance {
     pub mesh: MeshId,
     pub material: MaterialId,
     pub texture: TextureId,
+    /// L1: dithered LOD cross-fade; 1.0 = fully visible (see [`InstanceRaw::lod`]).
+    pub lod_fade: f32,
 }
 
 impl DrawInstance {
-    pub fn new(transform: Transform, mesh: MeshId, material: MaterialId, texture: TextureId) -> Self {
+    pub fn new(
+        transform: Transform,
+        mesh: MeshId,
+        material: MaterialId,
+        texture: TextureId,
+    ) -> Self {
         Self {
             transform,
             mesh,
             material,
             texture,
+            lod_fade: 1.0,
         }
     }
 
-    pub fn new_with_default_texture(transform: Transform, mesh: MeshId, material: MaterialId) -> Self {
+    pub fn new_with_default_texture(
+        transform: Transform,
+        mesh: MeshId,
+        material: MaterialId,
+    ) -> Self {
         Self {
             transform,
             mesh,
             material,
             texture: TextureId::INVALID, // Will be replaced with default texture
+            lod_fade: 1.0,
         }
     }
+
+    pub fn with_lod_fade(mut self, lod_fade: f32) -> Self {
+        self.lod_fade = lod_fade;
+        self
+    }
 }
 
 /// Per-instance model matrix as 4 **columns** (WGSL mat4x4 expects columns).
@@ -98,6 +189,9 @@ pub struct InstanceRaw {
     pub col1: [f32; 4],
     pub col2: [f32; 4],
     pub col3: [f32; 4],
+    /// L1: x = LOD fade. In [0,1): incoming level, dither coverage x.
+    /// In [1,2]: outgoing level, complementary coverage; 1.0 = no fade. yzw unused.
+    pub lod: [f32; 4],
 }
 
 impl InstanceRaw {
@@ -109,10 +203,15 @@ impl InstanceRaw {
             4 => Float32x4, // col1
             5 => Float32x4, // col2
             6 => Float32x4, // col3
+            7 => Float32x4, // lod
         ],
     };
 
     pub fn from_model(m: Mat4) -> Self {
+        Self::from_model_faded(m, 1.0)
+    }
+
+    pub fn from_model_faded(m: Mat4, lod_fade: f32) -> Self {
         // glam::Mat4 хранится в column-major; берём колонки напрямую.
         let c = m.to_cols_array();
         Self {
@@ -120,79 +219,252 @@ impl InstanceRaw {
             col1: [c[4], c[5], c[6], c[7]],
             col2: [c[8], c[9], c[10], c[11]],
             col3: [c[12], c[13], c[14], c[15]],
+            lod: [lod_fade, 0.0, 0.0, 0.0],
         }
     }
+
+    pub fn model(&self) -> Mat4 {
+        Mat4::from_cols_array_2d(&[self.col0, self.col1, self.col2, self.col3])
+    }
+}
+
+// M1: arenas of `MeshStore`; skinned meshes get a dedicated storage-capable block each
+// (the skinning pass writes their vertices from offset 0)
+const VERTEX_ARENA: usize = 0;
+const SKINNED_ARENA: usize = 1;
+const INDEX16_ARENA: usize = 2;
+const INDEX32_ARENA: usize = 3;
+
+/// One buffer of one of the mesh arenas.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ArenaBlock {
+    arena: usize,
+    block: usize,
+}
+
+/// Vertex data of one upload.
+struct VertexBytes<'a> {
+    bytes: &'a [u8],
+    layout: &'a VertexLayout,
+    /// Dedicated storage-capable buffer (see [`SKINNED_ARENA`]); [`Vertex`] layout only.
+    skinned: bool,
 }
 
 struct MeshGpu {
-    vertex_buf: Buffer,
-    index_buf: Buffer,
+    /// M1: id in `MeshStore::layouts`
+    layout: usize,
+    vertex_block: ArenaBlock,
+    index_block: ArenaBlock,
+    base_vertex: i32,
+    first_index: u32,
     index_count: u32,
     index_format: wgpu::IndexFormat,
+    bounds: MeshBounds,
 }
 
 struct MeshStore {
     meshes: Vec<MeshGpu>,
+    arenas: [BufferArena; 4],
+    layouts: VertexLayouts,
 }
 
 impl MeshStore {
-    fn new() -> Self {
-        Self { meshes: Vec::new() }
+    /// Without `base_vertex` support (GLES 3.0 / WebGL2) every mesh gets its own vertex block,
+    /// so it starts at offset 0 and draws with `base_vertex = 0`.
+    fn new(base_vertex: bool) -> Self {
+        let vertex_block_bytes = if base_vertex {
+            mesh_arena::VERTEX_BLOCK_BYTES
+        } else {
+            0
+        };
+        Self {
+            meshes: Vec::new(),
+            arenas: [
+                BufferArena::new("Mesh VB arena", BufferUsages::VERTEX, vertex_block_bytes),
+                BufferArena::new(
+                    "Skinned VB",
+                    BufferUsages::VERTEX | BufferUsages::STORAGE,
+                    0,
+                ),
+                BufferArena::new(
+                    "Mesh IB arena u16",
+                    BufferUsages::INDEX,
+                    mesh_arena::INDEX_BLOCK_BYTES,
+                ),
+                BufferArena::new(
+                    "Mesh IB arena u32",
+                    BufferUsages::INDEX,
+                    mesh_arena::INDEX_BLOCK_BYTES,
+                ),
+            ],
+            layouts: VertexLayouts::new(),
+        }
     }
 
-    fn add_mesh(&mut self, device: &Device, label: &str, mesh: &MeshData) -> MeshId {
-        assert!(mesh.is_valid(), "Mesh must contain vertices and indices");
-
-        let vertices: Vec<Vertex> = mesh.vertices.iter().copied().map(Vertex::from).collect();
-        let indices: &[u32] = &mesh.indices;
-
-        let vertex_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some(&format!("{label} VB")),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: BufferUsages::VERTEX,
-        });
-
-        let index_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some(&format!("{label} IB")),
-            contents: bytemuck::cast_slice(indices),
-            usage: BufferUsages::INDEX,
-        });
-
-        let index_count = u32::try_from(indices.len()).expect("index count exceeds u32");
+    fn add_mesh(&mut self, device: &Device, queue: &Queue, mesh: &MeshData) -> MeshId {
+        let whole = 0..mesh.indices.len();
+        self.add_submeshes(
+            device,
+            queue,
+            mesh,
+            std::slice::from_ref(&whole),
+            Some(&VertexLayout::default()),
+        )
+        .expect("every mesh fits the default layout")[0]
+    }
 
-        let id_raw = u32::try_from(self.meshes.len()).expect("Too many meshes");
-        let id = MeshId::new(id_raw);
-        self.meshes.push(MeshGpu {
-            vertex_buf,
-            index_buf,
-            index_count,
-            index_format: wgpu::IndexFormat::Uint32,
-        });
+    /// Upload `mesh` once in `layout` and register one mesh per index range (sharing its base
+    /// vertex). `None` is a skinned mesh: [`Vertex`] layout in its own storage-capable buffer.
+    fn add_submeshes(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        mesh: &MeshData,
+        ranges: &[std::ops::Range<usize>],
+        layout: Option<&VertexLayout>,
+    ) -> Result<Vec<MeshId>, String> {
+        assert!(mesh.is_valid(), "Mesh must contain vertices and indices");
+        let skinned = layout.is_none();
+        let default = VertexLayout::default();
+        let layout = layout.unwrap_or(&default);
+
+        // `Vertex` has the `MeshVertex` layout, so plain meshes upload without repacking
+        let encoded;
+        let bytes = if layout.is_mesh_vertex() {
+            bytemuck::cast_slice(&mesh.vertices)
+        } else {
+            encoded = layout.encode(mesh).map_err(|e| format!("{e:#}"))?;
+            &encoded[..]
+        };
+        let ranges: Vec<_> = ranges
+            .iter()
+            .map(|range| {
+                let bounds = if *range == (0..mesh.indices.len()) {
+                    mesh.bounds
+                } else {
+                    let used: Vec<MeshVertex> = mesh.indices[range.clone()]
+                        .iter()
+                        .map(|&i| mesh.vertices[i as usize])
+                        .collect();
+                    MeshBounds::from_vertices(&used)
+                };
+                (range.clone(), bounds)
+            })
+            .collect();
+        let vertices = VertexBytes {
+            bytes,
+            layout,
+            skinned,
+        };
+        Ok(self.add_mesh_bytes(device, queue, vertices, &mesh.indices, &ranges))
+    }
 
-        id
+    /// M1: upload vertices already in their final layout (e.g. straight from a mapped file);
+    /// `ranges` are index ranges with their bounds, one mesh each.
+    fn add_mesh_bytes(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        vertices: VertexBytes,
+        indices: &[u32],
+        ranges: &[(std::ops::Range<usize>, MeshBounds)],
+    ) -> Vec<MeshId> {
+        let layout = self.layouts.id(vertices.layout);
+        let stride = u64::from(vertices.layout.stride);
+        let vertex_arena = if vertices.skinned {
+            SKINNED_ARENA
+        } else {
+            VERTEX_ARENA
+        };
+        // Stride-aligned, so meshes of any layout can share a block
+        let vertex_alloc = self.arenas[vertex_arena].upload(device, queue, vertices.bytes, stride);
+
+        // Indices stay relative to the mesh's own vertices, so the format only depends on its size
+        let index_format = mesh_arena::index_format_for(vertices.bytes.len() / stride as usize);
+        let (index_arena, index_size) = match index_format {
+            wgpu::IndexFormat::Uint16 => (INDEX16_ARENA, 2),
+            wgpu::IndexFormat::Uint32 => (INDEX32_ARENA, 4),
+        };
+        let index_data = mesh_arena::index_bytes(indices, index_format);
+        let first = self.arenas[index_arena].upload(device, queue, &index_data, 4);
+
+        let base_vertex =
+            i32::try_from(vertex_alloc.offset / stride).expect("vertex arena exceeds i32 range");
+        let first_index =
+            u32::try_from(first.offset / index_size).expect("index arena exceeds u32 range");
+        ranges
+            .iter()
+            .map(|(range, bounds)| {
+                let id = MeshId::new(u32::try_from(self.meshes.len()).expect("Too many meshes"));
+                self.meshes.push(MeshGpu {
+                    layout,
+                    vertex_block: ArenaBlock {
+                        arena: vertex_arena,
+                        block: vertex_alloc.block,
+                    },
+                    index_block: ArenaBlock {
+                        arena: index_arena,
+                        block: first.block,
+                    },
+                    base_vertex,
+                    first_index: first_index
+                        + u32::try_from(range.start).expect("index count exceeds u32"),
+                    index_count: u32::try_from(range.len()).expect("index count exceeds u32"),
+                    index_format,
+                    bounds: *bounds,
+                });
+                id
+            })
+            .collect()
     }
 
     fn get(&self, id: MeshId) -> Option<&MeshGpu> {
         self.meshes.get(id.0 as usize)
     }
-}
 
-struct TextureGpu {
-    view: TextureView,
-    sampler: Sampler,
-}
+    fn get_mut(&mut self, id: MeshId) -> Option<&mut MeshGpu> {
+        self.meshes.get_mut(id.0 as usize)
+    }
 
-struct TextureStore {
-    textures: Vec<TextureGpu>,
+    fn buffer(&self, block: ArenaBlock) -> &Buffer {
+        self.arenas[block.arena].buffer(block.block)
+    }
 }
 
-impl TextureStore {
-    fn new() -> Self {
-        Self { textures: Vec::new() }
+impl MeshGpu {
+    /// L2: same vertex/index buffers and vertex layout — draws can be merged into one multi-draw.
+    fn shares_buffers(&self, other: &MeshGpu) -> bool {
+        self.layout == other.layout
+            && self.vertex_block == other.vertex_block
+            && self.index_block == other.index_block
+            && self.index_format == other.index_format
     }
+}
+
+pub(crate) struct TextureGpu {
+    view: TextureView,
+    sampler: Arc<Sampler>,
+    /// Bytes uploaded, generated mips included
+    bytes: u64,
+}
 
-    fn add_texture(&mut self, device: &Device, queue: &Queue, label: &str, data: &TextureData) -> TextureId {
+impl TextureGpu {
+    /// Upload RGBA8 sRGB texture data; E2: single-level data gets a mip chain generated first.
+    pub(crate) fn new(
+        device: &Device,
+        queue: &Queue,
+        label: &str,
+        data: &TextureData,
+        sampler: Arc<Sampler>,
+    ) -> Self {
         assert!(data.is_valid(), "Texture data must be valid");
+        let mipped;
+        let data = if data.mip_levels > 1 {
+            data
+        } else {
+            mipped = data.clone().with_mips();
+            &mipped
+        };
 
         let texture = device.create_texture_with_data(
             queue,
@@ -203,7 +475,7 @@ impl TextureStore {
                     height: data.height,
                     depth_or_array_layers: 1,
                 },
-                mip_level_count: 1,
+                mip_level_count: data.mip_levels,
                 sample_count: 1,
                 dimension: TextureDimension::D2,
                 format: TextureFormat::Rgba8UnormSrgb,
@@ -225,22 +497,82 @@ impl TextureStore {
             array_layer_count: None,
         });
 
-        let sampler = device.create_sampler(&SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Linear,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
+        Self {
+            view,
+            sampler,
+            bytes: data.data.len() as u64,
+        }
+    }
+}
+
+/// E2: samplers deduplicated by descriptor (shared by textures and material files).
+/// Behind a mutex so materials can be built through `&GpuState`.
+struct SamplerCache {
+    samplers: Mutex<HashMap<SamplerDesc, Arc<Sampler>>>,
+}
+
+impl SamplerCache {
+    fn new() -> Self {
+        Self {
+            samplers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, device: &Device, desc: &SamplerDesc) -> Arc<Sampler> {
+        let mut samplers = self.samplers.lock().expect("sampler cache poisoned");
+        let sampler = samplers.entry(*desc).or_insert_with(|| {
+            let address = |mode| match mode {
+                AddressMode::Repeat => wgpu::AddressMode::Repeat,
+                AddressMode::MirrorRepeat => wgpu::AddressMode::MirrorRepeat,
+                AddressMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+            };
+            let filter = |mode| match mode {
+                FilterMode::Nearest => wgpu::FilterMode::Nearest,
+                FilterMode::Linear => wgpu::FilterMode::Linear,
+            };
+            Arc::new(device.create_sampler(&SamplerDescriptor {
+                label: Some("Texture Sampler"),
+                address_mode_u: address(desc.address_u),
+                address_mode_v: address(desc.address_v),
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: filter(desc.mag_filter),
+                min_filter: filter(desc.min_filter),
+                mipmap_filter: filter(desc.mipmap_filter),
+                anisotropy_clamp: desc.anisotropy_clamp(),
+                lod_max_clamp: if desc.mipmaps { 32.0 } else { 0.0 },
+                ..Default::default()
+            }))
         });
+        Arc::clone(sampler)
+    }
+}
+
+struct TextureStore {
+    textures: Vec<TextureGpu>,
+    samplers: SamplerCache,
+}
+
+impl TextureStore {
+    fn new() -> Self {
+        Self {
+            textures: Vec::new(),
+            samplers: SamplerCache::new(),
+        }
+    }
 
+    fn add_texture(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        label: &str,
+        data: &TextureData,
+        sampler: &SamplerDesc,
+    ) -> TextureId {
+        let sampler = self.samplers.get(device, sampler);
+        let texture = TextureGpu::new(device, queue, label, data, sampler);
         let id_raw = u32::try_from(self.textures.len()).expect("Too many textures");
         let id = TextureId::new(id_raw);
-        self.textures.push(TextureGpu {
-            view,
-            sampler,
-        });
+        self.textures.push(texture);
 
         id
     }
@@ -252,9 +584,9 @@ impl TextureStore {
 
 /// Sorting key for draw commands to minimize state changes.
 /// Sort order: PSO (Pipeline) -> Material -> Texture -> Mesh -> Instance
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 struct DrawKey {
-    pso_id: u32,       // Pipeline state object (currently always 0)
+    pso_id: u32, // Pipeline state object (currently always 0)
     material: MaterialId,
     texture: TextureId,
     mesh: MeshId,
@@ -266,6 +598,68 @@ struct InstanceEntry {
     instance: InstanceRaw,
 }
 
+/// G1: per-thread output of CPU draw preparation.
+#[derive(Default)]
+struct PrepChunk {
+    entries: Vec<InstanceEntry>,
+    culled: u32,
+    occluded: u32,
+}
+
+/// Read-only state shared by the draw preparation threads.
+struct PrepContext<'a> {
+    meshes: &'a MeshStore,
+    frustum: Option<&'a Frustum>,
+    hiz: Option<&'a HiZSnapshot>,
+    default_texture: TextureId,
+}
+
+impl PrepContext<'_> {
+    /// Build, cull and key one instance into `out`.
+    fn prepare(&self, item: &DrawInstance, out: &mut PrepChunk) {
+        let model = item.transform.matrix();
+
+        // L1: reject instances outside the frustum, then those hidden in the Hi-Z snapshot
+        if let Some(mesh) = self.meshes.get(item.mesh)
+            && (self.frustum.is_some() || self.hiz.is_some())
+        {
+            let bounds = WorldBounds::from_local(&mesh.bounds, model);
+            if let Some(frustum) = self.frustum
+                && !bounds.is_visible(frustum)
+            {
+                out.culled += 1;
+                return;
+            }
+            if let Some(hiz) = self.hiz
+                && hiz.is_occluded(&bounds)
+            {
+                out.occluded += 1;
+                return;
+            }
+        }
+
+        // Replace INVALID texture with default texture
+        let texture = if item.texture == TextureId::INVALID {
+            self.default_texture
+        } else {
+            item.texture
+        };
+
+        let key = DrawKey {
+            pso_id: 0, // Currently only one PSO
+            material: item.material,
+            texture,
+            mesh: item.mesh,
+        };
+
+        out.entries.push(InstanceEntry {
+            key,
+            instance: InstanceRaw::from_model_faded(model, item.lod_fade),
+        });
+    }
+}
+
+#[derive(Clone, Copy)]
 struct DrawBatch {
     key: DrawKey,
     start: usize,
@@ -283,9 +677,11 @@ struct CameraUniform {
 #[repr(C)]
 #[derive(Clone, Copy, Pod, Zeroable)]
 pub struct MaterialUniform {
-    pub base_color: [f32; 4],    // RGBA albedo
+    pub base_color: [f32; 4],         // RGBA albedo
     pub metallic_roughness: [f32; 2], // metallic, roughness
-    pub _padding: [f32; 2],      // Pad to 16 bytes
+    pub alpha_cutoff: f32,            // J2: used by the ALPHA_MASK permutation
+    pub _padding: f32,                // Pad to 16 bytes
+    pub emissive: [f32; 4],           // J2: RGB emission, w unused
 }
 
 impl Default for MaterialUniform {
@@ -293,7 +689,25 @@ impl Default for MaterialUniform {
         Self {
             base_color: [0.8, 0.8, 0.9, 1.0],
             metallic_roughness: [0.0, 0.5],
-            _padding: [0.0, 0.0],
+            alpha_cutoff: 0.5,
+            _padding: 0.0,
+            emissive: [0.0; 4],
+        }
+    }
+}
+
+impl From<&MaterialDesc> for MaterialUniform {
+    fn from(desc: &MaterialDesc) -> Self {
+        let [r, g, b] = desc.emissive;
+        Self {
+            base_color: desc.base_color,
+            metallic_roughness: [desc.metallic, desc.roughness],
+            alpha_cutoff: match desc.alpha_mode {
+                AlphaMode::Mask(cutoff) => cutoff,
+                _ => 0.5,
+            },
+            _padding: 0.0,
+            emissive: [r, g, b, 0.0],
         }
     }
 }
@@ -321,6 +735,27 @@ impl Default for LightingUniform {
 
 const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth24Plus;
 
+/// J2: main shader file in the shader directory and its embedded fallback.
+pub const MAIN_SHADER_NAME: &str = "triangle.wgsl";
+pub const MAIN_SHADER_SOURCE: &str = include_str!("../../../assets/shaders/triangle.wgsl");
+
+/// J2: permutation of the main shader used by the cube pipeline.
+pub fn main_shader_defs() -> ShaderDefs {
+    shader_defs(&["LOD_CROSSFADE"])
+}
+
+/// J2: permutation a material file is built with (main defines + its alpha mode and graph).
+pub fn material_shader_defs(desc: &MaterialDesc) -> ShaderDefs {
+    let mut defs = main_shader_defs();
+    if matches!(desc.alpha_mode, AlphaMode::Mask(_)) {
+        defs.insert("ALPHA_MASK".to_string(), String::new());
+    }
+    if desc.graph.is_some() {
+        defs.insert("MATERIAL_GRAPH".to_string(), String::new());
+    }
+    defs
+}
+
 /// Converts OpenGL clip space (z in [-1,1]) to WGPU/D3D clip (z in [0,1]).
 const OPENGL_TO_WGPU: Mat4 = Mat4::from_cols_array(&[
     1.0, 0.0, 0.0, 0.0, //
@@ -342,6 +777,15 @@ pub struct GpuState {
 
     // Pipeline & geometry
     pipeline: RenderPipeline,
+    pipeline_layout: wgpu::PipelineLayout,
+    // J2: shader sources (embedded or from disk) + the main shader handle
+    shaders: ShaderManager,
+    main_shader: ShaderId,
+    // J2: materials loaded from files (MaterialId 0 = the default material above)
+    materials: MaterialLibrary,
+    // M1: (material, vertex layout) pipelines for meshes outside the default layout
+    // (`None` = failed to build); cleared whenever materials or shaders are rebuilt
+    layout_pipelines: HashMap<(MaterialId, usize), Option<RenderPipeline>>,
     mesh_store: MeshStore,
     cube_mesh_id: MeshId,
     texture_store: TextureStore,
@@ -350,6 +794,12 @@ pub struct GpuState {
     instance_capacity: u32,
     instance_count: u32,
     instance_entries: Vec<InstanceEntry>,
+    // G1: multi-threaded preparation scratch (reused between frames)
+    draw_prep_threads: usize,
+    prep_chunks: Vec<PrepChunk>,
+    sorted_entries: Vec<InstanceEntry>,
+    sort_items: Vec<SortItem>,
+    sort_scratch: Vec<SortItem>,
     draw_batches: Vec<DrawBatch>,
     instance_data: Vec<InstanceRaw>,
 
@@ -363,7 +813,11 @@ pub struct GpuState {
     material_bg: BindGroup,
     material_buf: Buffer,
     lighting_buf: Buffer,
-    texture_bg: BindGroup,
+    // E2: group 2 per uploaded texture (indexed by `TextureId`), built with `texture_bgl`
+    // for the group-2 bindings the main shader declares
+    texture_bgs: Vec<BindGroup>,
+    texture_bgl: wgpu::BindGroupLayout,
+    texture_bindings: Vec<u32>,
 
     // Depth
     depth_view: TextureView,
@@ -371,6 +825,57 @@ pub struct GpuState {
     // G2: FrameGraph system
     framegraph: FrameGraph,
 
+    // G2: registered compute shaders + dispatches queued for the next frame
+    compute: ComputeRegistry,
+    compute_supported: bool,
+    pending_compute: Vec<(String, ComputeDispatch)>,
+
+    // K3: GPU particles (None without compute support)
+    particles: Option<ParticleSystem>,
+    // J1: skinned meshes (compute pre-pass or CPU skinning)
+    skinning: SkinningSystem,
+
+    // H4: per-pass profiler
+    profiler: GpuProfiler,
+
+    // Bytes uploaded since the last rendered frame (reported in RenderStats)
+    pending_upload_bytes: u64,
+
+    // L1: CPU frustum culling toggle
+    frustum_culling: bool,
+
+    // L2: GPU-driven culling (None if compute is unsupported)
+    gpu_culler: Option<GpuCuller>,
+    gpu_culling: bool,
+    batch_lookup: HashMap<DrawKey, u32>,
+    gpu_instances: Vec<GpuCullInstance>,
+    // G1: draw-list index per GPU instance (parallel matrix fill)
+    gpu_sources: Vec<u32>,
+    gpu_batches: Vec<GpuCullBatch>,
+    gpu_draw_args: Vec<DrawIndexedIndirectArgs>,
+
+    // L1: Hi-Z occlusion (None if compute/storage textures are unsupported)
+    hiz: Option<HiZPyramid>,
+    occlusion_culling: bool,
+    hiz_debug_mip: Option<u32>,
+    // Depth buffer holds a rendered frame (a fresh one is all zeros and would occlude everything)
+    depth_valid: bool,
+    // WGPU clip matrix of the last rendered frame
+    prev_view_proj: Mat4,
+
+    // L1: LOD groups
+    lod_store: LodStore,
+    lod_settings: LodSettings,
+
+    // L2: retained instances; `retained_path` = (GPU culling, CPU culling) of the path
+    // holding their last full upload
+    retained: RetainedInstances,
+    retained_path: Option<(bool, bool)>,
+    // L1: CPU culling of retained instances; `retained_view` = (camera, frustum, occlusion)
+    // their visibility was last tested with
+    retained_visibility: RetainedVisibility,
+    retained_view: Option<(Camera, bool, bool)>,
+
     // Time (only for FPS in platform; left here in case we need timers)
     #[allow(dead_code)]
     start: Instant,
@@ -456,14 +961,32 @@ impl GpuState {
 
         let surface = surface_opt.expect("surface is None");
 
+        // H4: timestamps are optional — profiler falls back to CPU timing without them.
+        // L2: indirect features are optional too — GPU culling degrades to single draws.
+        let required_features = adapter.features()
+            & (wgpu::Features::TIMESTAMP_QUERY
+                | wgpu::Features::INDIRECT_FIRST_INSTANCE
+                | wgpu::Features::MULTI_DRAW_INDIRECT);
+        // L2: compute-capable adapters get downlevel (storage/compute) limits, GLES stays on WebGL2.
+        let base_limits = if adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        {
+            wgpu::Limits::downlevel_defaults()
+        } else {
+            wgpu::Limits::downlevel_webgl2_defaults()
+        };
         let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                label: Some("Svarog3D Device"),
-                required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::downlevel_webgl2_defaults()
-                    .using_resolution(adapter.limits()),
-                memory_hints: Default::default(),
-            }, None)
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: Some("Svarog3D Device"),
+                    required_features,
+                    required_limits: base_limits.using_resolution(adapter.limits()),
+                    memory_hints: Default::default(),
+                },
+                None,
+            )
             .await
             .expect("request_device failed");
 
@@ -492,29 +1015,9 @@ impl GpuState {
         // Depth texture
         let depth_view = create_depth_view(&device, &surface_config);
 
-        // Shaders
-        let shader_src: &str = include_str!("shaders/triangle.wgsl");
-        let shader = device.create_shader_module(ShaderModuleDescriptor {
-            label: Some("Basic WGSL"),
-            source: ShaderSource::Wgsl(shader_src.into()),
-        });
-
-        // Camera BGL/BG
-        let camera_bgl = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            label: Some("Camera BGL"),
-            entries: &[BindGroupLayoutEntry {
-                binding: 0,
-                visibility: ShaderStages::VERTEX,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: Some(
-                        NonZeroU64::new(std::mem::size_of::<CameraUniform>() as u64).unwrap(),
-                    ),
-                },
-                count: None,
-            }],
-        });
+        // Shaders (J2: embedded until a shader directory is set)
+        let mut shaders = ShaderManager::with_embedded_includes();
+        let main_shader = shaders.register(MAIN_SHADER_NAME, MAIN_SHADER_SOURCE);
 
         let camera_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Camera UBO"),
@@ -523,45 +1026,6 @@ impl GpuState {
             }),
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
-        let camera_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Camera BG"),
-            layout: &camera_bgl,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: camera_buf.as_entire_binding(),
-            }],
-        });
-
-        // Material/Lighting BGL/BG
-        let material_bgl = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            label: Some("Material BGL"),
-            entries: &[
-                BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Buffer {
-                        ty: BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: Some(
-                            NonZeroU64::new(std::mem::size_of::<MaterialUniform>() as u64).unwrap(),
-                        ),
-                    },
-                    count: None,
-                },
-                BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Buffer {
-                        ty: BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: Some(
-                            NonZeroU64::new(std::mem::size_of::<LightingUniform>() as u64).unwrap(),
-                        ),
-                    },
-                    count: None,
-                },
-            ],
-        });
 
         let material_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Material UBO"),
@@ -575,64 +1039,39 @@ impl GpuState {
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
 
-        let material_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Material BG"),
-            layout: &material_bgl,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: material_buf.as_entire_binding(),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: lighting_buf.as_entire_binding(),
-                },
-            ],
-        });
-
         // Texture store with default texture
         let mut texture_store = TextureStore::new();
         let default_texture_data = TextureData::create_test_texture(64);
-        let default_texture_id = texture_store.add_texture(&device, &queue, "Default", &default_texture_data);
-
-        // Texture BGL/BG
-        let texture_bgl = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            label: Some("Texture BGL"),
-            entries: &[
-                BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Texture {
-                        multisampled: false,
-                        view_dimension: TextureViewDimension::D2,
-                        sample_type: TextureSampleType::Float { filterable: true },
-                    },
-                    count: None,
-                },
-                BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                    count: None,
-                },
-            ],
-        });
-
-        let default_texture_gpu = texture_store.get(default_texture_id).expect("Default texture should exist");
-        let texture_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Texture BG"),
-            layout: &texture_bgl,
-            entries: &[
-                wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&default_texture_gpu.view),
-                },
-                wgpu::BindGroupEntry {
-                    binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&default_texture_gpu.sampler),
-                },
-            ],
-        });
+        let default_texture_id = texture_store.add_texture(
+            &device,
+            &queue,
+            "Default",
+            &default_texture_data,
+            &SamplerDesc::default(),
+        );
+        let default_texture_gpu = texture_store
+            .get(default_texture_id)
+            .expect("Default texture should exist");
+
+        // J2: bind group layouts are reflected from the main shader; the renderer fills them by slot
+        let main_source = shaders
+            .permutation(main_shader, &main_shader_defs())
+            .expect("embedded main shader preprocesses")
+            .source
+            .clone();
+        let main_reflection =
+            ShaderReflection::from_wgsl(&main_source).expect("embedded main shader reflects");
+        let main_resources = MainResources {
+            camera: &camera_buf,
+            material: &material_buf,
+            lighting: &lighting_buf,
+            texture: &default_texture_gpu.view,
+            sampler: &default_texture_gpu.sampler,
+        };
+        let (pipeline_layout, [camera_bg, material_bg, texture_bg], texture_bgl) =
+            create_main_bindings(&device, &main_reflection, &main_resources)
+                .expect("embedded main shader bindings");
+        let texture_bindings = main_reflection.group(2).map(|b| b.binding).collect();
 
         let instance_capacity = 0;
         let instance_buf = device.create_buffer(&wgpu::BufferDescriptor {
@@ -643,51 +1082,23 @@ impl GpuState {
         });
 
         // Pipeline
-        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: Some("Basic PipelineLayout"),
-            bind_group_layouts: &[&camera_bgl, &material_bgl, &texture_bgl],
-            push_constant_ranges: &[],
-        });
-        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some("Cube Pipeline"),
-            layout: Some(&pipeline_layout),
-            vertex: VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[Vertex::LAYOUT, InstanceRaw::LAYOUT],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            },
-            fragment: Some(FragmentState {
-                module: &shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(ColorTargetState {
-                    format: surface_format,
-                    blend: Some(BlendState::REPLACE),
-                    write_mask: ColorWrites::ALL,
-                })],
-                compilation_options: wgpu::PipelineCompilationOptions::default(),
-            }),
-            // На WSL/GLES — без culling для стабильности
-            primitive: wgpu::PrimitiveState {
-                cull_mode: None,
-                ..Default::default()
-            },
-            depth_stencil: Some(DepthStencilState {
-                format: DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: wgpu::CompareFunction::LessEqual,
-                stencil: wgpu::StencilState::default(),
-                bias: DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState::default(),
-            multiview: None,
-            cache: None,
-        });
+        let pipeline = create_main_pipeline(
+            &device,
+            &pipeline_layout,
+            &main_source,
+            surface_format,
+            Vertex::LAYOUT,
+            PipelineState::default(),
+        );
 
         // Geometry: store meshes (start with built-in cube)
-        let mut mesh_store = MeshStore::new();
+        let base_vertex = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::BASE_VERTEX);
+        let mut mesh_store = MeshStore::new(base_vertex);
         let cube_mesh = cube_mesh_data();
-        let cube_mesh_id = mesh_store.add_mesh(&device, "Cube", &cube_mesh);
+        let cube_mesh_id = mesh_store.add_mesh(&device, &queue, &cube_mesh);
 
         // Default camera/model (будут заданы снаружи)
         let camera = Camera::new_perspective(
@@ -701,6 +1112,26 @@ impl GpuState {
         );
         let model = Transform::default();
 
+        let profiler = GpuProfiler::new(&device, &queue);
+        let gpu_culler = GpuCuller::new(&device);
+        let compute_supported = device.limits().max_compute_workgroups_per_dimension > 0;
+        let mut compute = ComputeRegistry::new();
+        let particles = if compute_supported {
+            ParticleSystem::new(
+                &device,
+                &mut compute,
+                &shaders,
+                surface_format,
+                DEPTH_FORMAT,
+            )
+            .inspect_err(|e| log::warn!("K3: particles disabled: {e}"))
+            .ok()
+        } else {
+            None
+        };
+        let skinning = SkinningSystem::new(&device, compute_supported.then_some(&mut compute));
+        let hiz = HiZPyramid::new(&device, &depth_view, width, height, surface_format);
+
         Self {
             surface,
             surface_format,
@@ -708,6 +1139,11 @@ impl GpuState {
             device,
             queue,
             pipeline,
+            pipeline_layout,
+            shaders,
+            main_shader,
+            materials: MaterialLibrary::new(),
+            layout_pipelines: HashMap::new(),
             mesh_store,
             cube_mesh_id,
             texture_store,
@@ -717,9 +1153,38 @@ impl GpuState {
             material_bg,
             material_buf,
             lighting_buf,
-            texture_bg,
+            // The default texture is the first one
+            texture_bgs: vec![texture_bg],
+            texture_bgl,
+            texture_bindings,
             depth_view,
             framegraph: FrameGraph::new(),
+            compute,
+            compute_supported,
+            pending_compute: Vec::new(),
+            particles,
+            skinning,
+            profiler,
+            pending_upload_bytes: 0,
+            frustum_culling: true,
+            gpu_culler,
+            gpu_culling: false,
+            batch_lookup: HashMap::new(),
+            gpu_instances: Vec::new(),
+            gpu_sources: Vec::new(),
+            gpu_batches: Vec::new(),
+            gpu_draw_args: Vec::new(),
+            hiz,
+            occlusion_culling: false,
+            hiz_debug_mip: None,
+            depth_valid: false,
+            prev_view_proj: Mat4::IDENTITY,
+            lod_store: LodStore::default(),
+            lod_settings: LodSettings::default(),
+            retained: RetainedInstances::default(),
+            retained_path: None,
+            retained_visibility: RetainedVisibility::default(),
+            retained_view: None,
             start: Instant::now(),
             camera,
             model,
@@ -729,6 +1194,11 @@ impl GpuState {
             instance_capacity,
             instance_count: 0,
             instance_entries: Vec::new(),
+            draw_prep_threads: 1,
+            prep_chunks: Vec::new(),
+            sorted_entries: Vec::new(),
+            sort_items: Vec::new(),
+            sort_scratch: Vec::new(),
             draw_batches: Vec::new(),
             instance_data: Vec::new(),
         }
@@ -739,6 +1209,11 @@ impl GpuState {
         self.camera = *camera;
     }
 
+    /// Camera LODs and culling are currently evaluated against.
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
     /// External API: set current model transform.
     pub fn set_model(&mut self, model: &Transform) {
         self.model = *model;
@@ -750,13 +1225,112 @@ impl GpuState {
     }
 
     /// Upload mesh data to the GPU mesh store and receive a [`MeshId`].
+    /// M1: the vertex layout follows the mesh ([`VertexLayout::for_mesh`]): extra streams are
+    /// kept, plain meshes use the [`Vertex`] layout.
     pub fn upload_mesh(&mut self, label: &str, mesh: &MeshData) -> MeshId {
-        self.mesh_store.add_mesh(&self.device, label, mesh)
+        let layout = VertexLayout::for_mesh(mesh, LayoutOptions::default());
+        self.upload_mesh_with_layout(label, mesh, &layout)
+            .expect("layout is built from the mesh")
+    }
+
+    /// M1: upload `mesh` in an explicit (e.g. quantized) vertex layout; fails when the mesh
+    /// lacks one of its streams. Draws use the pipeline variant of the layout.
+    pub fn upload_mesh_with_layout(
+        &mut self,
+        label: &str,
+        mesh: &MeshData,
+        layout: &VertexLayout,
+    ) -> Result<MeshId, String> {
+        let whole = 0..mesh.indices.len();
+        Ok(self.upload_parts(label, mesh, std::slice::from_ref(&whole), layout)?[0])
+    }
+
+    /// M1: upload `mesh` once and split it into submeshes (e.g. meshlets or per-material
+    /// parts) by index ranges; each gets its own [`MeshId`] and bounds but shares the vertices.
+    pub fn upload_submeshes(
+        &mut self,
+        label: &str,
+        mesh: &MeshData,
+        ranges: &[std::ops::Range<usize>],
+    ) -> Result<Vec<MeshId>, String> {
+        if let Some(range) = ranges.iter().find(|r| {
+            r.is_empty() || r.end > mesh.indices.len() || r.start % 3 != 0 || r.len() % 3 != 0
+        }) {
+            return Err(format!(
+                "invalid submesh range {range:?} for {} indices",
+                mesh.indices.len()
+            ));
+        }
+        if ranges.is_empty() {
+            return Ok(Vec::new());
+        }
+        let layout = VertexLayout::for_mesh(mesh, LayoutOptions::default());
+        self.upload_parts(label, mesh, ranges, &layout)
+    }
+
+    fn upload_parts(
+        &mut self,
+        label: &str,
+        mesh: &MeshData,
+        ranges: &[std::ops::Range<usize>],
+        layout: &VertexLayout,
+    ) -> Result<Vec<MeshId>, String> {
+        let ids =
+            self.mesh_store
+                .add_submeshes(&self.device, &self.queue, mesh, ranges, Some(layout))?;
+        self.pending_upload_bytes += (mesh.vertices.len() * layout.stride as usize
+            + mesh.indices.len() * std::mem::size_of::<u32>())
+            as u64;
+        log::debug!(
+            "Uploaded mesh '{label}' ({}-byte vertices) as {ids:?}",
+            layout.stride
+        );
+        Ok(ids)
     }
 
     /// Upload texture data to the GPU texture store and receive a [`TextureId`].
+    /// Samples with the default (repeating trilinear) sampler.
     pub fn upload_texture(&mut self, label: &str, texture: &TextureData) -> TextureId {
-        self.texture_store.add_texture(&self.device, &self.queue, label, texture)
+        self.upload_texture_with_sampler(label, texture, &SamplerDesc::default())
+    }
+
+    /// E2: upload texture data sampled with `sampler` (equal descriptors share a GPU sampler).
+    pub fn upload_texture_with_sampler(
+        &mut self,
+        label: &str,
+        texture: &TextureData,
+        sampler: &SamplerDesc,
+    ) -> TextureId {
+        let id = self
+            .texture_store
+            .add_texture(&self.device, &self.queue, label, texture, sampler);
+        self.pending_upload_bytes += self.texture_store.get(id).map_or(0, |t| t.bytes);
+        let group = self.texture_bind_group(id);
+        self.texture_bgs.push(group);
+        id
+    }
+
+    // E2: group 2 of the main pipeline layout for an uploaded texture
+    fn texture_bind_group(&self, id: TextureId) -> BindGroup {
+        let texture = self.texture_store.get(id).expect("uploaded texture");
+        let entries: Vec<_> = self
+            .texture_bindings
+            .iter()
+            .map(|&binding| wgpu::BindGroupEntry {
+                binding,
+                // create_main_bindings only accepts binding 0 (view) and 1 (sampler) here
+                resource: if binding == 0 {
+                    wgpu::BindingResource::TextureView(&texture.view)
+                } else {
+                    wgpu::BindingResource::Sampler(&texture.sampler)
+                },
+            })
+            .collect();
+        self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Texture BG"),
+            layout: &self.texture_bgl,
+            entries: &entries,
+        })
     }
 
     /// Get the default texture ID.
@@ -765,21 +1339,17 @@ impl GpuState {
     }
 
     /// Update material properties.
-    pub fn update_material(&self, material: &MaterialUniform) {
-        self.queue.write_buffer(
-            &self.material_buf,
-            0,
-            bytemuck::bytes_of(material),
-        );
+    pub fn update_material(&mut self, material: &MaterialUniform) {
+        self.pending_upload_bytes += std::mem::size_of::<MaterialUniform>() as u64;
+        self.queue
+            .write_buffer(&self.material_buf, 0, bytemuck::bytes_of(material));
     }
 
     /// Update lighting properties.
-    pub fn update_lighting(&self, lighting: &LightingUniform) {
-        self.queue.write_buffer(
-            &self.lighting_buf,
-            0,
-            bytemuck::bytes_of(lighting),
-        );
+    pub fn update_lighting(&mut self, lighting: &LightingUniform) {
+        self.pending_upload_bytes += std::mem::size_of::<LightingUniform>() as u64;
+        self.queue
+            .write_buffer(&self.lighting_buf, 0, bytemuck::bytes_of(lighting));
     }
 
     /// G2: Setup a simple framegraph example with post-processing.
@@ -822,7 +1392,10 @@ impl GpuState {
             },
             Box::new(|_render_pass, resources| {
                 // In a real implementation, this would apply post-processing
-                log::info!("G2: Executing post-processing pass with {} resources", resources.len());
+                log::info!(
+                    "G2: Executing post-processing pass with {} resources",
+                    resources.len()
+                );
             }),
         );
 
@@ -832,133 +1405,1018 @@ impl GpuState {
         log::info!("G2: FrameGraph setup complete - main pass -> post pass");
     }
 
-    /// Resize: reconfigure surface & recreate depth view.
-    pub fn resize(&mut self, width: u32, height: u32) {
-        self.width = width.max(0);
-        self.height = height.max(0);
-
-        if self.width == 0 || self.height == 0 {
-            // окно свернуто/минимизировано — не трогаем surface
-            return;
+    /// G2: compile and register a WGSL compute shader (same label = replace in place).
+    pub fn register_compute_shader(
+        &mut self,
+        desc: &ComputeShaderDesc<'_>,
+    ) -> Result<ComputeShaderId, ComputeError> {
+        if !self.compute_supported {
+            return Err(ComputeError::Unsupported);
         }
-
-        self.surface_config.width = self.width;
-        self.surface_config.height = self.height;
-        self.surface.configure(&self.device, &self.surface_config);
-        self.depth_view = create_depth_view(&self.device, &self.surface_config);
+        self.compute.register(&self.device, desc)
     }
 
-    /// Render one frame: compute MVP from core::Camera/Transform, write UBO, draw cube.
-    pub fn render(&mut self) -> Result<(), SurfaceError> {
-        let draw = [DrawInstance::new(
-            self.model,
-            self.cube_mesh_id,
-            MaterialId::INVALID,
-            self.default_texture_id,
-        )];
-        self.render_models(&draw)
+    /// G2: bind group for a registered compute shader, checked against its declared bindings.
+    pub fn compute_bind_group(
+        &self,
+        shader: ComputeShaderId,
+        resources: &[(u32, ComputeResource<'_>)],
+    ) -> Result<wgpu::BindGroup, ComputeError> {
+        self.compute.bind_group(&self.device, shader, resources)
     }
 
-    pub fn is_surface_lost(err: &SurfaceError) -> bool {
-        matches!(err, SurfaceError::Lost | SurfaceError::Outdated)
+    pub fn compute_registry(&self) -> &ComputeRegistry {
+        &self.compute
     }
 
-    pub fn recreate_surface(&mut self) {
-        self.resize(self.width, self.height);
+    pub fn compute_supported(&self) -> bool {
+        self.compute_supported
     }
 
-    /// Render a list of draw instances with optimized batching (G1).
-    /// Sort order: PSO -> Material -> Texture -> Mesh to minimize state changes.
-    pub fn render_models(&mut self, draw_list: &[DrawInstance]) -> Result<(), SurfaceError> {
-        if self.width == 0 || self.height == 0 {
-            return Ok(());
-        }
+    /// G2: run `dispatch` in its own profiled pass at the start of the next frame.
+    pub fn queue_compute(&mut self, label: &str, dispatch: ComputeDispatch) {
+        self.pending_compute.push((label.to_string(), dispatch));
+    }
 
-        // G1: Prepare and sort draw commands for optimal batching
-        self.instance_entries.clear();
-        self.instance_entries.reserve(draw_list.len());
+    /// G2: record and submit `dispatches` right away (loading-time work, baking...).
+    pub fn dispatch_compute_now(&self, label: &str, dispatches: &[ComputeDispatch]) {
+        let mut encoder = self
+            .device
+            .create_command_encoder(&CommandEncoderDescriptor { label: Some(label) });
+        self.compute.dispatch(&mut encoder, None, label, dispatches);
+        self.queue.submit(Some(encoder.finish()));
+    }
 
-        for item in draw_list {
-            // Replace INVALID texture with default texture
-            let texture = if item.texture == TextureId::INVALID {
-                self.default_texture_id
-            } else {
-                item.texture
-            };
+    /// K3: sync particle emitters for the next frame; emitters not listed are removed.
+    pub fn update_particles<'a>(
+        &mut self,
+        dt: f32,
+        emitters: impl IntoIterator<Item = (Entity, &'a Transform, &'a ParticleEmitter)>,
+    ) {
+        let Some(particles) = self.particles.as_mut() else {
+            return;
+        };
+        let view_proj = OPENGL_TO_WGPU * self.camera.proj_view();
+        particles.update(
+            &self.device,
+            &self.queue,
+            &self.compute,
+            &self.camera,
+            view_proj,
+            dt,
+            emitters.into_iter().map(|(e, t, em)| (e, t.matrix(), em)),
+        );
+    }
 
-            let key = DrawKey {
-                pso_id: 0, // Currently only one PSO
-                material: item.material,
-                texture,
-                mesh: item.mesh,
-            };
+    /// K3: particle system (None without compute support).
+    pub fn particles(&self) -> Option<&ParticleSystem> {
+        self.particles.as_ref()
+    }
 
-            self.instance_entries.push(InstanceEntry {
-                key,
-                instance: InstanceRaw::from_model(item.transform.matrix()),
-            });
+    /// J1: upload a skinned mesh with its skeleton and clips. The returned skin owns its
+    /// skinned vertex buffer, so upload once per animated instance; draw it through
+    /// [`SkinnedMesh::mesh`] (e.g. as a one-level LOD group).
+    pub fn upload_skinned_mesh(
+        &mut self,
+        label: &str,
+        mesh: &MeshData,
+        weights: &SkinWeights,
+        skeleton: Skeleton,
+        clips: Vec<AnimationClip>,
+    ) -> Result<SkinId, String> {
+        if weights.len() != mesh.vertices.len() {
+            return Err(format!(
+                "{} skin weights for {} vertices",
+                weights.len(),
+                mesh.vertices.len()
+            ));
         }
+        if let Some(joint) = weights
+            .max_joint()
+            .filter(|&j| j as usize >= skeleton.len())
+        {
+            return Err(format!(
+                "vertex references joint {joint}, skeleton has {}",
+                skeleton.len()
+            ));
+        }
+        let whole = 0..mesh.indices.len();
+        let mesh_id = self.mesh_store.add_submeshes(
+            &self.device,
+            &self.queue,
+            mesh,
+            std::slice::from_ref(&whole),
+            None,
+        )?[0];
+        log::debug!("Uploaded skinned mesh '{label}' as {mesh_id:?}");
+        self.pending_upload_bytes += (mesh.vertices.len() * std::mem::size_of::<Vertex>()
+            + mesh.indices.len() * std::mem::size_of::<u32>())
+            as u64;
+        let vertex_buf = self.mesh_store.buffer(
+            self.mesh_store
+                .get(mesh_id)
+                .expect("mesh just added")
+                .vertex_block,
+        );
+        let index = self
+            .skinning
+            .add(
+                &self.device,
+                &self.compute,
+                mesh_id,
+                vertex_buf,
+                mesh,
+                weights,
+                skeleton,
+                clips,
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(SkinId::new(
+            u32::try_from(index).expect("Too many skinned meshes"),
+        ))
+    }
 
-        // G1: Sort by DrawKey (PSO -> Material -> Texture -> Mesh)
-        self.instance_entries.sort_by_key(|entry| entry.key);
+    /// J1: upload a mesh animated only by its morph targets (e.g. blend shapes): a skinned
+    /// mesh bound fully to a single identity joint, driven by an [`Animator`] the same way.
+    pub fn upload_morph_mesh(
+        &mut self,
+        label: &str,
+        mesh: &MeshData,
+        clips: Vec<AnimationClip>,
+    ) -> Result<SkinId, String> {
+        let joint = Joint {
+            name: None,
+            parent: None,
+            rest: JointTransform::IDENTITY,
+        };
+        let skeleton = Skeleton::new(
+            vec![joint],
+            vec![asset::gltf::IDENTITY],
+            asset::gltf::IDENTITY,
+        )
+        .map_err(|e| e.to_string())?;
+        let n = mesh.vertices.len();
+        let weights = SkinWeights::new(vec![[0; 4]; n], vec![[1.0, 0.0, 0.0, 0.0]; n])
+            .map_err(|e| e.to_string())?;
+        self.upload_skinned_mesh(label, mesh, &weights, skeleton, clips)
+    }
 
-        // G1: Create batches with same render state
-        self.draw_batches.clear();
-        self.draw_batches.reserve(self.instance_entries.len());
-        self.instance_data.clear();
-        self.instance_data.reserve(self.instance_entries.len());
+    /// J1: skinned mesh (output mesh, skeleton, clips).
+    pub fn skinned_mesh(&self, id: SkinId) -> Option<&SkinnedMesh> {
+        self.skinning.get(id.0 as usize)
+    }
 
-        if !self.instance_entries.is_empty() {
-            let mut batch_start = 0;
-            let mut current_key = self.instance_entries[0].key;
+    /// J1: advance animators by `dt` and skin their meshes for the next frame.
+    /// Animators of unknown skins are skipped, and so is any animator after the first on a
+    /// skin (its pose and vertex block are shared; `World::set_animator` refuses these).
+    pub fn update_animations<'a>(
+        &mut self,
+        dt: f32,
+        animators: impl IntoIterator<Item = &'a mut Animator>,
+    ) {
+        let mut animated = vec![false; self.skinning.len()];
+        for animator in animators {
+            let index = animator.skin.0 as usize;
+            let Some(skin) = self.skinning.get_mut(index) else {
+                continue;
+            };
+            if std::mem::replace(&mut animated[index], true) {
+                continue;
+            }
+            let Some(mesh) = self.mesh_store.get(skin.mesh()) else {
+                continue;
+            };
+            let aabb = skin.animate(
+                &self.queue,
+                self.mesh_store.buffer(mesh.vertex_block),
+                dt,
+                animator,
+            );
+            if let Some(mesh) = self.mesh_store.get_mut(skin.mesh()) {
+                mesh.bounds = MeshBounds::from_aabb(aabb);
+            }
+        }
+    }
 
-            for (idx, entry) in self.instance_entries.iter().enumerate() {
-                if entry.key != current_key {
-                    // End current batch
-                    let batch_count = idx - batch_start;
-                    self.draw_batches.push(DrawBatch {
-                        key: current_key,
-                        start: batch_start,
-                        count: batch_count,
-                    });
+    /// G2: the frame graph, e.g. for [`ComputeRegistry::add_to_graph`].
+    pub fn framegraph_mut(&mut self) -> &mut FrameGraph {
+        &mut self.framegraph
+    }
 
-                    // Start new batch
-                    batch_start = idx;
-                    current_key = entry.key;
+    /// H4: enable/disable per-pass profiling.
+    pub fn set_profiling_enabled(&mut self, enabled: bool) {
+        self.profiler.set_enabled(&self.device, enabled);
+    }
+
+    /// H4: profiler with the latest per-pass timings.
+    pub fn profiler(&self) -> &GpuProfiler {
+        &self.profiler
+    }
+
+    /// L1: enable/disable CPU frustum culling.
+    pub fn set_frustum_culling(&mut self, enabled: bool) {
+        self.frustum_culling = enabled;
+    }
+
+    /// G1: threads for draw preparation (1 = single-threaded, the default). Draw lists
+    /// shorter than [`parallel::MIN_ITEMS_PER_THREAD`] per thread use fewer threads.
+    pub fn set_draw_prep_threads(&mut self, threads: usize) {
+        self.draw_prep_threads = threads.max(1);
+    }
+
+    pub fn draw_prep_threads(&self) -> usize {
+        self.draw_prep_threads
+    }
+
+    /// J2: load shaders from `dir` (e.g. `assets/shaders`) and watch it for changes;
+    /// `None` goes back to the embedded sources.
+    pub fn set_shader_dir(&mut self, dir: Option<PathBuf>) {
+        let updates = self.shaders.set_dir(dir);
+        self.apply_shader_updates(updates);
+    }
+
+    /// J2: poll the shader directory (rate-limited) and rebuild pipelines of changed shaders.
+    pub fn poll_shaders(&mut self) {
+        let updates = self.shaders.poll();
+        self.apply_shader_updates(updates);
+    }
+
+    /// J2: shaders whose latest edit failed; the previous pipeline is still in use.
+    pub fn shader_errors(&self) -> Vec<ShaderError> {
+        self.shaders.errors()
+    }
+
+    /// J2: material files whose latest edit failed; the previous version stays in use.
+    pub fn material_errors(&self) -> Vec<MaterialError> {
+        self.materials.errors()
+    }
+
+    /// J2: directory asset paths in material files are relative to (e.g. `assets`).
+    pub fn set_asset_root(&mut self, root: PathBuf) {
+        self.materials.set_root(root);
+    }
+
+    /// J2: load a material file (asset path, e.g. `materials/gold.material.ron`) and build
+    /// its pipeline. Loading the same path again returns the same id.
+    pub fn load_material(&mut self, path: &str) -> Result<MaterialId, String> {
+        if let Some(id) = self.materials.find(path) {
+            return Ok(id);
+        }
+        let (source, stamps) = self.materials.read(path)?;
+        let shader = self.material_shader(&source.desc)?;
+        let gpu = self
+            .build_material(&source, shader, &[])
+            .map_err(|e| format!("{path}: {e}"))?;
+        let id = self.materials.insert(path, stamps, source, shader, gpu);
+        log::info!("Material {path} loaded as {id:?}");
+        Ok(id)
+    }
+
+    /// J2: poll material files (rate-limited) and rebuild the changed ones.
+    pub fn poll_materials(&mut self) {
+        for change in self.materials.poll() {
+            let rebuilt = change.source.and_then(|source| {
+                let shader = self.material_shader(&source.desc)?;
+                let gpu = self.build_material(&source, shader, &[])?;
+                Ok((source, shader, gpu))
+            });
+            match rebuilt {
+                Ok((source, shader, gpu)) => {
+                    self.materials.replace(change.id, source, shader, gpu);
+                    self.layout_pipelines.clear();
                 }
-                self.instance_data.push(entry.instance);
+                Err(message) => self.materials.fail(change.id, message),
             }
+        }
+    }
 
-            // Add final batch
-            let batch_count = self.instance_entries.len() - batch_start;
-            self.draw_batches.push(DrawBatch {
-                key: current_key,
-                start: batch_start,
-                count: batch_count,
-            });
+    /// J2: loaded materials built from a node graph, with their file paths.
+    pub fn graph_materials(&self) -> Vec<(MaterialId, String)> {
+        self.materials
+            .ids()
+            .filter(|&id| self.material_graph(id).is_some())
+            .filter_map(|id| Some((id, self.materials.path(id)?.to_string())))
+            .collect()
+    }
+
+    /// J2: the node graph a material is currently built from.
+    pub fn material_graph(&self, id: MaterialId) -> Option<&MaterialGraph> {
+        self.materials.source(id).and_then(|s| s.graph.as_ref())
+    }
+
+    /// J2: rebuild a graph material with an edited graph (in memory, see `save_material_graph`).
+    /// On error the previous graph keeps rendering.
+    pub fn set_material_graph(
+        &mut self,
+        id: MaterialId,
+        graph: MaterialGraph,
+    ) -> Result<(), String> {
+        let (Some(source), Some(shader)) = (self.materials.source(id), self.materials.shader(id))
+        else {
+            return Err(format!("unknown material {id:?}"));
+        };
+        let Some(old) = &source.graph else {
+            return Err(format!("{id:?} is not a graph material"));
+        };
+        // Layout-only edits (node positions) generate the same code
+        if compile_graph(old).is_ok_and(|code| compile_graph(&graph) == Ok(code)) {
+            self.materials.set_graph(id, graph);
+            return Ok(());
+        }
+        let source = MaterialSource {
+            graph: Some(graph),
+            ..source.clone()
+        };
+        let gpu = self.build_material(&source, shader, &[])?;
+        self.materials.replace(id, source, shader, gpu);
+        self.layout_pipelines.clear();
+        Ok(())
+    }
+
+    /// J2: write a material's current graph back to its `.matgraph.ron` file.
+    pub fn save_material_graph(&mut self, id: MaterialId) -> Result<(), String> {
+        self.materials.save_graph(id)
+    }
+
+    // Shader a material renders with: the main shader or a file from the shader directory.
+    fn material_shader(&mut self, desc: &MaterialDesc) -> Result<ShaderId, String> {
+        match desc.shader.as_deref() {
+            None => Ok(self.main_shader),
+            Some(name) => self.shaders.register_file(name),
         }
+    }
 
-        let needed =
-            (self.instance_data.len().max(1) as u64) * std::mem::size_of::<InstanceRaw>() as u64;
-        if needed > self.instance_buf.size() {
-            let new_cap = needed.next_power_of_two();
-            self.instance_buf = self.device.create_buffer(&wgpu::BufferDescriptor {
-                label: Some("Instance Buffer (grown)"),
-                size: new_cap,
-                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
-                mapped_at_creation: false,
+    // Pipeline + bind groups of one material, with `pending` shader updates applied.
+    fn build_material(
+        &self,
+        source: &MaterialSource,
+        shader: ShaderId,
+        pending: &[ShaderUpdate],
+    ) -> Result<MaterialGpu, String> {
+        let desc = &source.desc;
+        let wgsl = self.material_wgsl(source, shader, material_shader_defs(desc), pending)?;
+        let reflection = ShaderReflection::from_wgsl(&wgsl).map_err(|e| e.to_string())?;
+        let texture = match &desc.base_color_texture {
+            Some(path) => {
+                let file = self.materials.resolve(path)?;
+                let data = TextureData::load_png(&file).map_err(|e| format!("{e:#}"))?;
+                let sampler = self.texture_store.samplers.get(&self.device, &desc.sampler);
+                Some(TextureGpu::new(
+                    &self.device,
+                    &self.queue,
+                    path,
+                    &data,
+                    sampler,
+                ))
+            }
+            None => None,
+        };
+        let uniform_buf = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Material File UBO"),
+                contents: bytemuck::bytes_of(&MaterialUniform::from(desc)),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
             });
-            self.instance_capacity = (new_cap / std::mem::size_of::<InstanceRaw>() as u64) as u32;
+        let default_texture = self
+            .texture_store
+            .get(self.default_texture_id)
+            .expect("Default texture should exist");
+        let bound_texture = texture.as_ref().unwrap_or(default_texture);
+        let resources = MainResources {
+            camera: &self.camera_buf,
+            material: &uniform_buf,
+            lighting: &self.lighting_buf,
+            texture: &bound_texture.view,
+            sampler: &bound_texture.sampler,
+        };
+        let state = PipelineState::from(desc);
+        let (pipeline_layout, bind_groups, _, pipeline) =
+            self.create_checked_pipeline(&reflection, &resources, &wgsl, state)?;
+        Ok(MaterialGpu {
+            pipeline,
+            layout: pipeline_layout,
+            bind_groups,
+            uniform_buf,
+            texture,
+            blend: state.blend,
+        })
+    }
+
+    // Expanded material shader, with the graph's surface function appended for graph materials
+    fn material_wgsl(
+        &self,
+        source: &MaterialSource,
+        shader: ShaderId,
+        defs: ShaderDefs,
+        pending: &[ShaderUpdate],
+    ) -> Result<String, String> {
+        let mut expanded = self
+            .shaders
+            .expand_with(shader, &defs, pending)
+            .map_err(|e| e.to_string())?;
+        if let Some(graph) = &source.graph {
+            let code = compile_graph(graph).map_err(|e| format!("material graph: {e}"))?;
+            expanded.source.push('\n');
+            expanded.source.push_str(&code);
+        }
+        Ok(expanded.source)
+    }
+
+    // M1: pipeline of `material` (0 = built-in) for meshes in vertex layout `layout`. Bind
+    // group layouts don't depend on the `VERTEX_*` defines, so the material's bind groups fit.
+    fn build_layout_pipeline(
+        &self,
+        material: MaterialId,
+        layout: usize,
+    ) -> Result<RenderPipeline, String> {
+        let vertex = self.mesh_store.layouts.get(layout);
+        let (wgsl, pipeline_layout, state) = match (
+            self.materials.gpu(material),
+            self.materials.source(material),
+        ) {
+            (Some(gpu), Some(source)) => {
+                let shader = self
+                    .materials
+                    .shader(material)
+                    .ok_or("material without shader")?;
+                let mut defs = material_shader_defs(&source.desc);
+                vertex_layout::add_layout_defs(&vertex.layout, &mut defs);
+                (
+                    self.material_wgsl(source, shader, defs, &[])?,
+                    &gpu.layout,
+                    PipelineState::from(&source.desc),
+                )
+            }
+            _ => {
+                let mut defs = main_shader_defs();
+                vertex_layout::add_layout_defs(&vertex.layout, &mut defs);
+                let expanded = self
+                    .shaders
+                    .expand_with(self.main_shader, &defs, &[])
+                    .map_err(|e| e.to_string())?;
+                (
+                    expanded.source,
+                    &self.pipeline_layout,
+                    PipelineState::default(),
+                )
+            }
+        };
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = create_main_pipeline(
+            &self.device,
+            pipeline_layout,
+            &wgsl,
+            self.surface_format,
+            vertex.buffer_layout(),
+            state,
+        );
+        match pollster::block_on(self.device.pop_error_scope()) {
+            None => Ok(pipeline),
+            Some(err) => Err(err.to_string()),
         }
+    }
 
-        if !self.instance_data.is_empty() {
-            self.queue.write_buffer(
-                &self.instance_buf,
-                0,
-                bytemuck::cast_slice(&self.instance_data),
-            );
+    // M1: build the missing pipeline variants for this frame's batches (failures are logged once
+    // and their batches skipped)
+    fn prepare_layout_pipelines(&mut self) {
+        for i in 0..self.draw_batches.len() {
+            let key = self.draw_batches[i].key;
+            let Some(mesh) = self.mesh_store.get(key.mesh).filter(|m| m.layout != 0) else {
+                continue;
+            };
+            let material = if self.materials.gpu(key.material).is_some() {
+                key.material
+            } else {
+                MaterialId::new(0)
+            };
+            let variant = (material, mesh.layout);
+            if self.layout_pipelines.contains_key(&variant) {
+                continue;
+            }
+            let pipeline = self
+                .build_layout_pipeline(material, mesh.layout)
+                .inspect_err(|e| {
+                    log::warn!(
+                        "No pipeline for vertex layout {} with {material:?}: {e}",
+                        mesh.layout
+                    )
+                })
+                .ok();
+            self.layout_pipelines.insert(variant, pipeline);
         }
-        self.instance_count = self.instance_data.len() as u32;
+    }
+
+    // Layouts follow the shader; creation can still fail on the wgpu side
+    // (resource of the wrong kind in a slot, vertex inputs that don't match)
+    fn create_checked_pipeline(
+        &self,
+        reflection: &ShaderReflection,
+        resources: &MainResources,
+        source: &str,
+        state: PipelineState,
+    ) -> Result<
+        (
+            wgpu::PipelineLayout,
+            [BindGroup; 3],
+            wgpu::BindGroupLayout,
+            RenderPipeline,
+        ),
+        String,
+    > {
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let bindings = create_main_bindings(&self.device, reflection, resources);
+        let pipeline = bindings.as_ref().ok().map(|(layout, ..)| {
+            create_main_pipeline(
+                &self.device,
+                layout,
+                source,
+                self.surface_format,
+                Vertex::LAYOUT,
+                state,
+            )
+        });
+        let scope = pollster::block_on(self.device.pop_error_scope());
+        match (bindings, pipeline, scope) {
+            (Ok((layout, groups, texture_layout)), Some(pipeline), None) => {
+                Ok((layout, groups, texture_layout, pipeline))
+            }
+            (Err(message), ..) => Err(message),
+            (.., Some(err)) => Err(err.to_string()),
+            _ => unreachable!("pipeline exists whenever bindings do"),
+        }
+    }
+
+    // Does `shader` (expanded for `defs`) read any of the updated files?
+    fn shader_uses(
+        &mut self,
+        shader: ShaderId,
+        defs: &ShaderDefs,
+        updates: &[ShaderUpdate],
+    ) -> bool {
+        if updates.iter().any(|u| u.id == shader) {
+            return true;
+        }
+        let used = self
+            .shaders
+            .permutation(shader, defs)
+            .map(|p| p.includes.clone())
+            .unwrap_or_default();
+        updates
+            .iter()
+            .any(|u| used.iter().any(|n| n == self.shaders.name(u.id)))
+    }
+
+    fn apply_shader_updates(&mut self, updates: Vec<ShaderUpdate>) {
+        if updates.is_empty() {
+            return;
+        }
+        let defs = main_shader_defs();
+        let main_affected = self.shader_uses(self.main_shader, &defs, &updates);
+        let mut affected_materials = Vec::new();
+        for id in self.materials.ids() {
+            let (Some(source), Some(shader)) =
+                (self.materials.source(id), self.materials.shader(id))
+            else {
+                continue;
+            };
+            let material_defs = material_shader_defs(&source.desc);
+            if self.shader_uses(shader, &material_defs, &updates) {
+                affected_materials.push(id);
+            }
+        }
+        // Files nobody renders with (yet) are simply taken over
+        if !main_affected && affected_materials.is_empty() {
+            for update in updates {
+                self.shaders.accept(update);
+            }
+            return;
+        }
+
+        // Rebuild everything that uses the new sources together; all or nothing
+        let main = if main_affected {
+            self.shaders
+                .expand_with(self.main_shader, &defs, &updates)
+                .map_err(|e| e.to_string())
+                .and_then(|p| {
+                    let reflection =
+                        ShaderReflection::from_wgsl(&p.source).map_err(|e| e.to_string())?;
+                    let texture = self
+                        .texture_store
+                        .get(self.default_texture_id)
+                        .expect("Default texture should exist");
+                    let resources = MainResources {
+                        camera: &self.camera_buf,
+                        material: &self.material_buf,
+                        lighting: &self.lighting_buf,
+                        texture: &texture.view,
+                        sampler: &texture.sampler,
+                    };
+                    let bindings = reflection.group(2).map(|b| b.binding).collect::<Vec<_>>();
+                    self.create_checked_pipeline(
+                        &reflection,
+                        &resources,
+                        &p.source,
+                        PipelineState::default(),
+                    )
+                    .map(|main| (main, bindings))
+                })
+                .map(Some)
+        } else {
+            Ok(None)
+        };
+        let result = main.and_then(|main| {
+            let mut rebuilt = Vec::new();
+            for &id in &affected_materials {
+                let source = self.materials.source(id).expect("listed above").clone();
+                let shader = self.materials.shader(id).expect("listed above");
+                let gpu = self
+                    .build_material(&source, shader, &updates)
+                    .map_err(|e| {
+                        format!("material {}: {e}", self.materials.path(id).unwrap_or("?"))
+                    })?;
+                rebuilt.push((id, source, shader, gpu));
+            }
+            Ok((main, rebuilt))
+        });
+        match result {
+            Ok((main, rebuilt)) => {
+                if let Some((
+                    (layout, [camera_bg, material_bg, _], texture_bgl, pipeline),
+                    bindings,
+                )) = main
+                {
+                    self.pipeline_layout = layout;
+                    self.camera_bg = camera_bg;
+                    self.material_bg = material_bg;
+                    self.texture_bgl = texture_bgl;
+                    self.texture_bindings = bindings;
+                    self.texture_bgs = (0..self.texture_store.textures.len())
+                        .map(|i| self.texture_bind_group(TextureId::new(i as u32)))
+                        .collect();
+                    self.pipeline = pipeline;
+                }
+                for (id, source, shader, gpu) in rebuilt {
+                    self.materials.replace(id, source, shader, gpu);
+                }
+                self.layout_pipelines.clear();
+                for update in updates {
+                    self.shaders.accept(update);
+                }
+            }
+            Err(message) => {
+                for update in updates {
+                    self.shaders.reject(update.id, message.clone());
+                }
+            }
+        }
+    }
+
+    /// L2: enable/disable GPU-driven culling. Returns `false` if unsupported on this device.
+    pub fn set_gpu_culling(&mut self, enabled: bool) -> bool {
+        self.gpu_culling = enabled && self.gpu_culler.is_some();
+        self.gpu_culling == enabled
+    }
+
+    /// L2: whether the compute culling path is available.
+    pub fn gpu_culling_supported(&self) -> bool {
+        self.gpu_culler.is_some()
+    }
+
+    /// L1: enable/disable Hi-Z occlusion culling. Returns `false` if unsupported on this device.
+    pub fn set_occlusion_culling(&mut self, enabled: bool) -> bool {
+        self.occlusion_culling = enabled && self.hiz.is_some();
+        self.occlusion_culling == enabled
+    }
+
+    /// L1: draw the given Hi-Z mip in the corner of the screen (`None` = off).
+    pub fn set_hiz_debug_view(&mut self, mip: Option<u32>) {
+        self.hiz_debug_mip = mip;
+    }
+
+    /// L1: group already uploaded meshes (finest first) into a LOD group.
+    /// `thresholds` are per-level minimum screen sizes; `None` uses [`lod::default_thresholds`].
+    pub fn create_lod_group(
+        &mut self,
+        meshes: &[MeshId],
+        thresholds: Option<&[f32]>,
+    ) -> LodGroupId {
+        let defaults = lod::default_thresholds(meshes.len());
+        let thresholds = thresholds.unwrap_or(&defaults);
+        let sphere = meshes
+            .first()
+            .and_then(|&m| self.mesh_store.get(m))
+            .map(|m| m.bounds.sphere)
+            .unwrap_or_default();
+        let levels = meshes
+            .iter()
+            .enumerate()
+            .map(|(i, &mesh)| LodLevel {
+                mesh,
+                min_screen_size: thresholds.get(i).copied().unwrap_or(0.0),
+            })
+            .collect();
+        self.lod_store.add(LodGroup { levels, sphere })
+    }
+
+    /// L1: upload every level of a chain and group them with default thresholds.
+    pub fn upload_lod_group(&mut self, label: &str, lods: &MeshLods) -> LodGroupId {
+        let meshes: Vec<MeshId> = lods
+            .levels
+            .iter()
+            .enumerate()
+            .map(|(i, mesh)| self.upload_mesh(&format!("{label} LOD{i}"), mesh))
+            .collect();
+        self.create_lod_group(&meshes, None)
+    }
+
+    /// M1: upload every level of a binary mesh file straight from its mapping (the blobs
+    /// already use the [`Vertex`] layout) and group them with default thresholds.
+    pub fn upload_mesh_file(&mut self, label: &str, file: &MeshFile) -> LodGroupId {
+        let default_layout = VertexLayout::default();
+        let meshes: Vec<MeshId> = (0..file.lod_count())
+            .filter_map(|i| file.lod(i).map(|view| (i, view)))
+            .map(|(i, view)| {
+                self.pending_upload_bytes +=
+                    (view.vertex_bytes().len() + std::mem::size_of_val(view.indices)) as u64;
+                log::debug!("Uploading '{label}' LOD{i} from mesh file");
+                let ranges = [(0..view.indices.len(), view.bounds)];
+                let vertices = VertexBytes {
+                    bytes: view.vertex_bytes(),
+                    layout: &default_layout,
+                    skinned: false,
+                };
+                self.mesh_store.add_mesh_bytes(
+                    &self.device,
+                    &self.queue,
+                    vertices,
+                    view.indices,
+                    &ranges,
+                )[0]
+            })
+            .collect();
+        self.create_lod_group(&meshes, None)
+    }
+
+    /// L1: LOD group description (levels + thresholds).
+    pub fn lod_group(&self, id: LodGroupId) -> Option<&LodGroup> {
+        self.lod_store.get(id)
+    }
+
+    /// L1: hysteresis / cross-fade / bias tuning.
+    pub fn set_lod_settings(&mut self, settings: LodSettings) {
+        self.lod_settings = settings;
+    }
+
+    pub fn lod_settings(&self) -> LodSettings {
+        self.lod_settings
+    }
+
+    /// L1: pick the LOD for one instance against the current camera and advance its fade.
+    pub fn resolve_lod(
+        &self,
+        group: LodGroupId,
+        transform: &Transform,
+        state: &mut LodState,
+        dt: f32,
+    ) -> Option<LodDraw> {
+        self.lod_context().resolve(group, transform, state, dt)
+    }
+
+    /// G1: thread-shareable view for resolving LODs from parallel jobs.
+    pub fn lod_context(&self) -> LodContext<'_> {
+        LodContext {
+            store: &self.lod_store,
+            camera: &self.camera,
+            settings: self.lod_settings,
+        }
+    }
+
+    /// Resize: reconfigure surface & recreate depth view.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+
+        if self.width == 0 || self.height == 0 {
+            // окно свернуто/минимизировано — не трогаем surface
+            return;
+        }
+
+        self.surface_config.width = self.width;
+        self.surface_config.height = self.height;
+        self.surface.configure(&self.device, &self.surface_config);
+        self.depth_view = create_depth_view(&self.device, &self.surface_config);
+        if let Some(hiz) = self.hiz.as_mut() {
+            hiz.resize(&self.device, &self.depth_view, self.width, self.height);
+        }
+        self.depth_valid = false;
+    }
+
+    /// Render one frame: compute MVP from core::Camera/Transform, write UBO, draw cube.
+    pub fn render(&mut self) -> Result<(), SurfaceError> {
+        let draw = [DrawInstance::new(
+            self.model,
+            self.cube_mesh_id,
+            MaterialId::INVALID,
+            self.default_texture_id,
+        )];
+        self.render_models(&draw, None).map(|_| ())
+    }
+
+    pub fn is_surface_lost(err: &SurfaceError) -> bool {
+        matches!(err, SurfaceError::Lost | SurfaceError::Outdated)
+    }
+
+    pub fn recreate_surface(&mut self) {
+        self.resize(self.width, self.height);
+    }
+
+    /// Render a list of draw instances with optimized batching (G1).
+    /// Sort order: PSO -> Material -> Texture -> Mesh to minimize state changes.
+    /// Returns per-frame [`RenderStats`] (default stats for skipped frames).
+    /// I1: `overlay` is drawn over the scene before present.
+    pub fn render_models(
+        &mut self,
+        draw_list: &[DrawInstance],
+        overlay: Option<&mut dyn Overlay>,
+    ) -> Result<RenderStats, SurfaceError> {
+        if self.width == 0 || self.height == 0 {
+            return Ok(RenderStats::default());
+        }
+
+        // L2: immediate mode overwrites the buffers retained instances live in
+        self.retained_path = None;
+
+        let mut stats = RenderStats::default();
+        let flags = self.frame_flags();
+        let prepare_start = Instant::now();
+
+        if flags.gpu_culling {
+            self.prepare_gpu_batches(draw_list, &mut stats);
+        } else {
+            self.prepare_cpu_batches(draw_list, flags.occlusion, &mut stats);
+        }
+        stats.cpu_prepare_ms = prepare_start.elapsed().as_secs_f64() * 1000.0;
+
+        self.upload_instances(flags.gpu_culling, flags.occlusion, &mut stats);
+        self.submit_frame(flags, stats, overlay)
+    }
+
+    /// L2: insert/update a retained instance (see [`retained::instance_slot`]).
+    /// Only changed data is uploaded by [`GpuState::render_retained`].
+    pub fn set_instance(&mut self, slot: u32, draw: &DrawInstance) {
+        let texture = if draw.texture == TextureId::INVALID {
+            self.default_texture_id
+        } else {
+            draw.texture
+        };
+        let key = DrawKey {
+            pso_id: 0,
+            material: draw.material,
+            texture,
+            mesh: draw.mesh,
+        };
+        let instance = InstanceRaw::from_model_faded(draw.transform.matrix(), draw.lod_fade);
+        self.retained.set(slot, key, instance);
+    }
+
+    /// L2: remove a retained instance (no-op if the slot is empty).
+    pub fn remove_instance(&mut self, slot: u32) {
+        self.retained.remove(slot);
+    }
+
+    /// L2: number of live retained instances.
+    pub fn retained_instance_count(&self) -> usize {
+        self.retained.len()
+    }
+
+    /// L2: render all retained instances. The draw order is only re-sorted when instances
+    /// were added/removed or changed mesh/material/texture; otherwise only dirty ranges
+    /// are uploaded. With CPU culling (frustum / Hi-Z) on the CPU path, the visible instances
+    /// are compacted and re-uploaded only when the visible set or a visible instance changes.
+    pub fn render_retained(
+        &mut self,
+        overlay: Option<&mut dyn Overlay>,
+    ) -> Result<RenderStats, SurfaceError> {
+        if self.width == 0 || self.height == 0 {
+            return Ok(RenderStats::default());
+        }
+
+        let mut stats = RenderStats::default();
+        let flags = self.frame_flags();
+        let prepare_start = Instant::now();
+
+        // L1: culled frames overwrite the instance buffer with compacted copies
+        let cpu_culling = !flags.gpu_culling && (self.frustum_culling || flags.occlusion);
+        let path = (flags.gpu_culling, cpu_culling);
+        if self.retained_path != Some(path) {
+            self.retained.invalidate();
+        }
+        let update = self.retained.flush();
+        let mut changed = true;
+        if cpu_culling {
+            changed = self.cull_retained(update, flags.occlusion, &mut stats);
+        } else if update == RetainedUpdate::Full {
+            self.draw_batches.clear();
+            self.draw_batches.extend_from_slice(self.retained.batches());
+            self.batch_lookup.clear();
+            if flags.gpu_culling {
+                self.build_gpu_batch_args();
+            }
+        }
+        if flags.gpu_culling {
+            stats.instances = self.retained.len() as u32;
+        } else if !cpu_culling {
+            stats.visible_instances = self.retained.len() as u32;
+        }
+        stats.cpu_prepare_ms = prepare_start.elapsed().as_secs_f64() * 1000.0;
+
+        if cpu_culling {
+            if changed {
+                self.upload_instances(false, false, &mut stats);
+            }
+        } else {
+            self.upload_retained(update, flags, &mut stats);
+        }
+        self.retained_path = Some(path);
+        self.submit_frame(flags, stats, overlay)
+    }
+
+    /// L1: CPU path of retained mode — frustum/Hi-Z test the retained instances (already in
+    /// draw order) into compacted `instance_data` / `draw_batches`. Only dirty instances are
+    /// re-tested unless the view changed; the Hi-Z changes every frame, so occlusion re-tests
+    /// everything. Returns whether the compacted data changed.
+    fn cull_retained(
+        &mut self,
+        update: RetainedUpdate,
+        use_occlusion: bool,
+        stats: &mut RenderStats,
+    ) -> bool {
+        let view = (self.camera, self.frustum_culling, use_occlusion);
+        let all = use_occlusion || self.retained_view != Some(view);
+        self.retained_view = Some(view);
+
+        let frustum = self.camera.frustum();
+        let frustum = self.frustum_culling.then_some(&frustum);
+        let hiz = self
+            .hiz
+            .as_ref()
+            .filter(|_| use_occlusion)
+            .map(|h| h.snapshot());
+        let mesh_store = &self.mesh_store;
+        let test = |batch: &DrawBatch, raw: &InstanceRaw| {
+            let Some(mesh) = mesh_store.get(batch.key.mesh) else {
+                return Visibility::Visible;
+            };
+            let world = WorldBounds::from_local(&mesh.bounds, raw.model());
+            if frustum.is_some_and(|f| !world.is_visible(f)) {
+                Visibility::Culled
+            } else if hiz.is_some_and(|h| h.is_occluded(&world)) {
+                Visibility::Occluded
+            } else {
+                Visibility::Visible
+            }
+        };
+        let changed = self.retained_visibility.update(
+            &self.retained,
+            update,
+            all,
+            test,
+            &mut self.instance_data,
+            &mut self.draw_batches,
+        );
+        stats.culled_instances += self.retained_visibility.culled();
+        stats.occluded_instances += self.retained_visibility.occluded();
+        stats.visible_instances = self.instance_data.len() as u32;
+        changed
+    }
+
+    /// Per-frame path selection; also picks up a finished Hi-Z readback.
+    fn frame_flags(&mut self) -> FrameFlags {
+        let gpu_culling = self.gpu_culling && self.gpu_culler.is_some();
+        // L1: the pyramid is rebuilt from last frame's depth (needed for the debug view too)
+        let build_hiz = self.hiz.is_some()
+            && self.depth_valid
+            && (self.occlusion_culling || self.hiz_debug_mip.is_some());
+        if let Some(hiz) = self.hiz.as_mut() {
+            hiz.poll_readback(&self.device);
+        }
+        FrameFlags {
+            gpu_culling,
+            occlusion: self.occlusion_culling && build_hiz,
+            build_hiz,
+        }
+    }
+
+    /// Acquire the surface, encode culling + main pass for the prepared batches and present.
+    fn submit_frame(
+        &mut self,
+        flags: FrameFlags,
+        mut stats: RenderStats,
+        overlay: Option<&mut dyn Overlay>,
+    ) -> Result<RenderStats, SurfaceError> {
+        let (use_gpu_culling, build_hiz) = (flags.gpu_culling, flags.build_hiz);
+        self.prepare_layout_pipelines();
 
         let frame = match self.surface.get_current_texture() {
             Ok(f) => f,
@@ -968,7 +2426,7 @@ impl GpuState {
             }
             Err(SurfaceError::Timeout) => {
                 log::warn!("Surface timeout — skipping this frame");
-                return Ok(());
+                return Ok(stats);
             }
             Err(e @ SurfaceError::OutOfMemory) => return Err(e),
         };
@@ -979,6 +2437,42 @@ impl GpuState {
                 label: Some("MainEncoder"),
             });
 
+        self.profiler.begin_frame(&self.device);
+
+        // G2: user compute work (simulation, skinning...) runs before culling and drawing
+        for (label, dispatch) in self.pending_compute.drain(..) {
+            self.compute.dispatch(
+                &mut encoder,
+                Some(&mut self.profiler),
+                &label,
+                std::slice::from_ref(&dispatch),
+            );
+        }
+
+        self.skinning
+            .record(&mut encoder, &self.compute, &mut self.profiler);
+        if let Some(particles) = self.particles.as_ref() {
+            particles.simulate(&mut encoder, &self.compute, &mut self.profiler);
+        }
+
+        if build_hiz && let Some(hiz) = self.hiz.as_mut() {
+            hiz.build(&mut encoder, &mut self.profiler, self.prev_view_proj);
+        }
+
+        // L2: cull + compact on the GPU before the main pass consumes the indirect args
+        if use_gpu_culling && let Some(culler) = self.gpu_culler.as_ref() {
+            let cull_scope = self.profiler.begin_pass("CullPass");
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("CullPass"),
+                timestamp_writes: self.profiler.compute_timestamp_writes(cull_scope),
+            });
+            culler.dispatch(&mut cpass);
+            drop(cpass);
+            self.profiler.end_pass(cull_scope);
+        }
+
+        let main_scope = self.profiler.begin_pass("MainPass");
+
         let mut rpass = encoder.begin_render_pass(&RenderPassDescriptor {
             label: Some("MainPass"),
             color_attachments: &[Some(RenderPassColorAttachment {
@@ -1003,12 +2497,14 @@ impl GpuState {
                 stencil_ops: None,
             }),
             occlusion_query_set: None,
-            timestamp_writes: None,
+            timestamp_writes: self.profiler.render_timestamp_writes(main_scope),
         });
 
         // Set initial pipeline state
         rpass.set_pipeline(&self.pipeline);
         rpass.set_bind_group(0, &self.camera_bg, &[]);
+        stats.pipeline_switches += 1;
+        stats.bind_group_switches += 1;
 
         // Update camera uniforms once per frame
         let pv = self.camera.proj_view();
@@ -1020,6 +2516,7 @@ impl GpuState {
                 mvp: mvp.to_cols_array_2d(),
             }),
         );
+        stats.uploaded_bytes += std::mem::size_of::<CameraUniform>() as u64;
 
         // G1: Render batches with minimal state changes
         let stride = std::mem::size_of::<InstanceRaw>() as u64;
@@ -1027,44 +2524,172 @@ impl GpuState {
         let mut current_texture = TextureId::INVALID;
         let mut state_changes = 0u32;
 
-        for batch in &self.draw_batches {
-            if batch.count == 0 {
-                continue;
-            }
+        // L2: indirect path binds the compacted buffer once when first_instance is usable
+        let gpu_culler = self.gpu_culler.as_ref().filter(|_| use_gpu_culling);
+        let shared_visible = gpu_culler.is_some_and(|c| c.first_instance_supported);
+        if let Some(culler) = gpu_culler
+            && shared_visible
+        {
+            rpass.set_vertex_buffer(1, culler.visible_buffer().slice(..));
+        }
 
-            let key = batch.key;
+        // J2: blended materials go after all opaque batches
+        let mut file_material_bound = false;
+        let mut bound_pipeline = (MaterialId::new(0), 0);
+        let mut bound_vertex = None;
+        let mut bound_index = None;
+        for blend_pass in [false, true] {
+            let mut batch_idx = 0;
+            while batch_idx < self.draw_batches.len() {
+                let batch = &self.draw_batches[batch_idx];
+                if batch.count == 0 {
+                    batch_idx += 1;
+                    continue;
+                }
 
-            // G1: Only change material bind group when material changes
-            if key.material != current_material {
-                rpass.set_bind_group(1, &self.material_bg, &[]);
-                current_material = key.material;
-                state_changes += 1;
-            }
+                let key = batch.key;
+                if self.materials.is_blend(key.material) != blend_pass {
+                    batch_idx += 1;
+                    continue;
+                }
 
-            // G1: Only change texture bind group when texture changes
-            if key.texture != current_texture {
-                // For now, we use the same texture bind group for all textures
-                // In a full implementation, we'd have different bind groups per texture
-                rpass.set_bind_group(2, &self.texture_bg, &[]);
-                current_texture = key.texture;
-                state_changes += 1;
-            }
+                // Get mesh data
+                let Some(mesh) = self.mesh_store.get(key.mesh) else {
+                    log::warn!("Missing mesh id {:?}", key.mesh);
+                    batch_idx += 1;
+                    continue;
+                };
+
+                // J2: material files bring their own pipeline; M1: meshes outside the default
+                // vertex layout use the (material, layout) variant
+                let file_material = self.materials.gpu(key.material);
+                let pipeline_key = (
+                    file_material.map_or(MaterialId::new(0), |_| key.material),
+                    mesh.layout,
+                );
+                if pipeline_key != bound_pipeline {
+                    let pipeline = if mesh.layout == 0 {
+                        file_material.map_or(&self.pipeline, |m| &m.pipeline)
+                    } else if let Some(Some(variant)) = self.layout_pipelines.get(&pipeline_key) {
+                        variant
+                    } else {
+                        batch_idx += 1;
+                        continue;
+                    };
+                    rpass.set_pipeline(pipeline);
+                    stats.pipeline_switches += 1;
+                    bound_pipeline = pipeline_key;
+                }
 
-            // Get mesh data
-            let Some(mesh) = self.mesh_store.get(key.mesh) else {
-                log::warn!("Missing mesh id {:?}", key.mesh);
-                continue;
-            };
+                // G1: Only change material bind group when material changes
+                if key.material != current_material {
+                    if let Some(material) = file_material {
+                        // J2: material files bring all three groups
+                        for (index, group) in material.bind_groups.iter().enumerate() {
+                            rpass.set_bind_group(index as u32, group, &[]);
+                        }
+                        state_changes += material.bind_groups.len() as u32;
+                        file_material_bound = true;
+                    } else {
+                        if file_material_bound {
+                            rpass.set_bind_group(0, &self.camera_bg, &[]);
+                            state_changes += 1;
+                            current_texture = TextureId::INVALID;
+                            file_material_bound = false;
+                        }
+                        rpass.set_bind_group(1, &self.material_bg, &[]);
+                        state_changes += 1;
+                    }
+                    current_material = key.material;
+                }
+
+                // G1: Only change texture bind group when texture changes
+                if !file_material_bound && key.texture != current_texture {
+                    // E2: unknown ids sample the default texture
+                    let group = self
+                        .texture_bgs
+                        .get(key.texture.0 as usize)
+                        .unwrap_or(&self.texture_bgs[self.default_texture_id.0 as usize]);
+                    rpass.set_bind_group(2, group, &[]);
+                    current_texture = key.texture;
+                    state_changes += 1;
+                }
 
-            // Set vertex/index buffers and draw
-            let instance_start = batch.start as u64 * stride;
-            let instance_end = instance_start + batch.count as u64 * stride;
+                // Set vertex/index buffers and draw
+                let instance_start = batch.start as u64 * stride;
+                let instance_end = instance_start + batch.count as u64 * stride;
 
-            rpass.set_vertex_buffer(0, mesh.vertex_buf.slice(..));
-            rpass.set_vertex_buffer(1, self.instance_buf.slice(instance_start..instance_end));
-            rpass.set_index_buffer(mesh.index_buf.slice(..), mesh.index_format);
-            rpass.draw_indexed(0..mesh.index_count, 0, 0..batch.count as u32);
+                // M1: meshes share arena buffers, so rebinding is rare
+                if bound_vertex != Some(mesh.vertex_block) {
+                    rpass.set_vertex_buffer(0, self.mesh_store.buffer(mesh.vertex_block).slice(..));
+                    bound_vertex = Some(mesh.vertex_block);
+                    stats.buffer_binds += 1;
+                }
+                if bound_index != Some(mesh.index_block) {
+                    rpass.set_index_buffer(
+                        self.mesh_store.buffer(mesh.index_block).slice(..),
+                        mesh.index_format,
+                    );
+                    bound_index = Some(mesh.index_block);
+                    stats.buffer_binds += 1;
+                }
+                stats.triangles += (mesh.index_count / 3) as u64 * batch.count as u64;
+
+                let Some(culler) = gpu_culler else {
+                    rpass.set_vertex_buffer(
+                        1,
+                        self.instance_buf.slice(instance_start..instance_end),
+                    );
+                    let indices = mesh.first_index..mesh.first_index + mesh.index_count;
+                    rpass.draw_indexed(indices, mesh.base_vertex, 0..batch.count as u32);
+                    stats.draw_calls += 1;
+                    stats.instances += batch.count as u32;
+                    batch_idx += 1;
+                    continue;
+                };
+
+                // L2: instance counts come from the cull shader via indirect args
+                if !shared_visible {
+                    rpass.set_vertex_buffer(
+                        1,
+                        culler.visible_buffer().slice(instance_start..instance_end),
+                    );
+                }
+                let mut run = 1;
+                if shared_visible && culler.multi_draw_supported {
+                    // Consecutive batches with identical bindings go into one multi-draw.
+                    while let Some(next) = self.draw_batches.get(batch_idx + run) {
+                        let same_state = next.key.material == key.material
+                            && next.key.texture == key.texture
+                            && self
+                                .mesh_store
+                                .get(next.key.mesh)
+                                .is_some_and(|m| mesh.shares_buffers(m));
+                        if !same_state {
+                            break;
+                        }
+                        if let Some(m) = self.mesh_store.get(next.key.mesh) {
+                            stats.triangles += (m.index_count / 3) as u64 * next.count as u64;
+                        }
+                        run += 1;
+                    }
+                    rpass.multi_draw_indexed_indirect(
+                        culler.draw_args_buffer(),
+                        batch_idx as u64 * DrawIndexedIndirectArgs::SIZE,
+                        run as u32,
+                    );
+                } else {
+                    rpass.draw_indexed_indirect(
+                        culler.draw_args_buffer(),
+                        batch_idx as u64 * DrawIndexedIndirectArgs::SIZE,
+                    );
+                }
+                stats.draw_calls += 1;
+                batch_idx += run;
+            }
         }
+        stats.batches = self.draw_batches.len() as u32;
+        stats.bind_group_switches += state_changes;
 
         // G1: Log state changes for performance monitoring
         if !self.draw_batches.is_empty() {
@@ -1075,13 +2700,429 @@ impl GpuState {
                 state_changes as f32 / self.draw_batches.len() as f32
             );
         }
+
+        // K3: particles after opaque geometry (depth test, no depth writes)
+        if let Some(particles) = self.particles.as_ref() {
+            particles.draw(&mut rpass);
+        }
+
+        // L1: overlay drawn last inside the main pass
+        if let Some(mip) = self.hiz_debug_mip
+            && build_hiz
+            && let Some(hiz) = self.hiz.as_ref()
+        {
+            hiz.write_debug_params(&self.queue, mip);
+            hiz.draw_debug(&mut rpass, self.width, self.height);
+        }
         drop(rpass);
+        self.profiler.end_pass(main_scope);
+
+        // I1: UI over the finished scene
+        if let Some(overlay) = overlay {
+            overlay.draw(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                &view,
+                [self.width, self.height],
+            );
+        }
 
+        self.profiler.resolve(&mut encoder);
         self.queue.submit(Some(encoder.finish()));
+        self.profiler.end_frame();
+        if let Some(hiz) = self.hiz.as_mut() {
+            hiz.after_submit();
+        }
         frame.present();
-        Ok(())
+
+        self.depth_valid = true;
+        self.prev_view_proj = mvp;
+
+        stats.uploaded_bytes += std::mem::take(&mut self.pending_upload_bytes);
+        Ok(stats)
+    }
+
+    /// G1 + L1: CPU path — cull, sort by [`DrawKey`] and split into batches.
+    fn prepare_cpu_batches(
+        &mut self,
+        draw_list: &[DrawInstance],
+        use_occlusion: bool,
+        stats: &mut RenderStats,
+    ) {
+        // G1: Prepare and sort draw commands for optimal batching
+        self.instance_entries.clear();
+        self.instance_entries.reserve(draw_list.len());
+
+        // L1: frustum from the same camera that is uploaded below
+        let frustum = self.camera.frustum();
+        let ctx = PrepContext {
+            meshes: &self.mesh_store,
+            frustum: self.frustum_culling.then_some(&frustum),
+            hiz: self
+                .hiz
+                .as_ref()
+                .filter(|_| use_occlusion)
+                .map(|h| h.snapshot()),
+            default_texture: self.default_texture_id,
+        };
+
+        let threads = parallel::effective_threads(self.draw_prep_threads, draw_list.len());
+        stats.prep_threads = threads as u32;
+        if threads == 1 {
+            let mut out = PrepChunk {
+                entries: std::mem::take(&mut self.instance_entries),
+                ..Default::default()
+            };
+            for item in draw_list {
+                ctx.prepare(item, &mut out);
+            }
+            stats.culled_instances += out.culled;
+            stats.occluded_instances += out.occluded;
+            self.instance_entries = out.entries;
+
+            // G1: Sort by DrawKey (PSO -> Material -> Texture -> Mesh)
+            self.instance_entries.sort_by_key(|entry| entry.key);
+        } else {
+            // G1: matrices + culling per chunk, merged in draw-list order
+            if self.prep_chunks.len() < threads {
+                self.prep_chunks.resize_with(threads, PrepChunk::default);
+            }
+            let chunks = &mut self.prep_chunks[..threads];
+            for chunk in chunks.iter_mut() {
+                chunk.entries.clear();
+                chunk.culled = 0;
+                chunk.occluded = 0;
+            }
+            parallel::run_chunks(draw_list, chunks, |_, items, out| {
+                for item in items {
+                    ctx.prepare(item, out);
+                }
+            });
+            for chunk in chunks.iter() {
+                stats.culled_instances += chunk.culled;
+                stats.occluded_instances += chunk.occluded;
+                self.sorted_entries.extend_from_slice(&chunk.entries);
+            }
+
+            // G1: radix sort (key, index) pairs, then gather entries in key order
+            self.sort_items.clear();
+            self.sort_items.extend(
+                self.sorted_entries
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| SortItem {
+                        key: parallel::pack_key(&e.key),
+                        index: i as u32,
+                    }),
+            );
+            parallel::radix_sort(&mut self.sort_items, &mut self.sort_scratch, threads);
+            self.instance_entries.extend(
+                self.sort_items
+                    .iter()
+                    .map(|it| self.sorted_entries[it.index as usize]),
+            );
+            self.sorted_entries.clear();
+        }
+        stats.visible_instances = self.instance_entries.len() as u32;
+
+        // G1: Create batches with same render state
+        self.draw_batches.clear();
+        self.draw_batches.reserve(self.instance_entries.len());
+        self.instance_data.clear();
+        self.instance_data.reserve(self.instance_entries.len());
+
+        if !self.instance_entries.is_empty() {
+            let mut batch_start = 0;
+            let mut current_key = self.instance_entries[0].key;
+
+            for (idx, entry) in self.instance_entries.iter().enumerate() {
+                if entry.key != current_key {
+                    // End current batch
+                    let batch_count = idx - batch_start;
+                    self.draw_batches.push(DrawBatch {
+                        key: current_key,
+                        start: batch_start,
+                        count: batch_count,
+                    });
+
+                    // Start new batch
+                    batch_start = idx;
+                    current_key = entry.key;
+                }
+                self.instance_data.push(entry.instance);
+            }
+
+            // Add final batch
+            let batch_count = self.instance_entries.len() - batch_start;
+            self.draw_batches.push(DrawBatch {
+                key: current_key,
+                start: batch_start,
+                count: batch_count,
+            });
+        }
+    }
+
+    /// L2: GPU path — no sort; bucket instances per [`DrawKey`] and let the cull shader compact them.
+    fn prepare_gpu_batches(&mut self, draw_list: &[DrawInstance], stats: &mut RenderStats) {
+        self.batch_lookup.clear();
+        self.draw_batches.clear();
+        self.gpu_instances.clear();
+        self.gpu_instances.reserve(draw_list.len());
+        let threads = parallel::effective_threads(self.draw_prep_threads, draw_list.len());
+        stats.prep_threads = threads as u32;
+        self.gpu_sources.clear();
+
+        for (source, item) in draw_list.iter().enumerate() {
+            if self.mesh_store.get(item.mesh).is_none() {
+                log::warn!("Missing mesh id {:?}", item.mesh);
+                continue;
+            }
+            let texture = if item.texture == TextureId::INVALID {
+                self.default_texture_id
+            } else {
+                item.texture
+            };
+            let key = DrawKey {
+                pso_id: 0,
+                material: item.material,
+                texture,
+                mesh: item.mesh,
+            };
+
+            let batch = *self.batch_lookup.entry(key).or_insert_with(|| {
+                self.draw_batches.push(DrawBatch {
+                    key,
+                    start: 0,
+                    count: 0,
+                });
+                (self.draw_batches.len() - 1) as u32
+            });
+            self.draw_batches[batch as usize].count += 1;
+            // G1: matrices are filled in parallel below
+            let model = if threads == 1 {
+                item.transform.matrix().to_cols_array_2d()
+            } else {
+                self.gpu_sources.push(source as u32);
+                [[0.0; 4]; 4]
+            };
+            self.gpu_instances.push(GpuCullInstance {
+                model,
+                batch,
+                lod_fade: item.lod_fade,
+                _padding: [0; 2],
+            });
+        }
+
+        if threads > 1 {
+            parallel::zip_chunks_mut(
+                &self.gpu_sources,
+                &mut self.gpu_instances,
+                threads,
+                |src, dst| {
+                    for (&i, inst) in src.iter().zip(dst) {
+                        inst.model = draw_list[i as usize].transform.matrix().to_cols_array_2d();
+                    }
+                },
+            );
+        }
+
+        // Batches are few: sort them (not instances) by key and remap indices.
+        let mut order: Vec<u32> = (0..self.draw_batches.len() as u32).collect();
+        order.sort_by_key(|&i| self.draw_batches[i as usize].key);
+        let mut remap = vec![0u32; order.len()];
+        for (new_idx, &old_idx) in order.iter().enumerate() {
+            remap[old_idx as usize] = new_idx as u32;
+        }
+        for inst in &mut self.gpu_instances {
+            inst.batch = remap[inst.batch as usize];
+        }
+        // Output regions: prefix sum of per-batch instance counts.
+        let mut offset = 0;
+        let sorted: Vec<DrawBatch> = order
+            .iter()
+            .map(|&i| {
+                let b = &self.draw_batches[i as usize];
+                let start = offset;
+                offset += b.count;
+                DrawBatch {
+                    key: b.key,
+                    start,
+                    count: b.count,
+                }
+            })
+            .collect();
+        self.draw_batches = sorted;
+        self.build_gpu_batch_args();
+        // Visible/culled counts live on the GPU; stats report submitted instances.
+        stats.instances = self.gpu_instances.len() as u32;
+    }
+
+    /// L2: cull inputs and indirect args for `draw_batches` (starts already assigned).
+    fn build_gpu_batch_args(&mut self) {
+        let first_instance = self
+            .gpu_culler
+            .as_ref()
+            .is_some_and(|c| c.first_instance_supported);
+        self.gpu_batches.clear();
+        self.gpu_draw_args.clear();
+        for batch in &self.draw_batches {
+            let Some(mesh) = self.mesh_store.get(batch.key.mesh) else {
+                // Keep indices aligned with draw_batches; the draw loop skips missing meshes.
+                self.gpu_batches.push(GpuCullBatch::zeroed());
+                self.gpu_draw_args.push(DrawIndexedIndirectArgs::zeroed());
+                continue;
+            };
+            let sphere = mesh.bounds.sphere;
+            let aabb = mesh.bounds.aabb;
+            self.gpu_batches.push(GpuCullBatch {
+                sphere: [
+                    sphere.center[0],
+                    sphere.center[1],
+                    sphere.center[2],
+                    sphere.radius,
+                ],
+                aabb_min: [aabb.min[0], aabb.min[1], aabb.min[2], 0.0],
+                aabb_max: [aabb.max[0], aabb.max[1], aabb.max[2], 0.0],
+                out_start: batch.start as u32,
+                _padding: [0; 3],
+            });
+            self.gpu_draw_args.push(DrawIndexedIndirectArgs {
+                index_count: mesh.index_count,
+                instance_count: 0,
+                first_index: mesh.first_index,
+                base_vertex: mesh.base_vertex,
+                first_instance: if first_instance {
+                    batch.start as u32
+                } else {
+                    0
+                },
+            });
+        }
+    }
+
+    /// Upload per-frame instance data for the active path.
+    fn upload_instances(
+        &mut self,
+        use_gpu_culling: bool,
+        use_occlusion: bool,
+        stats: &mut RenderStats,
+    ) {
+        if use_gpu_culling {
+            let frustum = self.frustum_culling.then(|| self.camera.frustum());
+            let occlusion = occlusion_input(self.hiz.as_ref(), self.prev_view_proj, use_occlusion);
+            let culler = self.gpu_culler.as_mut().expect("checked by caller");
+            let (bytes, reallocations) = culler.prepare(
+                &self.device,
+                &self.queue,
+                frustum.as_ref(),
+                occlusion.as_ref(),
+                &self.gpu_instances,
+                &self.gpu_batches,
+                &self.gpu_draw_args,
+            );
+            stats.uploaded_bytes += bytes;
+            stats.buffer_reallocations += reallocations;
+            return;
+        }
+
+        self.ensure_instance_capacity(self.instance_data.len(), stats);
+        if !self.instance_data.is_empty() {
+            let bytes: &[u8] = bytemuck::cast_slice(&self.instance_data);
+            self.queue.write_buffer(&self.instance_buf, 0, bytes);
+            stats.uploaded_bytes += bytes.len() as u64;
+        }
+        self.instance_count = self.instance_data.len() as u32;
+    }
+
+    /// Grow the CPU-path instance buffer (contents are lost on growth).
+    fn ensure_instance_capacity(&mut self, count: usize, stats: &mut RenderStats) -> bool {
+        let needed = (count.max(1) as u64) * std::mem::size_of::<InstanceRaw>() as u64;
+        if needed <= self.instance_buf.size() {
+            return false;
+        }
+        let new_cap = needed.next_power_of_two();
+        self.instance_buf = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instance Buffer (grown)"),
+            size: new_cap,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        self.instance_capacity = (new_cap / std::mem::size_of::<InstanceRaw>() as u64) as u32;
+        stats.buffer_reallocations += 1;
+        true
     }
 
+    /// L2: upload retained instances — everything after a rebuild, dirty ranges otherwise.
+    fn upload_retained(
+        &mut self,
+        update: RetainedUpdate,
+        flags: FrameFlags,
+        stats: &mut RenderStats,
+    ) {
+        let count = self.retained.len();
+        if flags.gpu_culling {
+            let culler = self.gpu_culler.as_mut().expect("checked by caller");
+            let mut full = update == RetainedUpdate::Full;
+            let reallocations = culler.reserve(&self.device, count, self.gpu_batches.len());
+            stats.buffer_reallocations += reallocations;
+            full |= reallocations > 0;
+
+            let (data, batch_of) = (self.retained.data(), self.retained.batch_of());
+            let ranges: &[(u32, u32)] = if full {
+                &[(0, count as u32)]
+            } else {
+                self.retained.dirty_ranges()
+            };
+            for &(start, end) in ranges {
+                let (start_idx, end_idx) = (start as usize, end as usize);
+                self.gpu_instances.clear();
+                self.gpu_instances.extend(
+                    data[start_idx..end_idx]
+                        .iter()
+                        .zip(&batch_of[start_idx..end_idx])
+                        .map(|(raw, &batch)| cull_instance(raw, batch)),
+                );
+                stats.uploaded_bytes +=
+                    culler.write_instances(&self.queue, start, &self.gpu_instances);
+            }
+            if full {
+                stats.uploaded_bytes += culler.write_batches(&self.queue, &self.gpu_batches);
+            }
+
+            let frustum = self.frustum_culling.then(|| self.camera.frustum());
+            let occlusion =
+                occlusion_input(self.hiz.as_ref(), self.prev_view_proj, flags.occlusion);
+            stats.uploaded_bytes += culler.write_frame(
+                &self.device,
+                &self.queue,
+                frustum.as_ref(),
+                occlusion.as_ref(),
+                count as u32,
+                &self.gpu_draw_args,
+            );
+            return;
+        }
+
+        let grown = self.ensure_instance_capacity(count, stats);
+        let data = self.retained.data();
+        let stride = std::mem::size_of::<InstanceRaw>() as u64;
+        if update == RetainedUpdate::Full || grown {
+            if !data.is_empty() {
+                let bytes: &[u8] = bytemuck::cast_slice(data);
+                self.queue.write_buffer(&self.instance_buf, 0, bytes);
+                stats.uploaded_bytes += bytes.len() as u64;
+            }
+        } else {
+            for &(start, end) in self.retained.dirty_ranges() {
+                let bytes: &[u8] = bytemuck::cast_slice(&data[start as usize..end as usize]);
+                self.queue
+                    .write_buffer(&self.instance_buf, start as u64 * stride, bytes);
+                stats.uploaded_bytes += bytes.len() as u64;
+            }
+        }
+        self.instance_count = count as u32;
+    }
 
     /// Get device reference for egui integration.
     pub fn device(&self) -> &Device {
@@ -1099,7 +3140,7 @@ impl GpuState {
     }
 
     /// Get surface reference for egui integration.
-    pub fn surface(&self) -> &Surface {
+    pub fn surface(&self) -> &Surface<'_> {
         &self.surface
     }
 
@@ -1114,6 +3155,196 @@ impl GpuState {
     }
 }
 
+/// Path decisions for one frame.
+#[derive(Clone, Copy)]
+struct FrameFlags {
+    /// L2: compute culling + indirect draws.
+    gpu_culling: bool,
+    /// L1: Hi-Z occlusion test this frame.
+    occlusion: bool,
+    /// L1: rebuild the pyramid from the previous frame's depth.
+    build_hiz: bool,
+}
+
+/// L1: Hi-Z inputs for the cull shader (`None` when occlusion is off this frame).
+fn occlusion_input(
+    hiz: Option<&HiZPyramid>,
+    view_proj: Mat4,
+    enabled: bool,
+) -> Option<OcclusionInput<'_>> {
+    hiz.filter(|_| enabled).map(|hiz| OcclusionInput {
+        view: hiz.view(),
+        generation: hiz.generation(),
+        view_proj,
+        size: hiz.size(),
+        mip_count: hiz.mip_count(),
+    })
+}
+
+/// L2: retained instance -> cull shader input.
+fn cull_instance(raw: &InstanceRaw, batch: u32) -> GpuCullInstance {
+    GpuCullInstance {
+        model: [raw.col0, raw.col1, raw.col2, raw.col3],
+        batch,
+        lod_fade: raw.lod[0],
+        _padding: [0; 2],
+    }
+}
+
+/// J2: bind groups of the main pipeline: 0 = frame, 1 = material, 2 = textures.
+const MAIN_BIND_GROUPS: u32 = 3;
+
+/// J2: renderer-provided resources of the main shader, addressed by (group, binding).
+struct MainResources<'a> {
+    camera: &'a Buffer,
+    material: &'a Buffer,
+    lighting: &'a Buffer,
+    texture: &'a TextureView,
+    sampler: &'a Sampler,
+}
+
+impl MainResources<'_> {
+    fn resource(&self, group: u32, binding: u32) -> Option<wgpu::BindingResource<'_>> {
+        Some(match (group, binding) {
+            (0, 0) => self.camera.as_entire_binding(),
+            (1, 0) => self.material.as_entire_binding(),
+            (1, 1) => self.lighting.as_entire_binding(),
+            (2, 0) => wgpu::BindingResource::TextureView(self.texture),
+            (2, 1) => wgpu::BindingResource::Sampler(self.sampler),
+            _ => return None,
+        })
+    }
+}
+
+/// J2: pipeline layout + bind groups for whatever subset of the standard slots `reflection` uses,
+/// plus the texture group layout (E2: per-texture groups are built against it).
+fn create_main_bindings(
+    device: &Device,
+    reflection: &ShaderReflection,
+    resources: &MainResources<'_>,
+) -> Result<(wgpu::PipelineLayout, [BindGroup; 3], wgpu::BindGroupLayout), String> {
+    if reflection.group_count() > MAIN_BIND_GROUPS {
+        return Err(format!(
+            "main shader uses bind group {}, the renderer provides 0..{}",
+            reflection.group_count() - 1,
+            MAIN_BIND_GROUPS
+        ));
+    }
+    let mut layouts = Vec::new();
+    let mut groups = Vec::new();
+    for (group, label) in ["Camera", "Material", "Texture"].into_iter().enumerate() {
+        let group = group as u32;
+        let mut entries = Vec::new();
+        for b in reflection.group(group) {
+            let resource = resources.resource(b.group, b.binding).ok_or_else(|| {
+                format!(
+                    "binding '{}' (group {}, binding {}) is not provided by the renderer",
+                    b.name.as_deref().unwrap_or("<unnamed>"),
+                    b.group,
+                    b.binding
+                )
+            })?;
+            entries.push(wgpu::BindGroupEntry {
+                binding: b.binding,
+                resource,
+            });
+        }
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some(&format!("{label} BGL")),
+            entries: &reflection.group_entries(group),
+        });
+        groups.push(device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("{label} BG")),
+            layout: &layout,
+            entries: &entries,
+        }));
+        layouts.push(layout);
+    }
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some("Basic PipelineLayout"),
+        bind_group_layouts: &layouts.iter().collect::<Vec<_>>(),
+        push_constant_ranges: &[],
+    });
+    let groups: [BindGroup; 3] = groups.try_into().unwrap_or_else(|_| unreachable!());
+    let texture_layout = layouts.pop().expect("three groups");
+    Ok((pipeline_layout, groups, texture_layout))
+}
+
+/// Fixed-function state that differs between materials.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct PipelineState {
+    // Alpha blending without depth writes
+    blend: bool,
+    cull: Option<wgpu::Face>,
+}
+
+impl From<&MaterialDesc> for PipelineState {
+    fn from(desc: &MaterialDesc) -> Self {
+        Self {
+            blend: desc.alpha_mode == AlphaMode::Blend,
+            cull: match desc.cull_mode {
+                CullMode::None => None,
+                CullMode::Back => Some(wgpu::Face::Back),
+                CullMode::Front => Some(wgpu::Face::Front),
+            },
+        }
+    }
+}
+
+/// Main (cube/mesh) render pipeline from WGSL `source`.
+fn create_main_pipeline(
+    device: &Device,
+    layout: &wgpu::PipelineLayout,
+    source: &str,
+    color_format: TextureFormat,
+    vertex: VertexBufferLayout,
+    state: PipelineState,
+) -> RenderPipeline {
+    let shader = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Basic WGSL"),
+        source: ShaderSource::Wgsl(source.into()),
+    });
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Cube Pipeline"),
+        layout: Some(layout),
+        vertex: VertexState {
+            module: &shader,
+            entry_point: Some("vs_main"),
+            buffers: &[vertex, InstanceRaw::LAYOUT],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        },
+        fragment: Some(FragmentState {
+            module: &shader,
+            entry_point: Some("fs_main"),
+            targets: &[Some(ColorTargetState {
+                format: color_format,
+                blend: Some(if state.blend {
+                    BlendState::ALPHA_BLENDING
+                } else {
+                    BlendState::REPLACE
+                }),
+                write_mask: ColorWrites::ALL,
+            })],
+            compilation_options: wgpu::PipelineCompilationOptions::default(),
+        }),
+        // На WSL/GLES — без culling для стабильности (материалы могут включить его сами)
+        primitive: wgpu::PrimitiveState {
+            cull_mode: state.cull,
+            ..Default::default()
+        },
+        depth_stencil: Some(DepthStencilState {
+            format: DEPTH_FORMAT,
+            depth_write_enabled: !state.blend,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: wgpu::StencilState::default(),
+            bias: DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState::default(),
+        multiview: None,
+        cache: None,
+    })
+}
+
 fn create_depth_view(device: &Device, sc: &SurfaceConfiguration) -> TextureView {
     let tex = device.create_texture(&TextureDescriptor {
         label: Some("DepthTex"),
@@ -1126,7 +3357,8 @@ fn create_depth_view(device: &Device, sc: &SurfaceConfiguration) -> TextureView
         sample_count: 1,
         dimension: TextureDimension::D2,
         format: DEPTH_FORMAT,
-        usage: TextureUsages::RENDER_ATTACHMENT,
+        // L1: sampled by the Hi-Z build pass
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
         view_formats: &[],
     });
     tex.create_view(&TextureViewDescriptor::default())
@@ -0,0 +1,293 @@
+//! L1: per-instance LOD selection by projected screen size.
+//! Hysteresis keeps instances near a threshold from flipping every frame; switches can
+//! optionally cross-fade with complementary dither patterns (see `lod_fade` in the shader).
+
+use asset::mesh::BoundingSphere;
+use corelib::{
+    Mat4, Vec3,
+    camera::Camera,
+    ecs::{LodGroupId, LodState, MeshId},
+    transform::Transform,
+};
+
+/// One level of a [`LodGroup`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LodLevel {
+    pub mesh: MeshId,
+    /// Smallest projected size (fraction of screen height) at which this level is used.
+    /// Ignored for the last level.
+    pub min_screen_size: f32,
+}
+
+/// Meshes of one object from finest to coarsest.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LodGroup {
+    pub levels: Vec<LodLevel>,
+    /// LOD0 bounds; used for the screen-size metric of every level.
+    pub sphere: BoundingSphere,
+}
+
+/// Global LOD tuning.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LodSettings {
+    /// Relative dead zone around thresholds (0.15 = ±15%).
+    pub hysteresis: f32,
+    /// Cross-fade duration in seconds; 0 disables dithered fading.
+    pub fade_seconds: f32,
+    /// Multiplies screen sizes; >1 keeps finer levels longer.
+    pub bias: f32,
+}
+
+impl Default for LodSettings {
+    fn default() -> Self {
+        Self {
+            hysteresis: 0.15,
+            fade_seconds: 0.25,
+            bias: 1.0,
+        }
+    }
+}
+
+/// Default thresholds: each level takes over when the object halves on screen.
+pub fn default_thresholds(level_count: usize) -> Vec<f32> {
+    (0..level_count)
+        .map(|i| 0.5 * 0.5f32.powi(i as i32))
+        .collect()
+}
+
+/// Diameter of a world-space sphere as a fraction of the screen height.
+pub fn projected_screen_size(camera: &Camera, center: Vec3, radius: f32) -> f32 {
+    let distance = (center - camera.eye).length();
+    if distance <= radius {
+        return f32::MAX;
+    }
+    let half_fov_tan = (camera.fov_y_rad * 0.5).tan().max(1e-6);
+    radius / (distance * half_fov_tan)
+}
+
+/// Level for `screen_size` without hysteresis.
+fn raw_level(levels: &[LodLevel], screen_size: f32) -> usize {
+    let last = levels.len().saturating_sub(1);
+    levels[..last]
+        .iter()
+        .position(|l| screen_size >= l.min_screen_size)
+        .unwrap_or(last)
+}
+
+/// Select a level, only leaving `current` once the size is clearly past a threshold.
+pub fn select_level(
+    levels: &[LodLevel],
+    screen_size: f32,
+    current: usize,
+    hysteresis: f32,
+) -> usize {
+    if levels.is_empty() {
+        return 0;
+    }
+    let current = current.min(levels.len() - 1);
+    // Go coarser only if even the inflated size says so, finer only if the deflated one does.
+    let coarser = raw_level(levels, screen_size * (1.0 + hysteresis));
+    if coarser > current {
+        return coarser;
+    }
+    let finer = raw_level(levels, screen_size * (1.0 - hysteresis));
+    if finer < current {
+        return finer;
+    }
+    current
+}
+
+/// Meshes to draw for one instance this frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LodDraw {
+    pub mesh: MeshId,
+    /// Shader `lod_fade` value for `mesh` (1.0 = fully visible).
+    pub fade: f32,
+    /// Outgoing level during a cross-fade, with its `lod_fade` value.
+    pub fading_out: Option<(MeshId, f32)>,
+}
+
+/// Group storage indexed by [`LodGroupId`].
+#[derive(Default)]
+pub struct LodStore {
+    groups: Vec<LodGroup>,
+}
+
+impl LodStore {
+    pub fn add(&mut self, group: LodGroup) -> LodGroupId {
+        assert!(
+            !group.levels.is_empty(),
+            "LOD group must have at least one level"
+        );
+        let id = LodGroupId::new(u32::try_from(self.groups.len()).expect("Too many LOD groups"));
+        self.groups.push(group);
+        id
+    }
+
+    pub fn get(&self, id: LodGroupId) -> Option<&LodGroup> {
+        self.groups.get(id.0 as usize)
+    }
+
+    /// Update `state` for this frame and return what to draw.
+    pub fn resolve(
+        &self,
+        id: LodGroupId,
+        model: Mat4,
+        camera: &Camera,
+        settings: &LodSettings,
+        state: &mut LodState,
+        dt: f32,
+    ) -> Option<LodDraw> {
+        let group = self.get(id)?;
+        let center = model.transform_point3(Vec3::from(group.sphere.center));
+        let scale = model
+            .x_axis
+            .truncate()
+            .length()
+            .max(model.y_axis.truncate().length())
+            .max(model.z_axis.truncate().length());
+        let size =
+            projected_screen_size(camera, center, group.sphere.radius * scale) * settings.bias;
+
+        let level = select_level(
+            &group.levels,
+            size,
+            state.level as usize,
+            settings.hysteresis,
+        );
+        advance_state(state, level as u8, settings.fade_seconds, dt);
+
+        let mesh = group.levels[state.level as usize].mesh;
+        if !state.is_fading() {
+            return Some(LodDraw {
+                mesh,
+                fade: 1.0,
+                fading_out: None,
+            });
+        }
+        let previous = group.levels[(state.previous as usize).min(group.levels.len() - 1)].mesh;
+        Some(LodDraw {
+            mesh,
+            fade: state.fade,
+            fading_out: Some((previous, 1.0 + state.fade)),
+        })
+    }
+}
+
+/// G1: borrowed store + camera + settings; `Sync`, so LODs can be resolved from worker threads.
+#[derive(Clone, Copy)]
+pub struct LodContext<'a> {
+    pub store: &'a LodStore,
+    pub camera: &'a Camera,
+    pub settings: LodSettings,
+}
+
+impl LodContext<'_> {
+    /// See [`LodStore::resolve`].
+    pub fn resolve(
+        &self,
+        group: LodGroupId,
+        transform: &Transform,
+        state: &mut LodState,
+        dt: f32,
+    ) -> Option<LodDraw> {
+        self.store.resolve(
+            group,
+            transform.matrix(),
+            self.camera,
+            &self.settings,
+            state,
+            dt,
+        )
+    }
+}
+
+/// Move `state` towards `level`, restarting the fade on a switch.
+fn advance_state(state: &mut LodState, level: u8, fade_seconds: f32, dt: f32) {
+    if level != state.level {
+        // A switch mid-fade drops the oldest level.
+        state.previous = state.level;
+        state.level = level;
+        state.fade = if fade_seconds > 0.0 { 0.0 } else { 1.0 };
+        return;
+    }
+    if state.fade < 1.0 {
+        state.fade = if fade_seconds > 0.0 {
+            (state.fade + dt / fade_seconds).min(1.0)
+        } else {
+            1.0
+        };
+    }
+    if state.fade >= 1.0 {
+        state.previous = state.level;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels() -> Vec<LodLevel> {
+        default_thresholds(3)
+            .into_iter()
+            .enumerate()
+            .map(|(i, min_screen_size)| LodLevel {
+                mesh: MeshId::new(i as u32),
+                min_screen_size,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hysteresis_prevents_popping_at_threshold() {
+        let levels = levels();
+        // Thresholds: LOD0 >= 0.5, LOD1 >= 0.25, else LOD2.
+        assert_eq!(select_level(&levels, 0.6, 0, 0.1), 0);
+        // Slightly below the LOD0 threshold: stay on LOD0.
+        assert_eq!(select_level(&levels, 0.48, 0, 0.1), 0);
+        assert_eq!(select_level(&levels, 0.4, 0, 0.1), 1);
+        // Slightly above it again: stay on LOD1 until clearly past.
+        assert_eq!(select_level(&levels, 0.52, 1, 0.1), 1);
+        assert_eq!(select_level(&levels, 0.6, 1, 0.1), 0);
+        // Big jumps skip levels.
+        assert_eq!(select_level(&levels, 0.01, 0, 0.1), 2);
+    }
+
+    #[test]
+    fn lod_switch_cross_fades() {
+        let mut state = LodState::default();
+        advance_state(&mut state, 1, 0.5, 0.016);
+        assert_eq!((state.level, state.previous, state.fade), (1, 0, 0.0));
+        advance_state(&mut state, 1, 0.5, 0.25);
+        assert!(state.is_fading());
+        assert!((state.fade - 0.5).abs() < 1e-6);
+        advance_state(&mut state, 1, 0.5, 0.5);
+        assert!(!state.is_fading());
+        assert_eq!(state.previous, 1);
+
+        // Without fade time the switch is instant.
+        advance_state(&mut state, 2, 0.0, 0.016);
+        assert!(!state.is_fading());
+    }
+
+    #[test]
+    fn screen_size_shrinks_with_distance() {
+        let cam = Camera::test_default();
+        // 2 and 4 units in front of the camera; tan(30°) = 1/sqrt(3)
+        let near = projected_screen_size(&cam, Vec3::new(0.0, 0.0, 3.0), 1.0);
+        let far = projected_screen_size(&cam, Vec3::new(0.0, 0.0, 1.0), 1.0);
+        assert!((near - 3f32.sqrt() / 2.0).abs() < 1e-5);
+        assert!((far - 3f32.sqrt() / 4.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn main_shader_with_lod_dither_validates() {
+        let mut shaders = crate::shader::ShaderManager::with_embedded_includes();
+        let main = shaders.register(crate::MAIN_SHADER_NAME, crate::MAIN_SHADER_SOURCE);
+        let expanded = shaders
+            .permutation(main, &crate::main_shader_defs())
+            .expect("triangle.wgsl preprocesses");
+        assert!(expanded.source.contains("lod_dither_discard(in.pos.xy"));
+        crate::shader::validate_wgsl(&expanded.source).expect("triangle.wgsl validates");
+    }
+}
@@ -0,0 +1,353 @@
+//! J2: data-driven materials (`assets/materials/*.material.ron|json`) with hot reload.
+//! A material may reference a node graph file; editing either file rebuilds it.
+//!
+//! The library only keeps bookkeeping (descriptor, file state, built GPU objects);
+//! `GpuState` builds pipelines and bind groups, because they depend on its shared resources.
+//! `MaterialId(0)` is the built-in default material (main pipeline + `update_material`),
+//! loaded materials get ids from 1. A broken edit keeps the last good material.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime};
+
+use asset::material::{MaterialDesc, load_material};
+use asset::material_graph::{MaterialGraph, load_graph};
+use corelib::ecs::MaterialId;
+use wgpu::{BindGroup, Buffer, PipelineLayout, RenderPipeline};
+
+use crate::TextureGpu;
+use crate::shader::{DEFAULT_POLL_INTERVAL, ShaderId};
+
+/// A material file that currently fails to load or build.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MaterialError {
+    pub path: String,
+    pub message: String,
+}
+
+/// Everything needed to draw with one material.
+pub(crate) struct MaterialGpu {
+    pub pipeline: RenderPipeline,
+    // M1: reused by the material's pipelines for other vertex layouts
+    pub layout: PipelineLayout,
+    pub bind_groups: [BindGroup; 3],
+    // Referenced by the bind groups; owned here so they live as long as the material
+    #[allow(dead_code)]
+    pub uniform_buf: Buffer,
+    #[allow(dead_code)]
+    pub texture: Option<TextureGpu>,
+    pub blend: bool,
+}
+
+/// A material file plus the node graph it references.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct MaterialSource {
+    pub desc: MaterialDesc,
+    pub graph: Option<MaterialGraph>,
+}
+
+// Files a material was read from (asset path, mtime when read)
+type FileStamps = Vec<(String, Option<SystemTime>)>;
+
+struct MaterialEntry {
+    // Asset path relative to the assets root
+    path: String,
+    stamps: FileStamps,
+    source: MaterialSource,
+    shader: ShaderId,
+    gpu: MaterialGpu,
+    error: Option<String>,
+}
+
+/// A material (or its graph) changed on disk (parsed, not built yet).
+pub(crate) struct MaterialChange {
+    pub id: MaterialId,
+    pub source: Result<MaterialSource, String>,
+}
+
+pub(crate) struct MaterialLibrary {
+    root: Option<PathBuf>,
+    entries: Vec<MaterialEntry>,
+    poll_interval: Duration,
+    last_poll: Option<Instant>,
+}
+
+impl MaterialLibrary {
+    pub fn new() -> Self {
+        Self {
+            root: None,
+            entries: Vec::new(),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            last_poll: None,
+        }
+    }
+
+    pub fn set_root(&mut self, root: PathBuf) {
+        self.root = Some(root);
+    }
+
+    /// Absolute path of an asset path (relative to the root).
+    pub fn resolve(&self, path: &str) -> Result<PathBuf, String> {
+        self.root
+            .as_ref()
+            .map(|root| root.join(path))
+            .ok_or_else(|| format!("no asset root set for {path}"))
+    }
+
+    // Modification time of an asset file (`None` if it cannot be read).
+    fn modified(&self, path: &str) -> Option<SystemTime> {
+        let file = self.resolve(path).ok()?;
+        fs::metadata(file).and_then(|m| m.modified()).ok()
+    }
+
+    /// Read and parse a material file and its graph; also returns the files' modification times.
+    pub fn read(&self, path: &str) -> Result<(MaterialSource, FileStamps), String> {
+        let mut stamps = vec![(path.to_string(), self.modified(path))];
+        let desc = load_material(self.resolve(path)?).map_err(|e| format!("{e:#}"))?;
+        let graph = match &desc.graph {
+            Some(graph_path) => {
+                stamps.push((graph_path.clone(), self.modified(graph_path)));
+                Some(load_graph(self.resolve(graph_path)?).map_err(|e| format!("{e:#}"))?)
+            }
+            None => None,
+        };
+        Ok((MaterialSource { desc, graph }, stamps))
+    }
+
+    pub fn find(&self, path: &str) -> Option<MaterialId> {
+        self.entries
+            .iter()
+            .position(|e| e.path == path)
+            .map(|i| MaterialId::new(i as u32 + 1))
+    }
+
+    pub fn insert(
+        &mut self,
+        path: &str,
+        stamps: FileStamps,
+        source: MaterialSource,
+        shader: ShaderId,
+        gpu: MaterialGpu,
+    ) -> MaterialId {
+        self.entries.push(MaterialEntry {
+            path: path.to_string(),
+            stamps,
+            source,
+            shader,
+            gpu,
+            error: None,
+        });
+        MaterialId::new(self.entries.len() as u32)
+    }
+
+    fn entry(&self, id: MaterialId) -> Option<&MaterialEntry> {
+        (id.0 as usize)
+            .checked_sub(1)
+            .and_then(|i| self.entries.get(i))
+    }
+
+    fn entry_mut(&mut self, id: MaterialId) -> Option<&mut MaterialEntry> {
+        (id.0 as usize)
+            .checked_sub(1)
+            .and_then(|i| self.entries.get_mut(i))
+    }
+
+    /// Loaded material by id; `None` for the default material and unknown ids.
+    pub fn gpu(&self, id: MaterialId) -> Option<&MaterialGpu> {
+        self.entry(id).map(|e| &e.gpu)
+    }
+
+    /// Blended materials are drawn after everything opaque.
+    pub fn is_blend(&self, id: MaterialId) -> bool {
+        self.gpu(id).is_some_and(|g| g.blend)
+    }
+
+    pub fn path(&self, id: MaterialId) -> Option<&str> {
+        self.entry(id).map(|e| e.path.as_str())
+    }
+
+    pub fn source(&self, id: MaterialId) -> Option<&MaterialSource> {
+        self.entry(id).map(|e| &e.source)
+    }
+
+    pub fn shader(&self, id: MaterialId) -> Option<ShaderId> {
+        self.entry(id).map(|e| e.shader)
+    }
+
+    /// Ids of all loaded materials.
+    pub fn ids(&self) -> impl Iterator<Item = MaterialId> + use<> {
+        (1..=self.entries.len() as u32).map(MaterialId::new)
+    }
+
+    /// A rebuilt material replaces the old one.
+    pub fn replace(
+        &mut self,
+        id: MaterialId,
+        source: MaterialSource,
+        shader: ShaderId,
+        gpu: MaterialGpu,
+    ) {
+        if let Some(entry) = self.entry_mut(id) {
+            entry.source = source;
+            entry.shader = shader;
+            entry.gpu = gpu;
+            entry.error = None;
+            log::info!("Material {} reloaded", entry.path);
+        }
+    }
+
+    /// Swap the graph without rebuilding (edits that compile to the same code, e.g. node moves).
+    pub fn set_graph(&mut self, id: MaterialId, graph: MaterialGraph) {
+        if let Some(entry) = self.entry_mut(id) {
+            entry.source.graph = Some(graph);
+        }
+    }
+
+    /// Write the material's (possibly edited) graph back to its file.
+    pub fn save_graph(&mut self, id: MaterialId) -> Result<(), String> {
+        let entry = self
+            .entry(id)
+            .ok_or_else(|| format!("unknown material {id:?}"))?;
+        let (Some(path), Some(graph)) = (entry.source.desc.graph.clone(), &entry.source.graph)
+        else {
+            return Err(format!("{} has no material graph", entry.path));
+        };
+        let file = self.resolve(&path)?;
+        fs::write(&file, graph.to_ron())
+            .map_err(|e| format!("cannot write {}: {e}", file.display()))?;
+        // Our own write: not a change to reload
+        let modified = self.modified(&path);
+        if let Some(entry) = self.entry_mut(id) {
+            for stamp in entry.stamps.iter_mut().filter(|(p, _)| *p == path) {
+                stamp.1 = modified;
+            }
+        }
+        log::info!("Material graph {path} saved");
+        Ok(())
+    }
+
+    /// Loading or building failed; the previous version stays in use.
+    pub fn fail(&mut self, id: MaterialId, message: String) {
+        if let Some(entry) = self.entry_mut(id) {
+            log::error!(
+                "Material {} failed, keeping the last good version:\n{message}",
+                entry.path
+            );
+            entry.error = Some(message);
+        }
+    }
+
+    /// Material files modified since the last check (rate-limited like shader polling).
+    pub fn poll(&mut self) -> Vec<MaterialChange> {
+        let now = Instant::now();
+        if self.root.is_none()
+            || self
+                .last_poll
+                .is_some_and(|t| now.duration_since(t) < self.poll_interval)
+        {
+            return Vec::new();
+        }
+        self.last_poll = Some(now);
+
+        let mut changes = Vec::new();
+        for id in self.ids() {
+            let i = id.0 as usize - 1;
+            let current: FileStamps = self.entries[i]
+                .stamps
+                .iter()
+                .map(|(path, _)| (path.clone(), self.modified(path)))
+                .collect();
+            if current == self.entries[i].stamps {
+                continue;
+            }
+            let read = self.read(&self.entries[i].path);
+            let entry = &mut self.entries[i];
+            let source = match read {
+                Ok((source, stamps)) => {
+                    entry.stamps = stamps;
+                    Ok(source)
+                }
+                Err(message) => {
+                    entry.stamps = current;
+                    Err(message)
+                }
+            };
+            if source.as_ref().is_ok_and(|s| *s == entry.source) {
+                if entry.error.take().is_some() {
+                    log::info!("Material {} restored", entry.path);
+                }
+                continue;
+            }
+            changes.push(MaterialChange { id, source });
+        }
+        changes
+    }
+
+    /// Materials whose latest version on disk is broken.
+    pub fn errors(&self) -> Vec<MaterialError> {
+        self.entries
+            .iter()
+            .filter_map(|e| {
+                e.error.as_ref().map(|message| MaterialError {
+                    path: e.path.clone(),
+                    message: message.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material_graph::compile_graph;
+    use crate::reflect::ShaderReflection;
+    use crate::shader::{ShaderManager, validate_wgsl};
+    use asset::material::AlphaMode;
+
+    #[test]
+    fn sample_materials_expand_to_valid_shaders() {
+        let root = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../../assets");
+        let mut library = MaterialLibrary::new();
+        library.set_root(root);
+        let mut shaders = ShaderManager::with_embedded_includes();
+        let main = shaders.register(crate::MAIN_SHADER_NAME, crate::MAIN_SHADER_SOURCE);
+
+        let mut modes = Vec::new();
+        for path in [
+            "materials/gold.material.ron",
+            "materials/glass.material.json",
+            "materials/rim.material.ron",
+        ] {
+            let (source, stamps) = library.read(path).unwrap_or_else(|e| panic!("{e}"));
+            assert!(stamps.iter().all(|(_, modified)| modified.is_some()));
+            assert_eq!(stamps.len(), 1 + source.graph.is_some() as usize);
+            let expanded = shaders
+                .permutation(main, &crate::material_shader_defs(&source.desc))
+                .unwrap();
+            let mut wgsl = expanded.source.clone();
+            if let Some(graph) = &source.graph {
+                wgsl.push_str(&compile_graph(graph).unwrap());
+            }
+            validate_wgsl(&wgsl).unwrap_or_else(|e| panic!("{path}: {e}"));
+            // Materials bind the same slots as the default material
+            let reflection = ShaderReflection::from_wgsl(&wgsl).unwrap();
+            assert_eq!(reflection.group_count(), crate::MAIN_BIND_GROUPS);
+            modes.push(source.desc.alpha_mode);
+        }
+        assert_eq!(
+            modes,
+            [AlphaMode::Opaque, AlphaMode::Blend, AlphaMode::Opaque]
+        );
+
+        let mask = MaterialDesc {
+            alpha_mode: AlphaMode::Mask(0.3),
+            ..Default::default()
+        };
+        let expanded = shaders
+            .permutation(main, &crate::material_shader_defs(&mask))
+            .unwrap();
+        assert!(expanded.source.contains("alpha_cutoff) {"));
+        assert!(library.read("materials/missing.material.ron").is_err());
+    }
+}
@@ -0,0 +1,416 @@
+//! J2: material graphs compiled to WGSL.
+//!
+//! The generated `fn material_graph(in: SurfaceInput) -> Surface` is appended to the main
+//! shader expanded with `MATERIAL_GRAPH`, so it uses the main binding conventions:
+//! `u_material` for parameters and `t_diffuse`/`s_diffuse` for `TextureSample`.
+//! Values are `f32` or `vecN<f32>`; mismatched widths are converted (scalars splat,
+//! wider vectors are truncated, narrower ones padded with 0 and alpha 1).
+
+use std::fmt::{self, Write as _};
+
+use asset::material_graph::{GraphOutput, MaterialGraph, NodeId, NodeOp};
+
+/// Name of the generated function (called from `fs_main` under `MATERIAL_GRAPH`).
+pub const GRAPH_FUNCTION: &str = "material_graph";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GraphError {
+    /// The node (transitively) reads its own output.
+    Cycle {
+        node: NodeId,
+    },
+    /// An input points past the end of the node list.
+    MissingNode {
+        node: NodeId,
+        input: usize,
+    },
+    /// A graph output points past the end of the node list.
+    MissingOutput {
+        output: GraphOutput,
+    },
+    InvalidSwizzle {
+        node: NodeId,
+        mask: String,
+    },
+}
+
+impl fmt::Display for GraphError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cycle { node } => write!(f, "node {node} is part of a cycle"),
+            Self::MissingNode { node, input } => {
+                write!(f, "node {node} input {input} links to a missing node")
+            }
+            Self::MissingOutput { output } => {
+                write!(f, "output '{}' links to a missing node", output.name())
+            }
+            Self::InvalidSwizzle { node, mask } => {
+                write!(f, "node {node}: invalid swizzle \"{mask}\"")
+            }
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+/// Generate the WGSL surface function for `graph`. Only nodes reachable from an
+/// output are emitted; unconnected outputs read the material parameters.
+pub fn compile_graph(graph: &MaterialGraph) -> Result<String, GraphError> {
+    let mut compiler = Compiler {
+        graph,
+        state: vec![Visit::New; graph.nodes.len()],
+        body: String::new(),
+    };
+    let mut fields = Vec::new();
+    for output in GraphOutput::ALL {
+        let (width, default) = output_slot(output);
+        let expr = match graph.output(output) {
+            Some(node) if node >= graph.nodes.len() => {
+                return Err(GraphError::MissingOutput { output });
+            }
+            Some(node) => {
+                let from = compiler.node(node)?;
+                let var = format!("n{node}");
+                // Alpha of a color is its last component
+                if output == GraphOutput::Alpha && from == 4 {
+                    format!("{var}.w")
+                } else {
+                    convert(&var, from, width)
+                }
+            }
+            None => default.to_string(),
+        };
+        fields.push(expr);
+    }
+
+    let mut out = String::new();
+    let _ = writeln!(out, "// Generated from a material graph (J2).");
+    let _ = writeln!(out, "fn {GRAPH_FUNCTION}(in: SurfaceInput) -> Surface {{");
+    out.push_str(&compiler.body);
+    let _ = writeln!(out, "    return Surface(");
+    for field in fields {
+        let _ = writeln!(out, "        {field},");
+    }
+    let _ = writeln!(out, "    );");
+    let _ = writeln!(out, "}}");
+    Ok(out)
+}
+
+// Width of a `Surface` field and its value when the graph leaves it unconnected.
+fn output_slot(output: GraphOutput) -> (u8, &'static str) {
+    match output {
+        GraphOutput::BaseColor => (3, "u_material.base_color.rgb"),
+        GraphOutput::Alpha => (1, "u_material.base_color.a"),
+        GraphOutput::Metallic => (1, "u_material.metallic_roughness.x"),
+        GraphOutput::Roughness => (1, "u_material.metallic_roughness.y"),
+        GraphOutput::Emissive => (3, "u_material.emissive.rgb"),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Visit {
+    New,
+    InProgress,
+    // Emitted as `n<id>` with this width
+    Done(u8),
+}
+
+struct Compiler<'a> {
+    graph: &'a MaterialGraph,
+    state: Vec<Visit>,
+    body: String,
+}
+
+impl Compiler<'_> {
+    // Emit `let n<id>` (after its inputs) and return its width.
+    fn node(&mut self, id: NodeId) -> Result<u8, GraphError> {
+        match self.state[id] {
+            Visit::Done(width) => return Ok(width),
+            Visit::InProgress => return Err(GraphError::Cycle { node: id }),
+            Visit::New => {}
+        }
+        self.state[id] = Visit::InProgress;
+        let node = &self.graph.nodes[id];
+
+        // Inputs as (expression, width); unconnected ones fall back to `default`
+        let mut inputs = Vec::with_capacity(node.inputs.len());
+        for (port, link) in node.inputs.iter().enumerate() {
+            inputs.push(match *link {
+                Some(src) if src >= self.graph.nodes.len() => {
+                    return Err(GraphError::MissingNode {
+                        node: id,
+                        input: port,
+                    });
+                }
+                Some(src) => (format!("n{src}"), self.node(src)?),
+                None => input_default(&node.op, port),
+            });
+        }
+        let arg = |i: usize, width: u8| convert(&inputs[i].0, inputs[i].1, width);
+        let widest = |a: usize, b: usize| inputs[a].1.max(inputs[b].1);
+
+        let (expr, width) = match &node.op {
+            NodeOp::Float(v) => (float(*v), 1),
+            NodeOp::Color(c) => (format!("vec4<f32>({})", c.map(float).join(", ")), 4),
+            NodeOp::Uv => ("in.uv".to_string(), 2),
+            NodeOp::Normal => ("in.normal".to_string(), 3),
+            NodeOp::ViewDir => ("in.view_dir".to_string(), 3),
+            NodeOp::MaterialColor => ("u_material.base_color".to_string(), 4),
+            NodeOp::TextureSample => (
+                format!("textureSample(t_diffuse, s_diffuse, {})", arg(0, 2)),
+                4,
+            ),
+            NodeOp::UvTransform {
+                scale,
+                offset,
+                rotation,
+            } => {
+                let (s, c) = rotation.sin_cos();
+                (
+                    format!(
+                        "mat2x2<f32>({}, {}, {}, {}) * ((({}) - vec2<f32>(0.5)) * {}) + vec2<f32>(0.5) + {}",
+                        float(c),
+                        float(s),
+                        float(-s),
+                        float(c),
+                        arg(0, 2),
+                        vec2(*scale),
+                        vec2(*offset)
+                    ),
+                    2,
+                )
+            }
+            NodeOp::Fresnel { power } => (
+                format!(
+                    "pow(1.0 - saturate(dot(normalize({}), in.view_dir)), {})",
+                    arg(0, 3),
+                    float(*power)
+                ),
+                1,
+            ),
+            NodeOp::Add | NodeOp::Subtract | NodeOp::Multiply | NodeOp::Divide => {
+                let symbol = match node.op {
+                    NodeOp::Add => "+",
+                    NodeOp::Subtract => "-",
+                    NodeOp::Multiply => "*",
+                    _ => "/",
+                };
+                let width = widest(0, 1);
+                (
+                    format!("{} {symbol} {}", arg(0, width), arg(1, width)),
+                    width,
+                )
+            }
+            NodeOp::Power => {
+                let width = inputs[0].1;
+                (format!("pow({}, {})", arg(0, width), arg(1, width)), width)
+            }
+            NodeOp::Mix => {
+                let width = widest(0, 1);
+                // mix(vecN, vecN, f32) is valid WGSL, so a scalar factor stays scalar
+                let t = if inputs[2].1 == 1 {
+                    inputs[2].0.clone()
+                } else {
+                    arg(2, width)
+                };
+                (
+                    format!("mix({}, {}, {t})", arg(0, width), arg(1, width)),
+                    width,
+                )
+            }
+            NodeOp::OneMinus => {
+                let width = inputs[0].1;
+                (
+                    format!("{} - {}", convert("1.0", 1, width), inputs[0].0),
+                    width,
+                )
+            }
+            NodeOp::Saturate => (format!("saturate({})", inputs[0].0), inputs[0].1),
+            NodeOp::Swizzle(mask) => {
+                let invalid = || GraphError::InvalidSwizzle {
+                    node: id,
+                    mask: mask.clone(),
+                };
+                let components = swizzle_components(mask).ok_or_else(invalid)?;
+                let (src, from) = &inputs[0];
+                let width = components.len() as u8;
+                if *from == 1 {
+                    // A scalar reads the same in every component
+                    (convert(src, 1, width), width)
+                } else if components.iter().any(|&c| c >= *from) {
+                    return Err(invalid());
+                } else {
+                    (format!("{src}.{mask}"), width)
+                }
+            }
+        };
+        let _ = writeln!(self.body, "    let n{id}: {} = {expr};", wgsl_type(width));
+        self.state[id] = Visit::Done(width);
+        Ok(width)
+    }
+}
+
+// Value of an unconnected input: neutral for the operation where possible.
+fn input_default(op: &NodeOp, port: usize) -> (String, u8) {
+    let value = match (op, port) {
+        (NodeOp::TextureSample | NodeOp::UvTransform { .. }, _) => return ("in.uv".to_string(), 2),
+        (NodeOp::Fresnel { .. }, _) => return ("in.normal".to_string(), 3),
+        (NodeOp::Multiply | NodeOp::Divide | NodeOp::Power, _) => "1.0",
+        (NodeOp::Mix, 1) => "1.0",
+        (NodeOp::Mix, 2) => "0.5",
+        _ => "0.0",
+    };
+    (value.to_string(), 1)
+}
+
+fn swizzle_components(mask: &str) -> Option<Vec<u8>> {
+    if mask.is_empty() || mask.len() > 4 {
+        return None;
+    }
+    ["xyzw", "rgba"].iter().find_map(|set| {
+        mask.chars()
+            .map(|c| set.find(c).map(|i| i as u8))
+            .collect::<Option<Vec<u8>>>()
+    })
+}
+
+fn wgsl_type(width: u8) -> String {
+    match width {
+        1 => "f32".to_string(),
+        n => format!("vec{n}<f32>"),
+    }
+}
+
+// `expr` of width `from` as width `to`.
+fn convert(expr: &str, from: u8, to: u8) -> String {
+    match (from, to) {
+        _ if from == to => expr.to_string(),
+        (1, _) => format!("{}({expr})", wgsl_type(to)),
+        (_, 1) => format!("({expr}).x"),
+        (_, _) if to < from => format!("({expr}).{}", &"xyzw"[..to as usize]),
+        (2, 3) => format!("vec3<f32>({expr}, 0.0)"),
+        (2, 4) => format!("vec4<f32>({expr}, 0.0, 1.0)"),
+        _ => format!("vec4<f32>({expr}, 1.0)"),
+    }
+}
+
+// WGSL float literal (always with a decimal point or exponent).
+fn float(v: f32) -> String {
+    format!("{v:?}")
+}
+
+fn vec2(v: [f32; 2]) -> String {
+    format!("vec2<f32>({}, {})", float(v[0]), float(v[1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preprocess::ShaderDefs;
+    use crate::shader::{ShaderManager, validate_wgsl};
+    use asset::material_graph::{NodeOp, load_graph};
+    use std::path::Path;
+
+    // Main shader expanded for graph materials + the generated function
+    fn validate(graph: &MaterialGraph) -> Result<naga::Module, String> {
+        let code = compile_graph(graph).map_err(|e| e.to_string())?;
+        let mut shaders = ShaderManager::with_embedded_includes();
+        let main = shaders.register(crate::MAIN_SHADER_NAME, crate::MAIN_SHADER_SOURCE);
+        let mut defs: ShaderDefs = crate::main_shader_defs();
+        defs.insert("MATERIAL_GRAPH".to_string(), String::new());
+        let expanded = shaders.permutation(main, &defs).unwrap();
+        validate_wgsl(&format!("{}\n{code}", expanded.source)).map_err(|e| format!("{e}\n{code}"))
+    }
+
+    #[test]
+    fn empty_graph_uses_material_parameters() {
+        let code = compile_graph(&MaterialGraph::default()).unwrap();
+        assert!(code.contains("u_material.base_color.rgb,"));
+        validate(&MaterialGraph::default()).unwrap_or_else(|e| panic!("{e}"));
+    }
+
+    #[test]
+    fn every_node_kind_compiles() {
+        // Each kind feeds every output, with inputs of all widths
+        for op in NodeOp::palette() {
+            for src in [
+                NodeOp::Float(0.5),
+                NodeOp::Uv,
+                NodeOp::Normal,
+                NodeOp::MaterialColor,
+            ] {
+                if matches!(op, NodeOp::Swizzle(_)) && src == NodeOp::Uv {
+                    // "rgb" of a vec2 is rejected (see `invalid_graphs_are_errors`)
+                    continue;
+                }
+                let mut graph = MaterialGraph::default();
+                let input = graph.add_node(src.clone(), [0.0, 0.0]);
+                let node = graph.add_node(op.clone(), [0.0, 0.0]);
+                graph.nodes[node]
+                    .inputs
+                    .iter_mut()
+                    .for_each(|i| *i = Some(input));
+                for output in GraphOutput::ALL {
+                    graph.set_output(output, Some(node));
+                }
+                validate(&graph).unwrap_or_else(|e| panic!("{op:?} <- {src:?}: {e}"));
+            }
+        }
+    }
+
+    #[test]
+    fn sample_graph_compiles() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../assets/materials/graphs/rim.matgraph.ron");
+        let graph = load_graph(path).unwrap();
+        validate(&graph).unwrap_or_else(|e| panic!("{e}"));
+    }
+
+    #[test]
+    fn shared_nodes_are_emitted_once() {
+        let mut graph = MaterialGraph::default();
+        let f = graph.add_node(NodeOp::Fresnel { power: 2.0 }, [0.0, 0.0]);
+        let unused = graph.add_node(NodeOp::ViewDir, [0.0, 0.0]);
+        graph.set_output(GraphOutput::Emissive, Some(f));
+        graph.set_output(GraphOutput::Roughness, Some(f));
+        let code = compile_graph(&graph).unwrap();
+        assert_eq!(code.matches("let n0").count(), 1);
+        assert!(!code.contains(&format!("let n{unused}")));
+        assert!(code.contains("vec3<f32>(n0)"));
+    }
+
+    #[test]
+    fn invalid_graphs_are_errors() {
+        let mut graph = MaterialGraph::default();
+        let a = graph.add_node(NodeOp::Add, [0.0, 0.0]);
+        let b = graph.add_node(NodeOp::Saturate, [0.0, 0.0]);
+        graph.nodes[a].inputs[0] = Some(b);
+        graph.nodes[b].inputs[0] = Some(a);
+        graph.set_output(GraphOutput::Alpha, Some(a));
+        assert!(matches!(
+            compile_graph(&graph),
+            Err(GraphError::Cycle { .. })
+        ));
+
+        let mut graph = MaterialGraph::default();
+        let uv = graph.add_node(NodeOp::Uv, [0.0, 0.0]);
+        let swizzle = graph.add_node(NodeOp::Swizzle("xyz".to_string()), [0.0, 0.0]);
+        graph.nodes[swizzle].inputs[0] = Some(uv);
+        graph.set_output(GraphOutput::BaseColor, Some(swizzle));
+        assert!(matches!(
+            compile_graph(&graph),
+            Err(GraphError::InvalidSwizzle { .. })
+        ));
+        graph.nodes[swizzle].op = NodeOp::Swizzle("yx".to_string());
+        validate(&graph).unwrap_or_else(|e| panic!("{e}"));
+
+        graph.nodes[swizzle].inputs[0] = Some(7);
+        assert_eq!(
+            compile_graph(&graph),
+            Err(GraphError::MissingNode {
+                node: swizzle,
+                input: 0
+            })
+        );
+    }
+}
@@ -0,0 +1,235 @@
+//! M1: shared vertex/index arenas for mesh data.
+//! Meshes are sub-allocated from a few large buffers so consecutive draws of different meshes
+//! keep their bindings; each mesh draws with its own `first_index` / `base_vertex`.
+//! Adapters without `DownlevelFlags::BASE_VERTEX` use a vertex block size of 0 instead.
+//! Indices are stored as `u16` whenever the mesh's vertex count fits.
+
+use wgpu::{Buffer, BufferDescriptor, BufferUsages, Device, IndexFormat, Queue};
+
+/// Default size of one vertex arena block (bytes).
+pub const VERTEX_BLOCK_BYTES: u64 = 16 << 20;
+/// Default size of one index arena block (bytes).
+pub const INDEX_BLOCK_BYTES: u64 = 8 << 20;
+
+/// Smallest index format able to address `vertex_count` vertices (relative to the base vertex).
+/// Triangle lists have no primitive restart, so `0xFFFF` is a valid `u16` index.
+pub fn index_format_for(vertex_count: usize) -> IndexFormat {
+    if vertex_count <= u16::MAX as usize + 1 {
+        IndexFormat::Uint16
+    } else {
+        IndexFormat::Uint32
+    }
+}
+
+/// `indices` encoded as `format`, padded to a multiple of 4 bytes for `write_buffer`.
+pub fn index_bytes(indices: &[u32], format: IndexFormat) -> Vec<u8> {
+    let mut bytes: Vec<u8> = match format {
+        IndexFormat::Uint16 => indices
+            .iter()
+            .flat_map(|&i| u16::try_from(i).expect("index exceeds u16").to_le_bytes())
+            .collect(),
+        IndexFormat::Uint32 => bytemuck::cast_slice(indices).to_vec(),
+    };
+    bytes.resize(bytes.len().next_multiple_of(4), 0);
+    bytes
+}
+
+/// Where an allocation landed inside a [`BlockAllocator`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Allocation {
+    pub block: usize,
+    pub offset: u64,
+}
+
+/// Bump allocation over fixed-size blocks (bookkeeping only; buffers live in [`BufferArena`]).
+/// Requests larger than a block get a block of their own; a block size of 0 gives every
+/// allocation its own exactly sized block.
+#[derive(Clone, Debug)]
+pub struct BlockAllocator {
+    block_size: u64,
+    /// (capacity, used) per block
+    blocks: Vec<(u64, u64)>,
+}
+
+impl BlockAllocator {
+    pub fn new(block_size: u64) -> Self {
+        Self {
+            block_size,
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Place `size` bytes at a multiple of `align` (any non-zero value, e.g. a vertex stride).
+    /// Returns the allocation and whether a new block was opened.
+    pub fn alloc(&mut self, size: u64, align: u64) -> (Allocation, bool) {
+        let align = align.max(1);
+        for (block, (capacity, used)) in self.blocks.iter_mut().enumerate() {
+            let offset = used.next_multiple_of(align);
+            if offset + size <= *capacity {
+                *used = offset + size;
+                return (Allocation { block, offset }, false);
+            }
+        }
+        self.blocks.push((self.block_size.max(size), size));
+        (
+            Allocation {
+                block: self.blocks.len() - 1,
+                offset: 0,
+            },
+            true,
+        )
+    }
+
+    pub fn block_count(&self) -> usize {
+        self.blocks.len()
+    }
+
+    pub fn capacity(&self, block: usize) -> u64 {
+        self.blocks[block].0
+    }
+
+    /// Bytes in use across all blocks (including alignment gaps).
+    pub fn used_bytes(&self) -> u64 {
+        self.blocks.iter().map(|&(_, used)| used).sum()
+    }
+}
+
+/// GPU buffers backing a [`BlockAllocator`]; data is uploaded with `queue.write_buffer`.
+pub struct BufferArena {
+    label: &'static str,
+    usage: BufferUsages,
+    allocator: BlockAllocator,
+    buffers: Vec<Buffer>,
+}
+
+impl BufferArena {
+    /// `usage` is added to `COPY_DST`.
+    pub fn new(label: &'static str, usage: BufferUsages, block_size: u64) -> Self {
+        Self {
+            label,
+            usage: usage | BufferUsages::COPY_DST,
+            allocator: BlockAllocator::new(block_size),
+            buffers: Vec::new(),
+        }
+    }
+
+    /// Copy `bytes` (length a multiple of 4) into the arena at a multiple of `align`.
+    pub fn upload(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        bytes: &[u8],
+        align: u64,
+    ) -> Allocation {
+        assert!(
+            bytes.len().is_multiple_of(4),
+            "arena uploads must be 4-byte sized"
+        );
+        // write_buffer offsets must stay 4-byte aligned as well
+        let align = lcm(align.max(1), wgpu::COPY_BUFFER_ALIGNMENT);
+        let (allocation, new_block) = self.allocator.alloc(bytes.len() as u64, align);
+        if new_block {
+            self.buffers.push(device.create_buffer(&BufferDescriptor {
+                label: Some(&format!("{} #{}", self.label, allocation.block)),
+                size: self.allocator.capacity(allocation.block).max(4),
+                usage: self.usage,
+                mapped_at_creation: false,
+            }));
+        }
+        queue.write_buffer(&self.buffers[allocation.block], allocation.offset, bytes);
+        allocation
+    }
+
+    pub fn buffer(&self, block: usize) -> &Buffer {
+        &self.buffers[block]
+    }
+
+    pub fn allocator(&self) -> &BlockAllocator {
+        &self.allocator
+    }
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    a / x * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_u16_while_vertices_fit() {
+        assert_eq!(index_format_for(24), IndexFormat::Uint16);
+        assert_eq!(index_format_for(65_536), IndexFormat::Uint16);
+        assert_eq!(index_format_for(65_537), IndexFormat::Uint32);
+
+        // Three u16 indices are padded to 8 bytes; u32 data needs no padding
+        let bytes = index_bytes(&[1, 2, 0xFFFF], IndexFormat::Uint16);
+        assert_eq!(bytes, [1, 0, 2, 0, 0xFF, 0xFF, 0, 0]);
+        assert_eq!(index_bytes(&[1, 2, 3], IndexFormat::Uint32).len(), 12);
+    }
+
+    #[test]
+    fn allocations_pack_into_blocks() {
+        let mut alloc = BlockAllocator::new(100);
+        assert_eq!(
+            alloc.alloc(64, 32),
+            (
+                Allocation {
+                    block: 0,
+                    offset: 0
+                },
+                true
+            )
+        );
+        // 32-byte stride: the next mesh starts at vertex 2 of the same block
+        assert_eq!(
+            alloc.alloc(32, 32),
+            (
+                Allocation {
+                    block: 0,
+                    offset: 64
+                },
+                false
+            )
+        );
+        // Doesn't fit behind the others -> new block; small data still fills the gap
+        assert_eq!(
+            alloc.alloc(40, 4),
+            (
+                Allocation {
+                    block: 1,
+                    offset: 0
+                },
+                true
+            )
+        );
+        assert_eq!(
+            alloc.alloc(4, 4),
+            (
+                Allocation {
+                    block: 0,
+                    offset: 96
+                },
+                false
+            )
+        );
+        // Oversized requests get their own block
+        let (big, new) = alloc.alloc(500, 4);
+        assert!(new);
+        assert_eq!(alloc.capacity(big.block), 500);
+        assert_eq!(alloc.used_bytes(), 64 + 32 + 4 + 40 + 500);
+
+        // Block size 0: one block per allocation
+        let mut dedicated = BlockAllocator::new(0);
+        dedicated.alloc(12, 4);
+        dedicated.alloc(4, 4);
+        assert_eq!(dedicated.block_count(), 2);
+        assert_eq!(lcm(32, 4), 32);
+        assert_eq!(lcm(12, 8), 24);
+    }
+}
@@ -0,0 +1,16 @@
+//! I1: UI drawn over the finished 3D frame before it is presented (e.g. egui).
+
+use wgpu::{CommandEncoder, Device, Queue, TextureView};
+
+/// Records its own pass(es) into the frame's encoder. `target` already holds the scene, so
+/// overlays load it instead of clearing; `size` is the surface size in pixels.
+pub trait Overlay {
+    fn draw(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        target: &TextureView,
+        size: [u32; 2],
+    );
+}
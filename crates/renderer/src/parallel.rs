@@ -0,0 +1,280 @@
+//! G1: multi-threaded draw preparation.
+//! Opt-in via [`crate::GpuState::set_draw_prep_threads`]: the draw list is split into
+//! contiguous chunks processed on scoped threads (matrices + culling), then sorted with a
+//! radix sort on the packed [`DrawKey`]. Lists below [`MIN_ITEMS_PER_THREAD`] per thread
+//! stay on the calling thread, so small scenes pay nothing.
+
+use std::thread;
+
+use crate::DrawKey;
+
+/// Smallest chunk worth a thread (spawn cost vs. per-instance work).
+pub const MIN_ITEMS_PER_THREAD: usize = 1024;
+
+/// Threads to use for `len` items given the requested count (always >= 1).
+pub fn effective_threads(requested: usize, len: usize) -> usize {
+    requested.clamp(1, (len / MIN_ITEMS_PER_THREAD).max(1))
+}
+
+/// Run `f(first_index, chunk, output)` over `outputs.len()` contiguous chunks of `items`.
+/// The first chunk runs on the calling thread. Outputs without a chunk are left untouched.
+pub fn run_chunks<T, O, F>(items: &[T], outputs: &mut [O], f: F)
+where
+    T: Sync,
+    O: Send,
+    F: Fn(usize, &[T], &mut O) + Sync,
+{
+    if items.is_empty() || outputs.is_empty() {
+        return;
+    }
+    let chunk = items.len().div_ceil(outputs.len());
+    let f = &f;
+    thread::scope(|s| {
+        let mut jobs = items.chunks(chunk).zip(outputs.iter_mut()).enumerate();
+        let first = jobs.next();
+        for (i, (items, out)) in jobs {
+            s.spawn(move || f(i * chunk, items, out));
+        }
+        if let Some((_, (items, out))) = first {
+            f(0, items, out);
+        }
+    });
+}
+
+/// Run `f(input_chunk, output_chunk)` over matching chunks of two equally long slices.
+pub fn zip_chunks_mut<T, U, F>(items: &[T], out: &mut [U], threads: usize, f: F)
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&[T], &mut [U]) + Sync,
+{
+    assert_eq!(items.len(), out.len(), "zip_chunks_mut needs equal lengths");
+    if items.is_empty() {
+        return;
+    }
+    let chunk = items.len().div_ceil(threads.max(1));
+    let f = &f;
+    thread::scope(|s| {
+        let mut jobs = items.chunks(chunk).zip(out.chunks_mut(chunk));
+        let first = jobs.next();
+        for (items, out) in jobs {
+            s.spawn(move || f(items, out));
+        }
+        if let Some((items, out)) = first {
+            f(items, out);
+        }
+    });
+}
+
+/// Sort record: packed key + index of the entry it came from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct SortItem {
+    pub key: u128,
+    pub index: u32,
+}
+
+/// Pack a [`DrawKey`] so that integer order equals the derived `Ord` (pso, material, texture, mesh).
+pub(crate) fn pack_key(key: &DrawKey) -> u128 {
+    (key.pso_id as u128) << 96
+        | (key.material.0 as u128) << 64
+        | (key.texture.0 as u128) << 32
+        | key.mesh.0 as u128
+}
+
+/// Stable LSD radix sort by `key`, one byte per pass; bytes equal across all keys are skipped,
+/// so typical draw keys (few materials/meshes) need only a handful of passes.
+/// With `threads > 1` the items are first partitioned by their most significant varying byte,
+/// then the buckets are sorted on separate threads. `scratch` is resized as needed.
+pub(crate) fn radix_sort(items: &mut [SortItem], scratch: &mut Vec<SortItem>, threads: usize) {
+    if items.len() < 2 {
+        return;
+    }
+    let first = items[0].key;
+    let varying = items.iter().fold(0u128, |acc, it| acc | (it.key ^ first));
+    let digits: Vec<u32> = (0..16)
+        .filter(|&b| (varying >> (b * 8)) & 0xFF != 0)
+        .collect();
+    let Some((&top, rest)) = digits.split_last() else {
+        return; // all keys equal
+    };
+    scratch.resize(items.len(), SortItem::default());
+    let scratch = &mut scratch[..items.len()];
+
+    let threads = effective_threads(threads, items.len());
+    if threads == 1 {
+        lsd_passes(items, scratch, &digits);
+        return;
+    }
+
+    // MSD partition (stable), then independent LSD sorts of the buckets.
+    let counts = histogram(items, top);
+    let mut offsets = [0usize; 257];
+    for d in 0..256 {
+        offsets[d + 1] = offsets[d] + counts[d];
+    }
+    let mut cursor = offsets;
+    for it in items.iter() {
+        let d = digit(it.key, top);
+        scratch[cursor[d]] = *it;
+        cursor[d] += 1;
+    }
+    items.copy_from_slice(scratch);
+    if rest.is_empty() {
+        return;
+    }
+
+    // Group whole buckets into roughly equal ranges, one per thread.
+    let target = items.len().div_ceil(threads);
+    let mut groups: Vec<(usize, usize)> = Vec::with_capacity(threads);
+    let mut group_start = 0;
+    for (d, &end) in offsets.iter().enumerate().skip(1) {
+        if end - group_start >= target || d == 256 {
+            if end > group_start {
+                groups.push((group_start, end));
+            }
+            group_start = end;
+        }
+    }
+
+    thread::scope(|s| {
+        let (mut items_rest, mut scratch_rest) = (&mut *items, &mut *scratch);
+        let mut consumed = 0;
+        for &(start, end) in &groups {
+            let (group_items, tail_items) = items_rest.split_at_mut(end - consumed);
+            let (group_scratch, tail_scratch) = scratch_rest.split_at_mut(end - consumed);
+            items_rest = tail_items;
+            scratch_rest = tail_scratch;
+            consumed = end;
+            let offsets = &offsets;
+            s.spawn(move || {
+                // Buckets inside the group, relative to its start
+                for d in 0..256 {
+                    let (lo, hi) = (offsets[d].max(start), offsets[d + 1].min(end));
+                    if hi > lo + 1 {
+                        lsd_passes(
+                            &mut group_items[lo - start..hi - start],
+                            &mut group_scratch[lo - start..hi - start],
+                            rest,
+                        );
+                    }
+                }
+            });
+        }
+    });
+}
+
+#[inline]
+fn digit(key: u128, byte: u32) -> usize {
+    ((key >> (byte * 8)) & 0xFF) as usize
+}
+
+fn histogram(items: &[SortItem], byte: u32) -> [usize; 256] {
+    let mut counts = [0usize; 256];
+    for it in items {
+        counts[digit(it.key, byte)] += 1;
+    }
+    counts
+}
+
+/// Counting-sort passes over `digits` (least significant first); result ends in `items`.
+fn lsd_passes(items: &mut [SortItem], scratch: &mut [SortItem], digits: &[u32]) {
+    let (mut src, mut dst) = (items, scratch);
+    let mut swapped = false;
+    for &byte in digits {
+        let counts = histogram(src, byte);
+        let mut offsets = [0usize; 256];
+        let mut sum = 0;
+        for d in 0..256 {
+            offsets[d] = sum;
+            sum += counts[d];
+        }
+        for it in src.iter() {
+            let d = digit(it.key, byte);
+            dst[offsets[d]] = *it;
+            offsets[d] += 1;
+        }
+        std::mem::swap(&mut src, &mut dst);
+        swapped = !swapped;
+    }
+    if swapped {
+        // After an odd number of passes the sorted data sits in the scratch slice.
+        dst.copy_from_slice(src);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use corelib::ecs::{MaterialId, MeshId, TextureId};
+
+    fn items(n: u32) -> Vec<SortItem> {
+        // Deterministic scramble with repeated keys (stability check via index order).
+        (0..n)
+            .map(|i| {
+                let key = DrawKey {
+                    pso_id: 0,
+                    material: MaterialId::new(i.wrapping_mul(2_654_435_761) % 7),
+                    texture: TextureId::new(i % 3),
+                    mesh: MeshId::new((i.wrapping_mul(40_503) >> 3) % 300),
+                };
+                SortItem {
+                    key: pack_key(&key),
+                    index: i,
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn packed_key_order_matches_draw_key_order() {
+        let a = DrawKey {
+            pso_id: 0,
+            material: MaterialId::new(1),
+            texture: TextureId::new(0),
+            mesh: MeshId::new(u32::MAX),
+        };
+        let b = DrawKey {
+            material: MaterialId::new(2),
+            mesh: MeshId::new(0),
+            ..a
+        };
+        assert!(a < b);
+        assert!(pack_key(&a) < pack_key(&b));
+    }
+
+    #[test]
+    fn radix_sort_matches_stable_sort_single_and_multi_threaded() {
+        let n = (MIN_ITEMS_PER_THREAD * 4 + 17) as u32;
+        let mut expected = items(n);
+        expected.sort_by_key(|it| it.key);
+
+        let mut scratch = Vec::new();
+        for threads in [1, 4] {
+            let mut sorted = items(n);
+            radix_sort(&mut sorted, &mut scratch, threads);
+            assert_eq!(sorted, expected, "threads = {threads}");
+        }
+    }
+
+    #[test]
+    fn chunks_cover_every_item_once() {
+        let data: Vec<u32> = (0..5000).collect();
+        let mut sums = vec![0u64; 3];
+        run_chunks(&data, &mut sums, |first, chunk, sum| {
+            assert_eq!(chunk[0] as usize, first);
+            *sum = chunk.iter().map(|&v| v as u64).sum();
+        });
+        assert_eq!(
+            sums.iter().sum::<u64>(),
+            data.iter().map(|&v| v as u64).sum()
+        );
+
+        let mut doubled = vec![0u32; data.len()];
+        zip_chunks_mut(&data, &mut doubled, 3, |src, dst| {
+            for (d, s) in dst.iter_mut().zip(src) {
+                *d = s * 2;
+            }
+        });
+        assert!(doubled.iter().enumerate().all(|(i, &v)| v == i as u32 * 2));
+    }
+}
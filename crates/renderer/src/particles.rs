@@ -0,0 +1,704 @@
+//! K3: GPU particle system.
+//! Every emitter owns a ring buffer of particles simulated by compute shaders registered
+//! in the G2 [`ComputeRegistry`]: `cs_update` ages and integrates (gravity, drag, curl
+//! noise), `cs_spawn` fills the next slots of the ring. Additive emitters draw straight from
+//! the simulation buffer; alpha emitters are bitonic-sorted back-to-front and gathered into
+//! a second buffer first. Billboards are instanced quads drawn after opaque geometry.
+
+use std::collections::HashMap;
+
+use bytemuck::{Pod, Zeroable};
+use corelib::{
+    Mat4,
+    camera::Camera,
+    particles::{EmitterShape, ParticleBlend, ParticleEmitter},
+};
+use wgpu::{
+    BindGroup, BindGroupLayout, Buffer, BufferUsages, CommandEncoder, Device, Queue, RenderPass,
+    RenderPipeline, ShaderStages, TextureFormat,
+};
+
+use crate::compute::{
+    ComputeBinding, ComputeBindingKind, ComputeDispatch, ComputeError, ComputeRegistry,
+    ComputeResource, ComputeShaderDesc, ComputeShaderId, workgroup_count,
+};
+use crate::preprocess::{IncludeSource, ShaderDefs, preprocess};
+use crate::profiler::GpuProfiler;
+
+/// Ring buffer size limit per emitter (bounds the sort to 105 bitonic steps).
+pub const MAX_PARTICLES_PER_EMITTER: u32 = 16384;
+const WORKGROUP: u32 = 64;
+/// Sort step uniforms are bound at offsets of this stride (min uniform offset alignment).
+const SORT_STEP_STRIDE: u64 = 256;
+
+/// Must match `Emitter` in particles.wgsl / particle_sort.wgsl / particle_draw.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct EmitterUniform {
+    model: [[f32; 4]; 4],
+    color_start: [f32; 4],
+    color_end: [f32; 4],
+    gravity: [f32; 3],
+    drag: f32,
+    camera_pos: [f32; 3],
+    dt: f32,
+    shape_params: [f32; 4],
+    lifetime: [f32; 2],
+    size: [f32; 2],
+    curl: [f32; 2],
+    time: f32,
+    seed: u32,
+    shape: u32,
+    spawn_start: u32,
+    spawn_count: u32,
+    capacity: u32,
+}
+
+/// Must match `Camera` in particle_draw.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct ParticleCameraUniform {
+    view_proj: [[f32; 4]; 4],
+    right: [f32; 4],
+    up: [f32; 4],
+}
+
+/// Must match `SortStep` in particle_sort.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct SortStepUniform {
+    j: u32,
+    k: u32,
+    count: u32,
+    _pad: u32,
+}
+
+/// One particle; dead when `pos_age[3] >= vel_life[3]` (zeroed = dead).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct GpuParticle {
+    pos_age: [f32; 4],
+    vel_life: [f32; 4],
+}
+
+/// Ring buffer slots for an emitter: room for every particle alive at once.
+pub fn emitter_capacity(emitter: &ParticleEmitter) -> u32 {
+    (emitter.max_alive() + 1)
+        .next_multiple_of(WORKGROUP)
+        .clamp(WORKGROUP, MAX_PARTICLES_PER_EMITTER)
+}
+
+/// Bitonic network for `count` (power of two) elements as `(j, k)` pairs.
+pub fn bitonic_steps(count: u32) -> Vec<(u32, u32)> {
+    let mut steps = Vec::new();
+    let mut k = 2;
+    while k <= count {
+        let mut j = k / 2;
+        while j > 0 {
+            steps.push((j, k));
+            j /= 2;
+        }
+        k *= 2;
+    }
+    steps
+}
+
+/// Whole particles to spawn this frame; the fractional rest carries over in `accum`.
+fn spawn_count(accum: &mut f32, rate: f32, dt: f32, capacity: u32) -> u32 {
+    *accum += rate.max(0.0) * dt;
+    let n = accum.floor();
+    *accum -= n;
+    (n as u32).min(capacity)
+}
+
+struct EmitterGpu {
+    capacity: u32,
+    blend: ParticleBlend,
+    uniform: EmitterUniform,
+    uniform_buf: Buffer,
+    /// Simulation ring buffer (also the vertex buffer for additive emitters).
+    particles: Buffer,
+    /// Sorted copy for alpha emitters.
+    sorted: Option<Buffer>,
+    update: ComputeDispatch,
+    spawn: ComputeDispatch,
+    /// keys, bitonic steps, gather (empty for additive emitters).
+    sort: Vec<ComputeDispatch>,
+    render_bg: BindGroup,
+    spawn_accum: f32,
+    spawn_cursor: u32,
+    seen: bool,
+}
+
+/// Live particle emitters and the pipelines to simulate and draw them.
+pub struct ParticleSystem {
+    update_shader: ComputeShaderId,
+    spawn_shader: ComputeShaderId,
+    keys_shader: ComputeShaderId,
+    sort_shader: ComputeShaderId,
+    gather_shader: ComputeShaderId,
+    camera_buf: Buffer,
+    camera_bg: BindGroup,
+    emitter_bgl: BindGroupLayout,
+    additive_pipeline: RenderPipeline,
+    alpha_pipeline: RenderPipeline,
+    emitters: HashMap<u32, EmitterGpu>,
+    time: f32,
+    frame: u32,
+}
+
+impl ParticleSystem {
+    /// Registers the simulation/sort kernels in `compute` and builds the billboard pipelines.
+    /// Shared declarations come from `include/particle.wgsl` in `includes`.
+    pub fn new(
+        device: &Device,
+        compute: &mut ComputeRegistry,
+        includes: &dyn IncludeSource,
+        color_format: TextureFormat,
+        depth_format: TextureFormat,
+    ) -> Result<Self, ComputeError> {
+        let sim_bindings = [
+            ComputeBinding::new(0, ComputeBindingKind::Uniform),
+            ComputeBinding::new(1, ComputeBindingKind::STORAGE_RW),
+        ];
+        let sort_bindings = [
+            ComputeBinding::new(0, ComputeBindingKind::Uniform),
+            ComputeBinding::new(1, ComputeBindingKind::STORAGE_READ),
+            ComputeBinding::new(2, ComputeBindingKind::STORAGE_RW),
+            ComputeBinding::new(3, ComputeBindingKind::STORAGE_RW),
+            ComputeBinding::new(4, ComputeBindingKind::Uniform),
+        ];
+        let mut register =
+            |label: &str, source: &str, entry_point: &str, bindings: &[ComputeBinding]| {
+                compute.register(
+                    device,
+                    &ComputeShaderDesc {
+                        label,
+                        source,
+                        entry_point,
+                        workgroup_size: [WORKGROUP, 1, 1],
+                        bindings,
+                    },
+                )
+            };
+        let [sim_src, sort_src, draw_src] = particle_sources(includes)?;
+        let update_shader = register("ParticlesUpdate", &sim_src, "cs_update", &sim_bindings)?;
+        let spawn_shader = register("ParticlesSpawn", &sim_src, "cs_spawn", &sim_bindings)?;
+        let keys_shader = register("ParticlesSortKeys", &sort_src, "cs_keys", &sort_bindings)?;
+        let sort_shader = register("ParticlesSort", &sort_src, "cs_sort", &sort_bindings)?;
+        let gather_shader = register("ParticlesGather", &sort_src, "cs_gather", &sort_bindings)?;
+
+        let uniform_entry = |visibility| wgpu::BindGroupLayoutEntry {
+            binding: 0,
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let camera_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Particle Camera BGL"),
+            entries: &[uniform_entry(ShaderStages::VERTEX)],
+        });
+        let emitter_bgl = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Particle Emitter BGL"),
+            entries: &[uniform_entry(ShaderStages::VERTEX | ShaderStages::FRAGMENT)],
+        });
+        let camera_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Camera UBO"),
+            size: std::mem::size_of::<ParticleCameraUniform>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let camera_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Particle Camera BG"),
+            layout: &camera_bgl,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: camera_buf.as_entire_binding(),
+            }],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Particle Draw WGSL"),
+            source: wgpu::ShaderSource::Wgsl(draw_src.into()),
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Particle PipelineLayout"),
+            bind_group_layouts: &[&camera_bgl, &emitter_bgl],
+            push_constant_ranges: &[],
+        });
+        let attributes = wgpu::vertex_attr_array![0 => Float32x4, 1 => Float32x4];
+        let pipeline = |label: &str, blend: wgpu::BlendState| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: Some("vs_main"),
+                    buffers: &[wgpu::VertexBufferLayout {
+                        array_stride: std::mem::size_of::<GpuParticle>() as u64,
+                        step_mode: wgpu::VertexStepMode::Instance,
+                        attributes: &attributes,
+                    }],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: Some("fs_main"),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: color_format,
+                        blend: Some(blend),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                // Test against scene depth, never write it
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: depth_format,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Less,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                }),
+                multisample: Default::default(),
+                multiview: None,
+                cache: None,
+            })
+        };
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Zero,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+        };
+
+        Ok(Self {
+            update_shader,
+            spawn_shader,
+            keys_shader,
+            sort_shader,
+            gather_shader,
+            camera_buf,
+            camera_bg,
+            emitter_bgl,
+            additive_pipeline: pipeline("Particle Additive Pipeline", additive),
+            alpha_pipeline: pipeline("Particle Alpha Pipeline", wgpu::BlendState::ALPHA_BLENDING),
+            emitters: HashMap::new(),
+            time: 0.0,
+            frame: 0,
+        })
+    }
+
+    /// Sync emitters for this frame (`key` = owning entity) and advance spawn counters.
+    /// Emitters missing from `emitters` are dropped together with their particles.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update<'a>(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        compute: &ComputeRegistry,
+        camera: &Camera,
+        view_proj: Mat4,
+        dt: f32,
+        emitters: impl IntoIterator<Item = (u32, Mat4, &'a ParticleEmitter)>,
+    ) {
+        self.time += dt;
+        self.frame = self.frame.wrapping_add(1);
+
+        let view = camera.view();
+        let cam = ParticleCameraUniform {
+            view_proj: view_proj.to_cols_array_2d(),
+            right: view.row(0).truncate().extend(0.0).to_array(),
+            up: view.row(1).truncate().extend(0.0).to_array(),
+        };
+        queue.write_buffer(&self.camera_buf, 0, bytemuck::bytes_of(&cam));
+
+        for gpu in self.emitters.values_mut() {
+            gpu.seen = false;
+        }
+        for (key, model, emitter) in emitters {
+            let capacity = emitter_capacity(emitter);
+            let stale = self
+                .emitters
+                .get(&key)
+                .is_none_or(|g| g.capacity != capacity || g.blend != emitter.blend);
+            if stale {
+                if emitter.max_alive() >= MAX_PARTICLES_PER_EMITTER {
+                    log::warn!(
+                        "K3: emitter {key} needs {} particles, capped at {MAX_PARTICLES_PER_EMITTER}",
+                        emitter.max_alive()
+                    );
+                }
+                match self.create_emitter(device, compute, capacity, emitter.blend) {
+                    Ok(gpu) => {
+                        self.emitters.insert(key, gpu);
+                    }
+                    Err(e) => {
+                        log::error!("K3: failed to create emitter {key}: {e}");
+                        continue;
+                    }
+                }
+            }
+            let gpu = self.emitters.get_mut(&key).expect("inserted above");
+            gpu.seen = true;
+
+            let rate = if emitter.enabled { emitter.rate } else { 0.0 };
+            let spawn = spawn_count(&mut gpu.spawn_accum, rate, dt, gpu.capacity);
+            gpu.uniform = emitter_uniform(emitter, model, camera, dt, self.time);
+            gpu.uniform.seed = self.frame.wrapping_mul(0x9E37_79B9) ^ key;
+            gpu.uniform.spawn_start = gpu.spawn_cursor;
+            gpu.uniform.spawn_count = spawn;
+            gpu.uniform.capacity = gpu.capacity;
+            gpu.spawn_cursor = (gpu.spawn_cursor + spawn) % gpu.capacity;
+            gpu.spawn.workgroups = workgroup_count([spawn, 1, 1], [WORKGROUP, 1, 1]);
+            queue.write_buffer(&gpu.uniform_buf, 0, bytemuck::bytes_of(&gpu.uniform));
+        }
+        self.emitters.retain(|_, g| g.seen);
+    }
+
+    fn create_emitter(
+        &self,
+        device: &Device,
+        compute: &ComputeRegistry,
+        capacity: u32,
+        blend: ParticleBlend,
+    ) -> Result<EmitterGpu, ComputeError> {
+        let uniform_buf = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Emitter UBO"),
+            size: std::mem::size_of::<EmitterUniform>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        // Zeroed memory = all particles dead.
+        let particle_bytes = capacity as u64 * std::mem::size_of::<GpuParticle>() as u64;
+        let particles = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Buffer"),
+            size: particle_bytes,
+            usage: BufferUsages::STORAGE | BufferUsages::VERTEX,
+            mapped_at_creation: false,
+        });
+        let sim = |shader| {
+            compute.bind_group(
+                device,
+                shader,
+                &[
+                    (0, ComputeResource::Buffer(&uniform_buf)),
+                    (1, ComputeResource::Buffer(&particles)),
+                ],
+            )
+        };
+        let update = ComputeDispatch {
+            shader: self.update_shader,
+            bind_group: sim(self.update_shader)?,
+            workgroups: workgroup_count([capacity, 1, 1], [WORKGROUP, 1, 1]),
+        };
+        let spawn = ComputeDispatch {
+            shader: self.spawn_shader,
+            bind_group: sim(self.spawn_shader)?,
+            workgroups: [0, 1, 1],
+        };
+
+        let (sorted, sort) = if blend == ParticleBlend::AlphaSorted {
+            let count = capacity.next_power_of_two();
+            let steps = bitonic_steps(count);
+            let step_data: Vec<u8> = steps
+                .iter()
+                .flat_map(|&(j, k)| {
+                    let mut block = vec![0u8; SORT_STEP_STRIDE as usize];
+                    let step = SortStepUniform {
+                        j,
+                        k,
+                        count,
+                        _pad: 0,
+                    };
+                    block[..std::mem::size_of::<SortStepUniform>()]
+                        .copy_from_slice(bytemuck::bytes_of(&step));
+                    block
+                })
+                .collect();
+            let steps_buf = wgpu::util::DeviceExt::create_buffer_init(
+                device,
+                &wgpu::util::BufferInitDescriptor {
+                    label: Some("Particle Sort Steps"),
+                    contents: &step_data,
+                    usage: BufferUsages::UNIFORM,
+                },
+            );
+            let keys = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Particle Sort Keys"),
+                size: count as u64 * 8,
+                usage: BufferUsages::STORAGE,
+                mapped_at_creation: false,
+            });
+            let sorted = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Particle Sorted Buffer"),
+                size: particle_bytes,
+                usage: BufferUsages::STORAGE | BufferUsages::VERTEX,
+                mapped_at_creation: false,
+            });
+            let dispatch =
+                |shader, step: usize, threads: u32| -> Result<ComputeDispatch, ComputeError> {
+                    let bind_group = compute.bind_group(
+                        device,
+                        shader,
+                        &[
+                            (0, ComputeResource::Buffer(&uniform_buf)),
+                            (1, ComputeResource::Buffer(&particles)),
+                            (2, ComputeResource::Buffer(&keys)),
+                            (3, ComputeResource::Buffer(&sorted)),
+                            (
+                                4,
+                                ComputeResource::BufferRange {
+                                    buffer: &steps_buf,
+                                    offset: step as u64 * SORT_STEP_STRIDE,
+                                    size: wgpu::BufferSize::new(
+                                        std::mem::size_of::<SortStepUniform>() as u64,
+                                    ),
+                                },
+                            ),
+                        ],
+                    )?;
+                    Ok(ComputeDispatch {
+                        shader,
+                        bind_group,
+                        workgroups: workgroup_count([threads, 1, 1], [WORKGROUP, 1, 1]),
+                    })
+                };
+            let mut sort = Vec::with_capacity(steps.len() + 2);
+            sort.push(dispatch(self.keys_shader, 0, count)?);
+            for i in 0..steps.len() {
+                sort.push(dispatch(self.sort_shader, i, count)?);
+            }
+            sort.push(dispatch(self.gather_shader, 0, capacity)?);
+            (Some(sorted), sort)
+        } else {
+            (None, Vec::new())
+        };
+
+        let render_bg = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Particle Emitter BG"),
+            layout: &self.emitter_bgl,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buf.as_entire_binding(),
+            }],
+        });
+        log::info!("K3: particle emitter with {capacity} slots ({blend:?})");
+
+        Ok(EmitterGpu {
+            capacity,
+            blend,
+            uniform: EmitterUniform::zeroed(),
+            uniform_buf,
+            particles,
+            sorted,
+            update,
+            spawn,
+            sort,
+            render_bg,
+            spawn_accum: 0.0,
+            spawn_cursor: 0,
+            seen: true,
+        })
+    }
+
+    /// Record update, spawn and (alpha emitters) sort in one profiled compute pass.
+    pub fn simulate(
+        &self,
+        encoder: &mut CommandEncoder,
+        compute: &ComputeRegistry,
+        profiler: &mut GpuProfiler,
+    ) {
+        if self.emitters.is_empty() {
+            return;
+        }
+        let scope = profiler.begin_pass("Particles");
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Particles"),
+                timestamp_writes: profiler.compute_timestamp_writes(scope),
+            });
+            for gpu in self.emitters.values() {
+                compute.record(&mut cpass, &gpu.update);
+                compute.record(&mut cpass, &gpu.spawn);
+                for dispatch in &gpu.sort {
+                    compute.record(&mut cpass, dispatch);
+                }
+            }
+        }
+        profiler.end_pass(scope);
+    }
+
+    /// Draw all emitters into the current pass (after opaque geometry).
+    pub fn draw<'a>(&'a self, rpass: &mut RenderPass<'a>) {
+        if self.emitters.is_empty() {
+            return;
+        }
+        rpass.set_bind_group(0, &self.camera_bg, &[]);
+        // Additive first: order independent, then sorted alpha on top.
+        for blend in [ParticleBlend::Additive, ParticleBlend::AlphaSorted] {
+            let pipeline = match blend {
+                ParticleBlend::Additive => &self.additive_pipeline,
+                ParticleBlend::AlphaSorted => &self.alpha_pipeline,
+            };
+            rpass.set_pipeline(pipeline);
+            for gpu in self.emitters.values().filter(|g| g.blend == blend) {
+                let buffer = gpu.sorted.as_ref().unwrap_or(&gpu.particles);
+                rpass.set_bind_group(1, &gpu.render_bg, &[]);
+                rpass.set_vertex_buffer(0, buffer.slice(..));
+                rpass.draw(0..6, 0..gpu.capacity);
+            }
+        }
+    }
+
+    /// Number of live emitters.
+    pub fn emitter_count(&self) -> usize {
+        self.emitters.len()
+    }
+
+    /// Particle slots simulated this frame (alive counts stay on the GPU).
+    pub fn capacity(&self) -> u32 {
+        self.emitters.values().map(|g| g.capacity).sum()
+    }
+}
+
+fn emitter_uniform(
+    e: &ParticleEmitter,
+    model: Mat4,
+    camera: &Camera,
+    dt: f32,
+    time: f32,
+) -> EmitterUniform {
+    let (shape, radius, angle) = match e.shape {
+        EmitterShape::Point => (0, 0.0, 0.0),
+        EmitterShape::Sphere { radius } => (1, radius, 0.0),
+        EmitterShape::Cone { angle, radius } => (2, radius, angle),
+    };
+    EmitterUniform {
+        model: model.to_cols_array_2d(),
+        color_start: e.color_start.to_array(),
+        color_end: e.color_end.to_array(),
+        gravity: e.gravity.to_array(),
+        drag: e.drag.max(0.0),
+        camera_pos: camera.eye.to_array(),
+        dt,
+        shape_params: [radius, angle, e.speed.0, e.speed.1],
+        lifetime: [e.lifetime.0.max(0.0), e.lifetime.1.max(0.0)],
+        size: [e.size_start, e.size_end],
+        curl: [e.curl_strength, e.curl_scale],
+        time,
+        seed: 0,
+        shape,
+        spawn_start: 0,
+        spawn_count: 0,
+        capacity: 0,
+    }
+}
+
+/// Expanded simulation, sort and draw shaders.
+fn particle_sources(includes: &dyn IncludeSource) -> Result<[String; 3], ComputeError> {
+    let expand = |name: &str, source: &str| {
+        preprocess(name, source, &ShaderDefs::new(), includes)
+            .map(|p| p.source)
+            .map_err(|e| ComputeError::Shader {
+                label: name.to_string(),
+                message: e.to_string(),
+            })
+    };
+    Ok([
+        expand("particles.wgsl", include_str!("shaders/particles.wgsl"))?,
+        expand(
+            "particle_sort.wgsl",
+            include_str!("shaders/particle_sort.wgsl"),
+        )?,
+        expand(
+            "particle_draw.wgsl",
+            include_str!("shaders/particle_draw.wgsl"),
+        )?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader::{EMBEDDED_INCLUDES, struct_layout, validate_wgsl};
+
+    fn struct_size(module: &naga::Module, name: &str) -> u32 {
+        struct_layout(module, name)
+            .unwrap_or_else(|| panic!("{name} not found"))
+            .span
+    }
+
+    #[test]
+    fn particle_shaders_validate_and_match_layouts() {
+        let [sim, sort, draw] = particle_sources(&EMBEDDED_INCLUDES)
+            .unwrap()
+            .map(|src| validate_wgsl(&src).unwrap_or_else(|e| panic!("{e}")));
+        for module in [&sim, &sort, &draw] {
+            assert_eq!(
+                struct_size(module, "Emitter") as usize,
+                std::mem::size_of::<EmitterUniform>()
+            );
+        }
+        assert_eq!(
+            struct_size(&sim, "Particle") as usize,
+            std::mem::size_of::<GpuParticle>()
+        );
+        assert_eq!(
+            struct_size(&sort, "SortStep") as usize,
+            std::mem::size_of::<SortStepUniform>()
+        );
+        assert_eq!(
+            struct_size(&draw, "Camera") as usize,
+            std::mem::size_of::<ParticleCameraUniform>()
+        );
+    }
+
+    #[test]
+    fn bitonic_network_sorts() {
+        // CPU model of cs_sort: descending order with dead (-1) keys last.
+        let mut keys = [3.0f32, -1.0, 7.0, 0.5, 9.0, -1.0, 2.0, 4.0];
+        for (j, k) in bitonic_steps(keys.len() as u32) {
+            for i in 0..keys.len() as u32 {
+                let l = i ^ j;
+                if l <= i {
+                    continue;
+                }
+                let (a, b) = (keys[i as usize], keys[l as usize]);
+                let descending = i & k == 0;
+                if (descending && a < b) || (!descending && a > b) {
+                    keys.swap(i as usize, l as usize);
+                }
+            }
+        }
+        assert_eq!(keys, [9.0, 7.0, 4.0, 3.0, 2.0, 0.5, -1.0, -1.0]);
+        assert_eq!(bitonic_steps(MAX_PARTICLES_PER_EMITTER).len(), 105);
+    }
+
+    #[test]
+    fn spawning_carries_fractions_and_caps() {
+        let mut accum = 0.0;
+        let spawned: u32 = (0..10)
+            .map(|_| spawn_count(&mut accum, 15.0, 0.1, 64))
+            .sum();
+        assert_eq!(spawned, 15);
+        assert_eq!(spawn_count(&mut accum, 1e6, 1.0, 64), 64);
+
+        let emitter = ParticleEmitter {
+            rate: 100.0,
+            lifetime: (1.0, 2.0),
+            ..Default::default()
+        };
+        assert_eq!(emitter_capacity(&emitter), 256);
+    }
+}
@@ -0,0 +1,318 @@
+//! J2: minimal WGSL preprocessor.
+//!
+//! Directives (one per line, `#` first non-blank character):
+//! `#include "path.wgsl"`, `#define NAME [value]`, `#undef NAME`,
+//! `#ifdef NAME`, `#ifndef NAME`, `#else`, `#endif`.
+//! Every file is included at most once per output, so shared struct definitions can be
+//! included from several snippets. Defines with a value are substituted as whole identifiers.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Feature defines of one shader permutation (sorted, so usable as a cache key).
+pub type ShaderDefs = BTreeMap<String, String>;
+
+/// Flag-only defines (`#ifdef` switches).
+pub fn shader_defs(flags: &[&str]) -> ShaderDefs {
+    flags
+        .iter()
+        .map(|f| (f.to_string(), String::new()))
+        .collect()
+}
+
+/// Where `#include` looks up files.
+pub trait IncludeSource {
+    fn include_source(&self, name: &str) -> Option<&str>;
+}
+
+impl IncludeSource for HashMap<String, String> {
+    fn include_source(&self, name: &str) -> Option<&str> {
+        self.get(name).map(String::as_str)
+    }
+}
+
+impl IncludeSource for &[(&str, &str)] {
+    fn include_source(&self, name: &str) -> Option<&str> {
+        self.iter().find(|(n, _)| *n == name).map(|(_, s)| *s)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PreprocessError {
+    MissingInclude {
+        file: String,
+        line: usize,
+        include: String,
+    },
+    /// `#else`/`#endif` without a matching `#ifdef`, or a second `#else`.
+    UnbalancedDirective {
+        file: String,
+        line: usize,
+        directive: String,
+    },
+    /// `#ifdef` not closed by the end of the file.
+    UnterminatedConditional { file: String, line: usize },
+    InvalidDirective {
+        file: String,
+        line: usize,
+        text: String,
+    },
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingInclude {
+                file,
+                line,
+                include,
+            } => {
+                write!(f, "{file}:{line}: include \"{include}\" not found")
+            }
+            Self::UnbalancedDirective {
+                file,
+                line,
+                directive,
+            } => {
+                write!(f, "{file}:{line}: unbalanced #{directive}")
+            }
+            Self::UnterminatedConditional { file, line } => {
+                write!(f, "{file}:{line}: conditional is never closed with #endif")
+            }
+            Self::InvalidDirective { file, line, text } => {
+                write!(f, "{file}:{line}: invalid directive `{text}`")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PreprocessError {}
+
+/// Expanded WGSL plus every file it pulled in.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Preprocessed {
+    pub source: String,
+    pub includes: Vec<String>,
+}
+
+/// Expand `source` (named `name` in errors) for the permutation `defines`.
+pub fn preprocess(
+    name: &str,
+    source: &str,
+    defines: &ShaderDefs,
+    includes: &dyn IncludeSource,
+) -> Result<Preprocessed, PreprocessError> {
+    let mut state = State {
+        defines: defines.clone(),
+        out: Preprocessed::default(),
+    };
+    state.expand(name, source, includes)?;
+    Ok(state.out)
+}
+
+struct State {
+    defines: ShaderDefs,
+    out: Preprocessed,
+}
+
+struct Conditional {
+    line: usize,
+    // Branch currently taken
+    taking: bool,
+    seen_else: bool,
+}
+
+impl State {
+    fn expand(
+        &mut self,
+        file: &str,
+        source: &str,
+        includes: &dyn IncludeSource,
+    ) -> Result<(), PreprocessError> {
+        let mut stack: Vec<Conditional> = Vec::new();
+        for (i, raw) in source.lines().enumerate() {
+            let line = i + 1;
+            let active = stack.iter().all(|c| c.taking);
+            let trimmed = raw.trim_start();
+            let Some(directive) = trimmed.strip_prefix('#') else {
+                if active {
+                    self.push_line(raw);
+                }
+                continue;
+            };
+
+            let mut parts = directive.split_whitespace();
+            let keyword = parts.next().unwrap_or("");
+            let arg = parts.next();
+            let invalid = || PreprocessError::InvalidDirective {
+                file: file.to_string(),
+                line,
+                text: trimmed.to_string(),
+            };
+            match keyword {
+                "ifdef" | "ifndef" => {
+                    let name = arg.ok_or_else(invalid)?;
+                    let defined = self.defines.contains_key(name);
+                    stack.push(Conditional {
+                        line,
+                        taking: defined == (keyword == "ifdef"),
+                        seen_else: false,
+                    });
+                }
+                "else" | "endif" => {
+                    let unbalanced = || PreprocessError::UnbalancedDirective {
+                        file: file.to_string(),
+                        line,
+                        directive: keyword.to_string(),
+                    };
+                    if keyword == "endif" {
+                        stack.pop().ok_or_else(unbalanced)?;
+                    } else {
+                        let top = stack.last_mut().ok_or_else(unbalanced)?;
+                        if top.seen_else {
+                            return Err(unbalanced());
+                        }
+                        top.seen_else = true;
+                        top.taking = !top.taking;
+                    }
+                }
+                _ if !active => {}
+                "define" => {
+                    let name = arg.ok_or_else(invalid)?;
+                    let value = directive
+                        .trim_start()
+                        .strip_prefix("define")
+                        .map(|rest| rest.trim_start().strip_prefix(name).unwrap_or("").trim())
+                        .unwrap_or("");
+                    self.defines.insert(name.to_string(), value.to_string());
+                }
+                "undef" => {
+                    self.defines.remove(arg.ok_or_else(invalid)?);
+                }
+                "include" => {
+                    let target = arg
+                        .and_then(|a| a.strip_prefix('"'))
+                        .and_then(|a| a.strip_suffix('"'))
+                        .ok_or_else(invalid)?;
+                    if self.out.includes.iter().any(|n| n == target) {
+                        continue;
+                    }
+                    let text = includes.include_source(target).ok_or_else(|| {
+                        PreprocessError::MissingInclude {
+                            file: file.to_string(),
+                            line,
+                            include: target.to_string(),
+                        }
+                    })?;
+                    self.out.includes.push(target.to_string());
+                    self.expand(target, text, includes)?;
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        match stack.first() {
+            Some(open) => Err(PreprocessError::UnterminatedConditional {
+                file: file.to_string(),
+                line: open.line,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    fn push_line(&mut self, line: &str) {
+        if self.defines.values().all(String::is_empty) {
+            self.out.source.push_str(line);
+        } else {
+            substitute(line, &self.defines, &mut self.out.source);
+        }
+        self.out.source.push('\n');
+    }
+}
+
+// Replace identifiers that name a valued define.
+fn substitute(line: &str, defines: &ShaderDefs, out: &mut String) {
+    let mut rest = line;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        let (before, tail) = rest.split_at(start);
+        out.push_str(before);
+        let end = tail
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(tail.len());
+        let ident = &tail[..end];
+        // Letters right after digits (`1e5`, `0x1F`, `2u`) are part of a literal
+        let in_literal = before.ends_with(|c: char| c.is_ascii_digit() || c == '.');
+        match defines.get(ident) {
+            Some(value) if !value.is_empty() && !in_literal => out.push_str(value),
+            _ => out.push_str(ident),
+        }
+        rest = &tail[end..];
+    }
+    out.push_str(rest);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILES: &[(&str, &str)] = &[
+        ("common.wgsl", "struct A { x: f32 };\n"),
+        ("b.wgsl", "#include \"common.wgsl\"\nstruct B { a: A };\n"),
+    ];
+
+    fn run(src: &str, flags: &[&str]) -> Result<Preprocessed, PreprocessError> {
+        preprocess("main.wgsl", src, &shader_defs(flags), &FILES)
+    }
+
+    #[test]
+    fn includes_are_expanded_once() {
+        let out = run(
+            "#include \"common.wgsl\"\n#include \"b.wgsl\"\nfn f() {}\n",
+            &[],
+        )
+        .unwrap();
+        assert_eq!(out.source.matches("struct A").count(), 1);
+        assert!(out.source.contains("struct B"));
+        assert_eq!(out.includes, ["common.wgsl", "b.wgsl"]);
+    }
+
+    #[test]
+    fn conditionals_select_permutation() {
+        let src = "#ifdef FOG\nfog\n#else\nno_fog\n#endif\n#ifndef FOG\nplain\n#endif\n";
+        assert_eq!(run(src, &["FOG"]).unwrap().source, "fog\n");
+        assert_eq!(run(src, &[]).unwrap().source, "no_fog\nplain\n");
+    }
+
+    #[test]
+    fn defines_substitute_whole_identifiers() {
+        let src =
+            "#define COUNT 4u\n#define FLAG\n#ifdef FLAG\nlet n = COUNT + COUNT_2 + 1e5;\n#endif\n";
+        assert_eq!(
+            run(src, &[]).unwrap().source,
+            "let n = 4u + COUNT_2 + 1e5;\n"
+        );
+    }
+
+    #[test]
+    fn errors_point_at_file_and_line() {
+        assert_eq!(
+            run("fn f() {}\n#include \"nope.wgsl\"\n", &[]).unwrap_err(),
+            PreprocessError::MissingInclude {
+                file: "main.wgsl".into(),
+                line: 2,
+                include: "nope.wgsl".into()
+            }
+        );
+        assert!(matches!(
+            run("#ifdef X\n", &[]),
+            Err(PreprocessError::UnterminatedConditional { line: 1, .. })
+        ));
+        assert!(matches!(
+            run("#endif\n", &[]),
+            Err(PreprocessError::UnbalancedDirective { .. })
+        ));
+        assert!(matches!(
+            run("#pragma once\n", &[]),
+            Err(PreprocessError::InvalidDirective { .. })
+        ));
+    }
+}
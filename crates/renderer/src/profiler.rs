@@ -0,0 +1,366 @@
+//! H4: GPU profiler with per-pass timings.
+//! Uses `wgpu::QuerySet` timestamps when `Features::TIMESTAMP_QUERY` is available,
+//! otherwise falls back to CPU timing of pass encoding.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, Ordering},
+};
+use std::time::Instant;
+
+use wgpu::{
+    Buffer, BufferUsages, CommandEncoder, ComputePassTimestampWrites, Device, QuerySet,
+    QuerySetDescriptor, QueryType, Queue, RenderPassTimestampWrites,
+};
+
+/// Max number of profiled passes per frame (two timestamps each).
+pub const MAX_PROFILED_PASSES: u32 = 32;
+
+const TIMESTAMP_SIZE: u64 = std::mem::size_of::<u64>() as u64;
+
+/// Timing source used by the profiler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfilerMode {
+    /// GPU timestamps resolved from a query set.
+    GpuTimestamps,
+    /// CPU time spent encoding each pass (no timestamp support).
+    CpuFallback,
+}
+
+/// Duration of a single pass in milliseconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassTiming {
+    pub label: String,
+    pub ms: f64,
+}
+
+/// Handle for a pass opened with [`GpuProfiler::begin_pass`].
+#[derive(Clone, Copy, Debug)]
+pub struct PassScope {
+    slot: u32,
+    started: Instant,
+}
+
+struct TimestampQueries {
+    query_set: QuerySet,
+    resolve_buf: Buffer,
+    readback_buf: Buffer,
+    /// ns per timestamp tick.
+    period: f32,
+}
+
+/// Readback state of the timestamp buffer.
+enum Readback {
+    Idle,
+    /// `map_async` is in flight; flag is raised by the callback.
+    Pending {
+        mapped: Arc<AtomicBool>,
+        labels: Vec<String>,
+    },
+}
+
+/// Per-pass profiler. Results lag a frame or two behind (async readback).
+pub struct GpuProfiler {
+    enabled: bool,
+    queries: Option<TimestampQueries>,
+    readback: Readback,
+
+    // Текущий кадр
+    frame_labels: Vec<String>,
+    frame_cpu_ms: Vec<f64>,
+    gpu_frame_active: bool,
+
+    // Последние готовые результаты
+    timings: Vec<PassTiming>,
+}
+
+impl GpuProfiler {
+    /// Create profiler. Timestamp queries are used only if the device has `TIMESTAMP_QUERY`.
+    pub fn new(device: &Device, queue: &Queue) -> Self {
+        let queries = if device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            let count = MAX_PROFILED_PASSES * 2;
+            let size = count as u64 * TIMESTAMP_SIZE;
+            Some(TimestampQueries {
+                query_set: device.create_query_set(&QuerySetDescriptor {
+                    label: Some("Profiler QuerySet"),
+                    ty: QueryType::Timestamp,
+                    count,
+                }),
+                resolve_buf: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Profiler Resolve"),
+                    size,
+                    usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+                    mapped_at_creation: false,
+                }),
+                readback_buf: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Profiler Readback"),
+                    size,
+                    usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                period: queue.get_timestamp_period(),
+            })
+        } else {
+            None
+        };
+
+        log::info!(
+            "H4: profiler mode {:?}",
+            if queries.is_some() {
+                ProfilerMode::GpuTimestamps
+            } else {
+                ProfilerMode::CpuFallback
+            }
+        );
+
+        Self {
+            enabled: false,
+            queries,
+            readback: Readback::Idle,
+            frame_labels: Vec::new(),
+            frame_cpu_ms: Vec::new(),
+            gpu_frame_active: false,
+            timings: Vec::new(),
+        }
+    }
+
+    /// Disabling drops an in-flight readback, so stale timings don't show up on re-enable.
+    pub fn set_enabled(&mut self, device: &Device, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.timings.clear();
+            if let (Readback::Pending { .. }, Some(queries)) =
+                (&self.readback, self.queries.as_ref())
+            {
+                // Let the map finish rather than abort it (the callback would log a failure)
+                device.poll(wgpu::Maintain::Wait);
+                queries.readback_buf.unmap();
+                self.readback = Readback::Idle;
+            }
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn mode(&self) -> ProfilerMode {
+        if self.queries.is_some() {
+            ProfilerMode::GpuTimestamps
+        } else {
+            ProfilerMode::CpuFallback
+        }
+    }
+
+    /// Start a new frame: poll the previous readback and reset per-frame state.
+    pub fn begin_frame(&mut self, device: &Device) {
+        self.frame_labels.clear();
+        self.frame_cpu_ms.clear();
+        self.gpu_frame_active = false;
+        if !self.enabled {
+            return;
+        }
+
+        if matches!(self.readback, Readback::Pending { .. }) {
+            device.poll(wgpu::Maintain::Poll);
+            self.collect_readback();
+        }
+
+        // Пока буфер замаплен, GPU-таймстемпы в этом кадре не пишем.
+        self.gpu_frame_active = self.queries.is_some() && matches!(self.readback, Readback::Idle);
+    }
+
+    /// Open a pass scope. Returns `None` when disabled or out of query slots.
+    pub fn begin_pass(&mut self, label: &str) -> Option<PassScope> {
+        if !self.enabled || self.frame_labels.len() as u32 >= MAX_PROFILED_PASSES {
+            return None;
+        }
+        let slot = self.frame_labels.len() as u32;
+        self.frame_labels.push(label.to_string());
+        self.frame_cpu_ms.push(0.0);
+        Some(PassScope {
+            slot,
+            started: Instant::now(),
+        })
+    }
+
+    /// Timestamp writes for a render pass opened with [`Self::begin_pass`].
+    pub fn render_timestamp_writes(
+        &self,
+        scope: Option<PassScope>,
+    ) -> Option<RenderPassTimestampWrites<'_>> {
+        let (queries, slot) = self.gpu_slot(scope)?;
+        Some(RenderPassTimestampWrites {
+            query_set: &queries.query_set,
+            beginning_of_pass_write_index: Some(slot * 2),
+            end_of_pass_write_index: Some(slot * 2 + 1),
+        })
+    }
+
+    /// Timestamp writes for a compute pass opened with [`Self::begin_pass`].
+    pub fn compute_timestamp_writes(
+        &self,
+        scope: Option<PassScope>,
+    ) -> Option<ComputePassTimestampWrites<'_>> {
+        let (queries, slot) = self.gpu_slot(scope)?;
+        Some(ComputePassTimestampWrites {
+            query_set: &queries.query_set,
+            beginning_of_pass_write_index: Some(slot * 2),
+            end_of_pass_write_index: Some(slot * 2 + 1),
+        })
+    }
+
+    /// Close a pass scope (records CPU encode time for the fallback path).
+    pub fn end_pass(&mut self, scope: Option<PassScope>) {
+        if let Some(scope) = scope
+            && let Some(ms) = self.frame_cpu_ms.get_mut(scope.slot as usize)
+        {
+            *ms = scope.started.elapsed().as_secs_f64() * 1000.0;
+        }
+    }
+
+    /// Resolve queries into the readback buffer. Call before `encoder.finish()`.
+    pub fn resolve(&mut self, encoder: &mut CommandEncoder) {
+        if !self.gpu_frame_active || self.frame_labels.is_empty() {
+            return;
+        }
+        let Some(queries) = self.queries.as_ref() else {
+            return;
+        };
+        let count = self.frame_labels.len() as u32 * 2;
+        encoder.resolve_query_set(&queries.query_set, 0..count, &queries.resolve_buf, 0);
+        encoder.copy_buffer_to_buffer(
+            &queries.resolve_buf,
+            0,
+            &queries.readback_buf,
+            0,
+            count as u64 * TIMESTAMP_SIZE,
+        );
+    }
+
+    /// Finish the frame after `queue.submit`: start async readback or publish CPU timings.
+    pub fn end_frame(&mut self) {
+        if !self.enabled || self.frame_labels.is_empty() {
+            return;
+        }
+
+        if self.queries.is_none() {
+            self.timings.clear();
+            for (label, ms) in self.frame_labels.iter().zip(&self.frame_cpu_ms) {
+                self.timings.push(PassTiming {
+                    label: label.clone(),
+                    ms: *ms,
+                });
+            }
+            return;
+        }
+
+        if !self.gpu_frame_active {
+            return;
+        }
+        let Some(queries) = self.queries.as_ref() else {
+            return;
+        };
+        let mapped = Arc::new(AtomicBool::new(false));
+        let flag = mapped.clone();
+        let size = self.frame_labels.len() as u64 * 2 * TIMESTAMP_SIZE;
+        queries
+            .readback_buf
+            .slice(..size)
+            .map_async(wgpu::MapMode::Read, move |res| {
+                if let Err(e) = res {
+                    log::warn!("H4: timestamp readback failed: {e:?}");
+                }
+                flag.store(true, Ordering::Release);
+            });
+        self.readback = Readback::Pending {
+            mapped,
+            labels: std::mem::take(&mut self.frame_labels),
+        };
+        self.gpu_frame_active = false;
+    }
+
+    /// Latest available per-pass timings.
+    pub fn timings(&self) -> &[PassTiming] {
+        &self.timings
+    }
+
+    /// Sum of all pass timings in milliseconds.
+    pub fn total_ms(&self) -> f64 {
+        self.timings.iter().map(|t| t.ms).sum()
+    }
+
+    /// Dump the latest timings to the log.
+    pub fn log_timings(&self) {
+        if self.timings.is_empty() {
+            return;
+        }
+        let parts: Vec<String> = self
+            .timings
+            .iter()
+            .map(|t| format!("{} {:.3} ms", t.label, t.ms))
+            .collect();
+        log::info!(
+            "H4: {:?} total {:.3} ms [{}]",
+            self.mode(),
+            self.total_ms(),
+            parts.join(", ")
+        );
+    }
+
+    fn gpu_slot(&self, scope: Option<PassScope>) -> Option<(&TimestampQueries, u32)> {
+        let scope = scope?;
+        if !self.gpu_frame_active {
+            return None;
+        }
+        self.queries.as_ref().map(|q| (q, scope.slot))
+    }
+
+    fn collect_readback(&mut self) {
+        let Readback::Pending { mapped, labels } = &self.readback else {
+            return;
+        };
+        if !mapped.load(Ordering::Acquire) {
+            return;
+        }
+        let Some(queries) = self.queries.as_ref() else {
+            return;
+        };
+
+        let size = labels.len() as u64 * 2 * TIMESTAMP_SIZE;
+        {
+            let data = queries.readback_buf.slice(..size).get_mapped_range();
+            let ticks: &[u64] = bytemuck::cast_slice(&data);
+            self.timings = ticks_to_timings(labels, ticks, queries.period);
+        }
+        queries.readback_buf.unmap();
+        self.readback = Readback::Idle;
+    }
+}
+
+/// Convert begin/end timestamp pairs into per-pass milliseconds.
+fn ticks_to_timings(labels: &[String], ticks: &[u64], period_ns: f32) -> Vec<PassTiming> {
+    labels
+        .iter()
+        .zip(ticks.chunks_exact(2))
+        .map(|(label, pair)| PassTiming {
+            label: label.clone(),
+            ms: pair[1].saturating_sub(pair[0]) as f64 * period_ns as f64 / 1_000_000.0,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ticks_convert_to_milliseconds() {
+        let labels = vec!["MainPass".to_string(), "PostProcessPass".to_string()];
+        let ticks = [1_000, 3_000_000, 3_000_000, 3_500_000];
+        let timings = ticks_to_timings(&labels, &ticks, 1.0);
+        assert_eq!(timings.len(), 2);
+        assert!((timings[0].ms - 2.999).abs() < 1e-9);
+        assert!((timings[1].ms - 0.5).abs() < 1e-9);
+    }
+}
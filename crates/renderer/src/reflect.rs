@@ -0,0 +1,389 @@
+//! J2: bind group layouts derived from WGSL via naga reflection.
+//!
+//! Every resource an entry point actually uses becomes a layout entry: buffer kind and
+//! min binding size from the declared type, visibility from the stages that touch it.
+//! Declared but unused bindings are left out (wgpu only validates what the pipeline uses).
+
+use std::fmt;
+use std::num::NonZeroU64;
+
+use wgpu::{
+    BindGroupLayout, BindGroupLayoutEntry, BindingType, BufferBindingType, Device,
+    SamplerBindingType, ShaderStages, StorageTextureAccess, TextureFormat, TextureSampleType,
+    TextureViewDimension,
+};
+
+/// One resource binding of a shader.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BindingInfo {
+    pub group: u32,
+    pub binding: u32,
+    /// WGSL variable name.
+    pub name: Option<String>,
+    pub ty: BindingType,
+    pub visibility: ShaderStages,
+}
+
+impl BindingInfo {
+    pub fn layout_entry(&self) -> BindGroupLayoutEntry {
+        BindGroupLayoutEntry {
+            binding: self.binding,
+            visibility: self.visibility,
+            ty: self.ty,
+            count: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReflectError {
+    /// WGSL did not parse or validate (message includes source locations).
+    Invalid(String),
+    /// A binding uses a type the renderer cannot express as a layout entry.
+    Unsupported {
+        name: String,
+        group: u32,
+        binding: u32,
+        what: String,
+    },
+}
+
+impl fmt::Display for ReflectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(message) => f.write_str(message),
+            Self::Unsupported {
+                name,
+                group,
+                binding,
+                what,
+            } => write!(
+                f,
+                "binding '{name}' (group {group}, binding {binding}): unsupported {what}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReflectError {}
+
+/// Used bindings of a shader module, sorted by (group, binding).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShaderReflection {
+    pub bindings: Vec<BindingInfo>,
+}
+
+impl ShaderReflection {
+    /// Parse, validate and reflect WGSL.
+    pub fn from_wgsl(source: &str) -> Result<Self, ReflectError> {
+        let module = naga::front::wgsl::parse_str(source)
+            .map_err(|e| ReflectError::Invalid(e.emit_to_string(source)))?;
+        let info = naga::valid::Validator::new(
+            naga::valid::ValidationFlags::all(),
+            naga::valid::Capabilities::empty(),
+        )
+        .validate(&module)
+        .map_err(|e| ReflectError::Invalid(e.emit_to_string(source)))?;
+        Self::from_module(&module, &info)
+    }
+
+    pub fn from_module(
+        module: &naga::Module,
+        info: &naga::valid::ModuleInfo,
+    ) -> Result<Self, ReflectError> {
+        let mut bindings = Vec::new();
+        for (handle, var) in module.global_variables.iter() {
+            let Some(res) = &var.binding else {
+                continue;
+            };
+            let mut visibility = ShaderStages::NONE;
+            for (i, ep) in module.entry_points.iter().enumerate() {
+                if !info.get_entry_point(i)[handle].is_empty() {
+                    visibility |= match ep.stage {
+                        naga::ShaderStage::Vertex => ShaderStages::VERTEX,
+                        naga::ShaderStage::Fragment => ShaderStages::FRAGMENT,
+                        naga::ShaderStage::Compute => ShaderStages::COMPUTE,
+                    };
+                }
+            }
+            if visibility.is_empty() {
+                continue;
+            }
+            let name = var.name.clone().unwrap_or_else(|| "<unnamed>".to_string());
+            let unsupported = |what: &str| ReflectError::Unsupported {
+                name: name.clone(),
+                group: res.group,
+                binding: res.binding,
+                what: what.to_string(),
+            };
+            let inner = &module.types[var.ty].inner;
+            let ty = match var.space {
+                naga::AddressSpace::Uniform | naga::AddressSpace::Storage { .. } => {
+                    let ty = match var.space {
+                        naga::AddressSpace::Storage { access } => BufferBindingType::Storage {
+                            read_only: !access.contains(naga::StorageAccess::STORE),
+                        },
+                        _ => BufferBindingType::Uniform,
+                    };
+                    // Runtime-sized arrays report one element: the minimum that is valid
+                    let size = inner.size(module.to_ctx());
+                    BindingType::Buffer {
+                        ty,
+                        has_dynamic_offset: false,
+                        min_binding_size: NonZeroU64::new(size as u64),
+                    }
+                }
+                naga::AddressSpace::Handle => match *inner {
+                    naga::TypeInner::Sampler { comparison } => {
+                        BindingType::Sampler(if comparison {
+                            SamplerBindingType::Comparison
+                        } else {
+                            SamplerBindingType::Filtering
+                        })
+                    }
+                    naga::TypeInner::Image {
+                        dim,
+                        arrayed,
+                        class,
+                    } => {
+                        let view_dimension = view_dimension(dim, arrayed)
+                            .ok_or_else(|| unsupported("texture dimension"))?;
+                        match class {
+                            naga::ImageClass::Sampled { kind, multi } => BindingType::Texture {
+                                sample_type: match kind {
+                                    naga::ScalarKind::Float => {
+                                        TextureSampleType::Float { filterable: !multi }
+                                    }
+                                    naga::ScalarKind::Sint => TextureSampleType::Sint,
+                                    naga::ScalarKind::Uint => TextureSampleType::Uint,
+                                    _ => return Err(unsupported("texture sample type")),
+                                },
+                                view_dimension,
+                                multisampled: multi,
+                            },
+                            naga::ImageClass::Depth { multi } => BindingType::Texture {
+                                sample_type: TextureSampleType::Depth,
+                                view_dimension,
+                                multisampled: multi,
+                            },
+                            naga::ImageClass::Storage { format, access } => {
+                                BindingType::StorageTexture {
+                                    access: match (
+                                        access.contains(naga::StorageAccess::LOAD),
+                                        access.contains(naga::StorageAccess::STORE),
+                                    ) {
+                                        (true, true) => StorageTextureAccess::ReadWrite,
+                                        (true, false) => StorageTextureAccess::ReadOnly,
+                                        _ => StorageTextureAccess::WriteOnly,
+                                    },
+                                    format: storage_format(format)
+                                        .ok_or_else(|| unsupported("storage texture format"))?,
+                                    view_dimension,
+                                }
+                            }
+                        }
+                    }
+                    _ => return Err(unsupported("handle type")),
+                },
+                _ => return Err(unsupported("address space")),
+            };
+            bindings.push(BindingInfo {
+                group: res.group,
+                binding: res.binding,
+                name: var.name.clone(),
+                ty,
+                visibility,
+            });
+        }
+        bindings.sort_by_key(|b| (b.group, b.binding));
+        Ok(Self { bindings })
+    }
+
+    /// Number of bind groups the pipeline layout needs (highest used group + 1).
+    pub fn group_count(&self) -> u32 {
+        self.bindings.last().map_or(0, |b| b.group + 1)
+    }
+
+    pub fn get(&self, group: u32, binding: u32) -> Option<&BindingInfo> {
+        self.bindings
+            .iter()
+            .find(|b| b.group == group && b.binding == binding)
+    }
+
+    pub fn group(&self, group: u32) -> impl Iterator<Item = &BindingInfo> {
+        self.bindings.iter().filter(move |b| b.group == group)
+    }
+
+    pub fn group_entries(&self, group: u32) -> Vec<BindGroupLayoutEntry> {
+        self.group(group).map(BindingInfo::layout_entry).collect()
+    }
+
+    /// One layout per group in `0..group_count()` (groups without bindings get empty layouts).
+    pub fn create_bind_group_layouts(&self, device: &Device, label: &str) -> Vec<BindGroupLayout> {
+        (0..self.group_count())
+            .map(|group| {
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: Some(&format!("{label} BGL{group}")),
+                    entries: &self.group_entries(group),
+                })
+            })
+            .collect()
+    }
+}
+
+fn view_dimension(dim: naga::ImageDimension, arrayed: bool) -> Option<TextureViewDimension> {
+    Some(match (dim, arrayed) {
+        (naga::ImageDimension::D1, false) => TextureViewDimension::D1,
+        (naga::ImageDimension::D2, false) => TextureViewDimension::D2,
+        (naga::ImageDimension::D2, true) => TextureViewDimension::D2Array,
+        (naga::ImageDimension::D3, false) => TextureViewDimension::D3,
+        (naga::ImageDimension::Cube, false) => TextureViewDimension::Cube,
+        (naga::ImageDimension::Cube, true) => TextureViewDimension::CubeArray,
+        _ => return None,
+    })
+}
+
+fn storage_format(format: naga::StorageFormat) -> Option<TextureFormat> {
+    use naga::StorageFormat as S;
+    Some(match format {
+        S::R8Unorm => TextureFormat::R8Unorm,
+        S::R8Snorm => TextureFormat::R8Snorm,
+        S::R8Uint => TextureFormat::R8Uint,
+        S::R8Sint => TextureFormat::R8Sint,
+        S::R16Uint => TextureFormat::R16Uint,
+        S::R16Sint => TextureFormat::R16Sint,
+        S::R16Float => TextureFormat::R16Float,
+        S::Rg8Unorm => TextureFormat::Rg8Unorm,
+        S::Rg8Snorm => TextureFormat::Rg8Snorm,
+        S::Rg8Uint => TextureFormat::Rg8Uint,
+        S::Rg8Sint => TextureFormat::Rg8Sint,
+        S::R32Uint => TextureFormat::R32Uint,
+        S::R32Sint => TextureFormat::R32Sint,
+        S::R32Float => TextureFormat::R32Float,
+        S::Rg16Uint => TextureFormat::Rg16Uint,
+        S::Rg16Sint => TextureFormat::Rg16Sint,
+        S::Rg16Float => TextureFormat::Rg16Float,
+        S::Rgba8Unorm => TextureFormat::Rgba8Unorm,
+        S::Rgba8Snorm => TextureFormat::Rgba8Snorm,
+        S::Rgba8Uint => TextureFormat::Rgba8Uint,
+        S::Rgba8Sint => TextureFormat::Rgba8Sint,
+        S::Bgra8Unorm => TextureFormat::Bgra8Unorm,
+        S::Rgb10a2Uint => TextureFormat::Rgb10a2Uint,
+        S::Rgb10a2Unorm => TextureFormat::Rgb10a2Unorm,
+        S::Rg11b10Ufloat => TextureFormat::Rg11b10Ufloat,
+        S::Rg32Uint => TextureFormat::Rg32Uint,
+        S::Rg32Sint => TextureFormat::Rg32Sint,
+        S::Rg32Float => TextureFormat::Rg32Float,
+        S::Rgba16Uint => TextureFormat::Rgba16Uint,
+        S::Rgba16Sint => TextureFormat::Rgba16Sint,
+        S::Rgba16Float => TextureFormat::Rgba16Float,
+        S::Rgba32Uint => TextureFormat::Rgba32Uint,
+        S::Rgba32Sint => TextureFormat::Rgba32Sint,
+        S::Rgba32Float => TextureFormat::Rgba32Float,
+        S::R16Unorm => TextureFormat::R16Unorm,
+        S::R16Snorm => TextureFormat::R16Snorm,
+        S::Rg16Unorm => TextureFormat::Rg16Unorm,
+        S::Rg16Snorm => TextureFormat::Rg16Snorm,
+        S::Rgba16Unorm => TextureFormat::Rgba16Unorm,
+        S::Rgba16Snorm => TextureFormat::Rgba16Snorm,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn main_shader_layouts_match_uniforms() {
+        let mut shaders = crate::shader::ShaderManager::with_embedded_includes();
+        let main = shaders.register(crate::MAIN_SHADER_NAME, crate::MAIN_SHADER_SOURCE);
+        let expanded = shaders
+            .permutation(main, &crate::main_shader_defs())
+            .unwrap();
+        let refl = ShaderReflection::from_wgsl(&expanded.source).unwrap_or_else(|e| panic!("{e}"));
+        assert_eq!(refl.group_count(), 3);
+
+        let buffer = |size: usize| BindingType::Buffer {
+            ty: BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: NonZeroU64::new(size as u64),
+        };
+        let camera = refl.get(0, 0).unwrap();
+        assert_eq!(
+            camera.ty,
+            buffer(std::mem::size_of::<crate::CameraUniform>())
+        );
+        assert_eq!(camera.visibility, ShaderStages::VERTEX);
+        let material = refl.get(1, 0).unwrap();
+        assert_eq!(
+            material.ty,
+            buffer(std::mem::size_of::<crate::MaterialUniform>())
+        );
+        assert_eq!(material.visibility, ShaderStages::FRAGMENT);
+        assert_eq!(
+            refl.get(1, 1).unwrap().ty,
+            buffer(std::mem::size_of::<crate::LightingUniform>())
+        );
+        assert!(matches!(
+            refl.get(2, 0).unwrap().ty,
+            BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            }
+        ));
+        assert_eq!(
+            refl.get(2, 1).unwrap().ty,
+            BindingType::Sampler(SamplerBindingType::Filtering)
+        );
+    }
+
+    #[test]
+    fn storage_bindings_and_unused_globals() {
+        let src = r"
+            struct P { v: vec4<f32> };
+            @group(0) @binding(0) var<storage, read> src_buf: array<P>;
+            @group(0) @binding(1) var<storage, read_write> dst_buf: array<P>;
+            @group(0) @binding(2) var<uniform> unused: vec4<f32>;
+            @group(1) @binding(0) var img: texture_storage_2d<rgba16float, write>;
+            @compute @workgroup_size(64)
+            fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+                dst_buf[id.x] = src_buf[id.x];
+                textureStore(img, vec2<i32>(0), vec4<f32>(1.0));
+            }
+        ";
+        let refl = ShaderReflection::from_wgsl(src).unwrap();
+        assert_eq!(refl.bindings.len(), 3);
+        assert!(refl.get(0, 2).is_none());
+        assert_eq!(
+            refl.get(0, 0).unwrap().ty,
+            BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(16),
+            }
+        );
+        assert_eq!(
+            refl.get(0, 1).unwrap().ty,
+            BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: false },
+                has_dynamic_offset: false,
+                min_binding_size: NonZeroU64::new(16),
+            }
+        );
+        assert_eq!(
+            refl.get(1, 0).unwrap().ty,
+            BindingType::StorageTexture {
+                access: StorageTextureAccess::WriteOnly,
+                format: TextureFormat::Rgba16Float,
+                view_dimension: TextureViewDimension::D2,
+            }
+        );
+        assert!(
+            refl.bindings
+                .iter()
+                .all(|b| b.visibility == ShaderStages::COMPUTE)
+        );
+        assert!(ShaderReflection::from_wgsl("fn broken(").is_err());
+    }
+}
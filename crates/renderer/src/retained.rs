@@ -0,0 +1,383 @@
+//! L2: retained-mode instance storage.
+//! Instances live in persistent slots (one per entity, plus one for an outgoing LOD
+//! during a cross-fade). Only changed slots are re-uploaded; the draw order is re-sorted
+//! only when slots are added, removed or change their [`DrawKey`].
+//! L1: the CPU culling path keeps a visibility per sorted position ([`RetainedVisibility`]),
+//! so a static frame with a static camera tests and uploads nothing.
+
+use corelib::ecs::Entity;
+
+use crate::{DrawBatch, DrawKey, InstanceRaw};
+
+/// Dirty positions closer than this are uploaded as one range.
+const MERGE_GAP: u32 = 16;
+
+/// Slot id of an entity's instance (`fading_out` = outgoing LOD of a cross-fade).
+pub fn instance_slot(entity: Entity, fading_out: bool) -> u32 {
+    entity * 2 + fading_out as u32
+}
+
+#[derive(Clone, Copy)]
+struct Slot {
+    key: DrawKey,
+    instance: InstanceRaw,
+    /// Index into the sorted instance data (valid once the order is rebuilt).
+    position: u32,
+}
+
+/// What [`RetainedInstances::flush`] changed since the previous flush.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum RetainedUpdate {
+    /// Order and batches were rebuilt; everything must be uploaded.
+    Full,
+    /// Only [`RetainedInstances::dirty_ranges`] changed.
+    Partial,
+}
+
+#[derive(Default)]
+pub(crate) struct RetainedInstances {
+    slots: Vec<Option<Slot>>,
+    live: usize,
+    structure_dirty: bool,
+    /// Sorted instance data (draw order).
+    data: Vec<InstanceRaw>,
+    /// Batch index per sorted position (for the GPU culling path).
+    batch_of: Vec<u32>,
+    batches: Vec<DrawBatch>,
+    dirty_positions: Vec<u32>,
+    ranges: Vec<(u32, u32)>,
+}
+
+impl RetainedInstances {
+    /// Insert or update a slot.
+    pub fn set(&mut self, slot: u32, key: DrawKey, instance: InstanceRaw) {
+        let idx = slot as usize;
+        if idx >= self.slots.len() {
+            self.slots.resize(idx + 1, None);
+        }
+        match self.slots[idx].as_mut() {
+            // Re-resolved but unchanged (e.g. after a camera move): nothing to upload
+            Some(s)
+                if s.key == key
+                    && bytemuck::bytes_of(&s.instance) == bytemuck::bytes_of(&instance) => {}
+            Some(s) if s.key == key => {
+                s.instance = instance;
+                if !self.structure_dirty {
+                    self.data[s.position as usize] = instance;
+                    self.dirty_positions.push(s.position);
+                }
+            }
+            Some(s) => {
+                s.key = key;
+                s.instance = instance;
+                self.structure_dirty = true;
+            }
+            None => {
+                self.slots[idx] = Some(Slot {
+                    key,
+                    instance,
+                    position: 0,
+                });
+                self.live += 1;
+                self.structure_dirty = true;
+            }
+        }
+    }
+
+    pub fn remove(&mut self, slot: u32) {
+        if let Some(s) = self.slots.get_mut(slot as usize)
+            && s.take().is_some()
+        {
+            self.live -= 1;
+            self.structure_dirty = true;
+        }
+    }
+
+    /// Force a full rebuild and upload on the next flush.
+    pub fn invalidate(&mut self) {
+        self.structure_dirty = true;
+    }
+
+    /// Apply pending changes: rebuild the order if needed, otherwise coalesce dirty ranges.
+    pub fn flush(&mut self) -> RetainedUpdate {
+        if self.structure_dirty {
+            self.rebuild();
+            return RetainedUpdate::Full;
+        }
+
+        self.ranges.clear();
+        self.dirty_positions.sort_unstable();
+        self.dirty_positions.dedup();
+        for &p in &self.dirty_positions {
+            match self.ranges.last_mut() {
+                Some((_, end)) if p <= *end + MERGE_GAP => *end = p + 1,
+                _ => self.ranges.push((p, p + 1)),
+            }
+        }
+        self.dirty_positions.clear();
+        RetainedUpdate::Partial
+    }
+
+    fn rebuild(&mut self) {
+        let mut order: Vec<u32> = (0..self.slots.len() as u32)
+            .filter(|&i| self.slots[i as usize].is_some())
+            .collect();
+        // Slot id as tie-breaker keeps the order stable between rebuilds.
+        order.sort_unstable_by_key(|&i| (self.slots[i as usize].expect("filtered").key, i));
+
+        self.data.clear();
+        self.batch_of.clear();
+        self.batches.clear();
+        for (position, &i) in order.iter().enumerate() {
+            let slot = self.slots[i as usize].as_mut().expect("filtered");
+            slot.position = position as u32;
+            self.data.push(slot.instance);
+            match self.batches.last_mut() {
+                Some(b) if b.key == slot.key => b.count += 1,
+                _ => self.batches.push(DrawBatch {
+                    key: slot.key,
+                    start: position,
+                    count: 1,
+                }),
+            }
+            self.batch_of.push(self.batches.len() as u32 - 1);
+        }
+        self.structure_dirty = false;
+        self.dirty_positions.clear();
+        self.ranges.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.live
+    }
+
+    pub fn data(&self) -> &[InstanceRaw] {
+        &self.data
+    }
+
+    pub fn batch_of(&self) -> &[u32] {
+        &self.batch_of
+    }
+
+    pub fn batches(&self) -> &[DrawBatch] {
+        &self.batches
+    }
+
+    /// `[start, end)` positions changed by the last partial flush.
+    pub fn dirty_ranges(&self) -> &[(u32, u32)] {
+        &self.ranges
+    }
+}
+
+/// L1: culling result of one retained instance.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Visibility {
+    Visible,
+    Culled,
+    Occluded,
+}
+
+/// L1: visibility of the sorted retained instances for the CPU culling path.
+#[derive(Default)]
+pub(crate) struct RetainedVisibility {
+    state: Vec<Visibility>,
+    culled: u32,
+    occluded: u32,
+}
+
+impl RetainedVisibility {
+    /// Re-test the instances of the last [`RetainedInstances::flush`] (all of them when `all`,
+    /// e.g. after a camera move, otherwise only the dirty ranges) and rebuild the compacted
+    /// `data` / `batches` when the visible set or a visible instance changed.
+    /// Returns whether they were rebuilt (and need uploading).
+    pub fn update(
+        &mut self,
+        retained: &RetainedInstances,
+        update: RetainedUpdate,
+        all: bool,
+        mut test: impl FnMut(&DrawBatch, &InstanceRaw) -> Visibility,
+        data: &mut Vec<InstanceRaw>,
+        batches: &mut Vec<DrawBatch>,
+    ) -> bool {
+        let (sorted, batch_of) = (retained.data(), retained.batch_of());
+        let rebuilt = update == RetainedUpdate::Full || self.state.len() != sorted.len();
+        let mut changed = rebuilt;
+        let mut retest = |p: usize, state: &mut [Visibility]| {
+            let v = test(&retained.batches()[batch_of[p] as usize], &sorted[p]);
+            let flipped = state[p] != v;
+            state[p] = v;
+            flipped
+        };
+        if rebuilt {
+            self.state.clear();
+            self.state.resize(sorted.len(), Visibility::Visible);
+        }
+        if rebuilt || all {
+            for p in 0..sorted.len() {
+                changed |= retest(p, &mut self.state);
+            }
+        } else {
+            for &(start, end) in retained.dirty_ranges() {
+                for p in start as usize..end as usize {
+                    changed |= retest(p, &mut self.state);
+                }
+            }
+        }
+        // Moved instances that stay visible still need their new data uploaded
+        changed |= retained.dirty_ranges().iter().any(|&(start, end)| {
+            self.state[start as usize..end as usize].contains(&Visibility::Visible)
+        });
+        if !changed {
+            return false;
+        }
+
+        data.clear();
+        batches.clear();
+        for batch in retained.batches() {
+            let start = data.len();
+            let range = batch.start..batch.start + batch.count;
+            data.extend(
+                sorted[range.clone()]
+                    .iter()
+                    .zip(&self.state[range])
+                    .filter(|&(_, &v)| v == Visibility::Visible)
+                    .map(|(raw, _)| *raw),
+            );
+            if data.len() > start {
+                batches.push(DrawBatch {
+                    key: batch.key,
+                    start,
+                    count: data.len() - start,
+                });
+            }
+        }
+        let count = |v| self.state.iter().filter(|&&s| s == v).count() as u32;
+        (self.culled, self.occluded) = (count(Visibility::Culled), count(Visibility::Occluded));
+        true
+    }
+
+    /// Instances outside the frustum in the current result.
+    pub fn culled(&self) -> u32 {
+        self.culled
+    }
+
+    /// Instances hidden by the Hi-Z pyramid in the current result.
+    pub fn occluded(&self) -> u32 {
+        self.occluded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use corelib::{
+        Mat4,
+        ecs::{MaterialId, MeshId, TextureId},
+    };
+
+    fn key(mesh: u32) -> DrawKey {
+        DrawKey {
+            pso_id: 0,
+            material: MaterialId::new(0),
+            texture: TextureId::new(0),
+            mesh: MeshId::new(mesh),
+        }
+    }
+
+    fn at(x: f32) -> InstanceRaw {
+        InstanceRaw::from_model(Mat4::from_translation(corelib::vec3(x, 0.0, 0.0)))
+    }
+
+    #[test]
+    fn transform_updates_upload_only_dirty_ranges() {
+        let mut r = RetainedInstances::default();
+        for e in 0..100 {
+            r.set(instance_slot(e, false), key(e % 2), at(e as f32));
+        }
+        assert_eq!(r.flush(), RetainedUpdate::Full);
+        assert_eq!(r.batches().len(), 2);
+        assert_eq!(r.batches()[1].start, 50);
+
+        // Nothing changed: empty partial update.
+        assert_eq!(r.flush(), RetainedUpdate::Partial);
+        assert!(r.dirty_ranges().is_empty());
+
+        // Entities 0 and 2 are neighbours in the sorted order; 99 is far away.
+        r.set(instance_slot(0, false), key(0), at(-1.0));
+        r.set(instance_slot(2, false), key(0), at(-2.0));
+        r.set(instance_slot(99, false), key(1), at(-3.0));
+        assert_eq!(r.flush(), RetainedUpdate::Partial);
+        assert_eq!(r.dirty_ranges(), &[(0, 2), (99, 100)]);
+        assert_eq!(r.data()[1].col3[0], -2.0);
+
+        // Re-setting identical data (LODs re-resolved after a camera move) uploads nothing.
+        r.set(instance_slot(2, false), key(0), at(-2.0));
+        assert_eq!(r.flush(), RetainedUpdate::Partial);
+        assert!(r.dirty_ranges().is_empty());
+    }
+
+    #[test]
+    fn key_changes_and_removals_resort() {
+        let mut r = RetainedInstances::default();
+        r.set(instance_slot(0, false), key(1), at(0.0));
+        r.set(instance_slot(1, false), key(1), at(1.0));
+        r.flush();
+
+        // LOD switch: new mesh + outgoing fade slot.
+        r.set(instance_slot(1, false), key(0), at(1.0));
+        r.set(instance_slot(1, true), key(1), at(1.0));
+        assert_eq!(r.flush(), RetainedUpdate::Full);
+        assert_eq!(r.len(), 3);
+        assert_eq!(r.batch_of(), &[0, 1, 1]);
+
+        r.remove(instance_slot(1, true));
+        r.remove(instance_slot(1, true));
+        assert_eq!(r.flush(), RetainedUpdate::Full);
+        assert_eq!(r.len(), 2);
+        assert_eq!(r.batches().len(), 2);
+    }
+
+    #[test]
+    fn static_frames_upload_nothing() {
+        let mut r = RetainedInstances::default();
+        for e in 0..10 {
+            r.set(instance_slot(e, false), key(e % 2), at(e as f32));
+        }
+        // "Frustum": x < 5
+        let mut test = |_: &DrawBatch, raw: &InstanceRaw| {
+            if raw.col3[0] < 5.0 {
+                Visibility::Visible
+            } else {
+                Visibility::Culled
+            }
+        };
+        let (mut vis, mut data, mut batches) =
+            (RetainedVisibility::default(), Vec::new(), Vec::new());
+        let mut frame =
+            |r: &mut RetainedInstances,
+             all: bool,
+             test: &mut dyn FnMut(&DrawBatch, &InstanceRaw) -> Visibility| {
+                let update = r.flush();
+                let changed = vis.update(r, update, all, test, &mut data, &mut batches);
+                (changed, data.len(), batches.len(), vis.culled())
+            };
+        assert_eq!(frame(&mut r, true, &mut test), (true, 5, 2, 5));
+
+        // Nothing moved, same view: no re-test, no upload
+        let mut tested = 0;
+        let mut counting = |b: &DrawBatch, raw: &InstanceRaw| {
+            tested += 1;
+            test(b, raw)
+        };
+        assert!(!frame(&mut r, false, &mut counting).0);
+        assert_eq!(tested, 0);
+        // New view with the same result: re-tested, still nothing to upload
+        assert!(!frame(&mut r, true, &mut test).0);
+
+        // A hidden instance moving stays hidden; a visible one moving is uploaded
+        r.set(instance_slot(9, false), key(1), at(8.0));
+        assert!(!frame(&mut r, false, &mut test).0);
+        r.set(instance_slot(0, false), key(0), at(6.0));
+        assert_eq!(frame(&mut r, false, &mut test), (true, 4, 2, 6));
+    }
+}
@@ -0,0 +1,595 @@
+//! J2: WGSL sources loaded from the assets directory with polling hot-reload.
+//!
+//! Every shader has an embedded fallback, so the renderer works without the assets folder.
+//! Changed files are validated with naga before the renderer sees them; a broken edit keeps
+//! the last good source (and therefore the last good pipeline) and is reported via `errors()`.
+//! Sources go through the `preprocess` module; expanded permutations are cached per defines.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::preprocess::{IncludeSource, PreprocessError, Preprocessed, ShaderDefs, preprocess};
+
+/// Default interval between file modification checks.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Shared WGSL snippets for `#include "include/..."`, embedded as fallbacks.
+pub const EMBEDDED_INCLUDES: &[(&str, &str)] = &[
+    (
+        "include/camera.wgsl",
+        include_str!("../../../assets/shaders/include/camera.wgsl"),
+    ),
+    (
+        "include/material.wgsl",
+        include_str!("../../../assets/shaders/include/material.wgsl"),
+    ),
+    (
+        "include/particle.wgsl",
+        include_str!("../../../assets/shaders/include/particle.wgsl"),
+    ),
+    (
+        "include/surface.wgsl",
+        include_str!("../../../assets/shaders/include/surface.wgsl"),
+    ),
+];
+
+/// Handle of a shader registered in the `ShaderManager`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderId(pub u32);
+
+/// A shader that currently fails to load, parse, validate or build.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderError {
+    pub name: String,
+    pub message: String,
+}
+
+/// Validated new source; the owner rebuilds its pipelines and answers with
+/// `ShaderManager::accept` or `ShaderManager::reject`.
+#[derive(Clone, Debug)]
+pub struct ShaderUpdate {
+    pub id: ShaderId,
+    pub source: String,
+}
+
+struct ShaderEntry {
+    // File name relative to the shader directory
+    name: String,
+    embedded: String,
+    // Last good source (what live pipelines were built from)
+    source: String,
+    // mtime of the last file version we looked at (good or bad)
+    modified: Option<SystemTime>,
+    error: Option<String>,
+}
+
+pub struct ShaderManager {
+    dir: Option<PathBuf>,
+    entries: Vec<ShaderEntry>,
+    poll_interval: Duration,
+    last_poll: Option<Instant>,
+    // Expanded permutations of the last good sources
+    permutations: HashMap<(ShaderId, ShaderDefs), Preprocessed>,
+}
+
+impl Default for ShaderManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderManager {
+    /// Manager without a shader directory: only embedded sources, nothing is watched.
+    pub fn new() -> Self {
+        Self {
+            dir: None,
+            entries: Vec::new(),
+            poll_interval: DEFAULT_POLL_INTERVAL,
+            last_poll: None,
+            permutations: HashMap::new(),
+        }
+    }
+
+    /// Manager with `EMBEDDED_INCLUDES` registered (they hot-reload like any other file).
+    pub fn with_embedded_includes() -> Self {
+        let mut shaders = Self::new();
+        for (name, source) in EMBEDDED_INCLUDES {
+            shaders.register(name, source);
+        }
+        shaders
+    }
+
+    /// Register a shader file `name` (relative to the shader directory) with its embedded
+    /// fallback. The fallback is used until the directory is set and the file loads.
+    pub fn register(&mut self, name: &str, embedded: &str) -> ShaderId {
+        if let Some(i) = self.entries.iter().position(|e| e.name == name) {
+            return ShaderId(i as u32);
+        }
+        self.entries.push(ShaderEntry {
+            name: name.to_string(),
+            embedded: embedded.to_string(),
+            source: embedded.to_string(),
+            modified: None,
+            error: None,
+        });
+        ShaderId(self.entries.len() as u32 - 1)
+    }
+
+    /// Register a shader that only exists on disk (e.g. named by a material file).
+    /// The file must load and validate; its current source doubles as the fallback.
+    pub fn register_file(&mut self, name: &str) -> Result<ShaderId, String> {
+        if let Some(id) = self.find(name) {
+            return Ok(id);
+        }
+        let dir = self
+            .dir
+            .as_ref()
+            .ok_or_else(|| format!("no shader directory for {name}"))?;
+        let path = dir.join(name);
+        let modified = fs::metadata(&path).and_then(|m| m.modified()).ok();
+        let source = fs::read_to_string(&path)
+            .map_err(|err| format!("cannot read {}: {err}", path.display()))?;
+        preprocess(name, &source, &ShaderDefs::new(), self)
+            .map_err(|e| e.to_string())
+            .and_then(|p| validate_wgsl(&p.source))?;
+        let id = self.register(name, &source);
+        self.entries[id.0 as usize].modified = modified;
+        Ok(id)
+    }
+
+    /// Set (or clear) the directory shaders are loaded from and watched in.
+    /// Returns updates for files that differ from the current sources; without a
+    /// directory every shader goes back to its embedded source.
+    pub fn set_dir(&mut self, dir: Option<PathBuf>) -> Vec<ShaderUpdate> {
+        self.dir = dir;
+        for entry in &mut self.entries {
+            entry.modified = None;
+            entry.error = None;
+        }
+        self.last_poll = Some(Instant::now());
+        if self.dir.is_some() {
+            return self.check_files();
+        }
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.source != e.embedded)
+            .map(|(i, e)| ShaderUpdate {
+                id: ShaderId(i as u32),
+                source: e.embedded.clone(),
+            })
+            .collect()
+    }
+
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.poll_interval = interval;
+    }
+
+    /// Check modification times if the poll interval has elapsed.
+    pub fn poll(&mut self) -> Vec<ShaderUpdate> {
+        let now = Instant::now();
+        if self.dir.is_none()
+            || self
+                .last_poll
+                .is_some_and(|t| now.duration_since(t) < self.poll_interval)
+        {
+            return Vec::new();
+        }
+        self.last_poll = Some(now);
+        self.check_files()
+    }
+
+    /// Check every registered file right now.
+    pub fn check_files(&mut self) -> Vec<ShaderUpdate> {
+        let Some(dir) = self.dir.clone() else {
+            return Vec::new();
+        };
+        let mut candidates = Vec::new();
+        for (i, entry) in self.entries.iter_mut().enumerate() {
+            let path = dir.join(&entry.name);
+            let modified = match fs::metadata(&path).and_then(|m| m.modified()) {
+                Ok(t) => t,
+                Err(err) => {
+                    // Missing file: keep whatever we had, report once
+                    if entry.error.is_none() && entry.modified.is_some() {
+                        Self::fail(entry, format!("cannot read {}: {err}", path.display()));
+                    }
+                    continue;
+                }
+            };
+            if entry.modified == Some(modified) {
+                continue;
+            }
+            entry.modified = Some(modified);
+
+            let source = match fs::read_to_string(&path) {
+                Ok(s) => s,
+                Err(err) => {
+                    Self::fail(entry, format!("cannot read {}: {err}", path.display()));
+                    continue;
+                }
+            };
+            if source == entry.source {
+                if entry.error.take().is_some() {
+                    log::info!("Shader {} restored", entry.name);
+                }
+                continue;
+            }
+            candidates.push(ShaderUpdate {
+                id: ShaderId(i as u32),
+                source,
+            });
+        }
+
+        // Validate the default permutation of each candidate, seeing the other candidates
+        // (a struct may be renamed in an include and its users in the same save)
+        let mut updates = Vec::new();
+        let mut failed = Vec::new();
+        for update in &candidates {
+            let checked = self
+                .expand_with(update.id, &ShaderDefs::new(), &candidates)
+                .map_err(|e| e.to_string())
+                .and_then(|p| validate_wgsl(&p.source).map(|_| ()));
+            match checked {
+                Ok(()) => updates.push(update.clone()),
+                Err(message) => failed.push((update.id, message)),
+            }
+        }
+        for (id, message) in failed {
+            self.reject(id, message);
+        }
+        updates
+    }
+
+    /// Expand `id` for `defines` from the last good sources (cached per permutation).
+    pub fn permutation(
+        &mut self,
+        id: ShaderId,
+        defines: &ShaderDefs,
+    ) -> Result<&Preprocessed, PreprocessError> {
+        let key = (id, defines.clone());
+        if !self.permutations.contains_key(&key) {
+            let expanded = self.expand_with(id, defines, &[])?;
+            self.permutations.insert(key.clone(), expanded);
+        }
+        Ok(&self.permutations[&key])
+    }
+
+    /// Number of cached permutations (all are dropped when any source changes).
+    pub fn permutation_count(&self) -> usize {
+        self.permutations.len()
+    }
+
+    /// Expand `id` as if `pending` updates were already accepted (not cached).
+    pub fn expand_with(
+        &self,
+        id: ShaderId,
+        defines: &ShaderDefs,
+        pending: &[ShaderUpdate],
+    ) -> Result<Preprocessed, PreprocessError> {
+        let view = PendingSources {
+            shaders: self,
+            pending,
+        };
+        let source = view.source(id);
+        preprocess(self.name(id), source, defines, &view)
+    }
+
+    /// The owner rebuilt its pipelines from `update`: it becomes the last good source.
+    pub fn accept(&mut self, update: ShaderUpdate) {
+        let entry = &mut self.entries[update.id.0 as usize];
+        entry.source = update.source;
+        entry.error = None;
+        log::info!("Shader {} reloaded", entry.name);
+        self.permutations.clear();
+    }
+
+    /// Pipeline creation failed for a validated source; the old pipeline stays in use.
+    pub fn reject(&mut self, id: ShaderId, message: String) {
+        Self::fail(&mut self.entries[id.0 as usize], message);
+    }
+
+    fn fail(entry: &mut ShaderEntry, message: String) {
+        log::error!(
+            "Shader {} failed, keeping the last good version:\n{message}",
+            entry.name
+        );
+        entry.error = Some(message);
+    }
+
+    pub fn source(&self, id: ShaderId) -> &str {
+        &self.entries[id.0 as usize].source
+    }
+
+    pub fn name(&self, id: ShaderId) -> &str {
+        &self.entries[id.0 as usize].name
+    }
+
+    pub fn find(&self, name: &str) -> Option<ShaderId> {
+        self.entries
+            .iter()
+            .position(|e| e.name == name)
+            .map(|i| ShaderId(i as u32))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Shaders whose latest version on disk is broken.
+    pub fn errors(&self) -> Vec<ShaderError> {
+        self.entries
+            .iter()
+            .filter_map(|e| {
+                e.error.as_ref().map(|message| ShaderError {
+                    name: e.name.clone(),
+                    message: message.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl IncludeSource for ShaderManager {
+    fn include_source(&self, name: &str) -> Option<&str> {
+        self.find(name).map(|id| self.source(id))
+    }
+}
+
+// Last good sources overlaid with not yet accepted updates.
+struct PendingSources<'a> {
+    shaders: &'a ShaderManager,
+    pending: &'a [ShaderUpdate],
+}
+
+impl PendingSources<'_> {
+    fn source(&self, id: ShaderId) -> &str {
+        match self.pending.iter().find(|u| u.id == id) {
+            Some(update) => &update.source,
+            None => self.shaders.source(id),
+        }
+    }
+}
+
+impl IncludeSource for PendingSources<'_> {
+    fn include_source(&self, name: &str) -> Option<&str> {
+        self.shaders.find(name).map(|id| self.source(id))
+    }
+}
+
+/// Parse and validate WGSL with naga; the error text includes source locations.
+pub fn validate_wgsl(source: &str) -> Result<naga::Module, String> {
+    let module = naga::front::wgsl::parse_str(source).map_err(|e| e.emit_to_string(source))?;
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::empty(),
+    )
+    .validate(&module)
+    .map_err(|e| e.emit_to_string(source))?;
+    Ok(module)
+}
+
+/// Size and member offsets of a WGSL struct as naga lays it out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StructLayout {
+    pub span: u32,
+    pub members: Vec<(String, u32)>,
+}
+
+/// Look up struct `name` in a parsed module.
+pub fn struct_layout(module: &naga::Module, name: &str) -> Option<StructLayout> {
+    module.types.iter().find_map(|(_, ty)| match &ty.inner {
+        naga::TypeInner::Struct { members, span } if ty.name.as_deref() == Some(name) => {
+            Some(StructLayout {
+                span: *span,
+                members: members
+                    .iter()
+                    .map(|m| (m.name.clone().unwrap_or_default(), m.offset))
+                    .collect(),
+            })
+        }
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GOOD: &str =
+        "@fragment fn fs_main() -> @location(0) vec4<f32> { return vec4<f32>(1.0); }";
+    const GOOD_2: &str =
+        "@fragment fn fs_main() -> @location(0) vec4<f32> { return vec4<f32>(0.5); }";
+    const BROKEN: &str = "@fragment fn fs_main() -> @location(0) vec4<f32> { return 1.0 }";
+
+    fn temp_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("svarog_shaders_{tag}_{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // mtime granularity can be coarse; force a distinct timestamp
+    fn write_newer(path: &Path, contents: &str) {
+        let before = fs::metadata(path).and_then(|m| m.modified()).ok();
+        fs::write(path, contents).unwrap();
+        if let Some(before) = before {
+            let file = fs::File::options().write(true).open(path).unwrap();
+            file.set_modified(before + Duration::from_secs(1)).unwrap();
+        }
+    }
+
+    #[test]
+    fn broken_edit_keeps_last_good_source() {
+        let dir = temp_dir("reload");
+        let path = dir.join("main.wgsl");
+        fs::write(&path, GOOD_2).unwrap();
+
+        let mut shaders = ShaderManager::new();
+        let id = shaders.register("main.wgsl", GOOD);
+        assert_eq!(shaders.source(id), GOOD);
+
+        // Directory set: disk version differs from the embedded one
+        let updates = shaders.set_dir(Some(dir.clone()));
+        assert_eq!(updates.len(), 1);
+        shaders.accept(updates.into_iter().next().unwrap());
+        assert_eq!(shaders.source(id), GOOD_2);
+        assert!(shaders.check_files().is_empty());
+
+        // Broken edit: no update, error reported, source unchanged
+        write_newer(&path, BROKEN);
+        assert!(shaders.check_files().is_empty());
+        assert_eq!(shaders.source(id), GOOD_2);
+        let errors = shaders.errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].name, "main.wgsl");
+
+        // Fixed again
+        write_newer(&path, GOOD);
+        let updates = shaders.check_files();
+        assert_eq!(updates.len(), 1);
+        shaders.accept(updates.into_iter().next().unwrap());
+        assert_eq!(shaders.source(id), GOOD);
+        assert!(shaders.errors().is_empty());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejected_update_is_reported() {
+        let mut shaders = ShaderManager::new();
+        let id = shaders.register("a.wgsl", GOOD);
+        assert_eq!(shaders.register("a.wgsl", GOOD_2), id);
+        shaders.reject(id, "pipeline mismatch".into());
+        assert_eq!(shaders.source(id), GOOD);
+        assert_eq!(shaders.errors()[0].message, "pipeline mismatch");
+    }
+
+    fn assert_layout(module: &naga::Module, name: &str, size: usize, fields: &[(&str, usize)]) {
+        let layout = struct_layout(module, name).unwrap_or_else(|| panic!("{name} not declared"));
+        assert_eq!(layout.span as usize, size, "{name} size");
+        for (field, offset) in fields {
+            let member = layout.members.iter().find(|(n, _)| n == field);
+            assert_eq!(
+                member.map(|m| m.1 as usize),
+                Some(*offset),
+                "{name}.{field} offset"
+            );
+        }
+    }
+
+    #[test]
+    fn uniform_layouts_match_wgsl() {
+        use crate::{CameraUniform, LightingUniform, MaterialUniform};
+        use asset::material::{AlphaMode, MaterialDesc};
+        use std::mem::{offset_of, size_of};
+
+        let mut shaders = ShaderManager::with_embedded_includes();
+        let main = shaders.register(crate::MAIN_SHADER_NAME, crate::MAIN_SHADER_SOURCE);
+        let mask = MaterialDesc {
+            alpha_mode: AlphaMode::Mask(0.5),
+            ..Default::default()
+        };
+        for defs in [
+            ShaderDefs::new(),
+            crate::main_shader_defs(),
+            crate::material_shader_defs(&mask),
+        ] {
+            let expanded = shaders.permutation(main, &defs).unwrap();
+            let module = validate_wgsl(&expanded.source).unwrap_or_else(|e| panic!("{e}"));
+            assert_layout(
+                &module,
+                "Camera",
+                size_of::<CameraUniform>(),
+                &[("mvp", offset_of!(CameraUniform, mvp))],
+            );
+            assert_layout(
+                &module,
+                "Material",
+                size_of::<MaterialUniform>(),
+                &[
+                    ("base_color", offset_of!(MaterialUniform, base_color)),
+                    (
+                        "metallic_roughness",
+                        offset_of!(MaterialUniform, metallic_roughness),
+                    ),
+                    ("alpha_cutoff", offset_of!(MaterialUniform, alpha_cutoff)),
+                    ("emissive", offset_of!(MaterialUniform, emissive)),
+                ],
+            );
+            assert_layout(
+                &module,
+                "Lighting",
+                size_of::<LightingUniform>(),
+                &[
+                    (
+                        "light_direction",
+                        offset_of!(LightingUniform, light_direction),
+                    ),
+                    (
+                        "light_intensity",
+                        offset_of!(LightingUniform, light_intensity),
+                    ),
+                    ("light_color", offset_of!(LightingUniform, light_color)),
+                    (
+                        "ambient_intensity",
+                        offset_of!(LightingUniform, ambient_intensity),
+                    ),
+                ],
+            );
+        }
+        // One cache entry per permutation, reused on repeated lookups
+        assert_eq!(shaders.permutation_count(), 3);
+        shaders.permutation(main, &ShaderDefs::new()).unwrap();
+        assert_eq!(shaders.permutation_count(), 3);
+    }
+
+    #[test]
+    fn include_edit_is_validated_with_its_users() {
+        let mut shaders = ShaderManager::new();
+        let inc = shaders.register("inc.wgsl", "struct S { x: f32 };\n");
+        let main = shaders.register(
+            "main.wgsl",
+            "#include \"inc.wgsl\"\n@group(0) @binding(0) var<uniform> s: S;\n",
+        );
+        assert!(shaders.permutation(main, &ShaderDefs::new()).is_ok());
+        // Include renames the struct: fine alone, broken for main until main changes too
+        let renamed = ShaderUpdate {
+            id: inc,
+            source: "struct T { x: f32 };\n".into(),
+        };
+        let main_fixed = ShaderUpdate {
+            id: main,
+            source: "#include \"inc.wgsl\"\n@group(0) @binding(0) var<uniform> s: T;\n".into(),
+        };
+        let alone = shaders
+            .expand_with(main, &ShaderDefs::new(), std::slice::from_ref(&renamed))
+            .unwrap();
+        assert!(validate_wgsl(&alone.source).is_err());
+        let both = shaders
+            .expand_with(main, &ShaderDefs::new(), &[renamed.clone(), main_fixed])
+            .unwrap();
+        assert!(validate_wgsl(&both.source).is_ok());
+        assert_eq!(both.includes, ["inc.wgsl"]);
+
+        shaders.accept(renamed);
+        assert_eq!(shaders.permutation_count(), 0);
+    }
+
+    #[test]
+    fn validation_errors_carry_locations() {
+        assert!(validate_wgsl(GOOD).is_ok());
+        let err = validate_wgsl(BROKEN).unwrap_err();
+        assert!(err.contains("wgsl"), "{err}");
+    }
+}
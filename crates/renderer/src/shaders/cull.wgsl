@@ -0,0 +1,136 @@
+// L2: GPU frustum culling + compaction into per-batch indirect draws.
+// L1: optional Hi-Z occlusion test against the previous frame's depth pyramid.
+
+struct CullParams {
+    planes: array<vec4<f32>, 6>,
+    instance_count: u32,
+    cull_enabled: u32,
+    occlusion_enabled: u32,
+    hiz_mips: u32,
+    // WGPU clip matrix the pyramid depth was rendered with
+    hiz_view_proj: mat4x4<f32>,
+    hiz_size: vec2<f32>,
+    _pad0: u32,
+    _pad1: u32,
+};
+
+struct CullInstance {
+    model: mat4x4<f32>,
+    batch: u32,
+    lod_fade: f32,
+    _pad0: u32,
+    _pad1: u32,
+};
+
+// Same layout as InstanceRaw, bound as a vertex buffer afterwards.
+struct VisibleInstance {
+    model: mat4x4<f32>,
+    lod: vec4<f32>,
+};
+
+struct CullBatch {
+    // xyz = local bounding sphere center, w = radius
+    sphere: vec4<f32>,
+    // local AABB (w unused)
+    aabb_min: vec4<f32>,
+    aabb_max: vec4<f32>,
+    // first slot of this batch in `visible`
+    out_start: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+};
+
+struct DrawIndexedArgs {
+    index_count: u32,
+    instance_count: atomic<u32>,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+};
+
+@group(0) @binding(0) var<uniform> params: CullParams;
+@group(0) @binding(1) var<storage, read> instances: array<CullInstance>;
+@group(0) @binding(2) var<storage, read> batches: array<CullBatch>;
+@group(0) @binding(3) var<storage, read_write> draws: array<DrawIndexedArgs>;
+@group(0) @binding(4) var<storage, read_write> visible: array<VisibleInstance>;
+// Max-depth pyramid (1x1 dummy when occlusion is off).
+@group(0) @binding(5) var hiz: texture_2d<f32>;
+
+fn sphere_visible(center: vec3<f32>, radius: f32) -> bool {
+    for (var i = 0u; i < 6u; i = i + 1u) {
+        let p = params.planes[i];
+        if (dot(p.xyz, center) + p.w < -radius) {
+            return false;
+        }
+    }
+    return true;
+}
+
+// Conservative: `false` unless the whole box is behind the pyramid depth.
+fn box_occluded(model: mat4x4<f32>, bmin: vec3<f32>, bmax: vec3<f32>) -> bool {
+    let clip_from_local = params.hiz_view_proj * model;
+    var uv_min = vec2<f32>(1e30);
+    var uv_max = vec2<f32>(-1e30);
+    var nearest = 1e30;
+    for (var i = 0u; i < 8u; i = i + 1u) {
+        let corner = select(bmin, bmax, vec3<bool>((i & 1u) != 0u, (i & 2u) != 0u, (i & 4u) != 0u));
+        let clip = clip_from_local * vec4<f32>(corner, 1.0);
+        if (clip.w <= 1e-6) {
+            return false;
+        }
+        let ndc = clip.xyz / clip.w;
+        let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+        uv_min = min(uv_min, uv);
+        uv_max = max(uv_max, uv);
+        nearest = min(nearest, ndc.z);
+    }
+    uv_min = clamp(uv_min, vec2<f32>(0.0), vec2<f32>(1.0));
+    uv_max = clamp(uv_max, vec2<f32>(0.0), vec2<f32>(1.0));
+    if (uv_min.x >= uv_max.x || uv_min.y >= uv_max.y) {
+        return false;
+    }
+
+    // Pick the mip where the rect covers at most 2x2 texels.
+    let extent = (uv_max - uv_min) * params.hiz_size;
+    let mip = min(u32(ceil(log2(max(max(extent.x, extent.y), 1.0)))), params.hiz_mips - 1u);
+    let dims = textureDimensions(hiz, mip);
+    let lo = min(vec2<u32>(uv_min * vec2<f32>(dims)), dims - 1u);
+    let hi = min(vec2<u32>(uv_max * vec2<f32>(dims)), dims - 1u);
+
+    var max_depth = 0.0;
+    for (var y = lo.y; y <= hi.y; y = y + 1u) {
+        for (var x = lo.x; x <= hi.x; x = x + 1u) {
+            max_depth = max(max_depth, textureLoad(hiz, vec2<i32>(vec2<u32>(x, y)), i32(mip)).r);
+        }
+    }
+    return nearest > max_depth;
+}
+
+@compute @workgroup_size(64)
+fn cs_main(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let idx = gid.x;
+    if (idx >= params.instance_count) {
+        return;
+    }
+
+    let inst = instances[idx];
+    let batch = batches[inst.batch];
+
+    if (params.cull_enabled != 0u) {
+        let m = inst.model;
+        let center = (m * vec4<f32>(batch.sphere.xyz, 1.0)).xyz;
+        let scale = max(length(m[0].xyz), max(length(m[1].xyz), length(m[2].xyz)));
+        if (!sphere_visible(center, batch.sphere.w * scale)) {
+            return;
+        }
+    }
+
+    if (params.occlusion_enabled != 0u
+        && box_occluded(inst.model, batch.aabb_min.xyz, batch.aabb_max.xyz)) {
+        return;
+    }
+
+    let slot = atomicAdd(&draws[inst.batch].instance_count, 1u);
+    visible[batch.out_start + slot] = VisibleInstance(inst.model, vec4<f32>(inst.lod_fade, 0.0, 0.0, 0.0));
+}
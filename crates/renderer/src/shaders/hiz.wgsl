@@ -0,0 +1,39 @@
+// L1: Hi-Z pyramid build. Each texel stores the farthest (max) depth of its footprint.
+
+@group(0) @binding(0) var src_depth: texture_depth_2d;
+@group(0) @binding(1) var dst_copy: texture_storage_2d<r32float, write>;
+
+// Mip 0: copy the previous frame's depth buffer.
+@compute @workgroup_size(8, 8)
+fn cs_copy_depth(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let size = textureDimensions(dst_copy);
+    if (gid.x >= size.x || gid.y >= size.y) {
+        return;
+    }
+    let d = textureLoad(src_depth, vec2<i32>(gid.xy), 0);
+    textureStore(dst_copy, vec2<i32>(gid.xy), vec4<f32>(d, 0.0, 0.0, 0.0));
+}
+
+// Separate bindings: each entry point gets its own bind group layout.
+@group(0) @binding(2) var src_mip: texture_2d<f32>;
+@group(0) @binding(3) var dst_mip: texture_storage_2d<r32float, write>;
+
+// Mip N: max over the (up to 3x3 for odd sizes) footprint in mip N-1.
+@compute @workgroup_size(8, 8)
+fn cs_downsample(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let dst_size = textureDimensions(dst_mip);
+    if (gid.x >= dst_size.x || gid.y >= dst_size.y) {
+        return;
+    }
+    let src_size = textureDimensions(src_mip);
+    let lo = gid.xy * src_size / dst_size;
+    let hi = min(((gid.xy + 1u) * src_size + dst_size - 1u) / dst_size, src_size);
+
+    var d = 0.0;
+    for (var y = lo.y; y < hi.y; y = y + 1u) {
+        for (var x = lo.x; x < hi.x; x = x + 1u) {
+            d = max(d, textureLoad(src_mip, vec2<i32>(vec2<u32>(x, y)), 0).r);
+        }
+    }
+    textureStore(dst_mip, vec2<i32>(gid.xy), vec4<f32>(d, 0.0, 0.0, 0.0));
+}
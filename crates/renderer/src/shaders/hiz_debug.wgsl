@@ -0,0 +1,37 @@
+// L1: Hi-Z debug view — draws one pyramid mip as grayscale.
+
+struct DebugParams {
+    mip: u32,
+    _pad0: u32,
+    _pad1: u32,
+    _pad2: u32,
+};
+
+@group(0) @binding(0) var hiz: texture_2d<f32>;
+@group(0) @binding(1) var<uniform> params: DebugParams;
+
+struct VsOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+// Fullscreen triangle (no vertex buffer).
+@vertex
+fn vs_main(@builtin(vertex_index) vi: u32) -> VsOut {
+    let uv = vec2<f32>(f32((vi << 1u) & 2u), f32(vi & 2u));
+    var out: VsOut;
+    out.pos = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
+    let mip = i32(min(params.mip, textureNumLevels(hiz) - 1u));
+    let size = vec2<f32>(textureDimensions(hiz, mip));
+    let texel = vec2<i32>(clamp(in.uv * size, vec2<f32>(0.0), size - 1.0));
+    let d = textureLoad(hiz, texel, mip).r;
+    // Depth is packed near 1.0 — stretch it for visibility.
+    let v = pow(d, 64.0);
+    return vec4<f32>(v, v, v, 1.0);
+}
@@ -0,0 +1,60 @@
+// K3: camera-facing particle billboards (6 vertices per particle instance).
+
+struct Camera {
+    view_proj: mat4x4<f32>,
+    right: vec4<f32>,
+    up: vec4<f32>,
+};
+
+#include "include/particle.wgsl"
+
+@group(0) @binding(0) var<uniform> u_camera: Camera;
+@group(1) @binding(0) var<uniform> emitter: Emitter;
+
+struct VsIn {
+    @builtin(vertex_index) vertex: u32,
+    @location(0) pos_age: vec4<f32>,
+    @location(1) vel_life: vec4<f32>,
+};
+
+struct VsOut {
+    @builtin(position) pos: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(in: VsIn) -> VsOut {
+    var out: VsOut;
+    let life = in.vel_life.w;
+    if (in.pos_age.w >= life) {
+        // Dead: outside the clip volume.
+        out.pos = vec4<f32>(0.0, 0.0, 2.0, 1.0);
+        out.uv = vec2<f32>(0.0);
+        out.color = vec4<f32>(0.0);
+        return out;
+    }
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, -1.0), vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0), vec2<f32>(1.0, 1.0), vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[in.vertex % 6u];
+    let t = clamp(in.pos_age.w / max(life, 1e-4), 0.0, 1.0);
+    let size = mix(emitter.size.x, emitter.size.y, t);
+    let world = in.pos_age.xyz
+        + (u_camera.right.xyz * corner.x + u_camera.up.xyz * corner.y) * size;
+    out.pos = u_camera.view_proj * vec4<f32>(world, 1.0);
+    out.uv = corner;
+    out.color = mix(emitter.color_start, emitter.color_end, t);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VsOut) -> @location(0) vec4<f32> {
+    // Soft round sprite
+    let falloff = 1.0 - smoothstep(0.5, 1.0, length(in.uv));
+    if (falloff <= 0.0) {
+        discard;
+    }
+    return vec4<f32>(in.color.rgb, in.color.a * falloff);
+}
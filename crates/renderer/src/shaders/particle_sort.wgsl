@@ -0,0 +1,67 @@
+// K3: back-to-front ordering for alpha-blended particles.
+// cs_keys writes (distance, index) pairs, cs_sort runs one bitonic step per dispatch,
+// cs_gather copies particles in sorted order into the buffer used for drawing.
+
+#include "include/particle.wgsl"
+
+struct SortKey {
+    // Distance to the camera; -1 for dead particles (sorted last).
+    depth: f32,
+    index: u32,
+};
+
+struct SortStep {
+    j: u32,
+    k: u32,
+    // Power-of-two element count
+    count: u32,
+    _pad: u32,
+};
+
+@group(0) @binding(0) var<uniform> emitter: Emitter;
+@group(0) @binding(1) var<storage, read> particles: array<Particle>;
+@group(0) @binding(2) var<storage, read_write> keys: array<SortKey>;
+@group(0) @binding(3) var<storage, read_write> sorted: array<Particle>;
+@group(0) @binding(4) var<uniform> step: SortStep;
+
+@compute @workgroup_size(64)
+fn cs_keys(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if (i >= step.count) {
+        return;
+    }
+    var depth = -1.0;
+    if (i < emitter.capacity) {
+        let p = particles[i];
+        if (p.pos_age.w < p.vel_life.w) {
+            depth = distance(p.pos_age.xyz, emitter.camera_pos);
+        }
+    }
+    keys[i] = SortKey(depth, i);
+}
+
+// Bitonic compare-exchange; the sequence ends up in descending depth order.
+@compute @workgroup_size(64)
+fn cs_sort(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    let l = i ^ step.j;
+    if (i >= step.count || l <= i) {
+        return;
+    }
+    let a = keys[i];
+    let b = keys[l];
+    let descending = (i & step.k) == 0u;
+    if ((descending && a.depth < b.depth) || (!descending && a.depth > b.depth)) {
+        keys[i] = b;
+        keys[l] = a;
+    }
+}
+
+@compute @workgroup_size(64)
+fn cs_gather(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if (i >= emitter.capacity) {
+        return;
+    }
+    sorted[i] = particles[keys[i].index];
+}
@@ -0,0 +1,128 @@
+// K3: GPU particle simulation. cs_update ages/integrates every slot, cs_spawn fills
+// `spawn_count` slots of the ring buffer starting at `spawn_start`.
+
+#include "include/particle.wgsl"
+
+@group(0) @binding(0) var<uniform> emitter: Emitter;
+@group(0) @binding(1) var<storage, read_write> particles: array<Particle>;
+
+const SHAPE_POINT: u32 = 0u;
+const SHAPE_SPHERE: u32 = 1u;
+const SHAPE_CONE: u32 = 2u;
+const PI: f32 = 3.14159265;
+
+fn pcg(v: u32) -> u32 {
+    let state = v * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+fn rand(state: ptr<function, u32>) -> f32 {
+    *state = pcg(*state);
+    return f32(*state) / 4294967295.0;
+}
+
+fn random_unit_vector(state: ptr<function, u32>) -> vec3<f32> {
+    let z = rand(state) * 2.0 - 1.0;
+    let a = rand(state) * 2.0 * PI;
+    let r = sqrt(max(1.0 - z * z, 0.0));
+    return vec3<f32>(r * cos(a), r * sin(a), z);
+}
+
+// --- curl noise -----------------------------------------------------------
+
+fn hash3(p: vec3<i32>) -> f32 {
+    let h = pcg(bitcast<u32>(p.x) ^ pcg(bitcast<u32>(p.y) ^ pcg(bitcast<u32>(p.z))));
+    return f32(h) / 4294967295.0 * 2.0 - 1.0;
+}
+
+fn value_noise(p: vec3<f32>) -> f32 {
+    let i = vec3<i32>(floor(p));
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    let x00 = mix(hash3(i), hash3(i + vec3<i32>(1, 0, 0)), u.x);
+    let x10 = mix(hash3(i + vec3<i32>(0, 1, 0)), hash3(i + vec3<i32>(1, 1, 0)), u.x);
+    let x01 = mix(hash3(i + vec3<i32>(0, 0, 1)), hash3(i + vec3<i32>(1, 0, 1)), u.x);
+    let x11 = mix(hash3(i + vec3<i32>(0, 1, 1)), hash3(i + vec3<i32>(1, 1, 1)), u.x);
+    return mix(mix(x00, x10, u.y), mix(x01, x11, u.y), u.z);
+}
+
+fn potential(p: vec3<f32>) -> vec3<f32> {
+    return vec3<f32>(
+        value_noise(p),
+        value_noise(p + vec3<f32>(31.4, 17.1, 9.2)),
+        value_noise(p + vec3<f32>(-7.3, 43.7, 21.1)),
+    );
+}
+
+// Divergence-free field: curl of a noise potential (central differences).
+fn curl_noise(p: vec3<f32>) -> vec3<f32> {
+    let e = 0.1;
+    let dx = potential(p + vec3<f32>(e, 0.0, 0.0)) - potential(p - vec3<f32>(e, 0.0, 0.0));
+    let dy = potential(p + vec3<f32>(0.0, e, 0.0)) - potential(p - vec3<f32>(0.0, e, 0.0));
+    let dz = potential(p + vec3<f32>(0.0, 0.0, e)) - potential(p - vec3<f32>(0.0, 0.0, e));
+    return vec3<f32>(dy.z - dz.y, dz.x - dx.z, dx.y - dy.x) / (2.0 * e);
+}
+
+// --- kernels --------------------------------------------------------------
+
+@compute @workgroup_size(64)
+fn cs_update(@builtin(global_invocation_id) gid: vec3<u32>) {
+    let i = gid.x;
+    if (i >= emitter.capacity) {
+        return;
+    }
+    var p = particles[i];
+    if (p.pos_age.w >= p.vel_life.w) {
+        return;
+    }
+    let dt = emitter.dt;
+    var v = p.vel_life.xyz + emitter.gravity * dt;
+    v = v * exp(-emitter.drag * dt);
+    if (emitter.curl.x != 0.0) {
+        let q = p.pos_age.xyz * emitter.curl.y + vec3<f32>(0.0, 0.0, emitter.time * 0.2);
+        v = v + curl_noise(q) * emitter.curl.x * dt;
+    }
+    p.vel_life = vec4<f32>(v, p.vel_life.w);
+    p.pos_age = vec4<f32>(p.pos_age.xyz + v * dt, p.pos_age.w + dt);
+    particles[i] = p;
+}
+
+@compute @workgroup_size(64)
+fn cs_spawn(@builtin(global_invocation_id) gid: vec3<u32>) {
+    if (gid.x >= emitter.spawn_count) {
+        return;
+    }
+    let i = (emitter.spawn_start + gid.x) % emitter.capacity;
+    var rng = pcg(emitter.seed ^ pcg(gid.x));
+
+    var pos = vec3<f32>(0.0);
+    var dir = vec3<f32>(0.0, 1.0, 0.0);
+    let radius = emitter.shape_params.x;
+    switch emitter.shape {
+        case SHAPE_SPHERE: {
+            dir = random_unit_vector(&rng);
+            pos = dir * radius * pow(rand(&rng), 1.0 / 3.0);
+        }
+        case SHAPE_CONE: {
+            // Uniform direction inside the cone around +Y, start in a disc of `radius`.
+            let cos_max = cos(emitter.shape_params.y);
+            let c = mix(1.0, cos_max, rand(&rng));
+            let s = sqrt(max(1.0 - c * c, 0.0));
+            let a = rand(&rng) * 2.0 * PI;
+            dir = vec3<f32>(s * cos(a), c, s * sin(a));
+            let r = radius * sqrt(rand(&rng));
+            let b = rand(&rng) * 2.0 * PI;
+            pos = vec3<f32>(r * cos(b), 0.0, r * sin(b));
+        }
+        default: {
+            dir = random_unit_vector(&rng);
+        }
+    }
+
+    let speed = mix(emitter.shape_params.z, emitter.shape_params.w, rand(&rng));
+    let life = mix(emitter.lifetime.x, emitter.lifetime.y, rand(&rng));
+    let world_pos = (emitter.model * vec4<f32>(pos, 1.0)).xyz;
+    let world_vel = normalize((emitter.model * vec4<f32>(dir, 0.0)).xyz) * speed;
+    particles[i] = Particle(vec4<f32>(world_pos, 0.0), vec4<f32>(world_vel, life));
+}
@@ -0,0 +1,75 @@
+// J1: morph target + linear blend skinning pre-pass.
+// Reads bind-pose vertices with four joint influences, adds the weighted morph target deltas
+// and writes skinned vertices in the main pipeline's `Vertex` layout (position, normal, uv as
+// 8 floats) for regular drawing.
+
+struct SkinParams {
+    vertex_count: u32,
+    joint_count: u32,
+    target_count: u32,
+    _pad: u32,
+}
+
+struct SkinVertex {
+    // xyz = position, w = uv.x
+    position_u: vec4<f32>,
+    // xyz = normal, w = uv.y
+    normal_v: vec4<f32>,
+    joints: vec4<u32>,
+    weights: vec4<f32>,
+}
+
+struct MorphDelta {
+    // xyz used, w = 0
+    position: vec4<f32>,
+    normal: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> params: SkinParams;
+@group(0) @binding(1) var<storage, read> bind_vertices: array<SkinVertex>;
+@group(0) @binding(2) var<storage, read> joint_matrices: array<mat4x4<f32>>;
+@group(0) @binding(3) var<storage, read_write> out_vertices: array<f32>;
+// [target][vertex]
+@group(0) @binding(4) var<storage, read> morph_deltas: array<MorphDelta>;
+@group(0) @binding(5) var<storage, read> morph_weights: array<f32>;
+
+fn joint(index: u32) -> mat4x4<f32> {
+    return joint_matrices[min(index, params.joint_count - 1u)];
+}
+
+@compute @workgroup_size(64)
+fn cs_skin(@builtin(global_invocation_id) id: vec3<u32>) {
+    let i = id.x;
+    if (i >= params.vertex_count) {
+        return;
+    }
+    let v = bind_vertices[i];
+    var p = v.position_u.xyz;
+    var nrm = v.normal_v.xyz;
+    for (var t = 0u; t < params.target_count; t++) {
+        let w = morph_weights[t];
+        if (w != 0.0) {
+            let d = morph_deltas[t * params.vertex_count + i];
+            p += d.position.xyz * w;
+            nrm += d.normal.xyz * w;
+        }
+    }
+    let m = joint(v.joints.x) * v.weights.x
+        + joint(v.joints.y) * v.weights.y
+        + joint(v.joints.z) * v.weights.z
+        + joint(v.joints.w) * v.weights.w;
+    let position = (m * vec4<f32>(p, 1.0)).xyz;
+    let n = (m * vec4<f32>(nrm, 0.0)).xyz;
+    let len = length(n);
+    let normal = select(v.normal_v.xyz, n / len, len > 0.0);
+
+    let o = i * 8u;
+    out_vertices[o + 0u] = position.x;
+    out_vertices[o + 1u] = position.y;
+    out_vertices[o + 2u] = position.z;
+    out_vertices[o + 3u] = normal.x;
+    out_vertices[o + 4u] = normal.y;
+    out_vertices[o + 5u] = normal.z;
+    out_vertices[o + 6u] = v.position_u.w;
+    out_vertices[o + 7u] = v.normal_v.w;
+}
@@ -0,0 +1,446 @@
+//! J1: skinned and morphed meshes.
+//! Bind-pose vertices with joint indices/weights and the morph target deltas live in storage
+//! buffers. Every frame the animator's clip layers are sampled into a pose and morph weights on
+//! the CPU, the pose is turned into joint matrices and a compute pre-pass (`cs_skin`,
+//! registered in the G2 [`ComputeRegistry`]) morphs then skins the vertices into an ordinary
+//! vertex buffer, so the main pipeline draws a skinned mesh like any other [`MeshId`]. Without
+//! compute support the same math runs on the CPU and is uploaded. Tangent deltas are not used
+//! since the main vertex layout has no tangents.
+
+use asset::animation::{
+    AnimationClip, JointTransform, Skeleton, SkinWeights, blend_morph_weights, skin_vertices,
+    skinned_aabb,
+};
+use asset::gltf::IDENTITY;
+use asset::mesh::{Aabb, MeshData, MeshVertex};
+use bytemuck::{Pod, Zeroable};
+use corelib::{animation::Animator, ecs::MeshId};
+use wgpu::{Buffer, BufferUsages, CommandEncoder, Device, Queue, util::DeviceExt};
+
+use crate::Vertex;
+use crate::compute::{
+    ComputeBinding, ComputeBindingKind, ComputeDispatch, ComputeError, ComputeRegistry,
+    ComputeResource, ComputeShaderDesc, ComputeShaderId, workgroup_count,
+};
+use crate::profiler::GpuProfiler;
+
+const WORKGROUP: u32 = 64;
+
+/// Must match `SkinParams` in skinning.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct SkinParams {
+    vertex_count: u32,
+    joint_count: u32,
+    target_count: u32,
+    _pad: u32,
+}
+
+/// Must match `SkinVertex` in skinning.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Pod, Zeroable)]
+struct SkinVertex {
+    position_u: [f32; 4],
+    normal_v: [f32; 4],
+    joints: [u32; 4],
+    weights: [f32; 4],
+}
+
+/// Must match `MorphDelta` in skinning.wgsl.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Pod, Zeroable)]
+struct MorphDelta {
+    position: [f32; 4],
+    normal: [f32; 4],
+}
+
+/// Skeleton, clips and GPU buffers of one skinned mesh instance.
+pub struct SkinnedMesh {
+    mesh: MeshId,
+    skeleton: Skeleton,
+    clips: Vec<AnimationClip>,
+    bind: MeshData,
+    weights: SkinWeights,
+    joint_bounds: Vec<Option<Aabb>>,
+    joint_buf: Buffer,
+    morph_buf: Buffer,
+    // None = CPU skinning
+    dispatch: Option<ComputeDispatch>,
+    // Recorded in the next frame's skinning pass
+    pending: bool,
+    // Per-frame scratch, reused to avoid allocations
+    pose: Vec<JointTransform>,
+    scratch: Vec<JointTransform>,
+    matrices: Vec<[f32; 16]>,
+    morph_weights: Vec<f32>,
+    morph_scratch: Vec<f32>,
+    skinned: Vec<MeshVertex>,
+}
+
+impl SkinnedMesh {
+    /// Mesh holding the skinned vertices (draw it like any uploaded mesh).
+    pub fn mesh(&self) -> MeshId {
+        self.mesh
+    }
+
+    pub fn skeleton(&self) -> &Skeleton {
+        &self.skeleton
+    }
+
+    pub fn clips(&self) -> &[AnimationClip] {
+        &self.clips
+    }
+
+    /// Index of the first clip called `name`.
+    pub fn clip_index(&self, name: &str) -> Option<usize> {
+        self.clips
+            .iter()
+            .position(|c| c.name.as_deref() == Some(name))
+    }
+
+    /// Number of morph targets (0 = skinning only).
+    pub fn morph_target_count(&self) -> usize {
+        self.bind.morph_targets.len()
+    }
+
+    /// Whether vertices are skinned by the compute pre-pass (else on the CPU).
+    pub fn gpu_skinned(&self) -> bool {
+        self.dispatch.is_some()
+    }
+
+    /// Advance `animator`, pose the skeleton, blend morph weights (the animator's, else the
+    /// mesh defaults, where no clip animates them) and skin into `vertex_buf` (directly on the
+    /// CPU path, via the next frame's compute pass otherwise). Returns the skinned mesh bounds.
+    pub(crate) fn animate(
+        &mut self,
+        queue: &Queue,
+        vertex_buf: &Buffer,
+        dt: f32,
+        animator: &mut Animator,
+    ) -> Aabb {
+        let clips = &self.clips;
+        animator.advance(dt, |c| clips.get(c).map_or(0.0, |clip| clip.duration));
+        let layers: Vec<(&AnimationClip, f32, f32)> = animator
+            .layers
+            .iter()
+            .filter_map(|l| Some((clips.get(l.clip)?, l.time, l.weight)))
+            .collect();
+        self.skeleton
+            .sample_blended(&layers, &mut self.pose, &mut self.scratch);
+        self.skeleton.joint_matrices(&self.pose, &mut self.matrices);
+        let targets = self.bind.morph_targets.len();
+        if targets > 0 {
+            let mut base = self.bind.default_morph_weights();
+            for (b, &w) in base.iter_mut().zip(&animator.morph_weights) {
+                *b = w;
+            }
+            blend_morph_weights(
+                &layers,
+                &base,
+                &mut self.morph_weights,
+                &mut self.morph_scratch,
+            );
+            self.morph_weights.resize(targets, 0.0);
+        }
+
+        if self.dispatch.is_some() {
+            queue.write_buffer(&self.joint_buf, 0, bytemuck::cast_slice(&self.matrices));
+            if targets > 0 {
+                queue.write_buffer(
+                    &self.morph_buf,
+                    0,
+                    bytemuck::cast_slice(&self.morph_weights),
+                );
+            }
+            self.pending = true;
+        } else {
+            skin_vertices(
+                &self.bind,
+                &self.weights,
+                &self.matrices,
+                &self.morph_weights,
+                &mut self.skinned,
+            );
+            let vertices: Vec<Vertex> = self.skinned.iter().copied().map(Vertex::from).collect();
+            queue.write_buffer(vertex_buf, 0, bytemuck::cast_slice(&vertices));
+        }
+        skinned_aabb(&self.joint_bounds, &self.matrices)
+    }
+}
+
+/// All skinned meshes plus the shared `cs_skin` kernel.
+#[derive(Default)]
+pub struct SkinningSystem {
+    // None = no compute support (CPU skinning)
+    shader: Option<ComputeShaderId>,
+    skins: Vec<SkinnedMesh>,
+}
+
+impl SkinningSystem {
+    /// Registers `cs_skin` in `compute`; `None` selects CPU skinning.
+    pub fn new(device: &Device, compute: Option<&mut ComputeRegistry>) -> Self {
+        let shader = compute.and_then(|compute| {
+            compute
+                .register(
+                    device,
+                    &ComputeShaderDesc {
+                        label: "Skinning",
+                        source: include_str!("shaders/skinning.wgsl"),
+                        entry_point: "cs_skin",
+                        workgroup_size: [WORKGROUP, 1, 1],
+                        bindings: &[
+                            ComputeBinding::new(0, ComputeBindingKind::Uniform),
+                            ComputeBinding::new(1, ComputeBindingKind::STORAGE_READ),
+                            ComputeBinding::new(2, ComputeBindingKind::STORAGE_READ),
+                            ComputeBinding::new(3, ComputeBindingKind::STORAGE_RW),
+                            ComputeBinding::new(4, ComputeBindingKind::STORAGE_READ),
+                            ComputeBinding::new(5, ComputeBindingKind::STORAGE_READ),
+                        ],
+                    },
+                )
+                .inspect_err(|e| log::warn!("J1: GPU skinning disabled, skinning on the CPU: {e}"))
+                .ok()
+        });
+        Self {
+            shader,
+            skins: Vec::new(),
+        }
+    }
+
+    /// Add a skinned (and possibly morphed) mesh whose output vertices are `vertex_buf`
+    /// (bind pose, `mesh`'s buffer).
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn add(
+        &mut self,
+        device: &Device,
+        compute: &ComputeRegistry,
+        mesh: MeshId,
+        vertex_buf: &Buffer,
+        bind: &MeshData,
+        weights: &SkinWeights,
+        skeleton: Skeleton,
+        clips: Vec<AnimationClip>,
+    ) -> Result<usize, ComputeError> {
+        let joint_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Skin Joint Matrices"),
+            contents: bytemuck::cast_slice(&vec![IDENTITY; skeleton.len()]),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        });
+        // Storage bindings can't be empty: one zero weight without targets
+        let morph_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Skin Morph Weights"),
+            contents: bytemuck::cast_slice(&vec![0.0f32; bind.morph_targets.len().max(1)]),
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+        });
+        let dispatch = match self.shader {
+            Some(shader) => {
+                let vertices: Vec<SkinVertex> = skin_vertex_data(bind, weights);
+                let bind_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Skin Bind Vertices"),
+                    contents: bytemuck::cast_slice(&vertices),
+                    usage: BufferUsages::STORAGE,
+                });
+                let params = SkinParams {
+                    vertex_count: vertices.len() as u32,
+                    joint_count: skeleton.len() as u32,
+                    target_count: bind.morph_targets.len() as u32,
+                    _pad: 0,
+                };
+                let deltas = morph_delta_data(bind);
+                let delta_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Skin Morph Deltas"),
+                    contents: bytemuck::cast_slice(&deltas),
+                    usage: BufferUsages::STORAGE,
+                });
+                let params_buf = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Skin Params"),
+                    contents: bytemuck::bytes_of(&params),
+                    usage: BufferUsages::UNIFORM,
+                });
+                let bind_group = compute.bind_group(
+                    device,
+                    shader,
+                    &[
+                        (0, ComputeResource::Buffer(&params_buf)),
+                        (1, ComputeResource::Buffer(&bind_buf)),
+                        (2, ComputeResource::Buffer(&joint_buf)),
+                        (3, ComputeResource::Buffer(vertex_buf)),
+                        (4, ComputeResource::Buffer(&delta_buf)),
+                        (5, ComputeResource::Buffer(&morph_buf)),
+                    ],
+                )?;
+                Some(ComputeDispatch {
+                    shader,
+                    bind_group,
+                    workgroups: workgroup_count([params.vertex_count, 1, 1], [WORKGROUP, 1, 1]),
+                })
+            }
+            None => None,
+        };
+        let joint_bounds = weights.joint_bounds(bind, skeleton.len());
+        self.skins.push(SkinnedMesh {
+            mesh,
+            pose: skeleton.rest_pose(),
+            skeleton,
+            clips,
+            bind: bind.clone(),
+            weights: weights.clone(),
+            joint_bounds,
+            joint_buf,
+            morph_buf,
+            dispatch,
+            pending: false,
+            scratch: Vec::new(),
+            matrices: Vec::new(),
+            morph_weights: bind.default_morph_weights(),
+            morph_scratch: Vec::new(),
+            skinned: Vec::new(),
+        });
+        Ok(self.skins.len() - 1)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&SkinnedMesh> {
+        self.skins.get(index)
+    }
+
+    pub(crate) fn get_mut(&mut self, index: usize) -> Option<&mut SkinnedMesh> {
+        self.skins.get_mut(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.skins.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.skins.is_empty()
+    }
+
+    /// Record `cs_skin` for every mesh animated since the last frame in one profiled pass.
+    pub fn record(
+        &mut self,
+        encoder: &mut CommandEncoder,
+        compute: &ComputeRegistry,
+        profiler: &mut GpuProfiler,
+    ) {
+        if !self.skins.iter().any(|s| s.pending) {
+            return;
+        }
+        let scope = profiler.begin_pass("Skinning");
+        {
+            let mut cpass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Skinning"),
+                timestamp_writes: profiler.compute_timestamp_writes(scope),
+            });
+            for skin in self.skins.iter_mut().filter(|s| s.pending) {
+                if let Some(dispatch) = skin.dispatch.as_ref() {
+                    compute.record(&mut cpass, dispatch);
+                }
+                skin.pending = false;
+            }
+        }
+        profiler.end_pass(scope);
+    }
+}
+
+fn skin_vertex_data(mesh: &MeshData, weights: &SkinWeights) -> Vec<SkinVertex> {
+    mesh.vertices
+        .iter()
+        .zip(&weights.joints)
+        .zip(&weights.weights)
+        .map(|((v, joints), w)| SkinVertex {
+            position_u: [v.position[0], v.position[1], v.position[2], v.uv[0]],
+            normal_v: [v.normal[0], v.normal[1], v.normal[2], v.uv[1]],
+            joints: joints.map(u32::from),
+            weights: *w,
+        })
+        .collect()
+}
+
+/// Deltas laid out [target][vertex]; a single zero delta without targets.
+fn morph_delta_data(mesh: &MeshData) -> Vec<MorphDelta> {
+    let mut deltas = Vec::with_capacity(mesh.morph_targets.len() * mesh.vertices.len());
+    for target in &mesh.morph_targets {
+        for (i, p) in target.positions.iter().enumerate() {
+            let n = target.normals.as_ref().map_or([0.0; 3], |n| n[i]);
+            deltas.push(MorphDelta {
+                position: [p[0], p[1], p[2], 0.0],
+                normal: [n[0], n[1], n[2], 0.0],
+            });
+        }
+    }
+    if deltas.is_empty() {
+        deltas.push(MorphDelta::default());
+    }
+    deltas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader::{struct_layout, validate_wgsl};
+    use asset::mesh::MorphTarget;
+
+    #[test]
+    fn skinning_shader_validates_and_matches_layouts() {
+        let module =
+            validate_wgsl(include_str!("shaders/skinning.wgsl")).unwrap_or_else(|e| panic!("{e}"));
+        let size = |name| {
+            struct_layout(&module, name)
+                .unwrap_or_else(|| panic!("{name} not found"))
+                .span as usize
+        };
+        assert_eq!(size("SkinParams"), std::mem::size_of::<SkinParams>());
+        assert_eq!(size("SkinVertex"), std::mem::size_of::<SkinVertex>());
+        assert_eq!(size("MorphDelta"), std::mem::size_of::<MorphDelta>());
+        // Output written as 8 floats per vertex
+        assert_eq!(std::mem::size_of::<Vertex>(), 8 * 4);
+    }
+
+    #[test]
+    fn skin_vertices_pack_uv_into_w() {
+        let mesh = MeshData::new(
+            vec![MeshVertex::new(
+                [1.0, 2.0, 3.0],
+                [0.0, 1.0, 0.0],
+                [0.25, 0.75],
+            )],
+            vec![0, 0, 0],
+        );
+        let weights = SkinWeights::new(vec![[2, 0, 0, 0]], vec![[1.0, 0.0, 0.0, 0.0]]).unwrap();
+        let packed = skin_vertex_data(&mesh, &weights);
+        assert_eq!(packed[0].position_u, [1.0, 2.0, 3.0, 0.25]);
+        assert_eq!(packed[0].normal_v, [0.0, 1.0, 0.0, 0.75]);
+        assert_eq!(packed[0].joints, [2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn morph_deltas_are_laid_out_by_target() {
+        let vertices = vec![MeshVertex::new([0.0; 3], [0.0, 0.0, 1.0], [0.0; 2]); 2];
+        let target = |d: f32, normals| MorphTarget {
+            name: None,
+            positions: vec![[d, 0.0, 0.0], [0.0, d, 0.0]],
+            normals,
+            tangents: None,
+            default_weight: 0.0,
+        };
+        let mesh = MeshData::new(vertices.clone(), vec![0, 1, 0])
+            .with_morph_targets(vec![
+                target(1.0, None),
+                target(2.0, Some(vec![[0.0, 0.0, -1.0]; 2])),
+            ])
+            .unwrap();
+        let deltas = morph_delta_data(&mesh);
+        assert_eq!(deltas.len(), 4);
+        assert_eq!(deltas[1].position, [0.0, 1.0, 0.0, 0.0]);
+        assert_eq!(deltas[2].position, [2.0, 0.0, 0.0, 0.0]);
+        assert_eq!(
+            (deltas[0].normal, deltas[3].normal),
+            ([0.0; 4], [0.0, 0.0, -1.0, 0.0])
+        );
+        // Storage bindings can't be empty
+        assert_eq!(
+            morph_delta_data(&MeshData::new(vertices, vec![0, 1, 0])).len(),
+            1
+        );
+    }
+}
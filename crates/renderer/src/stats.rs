@@ -0,0 +1,137 @@
+//! Frame statistics counters returned by the renderer every frame.
+
+use std::collections::VecDeque;
+
+/// Per-frame renderer counters.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct RenderStats {
+    pub draw_calls: u32,
+    /// Submitted instances (before GPU culling when the L2 path is active).
+    pub instances: u32,
+    /// L1: instances that passed CPU frustum culling (0 with GPU culling).
+    pub visible_instances: u32,
+    /// L1: instances rejected by frustum culling.
+    pub culled_instances: u32,
+    /// L1: instances rejected by the Hi-Z occlusion test (CPU path).
+    pub occluded_instances: u32,
+    pub triangles: u64,
+    pub batches: u32,
+    pub bind_group_switches: u32,
+    pub pipeline_switches: u32,
+    /// M1: vertex/index buffer bindings (only when the mesh arena block changes).
+    pub buffer_binds: u32,
+    /// Bytes written via `queue.write_buffer`/texture uploads since the previous frame.
+    pub uploaded_bytes: u64,
+    pub buffer_reallocations: u32,
+    /// CPU time spent building and sorting the draw list.
+    pub cpu_prepare_ms: f64,
+    /// G1: threads used for draw preparation (0 when nothing was prepared).
+    pub prep_threads: u32,
+}
+
+impl RenderStats {
+    /// CSV header matching [`RenderStats::to_csv_row`].
+    pub const CSV_HEADER: &'static str = "frame,draw_calls,instances,visible_instances,\
+culled_instances,occluded_instances,triangles,batches,bind_group_switches,pipeline_switches,buffer_binds,uploaded_bytes,\
+buffer_reallocations,cpu_prepare_ms,prep_threads";
+
+    /// One CSV line (without trailing newline).
+    pub fn to_csv_row(&self, frame: u64) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{:.4},{}",
+            frame,
+            self.draw_calls,
+            self.instances,
+            self.visible_instances,
+            self.culled_instances,
+            self.occluded_instances,
+            self.triangles,
+            self.batches,
+            self.bind_group_switches,
+            self.pipeline_switches,
+            self.buffer_binds,
+            self.uploaded_bytes,
+            self.buffer_reallocations,
+            self.cpu_prepare_ms,
+            self.prep_threads
+        )
+    }
+}
+
+/// Fixed-size ring of recent frame stats for graphing.
+pub struct StatsHistory {
+    frames: VecDeque<RenderStats>,
+    capacity: usize,
+}
+
+impl StatsHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frames: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn push(&mut self, stats: RenderStats) {
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(stats);
+    }
+
+    pub fn latest(&self) -> Option<&RenderStats> {
+        self.frames.back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Oldest-to-newest iterator.
+    pub fn iter(&self) -> impl Iterator<Item = &RenderStats> {
+        self.frames.iter()
+    }
+
+    /// Extract one series as f32 values (oldest first) for plotting.
+    pub fn series(&self, f: impl Fn(&RenderStats) -> f32) -> Vec<f32> {
+        self.frames.iter().map(f).collect()
+    }
+}
+
+impl Default for StatsHistory {
+    fn default() -> Self {
+        Self::new(240)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_drops_oldest() {
+        let mut h = StatsHistory::new(2);
+        for i in 0..3 {
+            h.push(RenderStats {
+                draw_calls: i,
+                ..Default::default()
+            });
+        }
+        assert_eq!(h.len(), 2);
+        assert_eq!(h.series(|s| s.draw_calls as f32), vec![1.0, 2.0]);
+    }
+
+    #[test]
+    fn csv_row_matches_header() {
+        let row = RenderStats::default().to_csv_row(7);
+        assert_eq!(
+            row.split(',').count(),
+            RenderStats::CSV_HEADER.split(',').count()
+        );
+        assert!(row.starts_with("7,"));
+    }
+}
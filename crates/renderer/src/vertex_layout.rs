@@ -0,0 +1,177 @@
+//! M1: GPU side of `asset::vertex` layouts: wgpu vertex attributes, shader locations and the
+//! `VERTEX_*` permutation defines a layout's pipeline is built with.
+//!
+//! Locations 0..=2 are the base attributes and 3..=7 the instance data ([`crate::InstanceRaw`]);
+//! optional streams follow. Pipelines list every attribute of the mesh layout, shaders declare
+//! the ones they read (under `#ifdef VERTEX_*`), so unused streams cost nothing but memory.
+
+use asset::vertex::{AttributeFormat, Semantic, VertexLayout};
+use wgpu::{VertexBufferLayout, VertexFormat, VertexStepMode};
+
+use crate::preprocess::ShaderDefs;
+
+/// `@location` of an attribute in the main shader.
+pub fn shader_location(semantic: Semantic) -> u32 {
+    match semantic {
+        Semantic::Position => 0,
+        Semantic::Normal => 1,
+        Semantic::TexCoord0 => 2,
+        Semantic::Tangent => 8,
+        Semantic::TexCoord1 => 9,
+        Semantic::Color => 10,
+        Semantic::Joints => 11,
+        Semantic::Weights => 12,
+    }
+}
+
+pub fn vertex_format(format: AttributeFormat) -> VertexFormat {
+    match format {
+        AttributeFormat::Float32x2 => VertexFormat::Float32x2,
+        AttributeFormat::Float32x3 => VertexFormat::Float32x3,
+        AttributeFormat::Float32x4 => VertexFormat::Float32x4,
+        AttributeFormat::Float16x2 => VertexFormat::Float16x2,
+        AttributeFormat::Snorm8x4 => VertexFormat::Snorm8x4,
+        AttributeFormat::Snorm16x4 => VertexFormat::Snorm16x4,
+        AttributeFormat::Unorm8x4 => VertexFormat::Unorm8x4,
+        AttributeFormat::Unorm16x4 => VertexFormat::Unorm16x4,
+        AttributeFormat::Uint16x4 => VertexFormat::Uint16x4,
+    }
+}
+
+/// Define enabling the shader input of an optional stream.
+pub fn stream_define(semantic: Semantic) -> Option<&'static str> {
+    match semantic {
+        Semantic::Position | Semantic::Normal | Semantic::TexCoord0 => None,
+        Semantic::Tangent => Some("VERTEX_TANGENT"),
+        Semantic::TexCoord1 => Some("VERTEX_UV1"),
+        Semantic::Color => Some("VERTEX_COLOR"),
+        Semantic::Joints | Semantic::Weights => Some("VERTEX_SKIN"),
+    }
+}
+
+/// Add the `VERTEX_*` defines of the streams in `layout` to `defs`.
+pub fn add_layout_defs(layout: &VertexLayout, defs: &mut ShaderDefs) {
+    for define in layout
+        .attributes
+        .iter()
+        .filter_map(|a| stream_define(a.semantic))
+    {
+        defs.insert(define.to_string(), String::new());
+    }
+}
+
+/// A registered layout with its wgpu attributes (kept so buffer layouts can borrow them).
+pub struct GpuVertexLayout {
+    pub layout: VertexLayout,
+    attributes: Vec<wgpu::VertexAttribute>,
+}
+
+impl GpuVertexLayout {
+    pub fn new(layout: VertexLayout) -> Self {
+        let attributes = layout
+            .attributes
+            .iter()
+            .map(|a| wgpu::VertexAttribute {
+                format: vertex_format(a.format),
+                offset: a.offset as u64,
+                shader_location: shader_location(a.semantic),
+            })
+            .collect();
+        Self { layout, attributes }
+    }
+
+    pub fn buffer_layout(&self) -> VertexBufferLayout<'_> {
+        VertexBufferLayout {
+            array_stride: self.layout.stride as u64,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &self.attributes,
+        }
+    }
+}
+
+/// Distinct layouts of uploaded meshes; id 0 is the [`crate::Vertex`] layout.
+pub struct VertexLayouts {
+    layouts: Vec<GpuVertexLayout>,
+}
+
+impl Default for VertexLayouts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VertexLayouts {
+    pub fn new() -> Self {
+        Self {
+            layouts: vec![GpuVertexLayout::new(VertexLayout::default())],
+        }
+    }
+
+    /// Id of `layout`, registering it on first use.
+    pub fn id(&mut self, layout: &VertexLayout) -> usize {
+        match self.layouts.iter().position(|l| l.layout == *layout) {
+            Some(id) => id,
+            None => {
+                self.layouts.push(GpuVertexLayout::new(layout.clone()));
+                self.layouts.len() - 1
+            }
+        }
+    }
+
+    pub fn get(&self, id: usize) -> &GpuVertexLayout {
+        &self.layouts[id]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shader::{ShaderManager, validate_wgsl};
+    use asset::mesh::{MeshData, MeshVertex};
+    use asset::vertex::LayoutOptions;
+
+    #[test]
+    fn default_layout_matches_vertex() {
+        let layouts = VertexLayouts::new();
+        let default = layouts.get(0).buffer_layout();
+        assert_eq!(default.array_stride, crate::Vertex::LAYOUT.array_stride);
+        assert_eq!(default.attributes, crate::Vertex::LAYOUT.attributes);
+    }
+
+    #[test]
+    fn layout_permutations_are_valid_shaders() {
+        let v = |p| MeshVertex::new(p, [0.0, 0.0, 1.0], [0.0; 2]);
+        let mut mesh = MeshData::new(
+            vec![v([0.0; 3]), v([1.0, 0.0, 0.0]), v([0.0, 1.0, 0.0])],
+            vec![0, 1, 2],
+        );
+        mesh.streams.colors = Some(vec![[1.0; 4]; 3]);
+        mesh.streams.tangents = Some(vec![[1.0, 0.0, 0.0, 1.0]; 3]);
+        let layout = VertexLayout::for_mesh(&mesh, LayoutOptions { quantize: true });
+
+        let mut layouts = VertexLayouts::new();
+        let id = layouts.id(&layout);
+        assert_eq!((id, layouts.id(&layout)), (1, 1));
+        let gpu = layouts.get(id);
+        let locations: Vec<u32> = gpu
+            .buffer_layout()
+            .attributes
+            .iter()
+            .map(|a| a.shader_location)
+            .collect();
+        assert_eq!(locations, [0, 1, 2, 8, 10]);
+        assert_eq!(
+            gpu.buffer_layout().attributes[1].format,
+            VertexFormat::Snorm8x4
+        );
+
+        let mut defs = crate::main_shader_defs();
+        add_layout_defs(&layout, &mut defs);
+        assert!(defs.contains_key("VERTEX_COLOR") && defs.contains_key("VERTEX_TANGENT"));
+        let mut shaders = ShaderManager::with_embedded_includes();
+        let main = shaders.register(crate::MAIN_SHADER_NAME, crate::MAIN_SHADER_SOURCE);
+        let expanded = shaders.permutation(main, &defs).unwrap();
+        assert!(expanded.source.contains("@location(10)"));
+        validate_wgsl(&expanded.source).unwrap_or_else(|e| panic!("{e}"));
+    }
+}